@@ -0,0 +1,356 @@
+//! ウィンドウ・サーフェイス・デバイスの準備とイベントループを受け持つ、サンプル共通の土台
+//!
+//! サンプルは [`Example`] を実装し、`main` から [`run`] を呼ぶだけでよい。
+//! `wasm32-unknown-unknown` 向けにビルドすると、ブラウザのキャンバスに描画する（index.html を参照）。
+
+#[cfg(target_arch = "wasm32")]
+mod web;
+
+use std::sync::Arc;
+
+use web_time::{Duration, Instant};
+
+use winit::{
+    application::ApplicationHandler,
+    dpi::PhysicalSize,
+    event::{ElementState, KeyEvent, WindowEvent},
+    event_loop::{ActiveEventLoop, ControlFlow, EventLoop},
+    keyboard::{KeyCode, PhysicalKey},
+    window::{Window, WindowAttributes, WindowId},
+};
+
+/// 起動時と描画中に起きる、続けられないエラー
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("event loop failed: {0}")]
+    EventLoop(#[from] winit::error::EventLoopError),
+    #[error("window creation failed: {0}")]
+    Window(#[from] winit::error::OsError),
+    #[error("surface creation failed: {0}")]
+    Surface(#[from] wgpu::CreateSurfaceError),
+    #[error("no adapter can present to the surface")]
+    Adapter,
+    #[error("device request failed: {0}")]
+    Device(#[from] wgpu::RequestDeviceError),
+    #[error("could not acquire a frame: {0}")]
+    Frame(#[from] wgpu::SurfaceError),
+}
+
+/// サンプルに渡す、ウィンドウと GPU のリソース
+pub struct GpuContext {
+    pub window: Arc<Window>,
+    pub device: wgpu::Device,
+    pub queue: wgpu::Queue,
+    /// サーフェイスの今の設定（大きさとフォーマット）
+    pub config: wgpu::SurfaceConfiguration,
+}
+
+/// [`run`] で動かすサンプル
+///
+/// 描画先のビューとエンコーダーは、毎フレーム [`run`] が用意して提出する。
+pub trait Example: Sized + 'static {
+    /// デバイスができたところで、パイプラインやバッファを作る
+    fn init(ctx: &GpuContext) -> Self;
+
+    /// ウィンドウの大きさが変わった（サーフェイスは設定し直してある）
+    fn resize(&mut self, _ctx: &GpuContext, _size: PhysicalSize<u32>) {}
+
+    /// 使ったイベントなら true を返す（Escape キーとウィンドウを閉じる操作より先に呼ぶ）
+    fn input(&mut self, _event: &WindowEvent) -> bool {
+        false
+    }
+
+    /// 前のフレームからの経過時間で状態を進める
+    fn update(&mut self, _dt: Duration) {}
+
+    /// view に描画するコマンドを encoder に記録する
+    fn render(
+        &mut self,
+        ctx: &GpuContext,
+        view: &wgpu::TextureView,
+        encoder: &mut wgpu::CommandEncoder,
+    );
+}
+
+/// title のウィンドウを開いて E を動かし、閉じるか Escape キーで戻る
+///
+/// ブラウザではイベントループをブラウザに任せてすぐに戻り、その後のエラーはコンソールに表示する。
+pub fn run<E: Example>(title: &str) -> Result<(), Error> {
+    #[cfg(target_arch = "wasm32")]
+    web::init();
+
+    let event_loop = EventLoop::<Initialized<E>>::with_user_event().build()?;
+    event_loop.set_control_flow(ControlFlow::Wait);
+    let runner = Runner::<E> {
+        title: title.to_string(),
+        running: None,
+        error: None,
+        #[cfg(target_arch = "wasm32")]
+        proxy: event_loop.create_proxy(),
+    };
+    run_event_loop(event_loop, runner)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn run_event_loop<E: Example>(
+    event_loop: EventLoop<Initialized<E>>,
+    mut runner: Runner<E>,
+) -> Result<(), Error> {
+    event_loop.run_app(&mut runner)?;
+    match runner.error {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+// ブラウザのイベントループは止められないので、登録したらすぐに戻る
+#[cfg(target_arch = "wasm32")]
+fn run_event_loop<E: Example>(
+    event_loop: EventLoop<Initialized<E>>,
+    runner: Runner<E>,
+) -> Result<(), Error> {
+    use winit::platform::web::EventLoopExtWebSys;
+    event_loop.spawn_app(runner);
+    Ok(())
+}
+
+// バックエンドによって formats の並び順が異なるため、sRGB のフォーマットを優先し、なければ先頭のものを使う
+// （アダプタが表示できないサーフェイスでは formats が空なので None）
+fn choose_surface_format(formats: &[wgpu::TextureFormat]) -> Option<wgpu::TextureFormat> {
+    formats
+        .iter()
+        .copied()
+        .find(|format| format.is_srgb())
+        .or(formats.first().copied())
+}
+
+struct Running<E> {
+    ctx: GpuContext,
+    surface: wgpu::Surface<'static>,
+    example: E,
+    last_frame: Instant,
+}
+
+// 初期化が終わった Running（ブラウザでは非同期に終わるので、ユーザーイベントで受け取る）
+type Initialized<E> = Result<Running<E>, Error>;
+
+impl<E: Example> Running<E> {
+    async fn new(window: Arc<Window>) -> Result<Self, Error> {
+        #[cfg(target_arch = "wasm32")]
+        let size = web::canvas_size(&window);
+        #[cfg(not(target_arch = "wasm32"))]
+        let size = window.inner_size();
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+        let surface = instance.create_surface(window.clone())?;
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                compatible_surface: Some(&surface),
+                ..Default::default()
+            })
+            .await
+            .ok_or(Error::Adapter)?;
+        // WebGL2 などでは WebGPU の既定の制限を満たさないので、WebGL2 の制限にアダプタの解像度を合わせる
+        let required_limits = if wgpu::Limits::default().check_limits(&adapter.limits()) {
+            wgpu::Limits::default()
+        } else {
+            wgpu::Limits::downlevel_webgl2_defaults().using_resolution(adapter.limits())
+        };
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    required_limits,
+                    ..Default::default()
+                },
+                None,
+            )
+            .await?;
+
+        let caps = surface.get_capabilities(&adapter);
+        let format = choose_surface_format(&caps.formats).ok_or(Error::Adapter)?;
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format,
+            width: size.width.max(1),
+            height: size.height.max(1),
+            present_mode: wgpu::PresentMode::AutoVsync,
+            desired_maximum_frame_latency: 2,
+            alpha_mode: caps.alpha_modes[0],
+            view_formats: vec![],
+        };
+        surface.configure(&device, &config);
+
+        let ctx = GpuContext {
+            window,
+            device,
+            queue,
+            config,
+        };
+        let example = E::init(&ctx);
+        Ok(Self {
+            ctx,
+            surface,
+            example,
+            last_frame: Instant::now(),
+        })
+    }
+
+    fn resize(&mut self, size: PhysicalSize<u32>) {
+        // 最小化すると 0x0 になるので、元の大きさのままにしておく
+        if size.width == 0 || size.height == 0 {
+            return;
+        }
+        self.ctx.config.width = size.width;
+        self.ctx.config.height = size.height;
+        self.surface.configure(&self.ctx.device, &self.ctx.config);
+        self.example.resize(&self.ctx, size);
+    }
+
+    fn redraw(&mut self) -> Result<(), wgpu::SurfaceError> {
+        let now = Instant::now();
+        self.example.update(now - self.last_frame);
+        self.last_frame = now;
+
+        let frame = match self.surface.get_current_texture() {
+            Ok(frame) => frame,
+            // モニターを外した場合などにサーフェイスが使えなくなるので、設定し直して次のフレームで描画する
+            Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                self.surface.configure(&self.ctx.device, &self.ctx.config);
+                return Ok(());
+            }
+            // 表示が間に合わなかったフレームは飛ばす
+            Err(wgpu::SurfaceError::Timeout) => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        let view = frame
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let mut encoder = self
+            .ctx
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Example Encoder"),
+            });
+        self.example.render(&self.ctx, &view, &mut encoder);
+        self.ctx.queue.submit(Some(encoder.finish()));
+        self.ctx.window.pre_present_notify();
+        frame.present();
+        Ok(())
+    }
+}
+
+struct Runner<E> {
+    title: String,
+    running: Option<Running<E>>,
+    // 初期化や描画に失敗してイベントループを終えた理由（run が返す）
+    error: Option<Error>,
+    // 非同期に初期化した Running を送る
+    #[cfg(target_arch = "wasm32")]
+    proxy: winit::event_loop::EventLoopProxy<Initialized<E>>,
+}
+
+impl<E: Example> Runner<E> {
+    fn fail(&mut self, event_loop: &ActiveEventLoop, error: Error) {
+        #[cfg(target_arch = "wasm32")]
+        web::report(&error);
+        self.error = Some(error);
+        event_loop.exit();
+    }
+
+    fn initialized(&mut self, event_loop: &ActiveEventLoop, result: Initialized<E>) {
+        match result {
+            Ok(running) => {
+                running.ctx.window.request_redraw();
+                self.running = Some(running);
+            }
+            Err(e) => self.fail(event_loop, e),
+        }
+    }
+}
+
+impl<E: Example> ApplicationHandler<Initialized<E>> for Runner<E> {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        if self.running.is_some() || self.error.is_some() {
+            return;
+        }
+        let attributes = WindowAttributes::default().with_title(&self.title);
+        // ブラウザでは body の最後にキャンバスを追加する
+        #[cfg(target_arch = "wasm32")]
+        let attributes = {
+            use winit::platform::web::WindowAttributesExtWebSys;
+            attributes.with_append(true)
+        };
+        let window = match event_loop.create_window(attributes) {
+            Ok(window) => Arc::new(window),
+            Err(e) => {
+                self.fail(event_loop, e.into());
+                return;
+            }
+        };
+
+        // ブラウザでは待てないので、終わったら user_event で受け取る（それまでは何も描画しない）
+        #[cfg(target_arch = "wasm32")]
+        {
+            let proxy = self.proxy.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                let _ = proxy.send_event(Running::new(window).await);
+            });
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        self.initialized(event_loop, pollster::block_on(Running::new(window)));
+    }
+
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, result: Initialized<E>) {
+        self.initialized(event_loop, result);
+    }
+
+    fn window_event(&mut self, event_loop: &ActiveEventLoop, _id: WindowId, event: WindowEvent) {
+        // 非同期の初期化（wasm）が終わる前でも閉じられるよう、running を確かめる前に処理する
+        if event == WindowEvent::CloseRequested {
+            event_loop.exit();
+            return;
+        }
+        let Some(running) = self.running.as_mut() else {
+            return;
+        };
+        if running.example.input(&event) {
+            return;
+        }
+        match event {
+            WindowEvent::Resized(size) => running.resize(size),
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(KeyCode::Escape),
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                ..
+            } => event_loop.exit(),
+            WindowEvent::RedrawRequested => {
+                if let Err(e) = running.redraw() {
+                    self.fail(event_loop, e.into());
+                    return;
+                }
+                // 毎フレーム描画し続ける
+                running.ctx.window.request_redraw();
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn surface_format_prefers_srgb() {
+        use wgpu::TextureFormat::*;
+        assert_eq!(
+            choose_surface_format(&[Bgra8Unorm, Bgra8UnormSrgb]),
+            Some(Bgra8UnormSrgb)
+        );
+        assert_eq!(choose_surface_format(&[Rgb10a2Unorm]), Some(Rgb10a2Unorm));
+        assert_eq!(choose_surface_format(&[]), None);
+    }
+}
@@ -0,0 +1,50 @@
+// ブラウザで動かすときの、env_logger と標準エラー出力の代わり
+use wasm_bindgen::JsValue;
+use winit::dpi::PhysicalSize;
+use winit::platform::web::WindowExtWebSys;
+use winit::window::Window;
+
+// wgpu などの警告とエラーをブラウザのコンソールに表示する
+struct ConsoleLogger;
+
+impl log::Log for ConsoleLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::Level::Warn
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let message = JsValue::from(format!("{}: {}", record.target(), record.args()));
+        match record.level() {
+            log::Level::Error => web_sys::console::error_1(&message),
+            _ => web_sys::console::warn_1(&message),
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+// パニックとログの出力先をブラウザのコンソールにする
+pub fn init() {
+    std::panic::set_hook(Box::new(|info| {
+        web_sys::console::error_1(&JsValue::from(info.to_string()));
+    }));
+    if log::set_logger(&ConsoleLogger).is_ok() {
+        log::set_max_level(log::LevelFilter::Warn);
+    }
+}
+
+// run から戻った後に起きたエラーは返せないので、コンソールに表示する
+pub fn report(error: &crate::Error) {
+    web_sys::console::error_1(&JsValue::from(error.to_string()));
+}
+
+// ウェブでは作った直後の inner_size が 0 のことがあるので、キャンバスの大きさを使う
+pub fn canvas_size(window: &Window) -> PhysicalSize<u32> {
+    window.canvas().map_or_else(
+        || window.inner_size(),
+        |canvas| PhysicalSize::new(canvas.width(), canvas.height()),
+    )
+}
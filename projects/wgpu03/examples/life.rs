@@ -0,0 +1,604 @@
+// GPU で計算するライフゲーム（cargo run --example life）
+// `--seed <n>` で初期状態の乱数の種を、`--pattern <path>` で .cells 形式のパターンを指定する
+// （例: `--pattern assets/life/gosper_glider_gun.cells`）
+// スペースキーで一時停止・再開、クリックでセルの生死を切り替える
+use std::{
+    borrow::Cow,
+    path::Path,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use wgpu::util::DeviceExt;
+use winit::{
+    application::ApplicationHandler,
+    event::{ElementState, KeyEvent, MouseButton, WindowEvent},
+    event_loop::{ActiveEventLoop, ControlFlow, EventLoop},
+    keyboard::{KeyCode, PhysicalKey},
+    window::{Window, WindowAttributes, WindowId},
+};
+
+// セルの数（ウィンドウのサイズとは独立で、描画時に引き伸ばす）
+const GRID_WIDTH: u32 = 160;
+const GRID_HEIGHT: u32 = 120;
+// 1世代進める間隔
+const STEP_INTERVAL: Duration = Duration::from_millis(50);
+// 乱数で初期化するときに生きているセルの割合
+const DENSITY: f64 = 0.25;
+// life.wgsl の @workgroup_size と一致させる
+const WORKGROUP_SIZE: u32 = 8;
+
+// セルの状態は rgba8unorm のテクスチャの R チャンネルに 0 か 255 で持つ
+const ALIVE: [u8; 4] = [255, 0, 0, 255];
+const DEAD: [u8; 4] = [0, 0, 0, 255];
+
+fn arg_value(flag: &str) -> Option<String> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == flag {
+            return args.next();
+        }
+    }
+    None
+}
+
+// 乱数で初期状態を作る
+fn random_cells(seed: u64) -> Vec<u8> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    (0..GRID_WIDTH * GRID_HEIGHT)
+        .flat_map(|_| {
+            if rng.random_bool(DENSITY) {
+                ALIVE
+            } else {
+                DEAD
+            }
+        })
+        .collect()
+}
+
+// .cells 形式（`!` で始まる行はコメント、`O` が生きているセル）のパターンを読み込み、
+// 生きているセルの座標を返す
+fn parse_cells(text: &str) -> Vec<(u32, u32)> {
+    text.lines()
+        .filter(|line| !line.starts_with('!'))
+        .enumerate()
+        .flat_map(|(y, line)| {
+            line.chars()
+                .enumerate()
+                .filter(|&(_, c)| c == 'O')
+                .map(move |(x, _)| (x as u32, y as u32))
+        })
+        .collect()
+}
+
+// パターンをグリッドの中央に置いた初期状態を作る
+fn pattern_cells(path: &Path) -> Result<Vec<u8>> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("パターンを読み込めませんでした: {}", path.display()))?;
+    let cells = parse_cells(&text);
+    let width = cells.iter().map(|&(x, _)| x + 1).max().unwrap_or(0);
+    let height = cells.iter().map(|&(_, y)| y + 1).max().unwrap_or(0);
+    anyhow::ensure!(
+        width <= GRID_WIDTH && height <= GRID_HEIGHT,
+        "パターン（{}x{}）がグリッド（{}x{}）に収まりません",
+        width,
+        height,
+        GRID_WIDTH,
+        GRID_HEIGHT
+    );
+    let (offset_x, offset_y) = ((GRID_WIDTH - width) / 2, (GRID_HEIGHT - height) / 2);
+    let mut data: Vec<u8> = (0..GRID_WIDTH * GRID_HEIGHT).flat_map(|_| DEAD).collect();
+    for (x, y) in cells {
+        let index = (((y + offset_y) * GRID_WIDTH + x + offset_x) * 4) as usize;
+        data[index..index + 4].copy_from_slice(&ALIVE);
+    }
+    Ok(data)
+}
+
+// ウィンドウ座標のカーソル位置をセルの座標に変換する
+// カーソル位置とウィンドウのサイズはどちらも物理ピクセルなので、拡大率（scale factor）の影響を受けない
+fn cursor_to_cell(x: f64, y: f64, width: u32, height: u32) -> Option<(u32, u32)> {
+    if x < 0.0 || y < 0.0 || width == 0 || height == 0 {
+        return None;
+    }
+    let cell_x = (x / width as f64 * GRID_WIDTH as f64) as u32;
+    let cell_y = (y / height as f64 * GRID_HEIGHT as f64) as u32;
+    (cell_x < GRID_WIDTH && cell_y < GRID_HEIGHT).then_some((cell_x, cell_y))
+}
+
+// 2枚のテクスチャを交互に読み書きするライフゲームの計算と描画
+struct Life {
+    step_pipeline: wgpu::ComputePipeline,
+    toggle_pipeline: wgpu::ComputePipeline,
+    toggle_buffer: wgpu::Buffer,
+    render_pipeline: wgpu::RenderPipeline,
+    // compute_bind_groups[i] は textures[i] を読み込み、もう一方に書き込む
+    // 毎フレーム作らずに済むよう、両方の向きを最初に作っておく
+    compute_bind_groups: [wgpu::BindGroup; 2],
+    // render_bind_groups[i] は textures[i] を表示する
+    render_bind_groups: [wgpu::BindGroup; 2],
+    // 現在の世代が入っているテクスチャの番号
+    current: usize,
+}
+
+impl Life {
+    fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        shader: &wgpu::ShaderModule,
+        format: wgpu::TextureFormat,
+        initial: &[u8],
+    ) -> Self {
+        let size = wgpu::Extent3d {
+            width: GRID_WIDTH,
+            height: GRID_HEIGHT,
+            depth_or_array_layers: 1,
+        };
+        let textures = [0, 1].map(|i| {
+            device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(&format!("Life Texture {}", i)),
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8Unorm,
+                usage: wgpu::TextureUsages::STORAGE_BINDING
+                    | wgpu::TextureUsages::TEXTURE_BINDING
+                    | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            })
+        });
+        write_cells(
+            queue,
+            &textures[0],
+            (0, 0),
+            GRID_WIDTH,
+            GRID_HEIGHT,
+            initial,
+        );
+        let toggle_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Life Toggle Buffer"),
+            contents: bytemuck::cast_slice(&[-1i32, -1]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let views = textures
+            .each_ref()
+            .map(|texture| texture.create_view(&wgpu::TextureViewDescriptor::default()));
+
+        let compute_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Life Compute Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::StorageTexture {
+                        access: wgpu::StorageTextureAccess::WriteOnly,
+                        format: wgpu::TextureFormat::Rgba8Unorm,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let compute_bind_groups = [0, 1].map(|i| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some(&format!("Life Compute Bind Group {}", i)),
+                layout: &compute_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&views[i]),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(&views[1 - i]),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: toggle_buffer.as_entire_binding(),
+                    },
+                ],
+            })
+        });
+
+        // セルの境界がぼやけないように最近傍でサンプリングする
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+        let render_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Life Render Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let render_bind_groups = [0, 1].map(|i| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some(&format!("Life Render Bind Group {}", i)),
+                layout: &render_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&views[i]),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&sampler),
+                    },
+                ],
+            })
+        });
+
+        let compute_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Life Compute Pipeline Layout"),
+                bind_group_layouts: &[&compute_layout],
+                push_constant_ranges: &[],
+            });
+        let compute_pipeline = |entry_point: &str| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(entry_point),
+                layout: Some(&compute_pipeline_layout),
+                module: shader,
+                entry_point: Some(entry_point),
+                compilation_options: Default::default(),
+                cache: None,
+            })
+        };
+        let step_pipeline = compute_pipeline("cs_main");
+        let toggle_pipeline = compute_pipeline("cs_toggle");
+        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Life Render Pipeline"),
+            layout: Some(
+                &device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: Some("Life Render Pipeline Layout"),
+                    bind_group_layouts: &[&render_layout],
+                    push_constant_ranges: &[],
+                }),
+            ),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: Default::default(),
+        });
+
+        Self {
+            step_pipeline,
+            toggle_pipeline,
+            toggle_buffer,
+            render_pipeline,
+            compute_bind_groups,
+            render_bind_groups,
+            current: 0,
+        }
+    }
+
+    // 現在の世代から次のテクスチャに書き込み、読み書きするテクスチャを入れ替える
+    fn dispatch(&mut self, encoder: &mut wgpu::CommandEncoder, label: &str, toggle: bool) {
+        {
+            let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some(label),
+                timestamp_writes: None,
+            });
+            cpass.set_pipeline(if toggle {
+                &self.toggle_pipeline
+            } else {
+                &self.step_pipeline
+            });
+            cpass.set_bind_group(0, &self.compute_bind_groups[self.current], &[]);
+            cpass.dispatch_workgroups(
+                GRID_WIDTH.div_ceil(WORKGROUP_SIZE),
+                GRID_HEIGHT.div_ceil(WORKGROUP_SIZE),
+                1,
+            );
+        }
+        self.current = 1 - self.current;
+    }
+
+    // 1世代進める
+    fn step(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        self.dispatch(encoder, "Life Step", false);
+    }
+
+    // セルの生死を反転させる
+    // GPU 上の状態を読み戻さずに済むよう、反転もコンピュートシェーダーで行う
+    // 反転させるセルはユニフォームで渡すので、クリックごとにすぐ送信する
+    fn toggle(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, (x, y): (u32, u32)) {
+        queue.write_buffer(&self.toggle_buffer, 0, bytemuck::cast_slice(&[x, y]));
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Life Toggle Encoder"),
+        });
+        self.dispatch(&mut encoder, "Life Toggle", true);
+        queue.submit(Some(encoder.finish()));
+    }
+
+    fn draw(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Life Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        rpass.set_pipeline(&self.render_pipeline);
+        rpass.set_bind_group(0, &self.render_bind_groups[self.current], &[]);
+        rpass.draw(0..3, 0..1);
+    }
+}
+
+fn write_cells(
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+    (x, y): (u32, u32),
+    width: u32,
+    height: u32,
+    data: &[u8],
+) {
+    queue.write_texture(
+        wgpu::TexelCopyTextureInfo {
+            texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d { x, y, z: 0 },
+            aspect: wgpu::TextureAspect::All,
+        },
+        data,
+        wgpu::TexelCopyBufferLayout {
+            offset: 0,
+            bytes_per_row: Some(4 * width),
+            rows_per_image: Some(height),
+        },
+        wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+    );
+}
+
+struct State {
+    surface: wgpu::Surface<'static>,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    config: wgpu::SurfaceConfiguration,
+    life: Life,
+    paused: bool,
+    last_step: Instant,
+    cursor: Option<(f64, f64)>,
+}
+
+impl State {
+    fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
+        let frame = self.surface.get_current_texture()?;
+        let view = frame
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Life Frame Encoder"),
+            });
+        if !self.paused && self.last_step.elapsed() >= STEP_INTERVAL {
+            self.life.step(&mut encoder);
+            self.last_step = Instant::now();
+        }
+        self.life.draw(&mut encoder, &view);
+        self.queue.submit(Some(encoder.finish()));
+        frame.present();
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct App {
+    window: Option<Arc<Window>>,
+    state: Option<State>,
+}
+
+impl ApplicationHandler for App {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        pollster::block_on(async {
+            let window = Arc::new(
+                event_loop
+                    .create_window(WindowAttributes::default().with_title("wgpu:03 life"))
+                    .unwrap(),
+            );
+            let size = window.inner_size();
+            let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+            let surface = instance
+                .create_surface(window.clone())
+                .expect("Failed to create a surface");
+            let adapter = instance
+                .request_adapter(&wgpu::RequestAdapterOptions {
+                    compatible_surface: Some(&surface),
+                    ..Default::default()
+                })
+                .await
+                .expect("Failed to find an appropriate adapter");
+            let (device, queue) = adapter
+                .request_device(&wgpu::DeviceDescriptor::default(), None)
+                .await
+                .expect("Failed to create device");
+
+            let caps = surface.get_capabilities(&adapter);
+            let format = caps
+                .formats
+                .iter()
+                .copied()
+                .find(|format| format.is_srgb())
+                .unwrap_or(caps.formats[0]);
+            let config = wgpu::SurfaceConfiguration {
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                format,
+                width: size.width.max(1),
+                height: size.height.max(1),
+                present_mode: wgpu::PresentMode::Fifo,
+                desired_maximum_frame_latency: 2,
+                alpha_mode: wgpu::CompositeAlphaMode::default(),
+                view_formats: vec![],
+            };
+            surface.configure(&device, &config);
+
+            // パターンが指定されていればそれを、なければ乱数で初期状態を作る
+            let initial = match arg_value("--pattern") {
+                Some(path) => pattern_cells(Path::new(&path)).unwrap_or_else(|e| {
+                    eprintln!("{:#}", e);
+                    std::process::exit(1);
+                }),
+                None => {
+                    let seed = arg_value("--seed")
+                        .and_then(|seed| seed.parse().ok())
+                        .unwrap_or_else(|| rand::rng().random());
+                    println!("乱数の種: {}", seed);
+                    random_cells(seed)
+                }
+            };
+            let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Life Shader"),
+                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("life.wgsl"))),
+            });
+            let life = Life::new(&device, &queue, &shader, format, &initial);
+
+            window.request_redraw();
+            self.window = Some(window);
+            self.state = Some(State {
+                surface,
+                device,
+                queue,
+                config,
+                life,
+                paused: false,
+                last_step: Instant::now(),
+                cursor: None,
+            });
+        });
+    }
+
+    fn window_event(&mut self, target: &ActiveEventLoop, _id: WindowId, event: WindowEvent) {
+        let (Some(state), Some(window)) = (self.state.as_mut(), &self.window) else {
+            return;
+        };
+        match event {
+            WindowEvent::Resized(size) => {
+                state.config.width = size.width.max(1);
+                state.config.height = size.height.max(1);
+                state.surface.configure(&state.device, &state.config);
+            }
+            WindowEvent::CloseRequested => target.exit(),
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(KeyCode::Space),
+                        state: ElementState::Pressed,
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } => {
+                state.paused = !state.paused;
+                println!(
+                    "{}",
+                    if state.paused {
+                        "一時停止"
+                    } else {
+                        "再開"
+                    }
+                );
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                state.cursor = Some((position.x, position.y));
+            }
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button: MouseButton::Left,
+                ..
+            } => {
+                if let Some((x, y)) = state.cursor
+                    && let Some(cell) =
+                        cursor_to_cell(x, y, state.config.width, state.config.height)
+                {
+                    state.life.toggle(&state.device, &state.queue, cell);
+                }
+            }
+            WindowEvent::RedrawRequested => {
+                if let Err(e) = state.render() {
+                    eprintln!("フレームの取得に失敗しました: {}", e);
+                }
+                window.request_redraw();
+            }
+            _ => {}
+        }
+    }
+}
+
+fn main() {
+    let event_loop = EventLoop::new().expect("Failed to create an event loop");
+    event_loop.set_control_flow(ControlFlow::Wait);
+    wgpu03::logging::init(wgpu03::logging::LogFormat::default());
+    let mut app = App::default();
+    if let Err(e) = event_loop.run_app(&mut app) {
+        eprintln!("アプリケーションエラー: {}", e);
+        std::process::exit(1);
+    }
+}
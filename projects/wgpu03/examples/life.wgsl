@@ -0,0 +1,75 @@
+// 現在の世代（読み込み）と次の世代（書き込み）
+// 2枚のテクスチャを世代ごとに入れ替えて使う
+@group(0) @binding(0) var current: texture_2d<f32>;
+@group(0) @binding(1) var next: texture_storage_2d<rgba8unorm, write>;
+// cs_toggle で生死を反転させるセル
+@group(0) @binding(2) var<uniform> toggle_cell: vec2<i32>;
+
+fn alive(cell: vec2<i32>, size: vec2<i32>) -> u32 {
+    // 端は反対側とつながっているものとする（トーラス）
+    let wrapped = (cell + size) % size;
+    return select(0u, 1u, textureLoad(current, wrapped, 0).r > 0.5);
+}
+
+@compute @workgroup_size(8, 8)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = vec2<i32>(textureDimensions(current));
+    let cell = vec2<i32>(id.xy);
+    // グリッドの大きさが 8 の倍数でない場合の余ったスレッド
+    if cell.x >= size.x || cell.y >= size.y {
+        return;
+    }
+    var neighbors = 0u;
+    for (var dy = -1; dy <= 1; dy += 1) {
+        for (var dx = -1; dx <= 1; dx += 1) {
+            if dx != 0 || dy != 0 {
+                neighbors += alive(cell + vec2<i32>(dx, dy), size);
+            }
+        }
+    }
+    // 生きているセルは隣が 2 つか 3 つなら生き残り、死んでいるセルは隣がちょうど 3 つなら生まれる
+    let was_alive = alive(cell, size) == 1u;
+    let lives = neighbors == 3u || (was_alive && neighbors == 2u);
+    textureStore(next, cell, vec4<f32>(select(0.0, 1.0, lives), 0.0, 0.0, 1.0));
+}
+
+// 世代は進めずに現在の状態を書き写し、toggle_cell のセルだけ生死を反転させる
+@compute @workgroup_size(8, 8)
+fn cs_toggle(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = vec2<i32>(textureDimensions(current));
+    let cell = vec2<i32>(id.xy);
+    if cell.x >= size.x || cell.y >= size.y {
+        return;
+    }
+    var lives = alive(cell, size) == 1u;
+    if all(cell == toggle_cell) {
+        lives = !lives;
+    }
+    textureStore(next, cell, vec4<f32>(select(0.0, 1.0, lives), 0.0, 0.0, 1.0));
+}
+
+@group(0) @binding(0) var t_state: texture_2d<f32>;
+@group(0) @binding(1) var s_state: sampler;
+
+struct VOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+// 頂点バッファを使わずに画面全体を覆う大きな三角形を描画する
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VOutput;
+    out.position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    out.uv = vec2<f32>(uv.x, 1.0 - uv.y);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VOutput) -> @location(0) vec4<f32> {
+    let state = textureSample(t_state, s_state, in.uv).r;
+    let dead = vec3<f32>(0.02, 0.03, 0.05);
+    let live = vec3<f32>(0.3, 0.9, 0.5);
+    return vec4<f32>(mix(dead, live, state), 1.0);
+}
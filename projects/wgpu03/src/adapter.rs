@@ -0,0 +1,171 @@
+use tracing::{info, warn};
+
+use crate::init::AppError;
+
+// `--backend <vulkan|dx12|metal|gl>` でバックエンドを、`--adapter <番号|名前の一部>` と
+// `--power <low|high>` でアダプタを選ぶ（引数は Settings が読む）
+// それぞれ環境変数 WGPU_BACKEND・WGPU03_ADAPTER・WGPU03_POWER でも指定でき、コマンドライン引数が優先される
+const BACKEND_ENV: &str = "WGPU_BACKEND";
+const ADAPTER_ENV: &str = "WGPU03_ADAPTER";
+const POWER_ENV: &str = "WGPU03_POWER";
+
+fn env_var(env: &str) -> Option<String> {
+    std::env::var(env).ok().filter(|value| !value.is_empty())
+}
+
+// "vulkan" や "vulkan,gl" のようなバックエンドの指定（知らない名前があれば None）
+pub fn parse_backends(value: &str) -> Option<wgpu::Backends> {
+    value
+        .split(',')
+        .map(|name| match name.trim().to_ascii_lowercase().as_str() {
+            "vulkan" => Some(wgpu::Backends::VULKAN),
+            "dx12" => Some(wgpu::Backends::DX12),
+            "metal" => Some(wgpu::Backends::METAL),
+            "gl" => Some(wgpu::Backends::GL),
+            _ => None,
+        })
+        .try_fold(wgpu::Backends::empty(), |backends, backend| {
+            Some(backends | backend?)
+        })
+}
+
+// 指定されたバックエンド（None なら環境変数の指定）だけを使うインスタンスを作る
+// そのバックエンドにアダプタが1つもなければ、警告を表示してすべてのバックエンドで作り直す
+// （サーフェイスはインスタンスごとに作るので、作り直しはサーフェイスを作る前に行う）
+pub fn create_instance(backends: Option<wgpu::Backends>) -> wgpu::Instance {
+    let backends = match (backends, env_var(BACKEND_ENV)) {
+        (Some(backends), _) => backends,
+        (None, Some(value)) => parse_backends(&value).unwrap_or_else(|| {
+            warn!(
+                "{} には vulkan・dx12・metal・gl のどれかを指定してください: {}",
+                BACKEND_ENV, value
+            );
+            wgpu::Backends::all()
+        }),
+        (None, None) => wgpu::Backends::all(),
+    };
+    let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+        backends,
+        ..Default::default()
+    });
+    if backends == wgpu::Backends::all() || !instance.enumerate_adapters(backends).is_empty() {
+        return instance;
+    }
+    warn!(
+        "{:?} のアダプタが見つからないため、すべてのバックエンドから選びます",
+        backends
+    );
+    wgpu::Instance::new(&wgpu::InstanceDescriptor::default())
+}
+
+pub fn parse_power(value: &str) -> Option<wgpu::PowerPreference> {
+    match value.to_ascii_lowercase().as_str() {
+        "low" => Some(wgpu::PowerPreference::LowPower),
+        "high" => Some(wgpu::PowerPreference::HighPerformance),
+        _ => None,
+    }
+}
+
+// selector が番号として読めればその番号、読めなければ名前に大文字小文字を区別せずに含むアダプタの番号
+fn find_adapter(names: &[String], selector: &str) -> Option<usize> {
+    if let Ok(index) = selector.parse::<usize>() {
+        return (index < names.len()).then_some(index);
+    }
+    let selector = selector.to_lowercase();
+    names
+        .iter()
+        .position(|name| name.to_lowercase().contains(&selector))
+}
+
+// selector（None なら環境変数の指定）のアダプタを選ぶ（surface があれば表示できるものに限る）
+// 見つからないか surface に表示できなければ、警告を表示して power（None なら環境変数の指定）に従って wgpu に選ばせる
+pub async fn request(
+    instance: &wgpu::Instance,
+    surface: Option<&wgpu::Surface<'_>>,
+    selector: Option<&str>,
+    power: Option<wgpu::PowerPreference>,
+) -> Result<wgpu::Adapter, AppError> {
+    if let Some(selector) = selector.map(String::from).or_else(|| env_var(ADAPTER_ENV)) {
+        let adapters = instance.enumerate_adapters(wgpu::Backends::all());
+        let names: Vec<String> = adapters
+            .iter()
+            .map(|adapter| adapter.get_info().name)
+            .collect();
+        match find_adapter(&names, &selector) {
+            Some(index)
+                if surface.is_none_or(|surface| adapters[index].is_surface_supported(surface)) =>
+            {
+                info!("指定されたアダプタを使います: [{}] {}", index, names[index]);
+                return Ok(adapters.into_iter().nth(index).unwrap());
+            }
+            Some(index) => warn!(
+                "[{}] {} はこのウィンドウに表示できないため、自動で選びます",
+                index, names[index]
+            ),
+            None => warn!(
+                "アダプタ {:?} が見つからないため、自動で選びます（あるもの: {:?}）",
+                selector, names
+            ),
+        }
+    }
+
+    let power_preference = match (power, env_var(POWER_ENV)) {
+        (Some(power), _) => power,
+        (None, Some(value)) => parse_power(&value).unwrap_or_else(|| {
+            warn!(
+                "{} には low か high を指定してください: {}",
+                POWER_ENV, value
+            );
+            wgpu::PowerPreference::default()
+        }),
+        (None, None) => wgpu::PowerPreference::default(),
+    };
+    instance
+        .request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference,
+            force_fallback_adapter: false,
+            compatible_surface: surface,
+        })
+        .await
+        .ok_or(AppError::Adapter)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adapters_are_found_by_index_or_name() {
+        let names = [
+            "Intel(R) UHD Graphics 630".to_string(),
+            "NVIDIA GeForce RTX 3060 Laptop GPU".to_string(),
+        ];
+        assert_eq!(find_adapter(&names, "1"), Some(1));
+        assert_eq!(find_adapter(&names, "2"), None);
+        assert_eq!(find_adapter(&names, "nvidia"), Some(1));
+        assert_eq!(find_adapter(&names, "uhd"), Some(0));
+        assert_eq!(find_adapter(&names, "radeon"), None);
+        assert_eq!(
+            parse_power("HIGH"),
+            Some(wgpu::PowerPreference::HighPerformance)
+        );
+        assert_eq!(parse_power("fast"), None);
+        assert_eq!(parse_backends("Vulkan"), Some(wgpu::Backends::VULKAN));
+        assert_eq!(
+            parse_backends("vulkan, gl"),
+            Some(wgpu::Backends::VULKAN | wgpu::Backends::GL)
+        );
+        assert_eq!(parse_backends("vulkan,webgpu"), None);
+    }
+
+    #[test]
+    fn a_backend_that_is_not_compiled_in_has_no_adapter() {
+        // ネイティブのビルドでは WebGPU のバックエンドを組み込んでいない
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::BROWSER_WEBGPU,
+            ..Default::default()
+        });
+        let error = pollster::block_on(request(&instance, None, None, None)).unwrap_err();
+        assert!(matches!(error, AppError::Adapter), "{:?}", error);
+    }
+}
@@ -0,0 +1,394 @@
+use std::borrow::Cow;
+
+use glam::{Mat4, Quat, Vec3, Vec4};
+
+use crate::light::LightStorage;
+
+// ストレージバッファを使えない環境で、ユニフォーム配列で渡せる関節の最大数
+pub const MAX_UNIFORM_JOINTS: usize = 64;
+
+// スキンメッシュの頂点ごとの関節の番号と重み（頂点バッファのスロット1に置く）
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct JointVertex {
+    pub joints: [u32; 4],
+    pub weights: [f32; 4],
+}
+
+impl JointVertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 2] = wgpu::vertex_attr_array![
+        4 => Uint32x4,
+        5 => Float32x4,
+    ];
+
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<JointVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+// 関節の行列は光源の配列と同じ方法で渡す（ストレージバッファを使えなければ固定長のユニフォーム配列）
+pub fn shader_source(storage: LightStorage, source: Cow<'_, str>) -> Cow<'_, str> {
+    const STORAGE_DECL: &str = "var<storage, read> joints: array<mat4x4<f32>>;";
+    match storage {
+        LightStorage::Storage => source,
+        LightStorage::Uniform => Cow::Owned(source.replace(
+            STORAGE_DECL,
+            &format!(
+                "var<uniform> joints: array<mat4x4<f32>, {}>;",
+                MAX_UNIFORM_JOINTS
+            ),
+        )),
+    }
+}
+
+// 関節の行列のバッファだけを持つバインドグループのレイアウト
+pub struct JointLayout {
+    pub layout: wgpu::BindGroupLayout,
+    storage: LightStorage,
+}
+
+impl JointLayout {
+    pub fn new(device: &wgpu::Device, storage: LightStorage) -> Self {
+        let ty = match storage {
+            LightStorage::Storage => wgpu::BufferBindingType::Storage { read_only: true },
+            LightStorage::Uniform => wgpu::BufferBindingType::Uniform,
+        };
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Joint Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        Self { layout, storage }
+    }
+
+    // 書き込める関節の数（ユニフォーム配列の場合は上限で切り詰める）
+    pub fn capacity(&self, joint_count: usize) -> usize {
+        match self.storage {
+            LightStorage::Storage => joint_count.max(1),
+            LightStorage::Uniform => MAX_UNIFORM_JOINTS,
+        }
+    }
+
+    // joint_count 個の関節の行列を書き込むバッファと、そのバインドグループ
+    pub fn create_bind_group(
+        &self,
+        device: &wgpu::Device,
+        label: &str,
+        joint_count: usize,
+    ) -> (wgpu::Buffer, wgpu::BindGroup) {
+        let usage = match self.storage {
+            LightStorage::Storage => wgpu::BufferUsages::STORAGE,
+            LightStorage::Uniform => wgpu::BufferUsages::UNIFORM,
+        };
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(&format!("{} Joint Buffer", label)),
+            size: (self.capacity(joint_count) * std::mem::size_of::<Mat4>()) as wgpu::BufferAddress,
+            usage: usage | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some(&format!("{} Joint Bind Group", label)),
+            layout: &self.layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+        (buffer, bind_group)
+    }
+}
+
+// ノードの移動・回転・拡大縮小
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+}
+
+impl Transform {
+    pub const IDENTITY: Self = Self {
+        translation: Vec3::ZERO,
+        rotation: Quat::IDENTITY,
+        scale: Vec3::ONE,
+    };
+
+    pub fn matrix(&self) -> Mat4 {
+        Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.translation)
+    }
+}
+
+// キーフレームの間の補間方法
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Interpolation {
+    Step,
+    Linear,
+    // 各キーフレームが入力側の接線・値・出力側の接線の3つを持つエルミート補間
+    CubicSpline,
+}
+
+// アニメーションで変える値
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Property {
+    Translation,
+    // 値は (x, y, z, w) の四元数
+    Rotation,
+    Scale,
+}
+
+// 1つのノードの1つの値のキーフレーム
+pub struct Channel {
+    pub node: usize,
+    pub property: Property,
+    pub interpolation: Interpolation,
+    pub times: Vec<f32>,
+    // 移動と拡大縮小は w を使わない
+    pub values: Vec<Vec4>,
+}
+
+impl Channel {
+    // キーフレーム index の値（3次スプラインでは接線の間にある値を取り出す）
+    fn value(&self, index: usize) -> Vec4 {
+        match self.interpolation {
+            Interpolation::CubicSpline => self.values[index * 3 + 1],
+            _ => self.values[index],
+        }
+    }
+
+    // time の値（最初のキーフレームより前と最後のキーフレームより後は端の値のまま）
+    pub fn sample(&self, time: f32) -> Vec4 {
+        let last = self.times.len() - 1;
+        let next = self.times.partition_point(|&t| t <= time);
+        if next == 0 {
+            return self.value(0);
+        }
+        if next > last {
+            return self.value(last);
+        }
+        let previous = next - 1;
+        let duration = self.times[next] - self.times[previous];
+        let s = (time - self.times[previous]) / duration;
+        let value = match self.interpolation {
+            Interpolation::Step => return self.value(previous),
+            Interpolation::Linear if self.property == Property::Rotation => {
+                let (a, b) = (self.value(previous), self.value(next));
+                return Vec4::from(Quat::from_vec4(a).slerp(Quat::from_vec4(b), s));
+            }
+            Interpolation::Linear => self.value(previous).lerp(self.value(next), s),
+            Interpolation::CubicSpline => {
+                let (s2, s3) = (s * s, s * s * s);
+                let out_tangent = self.values[previous * 3 + 2] * duration;
+                let in_tangent = self.values[next * 3] * duration;
+                self.value(previous) * (2.0 * s3 - 3.0 * s2 + 1.0)
+                    + out_tangent * (s3 - 2.0 * s2 + s)
+                    + self.value(next) * (-2.0 * s3 + 3.0 * s2)
+                    + in_tangent * (s3 - s2)
+            }
+        };
+        // 3次スプラインで補間した四元数は単位長さにならないので正規化する
+        if self.property == Property::Rotation {
+            value.normalize()
+        } else {
+            value
+        }
+    }
+}
+
+// 名前の付いたアニメーション（最後のキーフレームまで再生したら最初に戻る）
+pub struct Clip {
+    pub name: String,
+    pub duration: f32,
+    pub channels: Vec<Channel>,
+}
+
+impl Clip {
+    pub fn new(name: String, channels: Vec<Channel>) -> Self {
+        let duration = channels
+            .iter()
+            .filter_map(|channel| channel.times.last().copied())
+            .fold(0.0, f32::max);
+        Self {
+            name,
+            duration,
+            channels,
+        }
+    }
+
+    // 経過時間を再生時間で割った余り
+    pub fn looped(&self, time: f32) -> f32 {
+        if self.duration > 0.0 {
+            time.rem_euclid(self.duration)
+        } else {
+            0.0
+        }
+    }
+}
+
+// ファイル内のすべてのノードの親子関係と、アニメーションしていないときの変換
+pub struct Skeleton {
+    parents: Vec<Option<usize>>,
+    rest: Vec<Transform>,
+    // 親が子より先に来るノードの順番
+    order: Vec<usize>,
+}
+
+impl Skeleton {
+    // children[i] はノード i の子の番号
+    pub fn new(rest: Vec<Transform>, children: &[Vec<usize>]) -> Self {
+        let mut parents = vec![None; rest.len()];
+        for (parent, children) in children.iter().enumerate() {
+            for &child in children {
+                parents[child] = Some(parent);
+            }
+        }
+        let mut order = Vec::with_capacity(rest.len());
+        let mut stack: Vec<usize> = (0..rest.len()).filter(|&i| parents[i].is_none()).collect();
+        while let Some(node) = stack.pop() {
+            order.push(node);
+            stack.extend(&children[node]);
+        }
+        Self {
+            parents,
+            rest,
+            order,
+        }
+    }
+
+    // clip を time まで再生したときの各ノードのワールド変換（clip が None なら静止した姿勢）
+    pub fn pose(&self, clip: Option<&Clip>, time: f32) -> Vec<Mat4> {
+        let mut locals = self.rest.clone();
+        for channel in clip.into_iter().flat_map(|clip| &clip.channels) {
+            let value = channel.sample(time);
+            let local = &mut locals[channel.node];
+            match channel.property {
+                Property::Translation => local.translation = value.truncate(),
+                Property::Rotation => local.rotation = Quat::from_vec4(value),
+                Property::Scale => local.scale = value.truncate(),
+            }
+        }
+        let mut globals = vec![Mat4::IDENTITY; locals.len()];
+        for &node in &self.order {
+            let local = locals[node].matrix();
+            globals[node] = match self.parents[node] {
+                Some(parent) => globals[parent] * local,
+                None => local,
+            };
+        }
+        globals
+    }
+}
+
+// 関節にするノードと、バインドポーズでのワールド変換の逆行列
+pub struct Skin {
+    pub joints: Vec<usize>,
+    pub inverse_bind_matrices: Vec<Mat4>,
+}
+
+impl Skin {
+    // 頂点を現在の姿勢に動かす関節ごとの行列
+    // （スキンメッシュの頂点はメッシュのノードの変換を使わず、関節の変換だけで配置する）
+    pub fn joint_matrices(&self, pose: &[Mat4]) -> Vec<Mat4> {
+        self.joints
+            .iter()
+            .zip(&self.inverse_bind_matrices)
+            .map(|(&joint, inverse_bind)| pose[joint] * *inverse_bind)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn channel(interpolation: Interpolation, values: Vec<Vec4>) -> Channel {
+        Channel {
+            node: 0,
+            property: Property::Translation,
+            interpolation,
+            times: vec![1.0, 3.0],
+            values,
+        }
+    }
+
+    #[test]
+    fn keyframes_are_interpolated_by_step_linear_and_cubic_spline() {
+        let (a, b) = (Vec4::ZERO, Vec4::new(4.0, 0.0, 0.0, 0.0));
+        let linear = channel(Interpolation::Linear, vec![a, b]);
+        assert_eq!(linear.sample(0.0), a);
+        assert_eq!(linear.sample(2.0), Vec4::new(2.0, 0.0, 0.0, 0.0));
+        assert_eq!(linear.sample(5.0), b);
+        assert_eq!(channel(Interpolation::Step, vec![a, b]).sample(2.9), a);
+        // 接線が 0 の3次スプラインは中間で同じ値、1/4 の位置では線形より端の値に近い
+        let cubic = channel(
+            Interpolation::CubicSpline,
+            vec![Vec4::ZERO, a, Vec4::ZERO, Vec4::ZERO, b, Vec4::ZERO],
+        );
+        assert!((cubic.sample(2.0).x - 2.0).abs() < 1e-5);
+        assert!((cubic.sample(1.5).x - 4.0 * 0.15625).abs() < 1e-5);
+
+        // 回転は球面線形補間で、単位四元数のまま補間される
+        let rotation = Channel {
+            property: Property::Rotation,
+            ..channel(
+                Interpolation::Linear,
+                vec![
+                    Vec4::from(Quat::IDENTITY),
+                    Vec4::from(Quat::from_rotation_z(std::f32::consts::FRAC_PI_2)),
+                ],
+            )
+        };
+        let half = Quat::from_vec4(rotation.sample(2.0));
+        assert!(half.abs_diff_eq(Quat::from_rotation_z(std::f32::consts::FRAC_PI_4), 1e-5));
+    }
+
+    #[test]
+    fn joint_matrices_are_identity_at_rest_and_follow_the_parent_joint() {
+        // 根元の関節 0 と、その 1 上にある子の関節 1
+        let transform = |translation: Vec3| Transform {
+            translation,
+            rotation: Quat::IDENTITY,
+            scale: Vec3::ONE,
+        };
+        let skeleton = Skeleton::new(
+            vec![transform(Vec3::ZERO), transform(Vec3::Y)],
+            &[vec![1], vec![]],
+        );
+        let skin = Skin {
+            joints: vec![0, 1],
+            inverse_bind_matrices: vec![Mat4::IDENTITY, Mat4::from_translation(Vec3::NEG_Y)],
+        };
+        for matrix in skin.joint_matrices(&skeleton.pose(None, 0.0)) {
+            assert!(matrix.abs_diff_eq(Mat4::IDENTITY, 1e-6));
+        }
+
+        // 根元を Z 軸まわりに 90 度回すと、子の関節に付いた先端 (0, 2, 0) は (-2, 0, 0) に動く
+        let clip = Clip::new(
+            "Bend".to_string(),
+            vec![Channel {
+                node: 0,
+                property: Property::Rotation,
+                interpolation: Interpolation::Step,
+                times: vec![0.0],
+                values: vec![Vec4::from(Quat::from_rotation_z(
+                    std::f32::consts::FRAC_PI_2,
+                ))],
+            }],
+        );
+        let matrices = skin.joint_matrices(&skeleton.pose(Some(&clip), 0.0));
+        let tip = matrices[1].transform_point3(Vec3::new(0.0, 2.0, 0.0));
+        assert!(tip.abs_diff_eq(Vec3::new(-2.0, 0.0, 0.0), 1e-5));
+        assert_eq!(clip.looped(0.5), 0.0);
+    }
+}
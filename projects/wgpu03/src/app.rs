@@ -0,0 +1,888 @@
+use std::{
+    collections::HashMap,
+    panic::AssertUnwindSafe,
+    sync::{Arc, atomic::Ordering},
+    time::Instant,
+};
+
+use tracing::{error, info, warn};
+use winit::{
+    application::ApplicationHandler,
+    dpi::{LogicalSize, PhysicalPosition, PhysicalSize},
+    event::{DeviceEvent, DeviceId, ElementState, KeyEvent, WindowEvent},
+    event_loop::{ActiveEventLoop, ControlFlow, EventLoop, EventLoopProxy},
+    keyboard::{KeyCode, ModifiersState, PhysicalKey},
+    window::{Window, WindowAttributes, WindowId},
+};
+
+use crate::clock::FrameClock;
+use crate::config::{Config, WindowConfig};
+use crate::fullscreen::FullscreenMode;
+use crate::gpu::{SurfaceTarget, init_gpu, is_vsync, log_window_system};
+use crate::init::AppError;
+use crate::keybindings::{Action, Keybindings};
+use crate::settings::Settings;
+use crate::simulation::{FixedTimestep, Simulation};
+use crate::state::{PickMethod, State};
+use crate::windows::WindowState;
+use crate::{fullscreen, hot_reload};
+
+// 別のスレッドからイベントループに送るイベント
+pub enum UserEvent {
+    // 監視しているシェーダーのファイルが変更された
+    ShaderChanged,
+    // 別のスレッドで行っていた GPU の初期化が終わった
+    GpuReady(Box<Result<State, AppError>>),
+}
+
+struct App {
+    // コマンドラインで指定した設定（デバイスを作り直すときにも使う）
+    settings: Settings,
+    // 起動時に読み込んだ config.toml（閉じるときに今の状態にして書き戻す）
+    config: Config,
+    // ウィンドウを作って GPU のリソースを初期化するまでと、デバイスを作り直している間は None
+    state: Option<State>,
+    // GPU の初期化が終わるのを待っているウィンドウ（終わったら State が持つ）
+    pending_window: Option<Arc<Window>>,
+    // 初期化を終えたスレッドから GpuReady を送る
+    proxy: EventLoopProxy<UserEvent>,
+    // 最小化されて大きさが 0 になっている間と、ほかのウィンドウに完全に隠れている間は描画を止める
+    minimized: bool,
+    occluded: bool,
+    // suspended から次の resumed までは、サーフェイスがないので描画しない
+    suspended: bool,
+    // GPU の初期化を待つ間に中断された（初期化で作ったサーフェイスの表示先はもう破棄されている）
+    suspended_during_init: bool,
+    // ウィンドウの今の拡大率（物理ピクセルと論理ピクセルの変換に使う）
+    scale_factor: f64,
+    render_mode: RenderMode,
+    // update に渡す経過時間（デバイスを作り直しても続けて進める）
+    clock: FrameClock,
+    // 描画とは別に、固定の間隔で進めるアニメーション
+    timestep: FixedTimestep,
+    simulation: Simulation,
+    keybindings: Keybindings,
+    // 全画面表示にする前のウィンドウの大きさと位置（全画面表示を終えたら元に戻す）
+    windowed: Option<(PhysicalSize<u32>, Option<PhysicalPosition<i32>>)>,
+    // 初期化などに失敗してイベントループを終えた理由（main が原因まで含めて表示する）
+    error: Option<AppError>,
+    // Ctrl+N で開いた、メインのウィンドウ以外のウィンドウ
+    windows: HashMap<WindowId, WindowState>,
+    // Ctrl+N を判定するための、今押されている修飾キー
+    modifiers: ModifiersState,
+    // RenderDoc から起動したときだけ Some（F10 キーで次のフレームを取り込む）
+    #[cfg(feature = "renderdoc")]
+    renderdoc: Option<crate::renderdoc::RenderDoc>,
+}
+
+impl App {
+    fn new(settings: Settings, config: Config, proxy: EventLoopProxy<UserEvent>) -> Self {
+        let keybindings = Keybindings::with_overrides(&config.keybindings).unwrap_or_else(|e| {
+            warn!(
+                "既定のキー割り当てを使います: config.toml の [keybindings]: {:#}",
+                e
+            );
+            Keybindings::default()
+        });
+        #[cfg(feature = "renderdoc")]
+        let renderdoc = crate::renderdoc::RenderDoc::load();
+        #[cfg(feature = "renderdoc")]
+        if renderdoc.is_some() {
+            info!("RenderDoc から起動しました（F10 キーで次のフレームを取り込みます）");
+        }
+        Self {
+            settings,
+            config,
+            state: None,
+            pending_window: None,
+            proxy,
+            minimized: false,
+            occluded: false,
+            suspended: false,
+            suspended_during_init: false,
+            scale_factor: 1.0,
+            render_mode: RenderMode::default(),
+            clock: FrameClock::default(),
+            timestep: FixedTimestep::default(),
+            simulation: Simulation::default(),
+            keybindings,
+            windowed: None,
+            error: None,
+            windows: HashMap::new(),
+            modifiers: ModifiersState::empty(),
+            #[cfg(feature = "renderdoc")]
+            renderdoc,
+        }
+    }
+
+    fn window(&self) -> Option<&Arc<Window>> {
+        self.state.as_ref().and_then(|state| state.window.as_ref())
+    }
+
+    // error を window のタイトルにも表示して、イベントループを終える
+    // イベントループの中でパニックさせると原因がわかりにくいので、main に戻ってから表示する
+    fn fail(&mut self, event_loop: &ActiveEventLoop, window: Option<&Window>, error: AppError) {
+        if let Some(window) = window {
+            window.set_title(&format!("{} - {}", self.settings.title, error));
+        }
+        self.error = Some(error);
+        event_loop.exit();
+    }
+}
+
+// 描画するタイミング（F5 キーで切り替える）
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum RenderMode {
+    // 毎フレーム RedrawRequested の最後に次の再描画を要求する（アニメーションが動く）
+    #[default]
+    Continuous,
+    // 入力や大きさの変更など、表示が変わるときだけ描画する（止まった画面で GPU を休ませる）
+    OnDemand,
+}
+
+impl RenderMode {
+    fn toggled(self) -> Self {
+        match self {
+            Self::Continuous => Self::OnDemand,
+            Self::OnDemand => Self::Continuous,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Continuous => "毎フレーム描画",
+            Self::OnDemand => "必要なときだけ描画",
+        }
+    }
+}
+
+impl App {
+    // Escape キーやウィンドウを閉じたときに、パイプラインキャッシュと設定を保存してから終了する
+    fn quit(&mut self, target: &ActiveEventLoop) {
+        if let Some(state) = self.state.as_ref()
+            && let Err(e) = state.pipeline_cache.save()
+        {
+            warn!("パイプラインキャッシュを保存できませんでした: {:#}", e);
+        }
+        self.save_config();
+        target.exit();
+    }
+
+    // 次に起動したときに同じ大きさと位置で開くよう、今の状態を config.toml に書き戻す
+    // 全画面表示の間は、全画面表示にする前のウィンドウの大きさと位置を保存する
+    fn save_config(&mut self) {
+        let Some(state) = &self.state else {
+            return;
+        };
+        let Some(window) = &state.window else {
+            return;
+        };
+        let (size, position) = self
+            .windowed
+            .unwrap_or_else(|| (window.inner_size(), window.outer_position().ok()));
+        let size = size.to_logical::<u32>(self.scale_factor);
+        self.config.window = WindowConfig {
+            width: Some(size.width),
+            height: Some(size.height),
+            x: position.map(|position| position.x),
+            y: position.map(|position| position.y),
+        };
+        self.config.vsync = Some(is_vsync(state.config.present_mode));
+        self.config.msaa = Some(state.sample_count);
+        if !state.transparent {
+            let color = state.clear_color;
+            self.config.clear_color = Some([color.r, color.g, color.b]);
+        }
+        if let Err(e) = self.config.save() {
+            warn!("設定を保存できませんでした: {:#}", e);
+        }
+    }
+
+    // クリックした位置の物体を調べて表示する
+    fn pick(&mut self, cursor: (f32, f32)) {
+        let Some(state) = self.state.as_mut() else {
+            return;
+        };
+        if !state.has_pickable_objects() {
+            info!("{:?} では物体を選べません", state.shape);
+            return;
+        }
+        match state.pick_method {
+            PickMethod::Gpu => match state.pick_object(cursor) {
+                Ok(Some(object)) => info!("選択した物体: {:?}", object),
+                Ok(None) => info!("選択した物体: なし"),
+                Err(e) => warn!("物体を選択できませんでした: {:#}", e),
+            },
+            PickMethod::Cpu => match state.ray_pick(cursor) {
+                Some((object, point)) => info!(
+                    "選択した物体: {:?}（交点 {:.2}, {:.2}, {:.2}）",
+                    object, point.x, point.y, point.z
+                ),
+                None => info!("選択した物体: なし"),
+            },
+        }
+    }
+
+    // キーに割り当てた操作を行う
+    fn run_action(&mut self, action: Action, target: &ActiveEventLoop) {
+        let Some(window) = self.window().cloned() else {
+            return;
+        };
+        match action {
+            Action::Quit => {
+                self.quit(target);
+                return;
+            }
+            Action::ToggleFullscreen | Action::ToggleExclusiveFullscreen => {
+                let mode = if action == Action::ToggleFullscreen {
+                    FullscreenMode::Borderless
+                } else {
+                    FullscreenMode::Exclusive
+                };
+                let mode = window.fullscreen().is_none().then_some(mode);
+                self.set_fullscreen(mode);
+                match mode {
+                    Some(mode) => info!("全画面表示: {:?}", mode),
+                    None => info!("ウィンドウ表示に戻します"),
+                }
+                return;
+            }
+            Action::CyclePresentMode => {
+                if let Some(state) = self.state.as_mut() {
+                    state.cycle_present_mode();
+                }
+            }
+            Action::TogglePause => {
+                let paused = !self.clock.is_paused();
+                self.clock.set_paused(paused);
+                info!(
+                    "アニメーション: {}",
+                    if paused { "一時停止" } else { "再生" }
+                );
+            }
+            Action::ToggleRenderMode => {
+                self.render_mode = self.render_mode.toggled();
+                info!("描画のタイミング: {}", self.render_mode.label());
+            }
+            Action::CaptureFrame => {
+                #[cfg(feature = "renderdoc")]
+                match &mut self.renderdoc {
+                    Some(renderdoc) => renderdoc.request(),
+                    None => warn!("RenderDoc から起動していないため、フレームを取り込めません"),
+                }
+                #[cfg(not(feature = "renderdoc"))]
+                warn!(
+                    "RenderDoc に対応していないビルドです（--features renderdoc でビルドしてください）"
+                );
+            }
+        }
+        window.request_redraw();
+    }
+
+    // mode の全画面表示にする（None ならウィンドウ表示に戻し、全画面表示にする前の大きさと位置にする）
+    // 大きさが変わると Resized が届き、いつもと同じようにサーフェイスを設定し直す
+    fn set_fullscreen(&mut self, mode: Option<FullscreenMode>) {
+        let Some(window) = self.window().cloned() else {
+            return;
+        };
+        match mode {
+            Some(mode) => {
+                if window.fullscreen().is_none() {
+                    self.windowed = Some((window.inner_size(), window.outer_position().ok()));
+                }
+                window.set_fullscreen(Some(fullscreen::fullscreen(&window, mode)));
+            }
+            None => {
+                window.set_fullscreen(None);
+                if let Some((size, position)) = self.windowed.take() {
+                    // すぐに大きさが変わった場合は Resized が届かないことがあるので、ここで設定し直す
+                    if let Some(size) = window.request_inner_size(size)
+                        && let Some(state) = self.state.as_mut()
+                    {
+                        state.resize(size);
+                    }
+                    if let Some(position) = position {
+                        window.set_outer_position(position);
+                    }
+                }
+            }
+        }
+        window.request_redraw();
+    }
+
+    // 必要なときだけ描画する場合に、表示が変わったので再描画を要求する
+    // （毎フレーム描画する場合はすでに次の再描画を要求している）
+    fn request_redraw_on_demand(&self) {
+        if self.render_mode == RenderMode::OnDemand {
+            self.request_scene_redraw();
+        }
+    }
+
+    fn paused(&self) -> bool {
+        self.minimized || self.occluded || self.suspended
+    }
+
+    // メインのウィンドウが最小化されたり隠れたりして描画を止めている間に、代わりにシーンを描画するウィンドウ
+    // （ほかのウィンドウがなければ None。中断している間はサーフェイスがないのでどのウィンドウも描画しない）
+    fn scene_window(&self) -> Option<WindowId> {
+        if !self.paused() || self.suspended {
+            return None;
+        }
+        self.windows.keys().min().copied()
+    }
+
+    // シーンを描画するウィンドウ（ふつうはメインのウィンドウ）に再描画を要求する
+    fn request_scene_redraw(&self) {
+        match self.scene_window().and_then(|id| self.windows.get(&id)) {
+            Some(window_state) => window_state.window.request_redraw(),
+            None => {
+                if let Some(state) = &self.state {
+                    state.request_redraw();
+                }
+            }
+        }
+    }
+
+    // 止めていた描画を再開する（描画を止めている間は再描画を要求していない）
+    fn resume_rendering(&self, was_paused: bool) {
+        if was_paused
+            && !self.paused()
+            && let Some(state) = &self.state
+        {
+            info!("描画を再開します");
+            state.request_redraw();
+        }
+    }
+
+    // Ctrl+N で、メインのウィンドウと同じシーンを表示するウィンドウを開く
+    fn open_window(&mut self, target: &ActiveEventLoop) {
+        let Some(state) = &self.state else {
+            return;
+        };
+        let attributes = WindowAttributes::default()
+            .with_title(format!(
+                "{} ({})",
+                self.settings.title,
+                self.windows.len() + 2
+            ))
+            .with_inner_size(LogicalSize::new(self.settings.width, self.settings.height));
+        let window = match target.create_window(attributes) {
+            Ok(window) => Arc::new(window),
+            Err(e) => {
+                warn!("ウィンドウを開けませんでした: {}", e);
+                return;
+            }
+        };
+        match state.open_window(window) {
+            Ok(window_state) => {
+                self.windows.insert(window_state.window.id(), window_state);
+                info!("ウィンドウを開きました（{} 個）", self.windows.len() + 1);
+                self.request_redraw_on_demand();
+            }
+            Err(e) => warn!("ウィンドウを開けませんでした: {:#}", e),
+        }
+    }
+
+    // メインのウィンドウ以外のウィンドウのイベント（シーンの操作はメインのウィンドウで行う）
+    fn extra_window_event(&mut self, target: &ActiveEventLoop, id: WindowId, event: WindowEvent) {
+        match event {
+            WindowEvent::Resized(size) => {
+                if let (Some(window_state), Some(state)) = (self.windows.get_mut(&id), &self.state)
+                {
+                    window_state.resize(&state.device, size);
+                }
+                self.request_redraw_on_demand();
+            }
+            // そのウィンドウのサーフェイスだけを捨てて閉じる
+            WindowEvent::CloseRequested => {
+                self.windows.remove(&id);
+                info!(
+                    "ウィンドウを閉じました（残り {} 個）",
+                    self.windows.len() + 1
+                );
+            }
+            WindowEvent::RedrawRequested => self.redraw_extra_window(id),
+            WindowEvent::ModifiersChanged(modifiers) => self.modifiers = modifiers.state(),
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(KeyCode::KeyN),
+                        state: ElementState::Pressed,
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } if self.modifiers.control_key() => self.open_window(target),
+            _ => {}
+        }
+    }
+
+    // ほかのウィンドウが残っていれば、そのうちの1つをメインのウィンドウにして続ける
+    // 最後のウィンドウを閉じたら終了する
+    fn close_main_window(&mut self, target: &ActiveEventLoop) {
+        let Some(id) = self.windows.keys().next().copied() else {
+            self.quit(target);
+            return;
+        };
+        let Some(state) = self.state.as_mut() else {
+            self.quit(target);
+            return;
+        };
+        if let Some(window_state) = self.windows.remove(&id) {
+            self.scale_factor = window_state.window.scale_factor();
+            state.adopt_window(window_state);
+            state.set_scale_factor(self.scale_factor);
+            state.request_redraw();
+            info!(
+                "ウィンドウを閉じました（残り {} 個）",
+                self.windows.len() + 1
+            );
+        }
+    }
+
+    // 別のスレッドで初期化した State を受け取り、描画を始める
+    fn gpu_ready(&mut self, event_loop: &ActiveEventLoop, result: Result<State, AppError>) {
+        let Some(window) = self.pending_window.take() else {
+            return;
+        };
+        let state = match result {
+            Ok(state) => state,
+            Err(e) => {
+                self.fail(event_loop, Some(&window), e);
+                return;
+            }
+        };
+
+        // 初期化を待つ間に拡大率が変わっていることがあるので、今の拡大率に合わせる
+        self.scale_factor = window.scale_factor();
+        self.state = Some(state);
+        if let Some(state) = self.state.as_mut() {
+            // 待つ間に中断されていれば、サーフェイスを捨てて今のウィンドウに作り直す
+            // （まだ中断中なら、次の resumed で作る）
+            if std::mem::take(&mut self.suspended_during_init) {
+                state.suspend();
+                if !self.suspended
+                    && let Err(e) = state.resume()
+                {
+                    self.fail(event_loop, Some(&window), e.into());
+                    return;
+                }
+            }
+            state.set_scale_factor(self.scale_factor);
+            // 待つ間に届いた Resized は使っていないので、今の大きさで設定し直す
+            let size = window.inner_size();
+            if size.width > 0 && size.height > 0 {
+                state.resize(size);
+            }
+            // アダプタが決まったので、シェーディングとともにタイトルに表示する
+            state.update_title();
+        }
+        if let Some(mode) = self.settings.fullscreen {
+            self.set_fullscreen(Some(mode));
+        }
+
+        // 最初のフレームの描画を要求する（以降は毎フレーム再描画を要求し続ける）
+        window.request_redraw();
+        info!("リソースの初期化が完了しました。")
+    }
+
+    // メインのウィンドウで最後に描画したシーンを表示する
+    // メインのウィンドウが描画を止めている間は、scene_window がシーンを描画してほかのウィンドウにも表示させる
+    fn redraw_extra_window(&mut self, id: WindowId) {
+        let drives_scene = self.scene_window() == Some(id);
+        let (Some(state), Some(window_state)) = (self.state.as_mut(), self.windows.get(&id)) else {
+            return;
+        };
+        if state.device_lost.load(Ordering::Relaxed) {
+            return;
+        }
+        if !drives_scene {
+            window_state.present(&state.device, &state.queue, &state.post);
+            return;
+        }
+        let frame_start = Instant::now();
+        let frame = self.clock.tick();
+        for _ in 0..self.timestep.advance(frame.animation_step) {
+            self.simulation.step();
+        }
+        state.render_scene(frame, self.simulation.angle(self.timestep.alpha()));
+        window_state.present(&state.device, &state.queue, &state.post);
+        for (other, window_state) in &self.windows {
+            if *other != id {
+                window_state.window.request_redraw();
+            }
+        }
+        state.cpu_stats.push(frame_start.elapsed());
+        if self.render_mode == RenderMode::Continuous {
+            window_state.window.request_redraw();
+        }
+    }
+
+    // 1フレームを描画し、毎フレーム描画する場合は次の再描画を要求する
+    fn redraw(&mut self, target: &ActiveEventLoop) {
+        // 止めている間は描画も次の再描画の要求もしない（再開するときに要求する）
+        if self.paused() {
+            return;
+        }
+        if self
+            .state
+            .as_ref()
+            .is_some_and(|state| state.device_lost.load(Ordering::Relaxed))
+        {
+            self.recover_device(target);
+            return;
+        }
+        let Some(state) = self.state.as_mut() else {
+            return;
+        };
+        let frame_start = Instant::now();
+        let frame = self.clock.tick();
+        for _ in 0..self.timestep.advance(frame.animation_step) {
+            self.simulation.step();
+        }
+        // 取り込みを予約していれば、このフレームの描画と表示だけを RenderDoc に取り込む
+        #[cfg(feature = "renderdoc")]
+        if let Some(renderdoc) = &mut self.renderdoc {
+            renderdoc.begin_frame();
+        }
+        let result = state.frame(frame, self.simulation.angle(self.timestep.alpha()));
+        #[cfg(feature = "renderdoc")]
+        if let Some(renderdoc) = &mut self.renderdoc {
+            renderdoc.end_frame();
+        }
+        if let Err(e) = result {
+            error!("フレームを取得できないため終了します: {}", e);
+            target.exit();
+            return;
+        }
+        // ほかのウィンドウには、それぞれの RedrawRequested でこのフレームで描画したシーンを表示する
+        for window_state in self.windows.values() {
+            window_state.window.request_redraw();
+        }
+        state.cpu_stats.push(frame_start.elapsed());
+        // アニメーションを続けるため、フレームの最後に次の再描画を明示的に要求する
+        // FPS の上限があれば、about_to_wait で描画する時刻まで待ってから要求する
+        if self.render_mode == RenderMode::Continuous
+            && !(state.limits_frame_rate() && state.frame_limiter.schedule(frame_start))
+        {
+            state.request_redraw();
+        }
+    }
+
+    // 失われたデバイスのリソースをすべて捨てて、init_gpu で作り直す
+    // 作り直せなければ（アダプタが見つからないなど）終了する
+    fn recover_device(&mut self, event_loop: &ActiveEventLoop) {
+        let Some(window) = self.window().cloned() else {
+            return;
+        };
+        // ほかのウィンドウのサーフェイスも古いデバイスと一緒に捨て、新しいデバイスで作り直す
+        let windows: Vec<Arc<Window>> = self
+            .windows
+            .drain()
+            .map(|(_, window_state)| window_state.window)
+            .collect();
+        self.state = None;
+        match std::panic::catch_unwind(AssertUnwindSafe(|| {
+            let target = SurfaceTarget::new(window.clone(), &self.settings)?;
+            pollster::block_on(init_gpu(target, &self.settings))
+        })) {
+            Ok(Ok(state)) => {
+                state.update_title();
+                for window in windows {
+                    match state.open_window(window) {
+                        Ok(window_state) => {
+                            self.windows.insert(window_state.window.id(), window_state);
+                        }
+                        Err(e) => warn!("ウィンドウを作り直せませんでした: {:#}", e),
+                    }
+                }
+                self.state = Some(state);
+                info!("デバイスを作り直しました");
+                window.request_redraw();
+            }
+            Ok(Err(e)) => {
+                error!("デバイスを作り直せなかったため終了します");
+                self.fail(event_loop, Some(&window), e);
+            }
+            Err(_) => {
+                error!("デバイスを作り直せなかったため終了します");
+                event_loop.exit();
+            }
+        }
+    }
+}
+
+impl ApplicationHandler<UserEvent> for App {
+    // 中断から再開したときにも呼ばれるので、ウィンドウと GPU のリソースは最初の1回だけ作り、
+    // 2回目以降は今のウィンドウに表示するサーフェイスだけを作り直す
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        // 初期化に失敗して終了する途中は何もしない
+        if self.error.is_some() {
+            return;
+        }
+        let was_paused = self.paused();
+        self.suspended = false;
+        // 初期化が終わるのを待っている間は、gpu_ready でサーフェイスを確かめる
+        if self.pending_window.is_some() {
+            return;
+        }
+        if let Some(state) = self.state.as_mut() {
+            if let Err(e) = state.resume() {
+                error!("サーフェイスを作り直せなかったため終了します");
+                let window = state.window.clone();
+                self.fail(event_loop, window.as_deref(), e.into());
+                return;
+            }
+            info!("サーフェイスを作り直しました");
+            self.resume_rendering(was_paused);
+            return;
+        }
+
+        let mut attributes = WindowAttributes::default()
+            .with_title(&self.settings.title)
+            .with_inner_size(LogicalSize::new(self.settings.width, self.settings.height))
+            .with_transparent(self.settings.transparent);
+        if let Some((x, y)) = self.settings.position {
+            attributes = attributes.with_position(PhysicalPosition::new(x, y));
+        }
+        let window = match event_loop.create_window(attributes) {
+            Ok(window) => Arc::new(window),
+            Err(e) => {
+                self.fail(event_loop, None, e.into());
+                return;
+            }
+        };
+
+        log_window_system(event_loop);
+
+        // サーフェイスはイベントループのスレッドで作る（SurfaceTarget を参照）
+        let target = match SurfaceTarget::new(window.clone(), &self.settings) {
+            Ok(target) => target,
+            Err(e) => {
+                self.fail(event_loop, Some(&window), e);
+                return;
+            }
+        };
+
+        // `--print-caps` ではサーフェイスの対応状況まで表示して終了する
+        if self.settings.print_caps {
+            pollster::block_on(target.print_caps(&self.settings));
+            event_loop.exit();
+            return;
+        }
+
+        // アダプタとデバイスを待つ間もイベントループを止めないよう、別のスレッドで初期化する
+        // 終わるまでは描画せず、ウィンドウを動かしたり閉じたりだけできる
+        self.scale_factor = window.scale_factor();
+        self.pending_window = Some(window);
+        let settings = self.settings.clone();
+        let proxy = self.proxy.clone();
+        let init = async move {
+            let result = init_gpu(target, &settings).await;
+            // 初期化を待たずに閉じられていれば、受け取るイベントループはもうない
+            let _ = proxy.send_event(UserEvent::GpuReady(Box::new(result)));
+        };
+        // wasm ではスレッドを作れないので、ブラウザのイベントループで待つ
+        #[cfg(target_arch = "wasm32")]
+        wasm_bindgen_futures::spawn_local(init);
+        #[cfg(not(target_arch = "wasm32"))]
+        std::thread::spawn(move || pollster::block_on(init));
+    }
+
+    fn window_event(&mut self, target: &ActiveEventLoop, id: WindowId, event: WindowEvent) {
+        if self.windows.contains_key(&id) {
+            self.extra_window_event(target, id, event);
+            return;
+        }
+        // クリックはカメラの操作（オービットカメラの左ドラッグ）に渡す前に見分ける
+        if let Some(cursor) = self.state.as_mut().and_then(|state| state.click(&event)) {
+            self.pick(cursor);
+        }
+        // 設定パネルやカメラ操作に使われたイベントはここで処理を終える
+        if let Some(state) = self.state.as_mut()
+            && state.input(&event)
+        {
+            self.request_redraw_on_demand();
+            return;
+        }
+
+        match event {
+            WindowEvent::Resized(size) => {
+                let was_paused = self.paused();
+                // 最小化すると 0x0 になるので、サーフェイスは設定し直さずに元の大きさのまま止めておく
+                self.minimized = size.width == 0 || size.height == 0;
+                if self.minimized {
+                    // 止めている間はほかのウィンドウがシーンを描画する
+                    self.request_scene_redraw();
+                    return;
+                }
+                if let Some(state) = self.state.as_mut() {
+                    state.resize(size);
+                }
+                self.request_redraw_on_demand();
+                self.resume_rendering(was_paused);
+            }
+            WindowEvent::Occluded(occluded) => {
+                let was_paused = self.paused();
+                self.occluded = occluded;
+                if occluded {
+                    self.request_scene_redraw();
+                }
+                self.resume_rendering(was_paused);
+            }
+            WindowEvent::ScaleFactorChanged {
+                scale_factor,
+                mut inner_size_writer,
+            } => {
+                let old = self.scale_factor;
+                self.scale_factor = scale_factor;
+                if let Some(state) = self.state.as_mut() {
+                    state.scale_factor_changed(old, scale_factor, &mut inner_size_writer);
+                }
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                if let Some(state) = self.state.as_mut() {
+                    state.cursor = Some((position.x as f32, position.y as f32));
+                }
+            }
+            WindowEvent::CursorLeft { .. } => {
+                if let Some(state) = self.state.as_mut() {
+                    state.cursor = None;
+                }
+            }
+            WindowEvent::CloseRequested => self.close_main_window(target),
+            WindowEvent::ModifiersChanged(modifiers) => self.modifiers = modifiers.state(),
+            // Android の戻るボタンで終了する
+            #[cfg(target_os = "android")]
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        logical_key:
+                            winit::keyboard::Key::Named(winit::keyboard::NamedKey::BrowserBack),
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                ..
+            } => self.quit(target),
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(code),
+                        state: ElementState::Pressed,
+                        repeat,
+                        ..
+                    },
+                ..
+            } => {
+                if code == KeyCode::KeyN && self.modifiers.control_key() {
+                    if !repeat {
+                        self.open_window(target);
+                    }
+                    return;
+                }
+                // 割り当てた操作が優先され、切り替える操作はキーリピートでは繰り返さない
+                if let Some(action) = self.keybindings.action(code) {
+                    if !(repeat && action.is_toggle()) {
+                        self.run_action(action, target);
+                    }
+                    return;
+                }
+                if repeat {
+                    return;
+                }
+                if let Some(state) = self.state.as_mut()
+                    && state.key_pressed(code)
+                {
+                    // Tab や W キーでシェーディングが変わっていればタイトルに反映する
+                    state.update_title();
+                    state.request_redraw();
+                }
+            }
+            WindowEvent::RedrawRequested => self.redraw(target),
+            // ソフトウェアキーボードなどの変換中の文字は使わない
+            WindowEvent::Ime(_) => {}
+            _ => {}
+        }
+    }
+
+    // イベントを処理し終えて眠る前に、FPS の上限で待っているフレームの時刻まで眠らせる
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        let Some(state) = self.state.as_mut() else {
+            return;
+        };
+        if state.frame_limiter.wait() {
+            state.request_redraw();
+        }
+        event_loop.set_control_flow(match state.frame_limiter.wake_time() {
+            Some(wake) => ControlFlow::WaitUntil(wake),
+            None => ControlFlow::Wait,
+        });
+    }
+
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: UserEvent) {
+        match event {
+            UserEvent::GpuReady(result) => self.gpu_ready(event_loop, *result),
+            UserEvent::ShaderChanged => {
+                if let Some(state) = self.state.as_mut() {
+                    state.shader_changed = true;
+                    state.request_redraw();
+                }
+            }
+        }
+    }
+
+    // Android ではこの後ウィンドウの表示先が破棄されるので、次の resumed まで描画しない
+    fn suspended(&mut self, _event_loop: &ActiveEventLoop) {
+        self.suspended = true;
+        if self.pending_window.is_some() {
+            self.suspended_during_init = true;
+        }
+        if let Some(state) = self.state.as_mut() {
+            state.suspend();
+        }
+    }
+
+    fn exiting(&mut self, _event_loop: &ActiveEventLoop) {
+        let Some(state) = self.state.as_mut() else {
+            return;
+        };
+        // 録画中のフレームを書き終えてから終了する
+        if let Some(recorder) = &mut state.recorder {
+            recorder.finish(&state.device);
+        }
+        // 提出したコマンドがすべて終わってからリソースを破棄する
+        state.device.poll(wgpu::Maintain::Wait);
+    }
+
+    fn device_event(&mut self, _target: &ActiveEventLoop, _id: DeviceId, event: DeviceEvent) {
+        // マウスの生の移動量はウィンドウイベントではなくデバイスイベントとして届く
+        if let Some(state) = self.state.as_mut() {
+            state.device_input(&event);
+            self.request_redraw_on_demand();
+        }
+    }
+}
+
+// ウィンドウを開いて描画するイベントループを、ウィンドウが閉じられるまで回す
+// （デスクトップでは run、Android では android_main がイベントループを作って呼ぶ）
+pub fn run_event_loop(
+    event_loop: EventLoop<UserEvent>,
+    settings: Settings,
+    config: Config,
+) -> anyhow::Result<()> {
+    // イベント待ちで動作し、毎フレームの描画は RedrawRequested の最後で request_redraw を呼んで行う
+    event_loop.set_control_flow(ControlFlow::Wait);
+
+    // シェーダーのファイルを保存し直したら、監視用のスレッドからイベントループを起こして読み込み直す
+    // （Android にはシェーダーのディレクトリがないので、埋め込んだシェーダーだけを使う）
+    #[cfg(not(target_os = "android"))]
+    hot_reload::watch(hot_reload::SHADER_DIR.into(), event_loop.create_proxy());
+
+    let mut app = App::new(settings, config, event_loop.create_proxy());
+    event_loop.run_app(&mut app)?;
+    // resumed などで失敗してイベントループを終えた場合は、その原因を返す
+    match app.error {
+        Some(e) => Err(e.into()),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,169 @@
+use anyhow::{Result, bail};
+
+use crate::sprite::UvRect;
+use crate::texture::Texture;
+
+// 線形補間やミップマップで隣の画像の色がにじまないよう、画像の間に空ける画素数
+const PADDING: u32 = 1;
+// 最初に試すアトラスの最小の大きさ
+const MIN_SIZE: u32 = 64;
+
+// size × size の正方形に、高さの順に並べた画像を棚（横一列の段）に左から詰めていく
+// 収まらない場合は None
+fn pack_into(sizes: &[(u32, u32)], size: u32) -> Option<Vec<(u32, u32)>> {
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by_key(|&i| std::cmp::Reverse((sizes[i].1, sizes[i].0)));
+
+    let mut positions = vec![(0, 0); sizes.len()];
+    let (mut x, mut y, mut shelf_height) = (0, 0, 0);
+    for i in order {
+        let (width, height) = sizes[i];
+        if width > size || height > size {
+            return None;
+        }
+        // 今の棚に入りきらなければ、その下に新しい棚を作る
+        if x + width > size {
+            y += shelf_height + PADDING;
+            x = 0;
+            shelf_height = 0;
+        }
+        if y + height > size {
+            return None;
+        }
+        positions[i] = (x, y);
+        x += width + PADDING;
+        shelf_height = shelf_height.max(height);
+    }
+    Some(positions)
+}
+
+// すべての画像が収まるまでアトラスの一辺を2倍にしていき、その大きさと各画像の左上の位置を返す
+// max_size（デバイスのテクスチャの最大サイズ）でも収まらない場合はエラー
+pub fn pack(sizes: &[(u32, u32)], max_size: u32) -> Result<(u32, Vec<(u32, u32)>)> {
+    let area: u64 = sizes.iter().map(|&(w, h)| w as u64 * h as u64).sum();
+    let largest = sizes.iter().map(|&(w, h)| w.max(h)).max().unwrap_or(0);
+    // 面積と最も大きい画像の辺から、少なくとも必要な大きさを見積もってから始める
+    let mut size = (area as f64).sqrt().ceil().max(largest as f64) as u32;
+    size = size.max(MIN_SIZE).next_power_of_two().min(max_size);
+    loop {
+        if let Some(positions) = pack_into(sizes, size) {
+            return Ok((size, positions));
+        }
+        if size >= max_size {
+            bail!(
+                "{} 枚の画像が最大サイズ {}x{} のアトラスに収まりません",
+                sizes.len(),
+                max_size,
+                max_size
+            );
+        }
+        size = (size * 2).min(max_size);
+    }
+}
+
+// 1枚のテクスチャに詰め込んだ画像と、それぞれの画像のテクスチャ上の範囲
+pub struct Atlas {
+    pub texture: Texture,
+    // add で追加した順番に並ぶ
+    pub uv_rects: Vec<UvRect>,
+    // 元の画像のピクセル単位の大きさ
+    pub sizes: Vec<glam::Vec2>,
+}
+
+// 小さな画像をいくつも追加してから、1枚のアトラスのテクスチャにまとめる
+#[derive(Default)]
+pub struct AtlasBuilder {
+    images: Vec<image::RgbaImage>,
+}
+
+impl AtlasBuilder {
+    // 追加した画像の番号（Atlas の uv_rects の添字）を返す
+    pub fn add(&mut self, image: &image::DynamicImage) -> usize {
+        self.images.push(image.to_rgba8());
+        self.images.len() - 1
+    }
+
+    pub fn build(&self, device: &wgpu::Device, queue: &wgpu::Queue, label: &str) -> Result<Atlas> {
+        let sizes: Vec<(u32, u32)> = self.images.iter().map(|img| img.dimensions()).collect();
+        let max_size = device.limits().max_texture_dimension_2d;
+        let (size, positions) = pack(&sizes, max_size)?;
+
+        let mut pixels = image::RgbaImage::new(size, size);
+        for (img, &(x, y)) in self.images.iter().zip(&positions) {
+            image::imageops::replace(&mut pixels, img, x as i64, y as i64);
+        }
+        let texture = Texture::from_image(
+            device,
+            queue,
+            &image::DynamicImage::ImageRgba8(pixels),
+            Some(label),
+        );
+
+        let uv_rects = sizes
+            .iter()
+            .zip(&positions)
+            .map(|(&(w, h), &(x, y))| UvRect {
+                min: glam::Vec2::new(x as f32, y as f32) / size as f32,
+                max: glam::Vec2::new((x + w) as f32, (y + h) as f32) / size as f32,
+            })
+            .collect();
+        Ok(Atlas {
+            texture,
+            uv_rects,
+            sizes: sizes
+                .iter()
+                .map(|&(w, h)| glam::Vec2::new(w as f32, h as f32))
+                .collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // すべての画像がアトラスの中にあり、互いに重なっていないことを確かめる
+    fn assert_valid(sizes: &[(u32, u32)], size: u32, positions: &[(u32, u32)]) {
+        let rects: Vec<_> = sizes
+            .iter()
+            .zip(positions)
+            .map(|(&(w, h), &(x, y))| (x, y, x + w, y + h))
+            .collect();
+        for (i, a) in rects.iter().enumerate() {
+            assert!(a.2 <= size && a.3 <= size, "画像 {} がはみ出している", i);
+            for b in &rects[i + 1..] {
+                let overlaps = a.0 < b.2 && b.0 < a.2 && a.1 < b.3 && b.1 < a.3;
+                assert!(!overlaps, "{:?} と {:?} が重なっている", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn packs_pathological_sizes_without_overlap() {
+        let cases: Vec<Vec<(u32, u32)>> = vec![
+            vec![],
+            vec![(0, 0), (0, 5), (5, 0)],
+            vec![(1, 1); 1000],
+            // 細長い画像が縦横に混ざっている
+            vec![(1, 500), (500, 1), (1, 500), (500, 1), (37, 91)],
+            // 奇数の大きさばかり
+            (1..60).map(|i| (i * 3 + 1, 61 - i)).collect(),
+        ];
+        for sizes in cases {
+            let (size, positions) = pack(&sizes, 4096).unwrap();
+            assert!(size.is_power_of_two());
+            assert_valid(&sizes, size, &positions);
+        }
+    }
+
+    #[test]
+    fn grows_up_to_the_device_limit_then_errors() {
+        // 最大サイズちょうどの画像は余白なしで収まる
+        assert_eq!(pack(&[(256, 256)], 256).unwrap().0, 256);
+        // 1枚なら収まる大きさでも、2枚になると大きくできずに失敗する
+        assert_eq!(pack(&[(200, 200)], 1024).unwrap().0, 256);
+        assert_eq!(pack(&[(200, 200); 2], 1024).unwrap().0, 512);
+        assert!(pack(&[(200, 200); 2], 256).is_err());
+        assert!(pack(&[(257, 1)], 256).is_err());
+    }
+}
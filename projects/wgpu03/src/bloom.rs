@@ -0,0 +1,361 @@
+use wgpu::util::DeviceExt;
+
+use crate::crash;
+use crate::gpu_timer;
+use crate::pipeline_cache::PipelineCache;
+use crate::post::PostPass;
+use crate::texture::Texture;
+
+// 縮小テクスチャの段数の上限と、最も小さいテクスチャの短辺の最小サイズ
+const MAX_LEVELS: usize = 6;
+const MIN_LEVEL_SIZE: u32 = 4;
+
+// 実行時に調整できるブルームの設定（bloom.wgsl の Bloom 構造体に対応）
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct BloomSettings {
+    pub threshold: f32,
+    pub knee: f32,
+    pub radius: f32,
+    pub intensity: f32,
+}
+
+impl Default for BloomSettings {
+    fn default() -> Self {
+        Self {
+            threshold: 0.8,
+            knee: 0.2,
+            radius: 1.0,
+            intensity: 0.6,
+        }
+    }
+}
+
+// 半分ずつ縮小していくテクスチャのサイズを求める
+// 短辺が MIN_LEVEL_SIZE を下回る段は作らないので、小さなウィンドウでは段数が減る（0 段もありうる）
+fn level_sizes(width: u32, height: u32) -> Vec<(u32, u32)> {
+    (1..=MAX_LEVELS as u32)
+        .map(|level| ((width >> level).max(1), (height >> level).max(1)))
+        .take_while(|&(w, h)| w.min(h) >= MIN_LEVEL_SIZE)
+        .collect()
+}
+
+// 縮小テクスチャの1段（描画先のテクスチャと、それを入力にするバインドグループ）
+struct Level {
+    texture: Texture,
+    bind_group: wgpu::BindGroup,
+}
+
+// 明るい画素の抽出 → 縮小しながらぼかす → 拡大しながら加算 → シーンに合成、の順に描画するブルーム
+pub struct Bloom {
+    threshold_pipeline: wgpu::RenderPipeline,
+    downsample_pipeline: wgpu::RenderPipeline,
+    upsample_pipeline: wgpu::RenderPipeline,
+    composite_pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    settings_buffer: wgpu::Buffer,
+    pub settings: BloomSettings,
+    // シーンのテクスチャを入力にするバインドグループ
+    scene_bind_group: wgpu::BindGroup,
+    levels: Vec<Level>,
+}
+
+impl Bloom {
+    pub fn new(
+        device: &wgpu::Device,
+        pipeline_cache: &PipelineCache,
+        shader: &wgpu::ShaderModule,
+        scene: &Texture,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Bloom Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let settings = BloomSettings::default();
+        let settings_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Bloom Settings Buffer"),
+            contents: bytemuck::cast_slice(&[settings]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Bloom Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        // 拡大と合成は描画先にすでにある色に足し合わせる
+        // アルファは描画先のまま残す（透明なウィンドウでは、シーンのアルファがそのまま背景の透け具合になる）
+        let additive = wgpu::BlendState {
+            color: wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::One,
+                dst_factor: wgpu::BlendFactor::One,
+                operation: wgpu::BlendOperation::Add,
+            },
+            alpha: wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::Zero,
+                dst_factor: wgpu::BlendFactor::One,
+                operation: wgpu::BlendOperation::Add,
+            },
+        };
+        let pipeline = |entry_point: &str, blend: wgpu::BlendState| {
+            pipeline_cache.create(|cache| {
+                device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label: Some(entry_point),
+                    layout: Some(&layout),
+                    vertex: wgpu::VertexState {
+                        module: shader,
+                        entry_point: Some("vs_main"),
+                        buffers: &[],
+                        compilation_options: Default::default(),
+                    },
+                    fragment: Some(wgpu::FragmentState {
+                        module: shader,
+                        entry_point: Some(entry_point),
+                        targets: &[Some(wgpu::ColorTargetState {
+                            format: PostPass::FORMAT,
+                            blend: Some(blend),
+                            write_mask: wgpu::ColorWrites::ALL,
+                        })],
+                        compilation_options: Default::default(),
+                    }),
+                    primitive: wgpu::PrimitiveState::default(),
+                    depth_stencil: None,
+                    multisample: wgpu::MultisampleState::default(),
+                    multiview: None,
+                    cache,
+                })
+            })
+        };
+        let threshold_pipeline = pipeline("fs_threshold", wgpu::BlendState::REPLACE);
+        let downsample_pipeline = pipeline("fs_downsample", wgpu::BlendState::REPLACE);
+        let upsample_pipeline = pipeline("fs_upsample", additive);
+        let composite_pipeline = pipeline("fs_composite", additive);
+
+        let scene_bind_group =
+            create_bind_group(device, &bind_group_layout, scene, &settings_buffer);
+        let levels = create_levels(device, &bind_group_layout, &settings_buffer, width, height);
+
+        Self {
+            threshold_pipeline,
+            downsample_pipeline,
+            upsample_pipeline,
+            composite_pipeline,
+            bind_group_layout,
+            settings_buffer,
+            settings,
+            scene_bind_group,
+            levels,
+        }
+    }
+
+    // シーンのテクスチャが作り直されたら、縮小テクスチャもそのサイズに合わせて作り直す
+    pub fn resize(&mut self, device: &wgpu::Device, scene: &Texture, width: u32, height: u32) {
+        self.scene_bind_group = create_bind_group(
+            device,
+            &self.bind_group_layout,
+            scene,
+            &self.settings_buffer,
+        );
+        self.levels = create_levels(
+            device,
+            &self.bind_group_layout,
+            &self.settings_buffer,
+            width,
+            height,
+        );
+    }
+
+    // settings を変更した後に呼び出してユニフォームバッファに反映する
+    pub fn write_settings(&self, queue: &wgpu::Queue) {
+        queue.write_buffer(
+            &self.settings_buffer,
+            0,
+            bytemuck::cast_slice(&[self.settings]),
+        );
+    }
+
+    // ウィンドウが小さすぎて縮小テクスチャを作れない場合と、強さが 0 の場合は draw が何も記録しない
+    pub fn is_enabled(&self) -> bool {
+        !self.levels.is_empty() && self.settings.intensity > 0.0
+    }
+
+    // scene（シーンを描画したテクスチャ）にブルームを加算する
+    // timestamp_writes の開始は最初のパス、終了は最後のパスで書き込む
+    pub fn draw(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        scene: &wgpu::TextureView,
+        timestamp_writes: Option<wgpu::RenderPassTimestampWrites<'_>>,
+    ) {
+        let Some(first) = self.levels.first().filter(|_| self.is_enabled()) else {
+            return;
+        };
+        fullscreen_pass(
+            encoder,
+            "Bloom Threshold",
+            &self.threshold_pipeline,
+            &self.scene_bind_group,
+            &first.texture.view,
+            true,
+            timestamp_writes.clone().map(gpu_timer::beginning_only),
+        );
+        for pair in self.levels.windows(2) {
+            fullscreen_pass(
+                encoder,
+                "Bloom Downsample",
+                &self.downsample_pipeline,
+                &pair[0].bind_group,
+                &pair[1].texture.view,
+                true,
+                None,
+            );
+        }
+        for pair in self.levels.windows(2).rev() {
+            fullscreen_pass(
+                encoder,
+                "Bloom Upsample",
+                &self.upsample_pipeline,
+                &pair[1].bind_group,
+                &pair[0].texture.view,
+                false,
+                None,
+            );
+        }
+        fullscreen_pass(
+            encoder,
+            "Bloom Composite",
+            &self.composite_pipeline,
+            &first.bind_group,
+            scene,
+            false,
+            timestamp_writes.map(gpu_timer::end_only),
+        );
+    }
+}
+
+fn create_levels(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    settings_buffer: &wgpu::Buffer,
+    width: u32,
+    height: u32,
+) -> Vec<Level> {
+    level_sizes(width, height)
+        .into_iter()
+        .map(|(w, h)| {
+            let texture =
+                Texture::create_render_target(device, w, h, PostPass::FORMAT, "Bloom Texture");
+            let bind_group = create_bind_group(device, layout, &texture, settings_buffer);
+            Level {
+                texture,
+                bind_group,
+            }
+        })
+        .collect()
+}
+
+fn create_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    texture: &Texture,
+    settings_buffer: &wgpu::Buffer,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Bloom Bind Group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&texture.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(&texture.sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: settings_buffer.as_entire_binding(),
+            },
+        ],
+    })
+}
+
+// 画面全体を覆う三角形を1つ描画するパス
+// clear が false の場合は描画先の内容を残したまま加算する
+fn fullscreen_pass(
+    encoder: &mut wgpu::CommandEncoder,
+    label: &'static str,
+    pipeline: &wgpu::RenderPipeline,
+    bind_group: &wgpu::BindGroup,
+    target: &wgpu::TextureView,
+    clear: bool,
+    timestamp_writes: Option<wgpu::RenderPassTimestampWrites<'_>>,
+) {
+    let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: crash::pass(label),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view: target,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: if clear {
+                    wgpu::LoadOp::Clear(wgpu::Color::BLACK)
+                } else {
+                    wgpu::LoadOp::Load
+                },
+                store: wgpu::StoreOp::Store,
+            },
+        })],
+        depth_stencil_attachment: None,
+        timestamp_writes,
+        occlusion_query_set: None,
+    });
+    rpass.set_pipeline(pipeline);
+    rpass.set_bind_group(0, bind_group, &[]);
+    rpass.draw(0..3, 0..1);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn level_count_adapts_to_window_size() {
+        assert_eq!(level_sizes(1920, 1080).len(), MAX_LEVELS);
+        assert_eq!(level_sizes(1920, 1080)[0], (960, 540));
+        // 短辺 16 なら 8, 4 の2段だけ
+        assert_eq!(level_sizes(200, 16), vec![(100, 8), (50, 4)]);
+        assert!(level_sizes(1, 1).is_empty());
+        for (w, h) in level_sizes(3000, 7) {
+            assert!(w > 0 && h > 0);
+        }
+    }
+}
@@ -0,0 +1,100 @@
+struct Bloom {
+    // この明るさを超えた分だけが光のにじみになる
+    threshold: f32,
+    // しきい値付近をなめらかにつなぐ幅
+    knee: f32,
+    // アップサンプリング時のぼかしの半径（テクセル単位）
+    radius: f32,
+    // 元のシーンに足し合わせる強さ
+    intensity: f32,
+};
+
+@group(0) @binding(0) var t_source: texture_2d<f32>;
+@group(0) @binding(1) var s_source: sampler;
+@group(0) @binding(2) var<uniform> bloom: Bloom;
+
+struct VOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+// 頂点バッファを使わずに画面全体を覆う大きな三角形を描画する
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VOutput;
+    out.position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    out.uv = vec2<f32>(uv.x, 1.0 - uv.y);
+    return out;
+}
+
+// 指数部がすべて 1 のビットパターンは NaN か無限大
+fn is_finite(x: f32) -> bool {
+    return (bitcast<u32>(x) & 0x7f800000u) != 0x7f800000u;
+}
+
+// 明るい画素だけを取り出す（しきい値の前後はソフトニーでなめらかにする）
+@fragment
+fn fs_threshold(in: VOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(t_source, s_source, in.uv).rgb;
+    // NaN や無限大の画素はぼかしで周囲に広がるので、ここで取り除く
+    if !(is_finite(color.r) && is_finite(color.g) && is_finite(color.b)) {
+        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    }
+    let brightness = max(color.r, max(color.g, color.b));
+    var soft = clamp(brightness - bloom.threshold + bloom.knee, 0.0, 2.0 * bloom.knee);
+    soft = soft * soft / (4.0 * bloom.knee + 1e-4);
+    let contribution = max(soft, brightness - bloom.threshold) / max(brightness, 1e-4);
+    return vec4<f32>(color * contribution, 1.0);
+}
+
+fn sample_offset(uv: vec2<f32>, texel: vec2<f32>, x: f32, y: f32) -> vec3<f32> {
+    return textureSample(t_source, s_source, uv + texel * vec2<f32>(x, y)).rgb;
+}
+
+// 13 タップのフィルタで半分の解像度に縮小する（縮小時のちらつきを抑える）
+@fragment
+fn fs_downsample(in: VOutput) -> @location(0) vec4<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(t_source));
+    let a = sample_offset(in.uv, texel, -2.0, -2.0);
+    let b = sample_offset(in.uv, texel, 0.0, -2.0);
+    let c = sample_offset(in.uv, texel, 2.0, -2.0);
+    let d = sample_offset(in.uv, texel, -2.0, 0.0);
+    let e = sample_offset(in.uv, texel, 0.0, 0.0);
+    let f = sample_offset(in.uv, texel, 2.0, 0.0);
+    let g = sample_offset(in.uv, texel, -2.0, 2.0);
+    let h = sample_offset(in.uv, texel, 0.0, 2.0);
+    let i = sample_offset(in.uv, texel, 2.0, 2.0);
+    let j = sample_offset(in.uv, texel, -1.0, -1.0);
+    let k = sample_offset(in.uv, texel, 1.0, -1.0);
+    let l = sample_offset(in.uv, texel, -1.0, 1.0);
+    let m = sample_offset(in.uv, texel, 1.0, 1.0);
+    var color = e * 0.125;
+    color += (a + c + g + i) * 0.03125;
+    color += (b + d + f + h) * 0.0625;
+    color += (j + k + l + m) * 0.125;
+    return vec4<f32>(color, 1.0);
+}
+
+// 3x3 のテントフィルタで拡大しながらぼかす
+fn tent(uv: vec2<f32>) -> vec3<f32> {
+    let texel = bloom.radius / vec2<f32>(textureDimensions(t_source));
+    var color = sample_offset(uv, texel, 0.0, 0.0) * 4.0;
+    color += (sample_offset(uv, texel, 0.0, -1.0) + sample_offset(uv, texel, -1.0, 0.0)
+        + sample_offset(uv, texel, 1.0, 0.0) + sample_offset(uv, texel, 0.0, 1.0)) * 2.0;
+    color += sample_offset(uv, texel, -1.0, -1.0) + sample_offset(uv, texel, 1.0, -1.0)
+        + sample_offset(uv, texel, -1.0, 1.0) + sample_offset(uv, texel, 1.0, 1.0);
+    return color / 16.0;
+}
+
+// 1段小さいテクスチャを拡大して加算合成する
+@fragment
+fn fs_upsample(in: VOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(tent(in.uv), 1.0);
+}
+
+// 最も大きいテクスチャを強さを掛けてシーンに加算合成する
+@fragment
+fn fs_composite(in: VOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(tent(in.uv) * bloom.intensity, 1.0);
+}
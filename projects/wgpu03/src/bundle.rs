@@ -0,0 +1,320 @@
+use std::borrow::Cow;
+use std::time::{Duration, Instant};
+
+use anyhow::{Result, bail};
+use wgpu::util::RenderEncoder;
+
+use crate::adapter;
+use crate::clock::FrameClock;
+use crate::mesh::{Mesh, Vertex};
+use crate::per_draw::{DYNAMIC_UNIFORM_GROUP, DrawData};
+use crate::pipeline_cache::PipelineCache;
+use crate::post::PostPass;
+use crate::render::{PipelineOptions, create_render_pipeline};
+use crate::settings::Settings;
+use crate::shape::Shape;
+use crate::state::State;
+use crate::stats::{FRAME_HISTORY, FrameStats};
+use crate::texture::Texture;
+use crate::uniform_arena::UniformArena;
+
+// 格子状に並べる立方体の1辺の数（GRID_SIZE × GRID_SIZE 回の描画呼び出しになる）
+const GRID_SIZE: usize = 32;
+const GRID_EXTENT: f32 = 1.4;
+
+// 格子の各位置に置いた立方体の変換行列と色（作成時に一度だけ書き込み、その後は動かさない）
+fn grid_draws() -> impl Iterator<Item = DrawData> {
+    (0..GRID_SIZE * GRID_SIZE).map(|i| {
+        let (col, row) = ((i % GRID_SIZE) as f32, (i / GRID_SIZE) as f32);
+        let t = |n: f32| n / (GRID_SIZE - 1) as f32;
+        let position = glam::Vec3::new(
+            (t(col) - 0.5) * GRID_EXTENT,
+            (t(row) - 0.5) * GRID_EXTENT,
+            0.0,
+        );
+        let transform = glam::Mat4::from_translation(position)
+            * glam::Mat4::from_euler(glam::EulerRot::XYZ, t(row) * 2.0, t(col) * 2.0, 0.0)
+            * glam::Mat4::from_scale(glam::Vec3::splat(GRID_EXTENT / GRID_SIZE as f32 * 0.6));
+        DrawData::new(transform, [t(col), 0.4 + 0.6 * t(row), 1.0 - t(col), 1.0])
+    })
+}
+
+// 1つずつ別の描画呼び出しで描画する、動かない多数の立方体
+// 描画のコマンドはレンダーバンドルに記録しておき、毎フレーム記録し直さずに再生する
+pub struct StaticScene {
+    draws: UniformArena<DrawData>,
+    shader: wgpu::ShaderModule,
+    pipeline_layout: wgpu::PipelineLayout,
+    pipeline: wgpu::RenderPipeline,
+    bundle: wgpu::RenderBundle,
+}
+
+impl StaticScene {
+    // camera は記録するカメラのバインドグループ、mesh は格子の各位置に描画するメッシュ
+    pub fn new(
+        device: &wgpu::Device,
+        pipeline_cache: &PipelineCache,
+        queue: &wgpu::Queue,
+        camera_layout: &wgpu::BindGroupLayout,
+        camera: &wgpu::BindGroup,
+        mesh: &Mesh,
+        sample_count: u32,
+    ) -> Self {
+        let mut draws = UniformArena::new(device, "Static Scene Buffer", GRID_SIZE * GRID_SIZE);
+        for draw in grid_draws() {
+            draws.push(&draw);
+        }
+        draws.upload(queue);
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Static Scene Shader"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("per_draw.wgsl"))),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Static Scene Pipeline Layout"),
+            bind_group_layouts: &[camera_layout, draws.layout()],
+            push_constant_ranges: &[],
+        });
+        let pipeline = Self::create_pipeline(
+            device,
+            pipeline_cache,
+            &pipeline_layout,
+            &shader,
+            sample_count,
+        );
+        let bundle = Self::record_bundle(device, &pipeline, &draws, camera, mesh, sample_count);
+        Self {
+            draws,
+            shader,
+            pipeline_layout,
+            pipeline,
+            bundle,
+        }
+    }
+
+    fn create_pipeline(
+        device: &wgpu::Device,
+        pipeline_cache: &PipelineCache,
+        layout: &wgpu::PipelineLayout,
+        shader: &wgpu::ShaderModule,
+        sample_count: u32,
+    ) -> wgpu::RenderPipeline {
+        create_render_pipeline(
+            device,
+            pipeline_cache,
+            layout,
+            shader,
+            &[Vertex::desc()],
+            PostPass::FORMAT,
+            sample_count,
+            &PipelineOptions::OPAQUE.with_label("Bundle Benchmark Pipeline"),
+        )
+    }
+
+    // バンドルは記録したときの色・深度の形式とサンプル数のレンダーパスでしか再生できない
+    fn record_bundle(
+        device: &wgpu::Device,
+        pipeline: &wgpu::RenderPipeline,
+        draws: &UniformArena<DrawData>,
+        camera: &wgpu::BindGroup,
+        mesh: &Mesh,
+        sample_count: u32,
+    ) -> wgpu::RenderBundle {
+        let mut encoder =
+            device.create_render_bundle_encoder(&wgpu::RenderBundleEncoderDescriptor {
+                label: Some("Static Scene Bundle Encoder"),
+                color_formats: &[Some(PostPass::FORMAT)],
+                depth_stencil: Some(wgpu::RenderBundleDepthStencil {
+                    format: Texture::DEPTH_FORMAT,
+                    depth_read_only: false,
+                    stencil_read_only: false,
+                }),
+                sample_count,
+                multiview: None,
+            });
+        Self::encode(&mut encoder, pipeline, draws, camera, mesh);
+        encoder.finish(&wgpu::RenderBundleDescriptor {
+            label: Some("Static Scene Bundle"),
+        })
+    }
+
+    // レンダーパスにもバンドルにも同じコマンドを記録できるよう、RenderEncoder に対して描画する
+    fn encode<'a>(
+        encoder: &mut impl RenderEncoder<'a>,
+        pipeline: &'a wgpu::RenderPipeline,
+        draws: &'a UniformArena<DrawData>,
+        camera: &'a wgpu::BindGroup,
+        mesh: &'a Mesh,
+    ) {
+        encoder.set_pipeline(pipeline);
+        encoder.set_bind_group(0, Some(camera), &[]);
+        for index in 0..draws.len() {
+            encoder.set_bind_group(
+                DYNAMIC_UNIFORM_GROUP,
+                Some(draws.bind_group()),
+                &[draws.offset(index)],
+            );
+            mesh.encode(encoder, 0..1);
+        }
+    }
+
+    // MSAA のサンプル数が変わったら、パイプラインと記録したコマンドを作り直す
+    // （ウィンドウの大きさが変わっても形式は変わらないので、作り直す必要はない）
+    pub fn rebuild(
+        &mut self,
+        device: &wgpu::Device,
+        pipeline_cache: &PipelineCache,
+        camera: &wgpu::BindGroup,
+        mesh: &Mesh,
+        sample_count: u32,
+    ) {
+        self.pipeline = Self::create_pipeline(
+            device,
+            pipeline_cache,
+            &self.pipeline_layout,
+            &self.shader,
+            sample_count,
+        );
+        self.bundle = Self::record_bundle(
+            device,
+            &self.pipeline,
+            &self.draws,
+            camera,
+            mesh,
+            sample_count,
+        );
+    }
+
+    // 記録したコマンドを再生する（再生の後はパイプラインやバインドグループの設定が消える）
+    pub fn execute(&self, rpass: &mut wgpu::RenderPass) {
+        rpass.execute_bundles(std::iter::once(&self.bundle));
+    }
+
+    // バンドルを使わずに同じ描画を記録する（記録したものと別のカメラで描画する場合と、比較用）
+    pub fn draw<'a>(
+        &'a self,
+        rpass: &mut wgpu::RenderPass<'a>,
+        camera: &'a wgpu::BindGroup,
+        mesh: &'a Mesh,
+    ) {
+        Self::encode(rpass, &self.pipeline, &self.draws, camera, mesh);
+    }
+}
+
+// ベンチマークで記録する回数（FrameStats が保持するフレーム数と同じにする）
+const BENCHMARK_ITERATIONS: usize = FRAME_HISTORY;
+
+// `--bench-bundles` ではウィンドウを作らずに、格子の立方体を描画するレンダーパスの記録時間を
+// バンドルを再生する場合と描画呼び出しを毎回記録する場合で比べる
+// 記録の時間は CommandEncoder::finish までで、GPU の実行は待つが計測には含めない
+pub fn run_benchmark(settings: &Settings) -> Result<()> {
+    pollster::block_on(async {
+        let instance = adapter::create_instance(settings.backends);
+        let mut state = State::new(&instance, None, 800, 600, settings).await?;
+        state.shape = Shape::StaticGrid;
+        state.update(FrameClock::default().tick(), 0.0);
+        println!(
+            "{} 回の描画呼び出しを {} 回記録します（MSAA サンプル数 {}）",
+            GRID_SIZE * GRID_SIZE,
+            BENCHMARK_ITERATIONS,
+            state.sample_count
+        );
+        for bundled in [false, true] {
+            // 最初の数回はシェーダーのコンパイルなどが入るので捨てる
+            for _ in 0..BENCHMARK_ITERATIONS / 10 {
+                encode_static_grid(&state, bundled);
+            }
+            let mut stats = FrameStats::default();
+            for _ in 0..BENCHMARK_ITERATIONS {
+                stats.push(encode_static_grid(&state, bundled));
+            }
+            let Some(summary) = stats.summary() else {
+                bail!("計測できませんでした");
+            };
+            let ms = |d: Duration| d.as_secs_f32() * 1000.0;
+            println!(
+                "{}: avg {:.3} / min {:.3} / p99 {:.3} ms",
+                if bundled {
+                    "バンドル"
+                } else {
+                    "直接記録"
+                },
+                ms(summary.average),
+                ms(summary.min),
+                ms(summary.p99)
+            );
+        }
+        Ok(())
+    })
+}
+
+// 格子の立方体だけを描画するレンダーパスを記録して送信し、記録にかかった時間を返す
+fn encode_static_grid(state: &State, bundled: bool) -> Duration {
+    let camera = &state.camera_bindings.uniform_bind_group;
+    let start = Instant::now();
+    let mut encoder = state
+        .device
+        .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Bundle Benchmark Encoder"),
+        });
+    {
+        let view = &state.post.texture.view;
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Bundle Benchmark Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: state.msaa_view.as_ref().unwrap_or(view),
+                resolve_target: state.msaa_view.as_ref().map(|_| view),
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &state.depth_texture.view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: wgpu::StoreOp::Store,
+                }),
+                // バンドルはステンシルを書き込めるものとして記録したので、メインのパスと同じく読み書きにする
+                stencil_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(0),
+                    store: wgpu::StoreOp::Discard,
+                }),
+            }),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        if bundled {
+            state.static_scene.execute(&mut rpass);
+        } else {
+            state.static_scene.draw(&mut rpass, camera, &state.cube);
+        }
+    }
+    let commands = encoder.finish();
+    let elapsed = start.elapsed();
+    state.queue.submit(Some(commands));
+    state.device.poll(wgpu::Maintain::Wait);
+    elapsed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grid_cubes_are_spread_over_the_extent() {
+        let positions: Vec<glam::Vec3> = grid_draws()
+            .map(|draw| {
+                glam::Mat4::from_cols_array_2d(&draw.transform)
+                    .w_axis
+                    .truncate()
+            })
+            .collect();
+        assert_eq!(positions.len(), GRID_SIZE * GRID_SIZE);
+        assert_eq!(positions[0], glam::Vec3::new(-0.7, -0.7, 0.0));
+        assert_eq!(
+            positions[GRID_SIZE * GRID_SIZE - 1],
+            glam::Vec3::new(0.7, 0.7, 0.0)
+        );
+    }
+}
@@ -0,0 +1,502 @@
+//! 透視投影カメラと、キーボード・マウスで動かすカメラコントローラー
+
+use glam::{Mat3, Mat4, Vec3, Vec4};
+use winit::{
+    event::{DeviceEvent, ElementState, KeyEvent, MouseButton, MouseScrollDelta, WindowEvent},
+    keyboard::{KeyCode, PhysicalKey},
+};
+
+/// OpenGLのクリップ空間（z: -1..1）をwgpuのクリップ空間（z: 0..1）に変換する行列
+#[rustfmt::skip]
+pub const OPENGL_TO_WGPU_MATRIX: Mat4 = Mat4::from_cols(
+    Vec4::new(1.0, 0.0, 0.0, 0.0),
+    Vec4::new(0.0, 1.0, 0.0, 0.0),
+    Vec4::new(0.0, 0.0, 0.5, 0.0),
+    Vec4::new(0.0, 0.0, 0.5, 1.0),
+);
+
+/// 透視投影カメラ
+#[derive(Clone, Debug)]
+pub struct Camera {
+    pub eye: Vec3,
+    pub target: Vec3,
+    pub up: Vec3,
+    pub aspect: f32,
+    /// 垂直方向の視野角（ラジアン）
+    pub fovy: f32,
+    pub znear: f32,
+    pub zfar: f32,
+}
+
+impl Camera {
+    pub fn new(width: u32, height: u32) -> Self {
+        let mut camera = Self {
+            eye: Vec3::new(0.0, 0.0, 2.0),
+            target: Vec3::ZERO,
+            up: Vec3::Y,
+            aspect: 1.0,
+            fovy: 45f32.to_radians(),
+            znear: 0.1,
+            zfar: 100.0,
+        };
+        camera.set_aspect(width, height);
+        camera
+    }
+
+    /// サーフェイスのサイズからアスペクト比を設定する
+    pub fn set_aspect(&mut self, width: u32, height: u32) {
+        self.aspect = width.max(1) as f32 / height.max(1) as f32;
+    }
+
+    pub fn build_view_matrix(&self) -> Mat4 {
+        Mat4::look_at_rh(self.eye, self.target, self.up)
+    }
+
+    pub fn build_projection_matrix(&self) -> Mat4 {
+        OPENGL_TO_WGPU_MATRIX
+            * Mat4::perspective_rh_gl(self.fovy, self.aspect, self.znear, self.zfar)
+    }
+
+    pub fn build_view_projection_matrix(&self) -> Mat4 {
+        self.build_projection_matrix() * self.build_view_matrix()
+    }
+
+    /// スカイボックス用に、クリップ座標から視線方向へ戻す逆ビュー・射影行列を作成する
+    /// ビュー行列の平行移動を取り除くので、カメラが移動してもスカイボックスは回転だけに追従する
+    pub fn build_skybox_matrix(&self) -> Mat4 {
+        let rotation = Mat4::from_mat3(Mat3::from_mat4(self.build_view_matrix()));
+        (self.build_projection_matrix() * rotation).inverse()
+    }
+
+    /// ウィンドウ座標（左上が原点のピクセル単位）のカーソルを通る視線と、z = plane_z の平面との交点
+    /// 視線が平面と平行か、平面がカメラの後ろにある場合は None
+    pub fn cursor_to_plane(
+        &self,
+        x: f32,
+        y: f32,
+        width: u32,
+        height: u32,
+        plane_z: f32,
+    ) -> Option<Vec3> {
+        let ndc_x = x / width.max(1) as f32 * 2.0 - 1.0;
+        let ndc_y = 1.0 - y / height.max(1) as f32 * 2.0;
+        let inverse = self.build_view_projection_matrix().inverse();
+        let near = inverse.project_point3(Vec3::new(ndc_x, ndc_y, 0.0));
+        let far = inverse.project_point3(Vec3::new(ndc_x, ndc_y, 1.0));
+        let dir = far - near;
+        if dir.z.abs() < f32::EPSILON {
+            return None;
+        }
+        let t = (plane_z - near.z) / dir.z;
+        (t >= 0.0).then(|| near + dir * t)
+    }
+}
+
+// 真上・真下を向いたときに視線と上方向が平行にならないようにするための仰角の上限
+const MAX_PITCH: f32 = std::f32::consts::FRAC_PI_2 - 0.01;
+
+/// WASDキーで移動し、右ドラッグで視点を回転させるカメラコントローラー
+#[derive(Debug)]
+pub struct CameraController {
+    /// 移動速度（単位/秒）
+    pub speed: f32,
+    /// マウス移動量に対する回転量（ラジアン/ピクセル）
+    pub sensitivity: f32,
+    forward: bool,
+    backward: bool,
+    left: bool,
+    right: bool,
+    up: bool,
+    down: bool,
+    rotating: bool,
+    yaw_delta: f32,
+    pitch_delta: f32,
+}
+
+impl CameraController {
+    pub fn new(speed: f32, sensitivity: f32) -> Self {
+        Self {
+            speed,
+            sensitivity,
+            forward: false,
+            backward: false,
+            left: false,
+            right: false,
+            up: false,
+            down: false,
+            rotating: false,
+            yaw_delta: 0.0,
+            pitch_delta: 0.0,
+        }
+    }
+
+    /// ウィンドウイベントを処理し、カメラ操作として消費した場合は true を返す
+    pub fn process_window_event(&mut self, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(code),
+                        state,
+                        ..
+                    },
+                ..
+            } => {
+                let pressed = *state == ElementState::Pressed;
+                match code {
+                    KeyCode::KeyW => self.forward = pressed,
+                    KeyCode::KeyS => self.backward = pressed,
+                    KeyCode::KeyA => self.left = pressed,
+                    KeyCode::KeyD => self.right = pressed,
+                    KeyCode::Space => self.up = pressed,
+                    KeyCode::ShiftLeft => self.down = pressed,
+                    _ => return false,
+                }
+                true
+            }
+            WindowEvent::MouseInput {
+                state,
+                button: MouseButton::Right,
+                ..
+            } => {
+                self.rotating = *state == ElementState::Pressed;
+                true
+            }
+            WindowEvent::Focused(false) => {
+                // フォーカスを失うとキーを離したイベントが届かないため、入力状態を解除する
+                self.reset();
+                false
+            }
+            _ => false,
+        }
+    }
+
+    /// 生のマウス移動量を処理する（右ドラッグ中のみ視点を回転させる）
+    pub fn process_device_event(&mut self, event: &DeviceEvent) {
+        if let DeviceEvent::MouseMotion { delta: (dx, dy) } = event
+            && self.rotating
+        {
+            self.yaw_delta += *dx as f32;
+            self.pitch_delta += *dy as f32;
+        }
+    }
+
+    /// 押下中のキーとドラッグ状態をすべて解除する
+    pub fn reset(&mut self) {
+        self.forward = false;
+        self.backward = false;
+        self.left = false;
+        self.right = false;
+        self.up = false;
+        self.down = false;
+        self.rotating = false;
+        self.yaw_delta = 0.0;
+        self.pitch_delta = 0.0;
+    }
+
+    /// 蓄積した入力をカメラに適用する
+    pub fn update_camera(&mut self, camera: &mut Camera, dt: f32) {
+        let offset = camera.target - camera.eye;
+        let distance = offset.length().max(f32::EPSILON);
+
+        // 視線方向を方位角・仰角に分解して回転量を加える
+        let direction = offset / distance;
+        let mut yaw = direction.z.atan2(direction.x);
+        let mut pitch = direction.y.clamp(-1.0, 1.0).asin();
+        yaw += self.yaw_delta * self.sensitivity;
+        pitch = (pitch - self.pitch_delta * self.sensitivity).clamp(-MAX_PITCH, MAX_PITCH);
+        self.yaw_delta = 0.0;
+        self.pitch_delta = 0.0;
+
+        let forward = Vec3::new(
+            yaw.cos() * pitch.cos(),
+            pitch.sin(),
+            yaw.sin() * pitch.cos(),
+        );
+        let right = forward.cross(camera.up).normalize();
+
+        let mut movement = Vec3::ZERO;
+        if self.forward {
+            movement += forward;
+        }
+        if self.backward {
+            movement -= forward;
+        }
+        if self.right {
+            movement += right;
+        }
+        if self.left {
+            movement -= right;
+        }
+        if self.up {
+            movement += camera.up;
+        }
+        if self.down {
+            movement -= camera.up;
+        }
+
+        camera.eye += movement.normalize_or_zero() * self.speed * dt;
+        camera.target = camera.eye + forward * distance;
+    }
+}
+
+// PixelDelta のスクロール量を行数に換算するときの1行あたりのピクセル数
+const PIXELS_PER_LINE: f32 = 100.0;
+
+/// 注視点のまわりを回転する（オービット）カメラコントローラー
+/// 左ドラッグで回転、ホイールでズーム、中ドラッグで平行移動する
+#[derive(Debug)]
+pub struct OrbitCameraController {
+    /// マウス移動量に対する回転量（ラジアン/ピクセル）
+    pub sensitivity: f32,
+    /// ホイール1行あたりのズーム率
+    pub zoom_speed: f32,
+    /// 視点が注視点に近づける最小距離
+    pub min_distance: f32,
+    rotating: bool,
+    panning: bool,
+    rotate_delta: (f32, f32),
+    pan_delta: (f32, f32),
+    scroll_delta: f32,
+}
+
+impl OrbitCameraController {
+    pub fn new(sensitivity: f32, zoom_speed: f32) -> Self {
+        Self {
+            sensitivity,
+            zoom_speed,
+            min_distance: 0.1,
+            rotating: false,
+            panning: false,
+            rotate_delta: (0.0, 0.0),
+            pan_delta: (0.0, 0.0),
+            scroll_delta: 0.0,
+        }
+    }
+
+    /// ウィンドウイベントを処理し、カメラ操作として消費した場合は true を返す
+    pub fn process_window_event(&mut self, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::MouseInput { state, button, .. } => {
+                let pressed = *state == ElementState::Pressed;
+                match button {
+                    MouseButton::Left => self.rotating = pressed,
+                    MouseButton::Middle => self.panning = pressed,
+                    _ => return false,
+                }
+                true
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                // プラットフォームによって行単位とピクセル単位のどちらかで届く
+                self.scroll_delta += match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y,
+                    MouseScrollDelta::PixelDelta(position) => position.y as f32 / PIXELS_PER_LINE,
+                };
+                true
+            }
+            WindowEvent::Focused(false) => {
+                self.reset();
+                false
+            }
+            _ => false,
+        }
+    }
+
+    /// 生のマウス移動量を処理する（ドラッグ中のみ反映する）
+    pub fn process_device_event(&mut self, event: &DeviceEvent) {
+        if let DeviceEvent::MouseMotion { delta: (dx, dy) } = event {
+            let delta = (*dx as f32, *dy as f32);
+            if self.rotating {
+                self.rotate_delta.0 += delta.0;
+                self.rotate_delta.1 += delta.1;
+            }
+            if self.panning {
+                self.pan_delta.0 += delta.0;
+                self.pan_delta.1 += delta.1;
+            }
+        }
+    }
+
+    /// ドラッグ状態と蓄積した入力をすべて解除する
+    pub fn reset(&mut self) {
+        self.rotating = false;
+        self.panning = false;
+        self.rotate_delta = (0.0, 0.0);
+        self.pan_delta = (0.0, 0.0);
+        self.scroll_delta = 0.0;
+    }
+
+    /// 蓄積した入力をカメラに適用する
+    pub fn update_camera(&mut self, camera: &mut Camera) {
+        let offset = camera.eye - camera.target;
+        let mut distance = offset.length().max(self.min_distance);
+
+        // 注視点から見た視点の方向を方位角・仰角に分解して回転させる
+        let direction = offset / offset.length().max(f32::EPSILON);
+        let mut yaw = direction.z.atan2(direction.x);
+        let mut pitch = direction.y.clamp(-1.0, 1.0).asin();
+        yaw += self.rotate_delta.0 * self.sensitivity;
+        pitch = (pitch + self.rotate_delta.1 * self.sensitivity).clamp(-MAX_PITCH, MAX_PITCH);
+
+        // ズームは距離に比例させ、視点が注視点を越えないように制限する
+        distance *= (1.0 - self.scroll_delta * self.zoom_speed).max(0.0);
+        distance = distance.max(self.min_distance);
+
+        let direction = Vec3::new(
+            yaw.cos() * pitch.cos(),
+            pitch.sin(),
+            yaw.sin() * pitch.cos(),
+        );
+
+        // 平行移動は画面上の右方向・上方向に沿って、距離に比例した量だけ動かす
+        let forward = -direction;
+        let right = forward.cross(camera.up).normalize();
+        let up = right.cross(forward);
+        let pan_scale = distance * self.sensitivity * 0.5;
+        camera.target += (-right * self.pan_delta.0 + up * self.pan_delta.1) * pan_scale;
+        camera.eye = camera.target + direction * distance;
+
+        self.rotate_delta = (0.0, 0.0);
+        self.pan_delta = (0.0, 0.0);
+        self.scroll_delta = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn project(camera: &Camera, point: Vec3) -> Vec3 {
+        let clip = camera.build_view_projection_matrix() * point.extend(1.0);
+        clip.truncate() / clip.w
+    }
+
+    #[test]
+    fn projects_into_wgpu_depth_range() {
+        let camera = Camera::new(800, 600);
+
+        // 注視点は画面中央に投影される
+        let center = project(&camera, camera.target);
+        assert!(center.x.abs() < 1e-6 && center.y.abs() < 1e-6);
+
+        // ニアクリップ面は深度0、ファークリップ面は深度1になる
+        let forward = (camera.target - camera.eye).normalize();
+        let near = project(&camera, camera.eye + forward * camera.znear);
+        let far = project(&camera, camera.eye + forward * camera.zfar);
+        assert!(near.z.abs() < 1e-5, "near depth = {}", near.z);
+        assert!((far.z - 1.0).abs() < 1e-5, "far depth = {}", far.z);
+
+        // 視野角の上端は y = 1 に投影される
+        let distance = (camera.target - camera.eye).length();
+        let top = camera.target + camera.up * distance * (camera.fovy / 2.0).tan();
+        let top = project(&camera, top);
+        assert!((top.y - 1.0).abs() < 1e-5, "top y = {}", top.y);
+    }
+
+    #[test]
+    fn skybox_matrix_ignores_camera_translation() {
+        let mut camera = Camera::new(800, 600);
+        let ray = |camera: &Camera| {
+            let p = camera.build_skybox_matrix() * Vec4::new(0.0, 0.0, 1.0, 1.0);
+            (p.truncate() / p.w).normalize()
+        };
+        let before = ray(&camera);
+        let forward = (camera.target - camera.eye).normalize();
+        assert!((before - forward).length() < 1e-4, "ray = {}", before);
+
+        // 平行移動しても画面中央の視線方向は変わらない
+        let offset = Vec3::new(3.0, -2.0, 5.0);
+        camera.eye += offset;
+        camera.target += offset;
+        assert!((ray(&camera) - before).length() < 1e-4);
+    }
+
+    #[test]
+    fn cursor_at_window_center_hits_target() {
+        let camera = Camera::new(800, 600);
+        let hit = camera.cursor_to_plane(400.0, 300.0, 800, 600, 0.0).unwrap();
+        assert!(hit.length() < 1e-4, "hit = {}", hit);
+        // 左上のカーソルは平面上でも左上（x < 0, y > 0）に対応する
+        let corner = camera.cursor_to_plane(0.0, 0.0, 800, 600, 0.0).unwrap();
+        assert!(corner.x < 0.0 && corner.y > 0.0, "corner = {}", corner);
+        // カメラの後ろの平面とは交わらない
+        assert!(
+            camera
+                .cursor_to_plane(400.0, 300.0, 800, 600, 5.0)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn aspect_ratio_scales_x() {
+        let camera = Camera::new(1600, 800);
+        assert_eq!(camera.aspect, 2.0);
+
+        let distance = (camera.target - camera.eye).length();
+        let half_height = distance * (camera.fovy / 2.0).tan();
+        let right = project(&camera, Vec3::new(half_height * camera.aspect, 0.0, 0.0));
+        assert!((right.x - 1.0).abs() < 1e-5, "right x = {}", right.x);
+    }
+
+    #[test]
+    fn focus_loss_releases_keys() {
+        let mut camera = Camera::new(800, 600);
+        let mut controller = CameraController::new(1.0, 0.01);
+        controller.forward = true;
+        controller.rotating = true;
+
+        controller.process_window_event(&WindowEvent::Focused(false));
+        let eye = camera.eye;
+        controller.update_camera(&mut camera, 1.0);
+        assert_eq!(camera.eye, eye);
+    }
+
+    #[test]
+    fn pitch_is_clamped_at_poles() {
+        let mut camera = Camera::new(800, 600);
+        let mut controller = CameraController::new(1.0, 1.0);
+        controller.pitch_delta = -100.0;
+        controller.update_camera(&mut camera, 0.0);
+
+        let direction = (camera.target - camera.eye).normalize();
+        assert!(direction.y < 1.0);
+        assert!(direction.cross(camera.up).length() > 0.0);
+    }
+
+    #[test]
+    fn orbit_zoom_never_crosses_target() {
+        let mut camera = Camera::new(800, 600);
+        let mut controller = OrbitCameraController::new(0.01, 0.1);
+        controller.process_window_event(&WindowEvent::MouseWheel {
+            device_id: winit::event::DeviceId::dummy(),
+            delta: MouseScrollDelta::LineDelta(0.0, 1000.0),
+            phase: winit::event::TouchPhase::Moved,
+        });
+        controller.update_camera(&mut camera);
+
+        let offset = camera.eye - camera.target;
+        assert!(offset.z > 0.0);
+        assert!((offset.length() - controller.min_distance).abs() < 1e-5);
+    }
+
+    #[test]
+    fn orbit_pixel_delta_zooms_like_line_delta() {
+        let mut line_camera = Camera::new(800, 600);
+        let mut pixel_camera = line_camera.clone();
+        let mut controller = OrbitCameraController::new(0.01, 0.1);
+
+        controller.scroll_delta = 1.0;
+        controller.update_camera(&mut line_camera);
+        controller.process_window_event(&WindowEvent::MouseWheel {
+            device_id: winit::event::DeviceId::dummy(),
+            delta: MouseScrollDelta::PixelDelta(winit::dpi::PhysicalPosition::new(
+                0.0,
+                PIXELS_PER_LINE as f64,
+            )),
+            phase: winit::event::TouchPhase::Moved,
+        });
+        controller.update_camera(&mut pixel_camera);
+
+        assert!((line_camera.eye - pixel_camera.eye).length() < 1e-5);
+    }
+}
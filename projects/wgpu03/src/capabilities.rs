@@ -0,0 +1,108 @@
+// wave.wgsl と particles.wgsl のワークグループの大きさ
+const COMPUTE_WORKGROUP_SIZE: u32 = 64;
+
+// デバイスを作るときに要求する制限
+// アダプタが WebGPU の既定の制限を満たさなければ、ダウンレベル（GLES 3.1）か WebGL2 の制限にする
+// （テクスチャの最大の大きさだけはアダプタに合わせ、大きなウィンドウのサーフェイスも作れるようにする）
+pub fn required_limits(adapter: &wgpu::Limits) -> wgpu::Limits {
+    let defaults = wgpu::Limits::default();
+    if defaults.check_limits(adapter) {
+        return defaults;
+    }
+    let downlevel = wgpu::Limits::downlevel_defaults().using_resolution(adapter.clone());
+    if downlevel.check_limits(adapter) {
+        return downlevel;
+    }
+    wgpu::Limits::downlevel_webgl2_defaults().using_resolution(adapter.clone())
+}
+
+// デバイスを作ったときに一度だけ調べる、実際に使える機能
+// パイプラインやバッファを作るところはアダプタではなくこれを見て、使えない方法を避ける
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GpuCapabilities {
+    // WebGPU の既定の制限を満たさないデバイス
+    pub downlevel: bool,
+    // 頂点・フラグメントシェーダーからストレージバッファを読める（光源と関節の配列に使う）
+    pub fragment_storage: bool,
+    // コンピュートパスでストレージバッファに書き込める（波とパーティクルに使う）
+    pub compute: bool,
+    // レンダーパスの所要時間を計測できる
+    pub timestamp_query: bool,
+    // draw_indirect を使える
+    pub indirect: bool,
+    pub anisotropic_filtering: bool,
+}
+
+impl GpuCapabilities {
+    pub fn new(device: &wgpu::Device, adapter: &wgpu::Adapter) -> Self {
+        Self::from_parts(
+            device.features(),
+            &device.limits(),
+            adapter.get_downlevel_capabilities().flags,
+        )
+    }
+
+    // 機能と制限は要求して認められたもの、ダウンレベルの対応状況はアダプタのものを渡す
+    fn from_parts(
+        features: wgpu::Features,
+        limits: &wgpu::Limits,
+        flags: wgpu::DownlevelFlags,
+    ) -> Self {
+        let storage_buffers = limits.max_storage_buffers_per_shader_stage > 0;
+        Self {
+            downlevel: !wgpu::Limits::default().check_limits(limits),
+            fragment_storage: storage_buffers
+                && flags.contains(
+                    wgpu::DownlevelFlags::VERTEX_STORAGE | wgpu::DownlevelFlags::FRAGMENT_STORAGE,
+                ),
+            compute: storage_buffers
+                && flags.contains(wgpu::DownlevelFlags::COMPUTE_SHADERS)
+                && limits.max_compute_invocations_per_workgroup >= COMPUTE_WORKGROUP_SIZE
+                && limits.max_compute_workgroup_size_x >= COMPUTE_WORKGROUP_SIZE,
+            timestamp_query: features.contains(wgpu::Features::TIMESTAMP_QUERY),
+            indirect: flags.contains(wgpu::DownlevelFlags::INDIRECT_EXECUTION),
+            anisotropic_filtering: flags.contains(wgpu::DownlevelFlags::ANISOTROPIC_FILTERING),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn webgl2_adapters_get_webgl2_limits_with_their_own_resolution() {
+        assert_eq!(
+            required_limits(&wgpu::Limits::default()),
+            wgpu::Limits::default()
+        );
+        let adapter = wgpu::Limits {
+            max_texture_dimension_2d: 4096,
+            ..wgpu::Limits::downlevel_webgl2_defaults()
+        };
+        let limits = required_limits(&adapter);
+        assert_eq!(limits.max_storage_buffers_per_shader_stage, 0);
+        assert_eq!(limits.max_texture_dimension_2d, 4096);
+        assert!(limits.check_limits(&adapter));
+    }
+
+    #[test]
+    fn features_follow_what_the_device_was_granted() {
+        let full = GpuCapabilities::from_parts(
+            wgpu::Features::TIMESTAMP_QUERY,
+            &wgpu::Limits::default(),
+            wgpu::DownlevelFlags::all(),
+        );
+        assert!(!full.downlevel);
+        assert!(full.fragment_storage && full.compute && full.timestamp_query && full.indirect);
+
+        // フラグがあっても、WebGL2 の制限ではストレージバッファもコンピュートパスも使えない
+        let webgl2 = GpuCapabilities::from_parts(
+            wgpu::Features::empty(),
+            &wgpu::Limits::downlevel_webgl2_defaults(),
+            wgpu::DownlevelFlags::all(),
+        );
+        assert!(webgl2.downlevel);
+        assert!(!webgl2.fragment_storage && !webgl2.compute && !webgl2.timestamp_query);
+    }
+}
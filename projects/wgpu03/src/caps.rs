@@ -0,0 +1,141 @@
+// 不具合の報告に添付できるよう、アダプタとサーフェイスの対応状況を1行に1項目ずつ並べる
+// 行は `キー: 値` の形式で、同じキーの行が続くもの（feature など）は対応しているものの一覧になる
+pub fn report(
+    adapters: &[wgpu::AdapterInfo],
+    info: &wgpu::AdapterInfo,
+    features: wgpu::Features,
+    limits: &wgpu::Limits,
+    surface: Option<&wgpu::SurfaceCapabilities>,
+) -> Vec<String> {
+    let mut lines: Vec<String> = adapters
+        .iter()
+        .enumerate()
+        .map(|(i, adapter)| {
+            format!(
+                "adapters[{}]: {} ({:?}, {:?}){}",
+                i,
+                adapter.name,
+                adapter.backend,
+                adapter.device_type,
+                if adapter == info { " *" } else { "" }
+            )
+        })
+        .collect();
+    lines.extend([
+        format!("adapter.name: {}", info.name),
+        format!("adapter.backend: {:?}", info.backend),
+        format!("adapter.device_type: {:?}", info.device_type),
+        format!("adapter.vendor: {:#06x}", info.vendor),
+        format!("adapter.device: {:#06x}", info.device),
+        format!("adapter.driver: {}", info.driver),
+        format!("adapter.driver_info: {}", info.driver_info),
+    ]);
+    lines.extend(
+        features
+            .iter_names()
+            .map(|(name, _)| format!("feature: {}", name)),
+    );
+    lines.extend(
+        debug_fields(limits)
+            .into_iter()
+            .map(|field| format!("limit.{}", field)),
+    );
+    match surface {
+        Some(caps) => {
+            lines.extend(
+                caps.formats
+                    .iter()
+                    .map(|f| format!("surface.format: {:?}", f)),
+            );
+            lines.extend(
+                caps.present_modes
+                    .iter()
+                    .map(|mode| format!("surface.present_mode: {:?}", mode)),
+            );
+            lines.extend(
+                caps.alpha_modes
+                    .iter()
+                    .map(|mode| format!("surface.alpha_mode: {:?}", mode)),
+            );
+            lines.push(format!("surface.usages: {:?}", caps.usages));
+        }
+        None => lines.push("surface: none".to_string()),
+    }
+    lines
+}
+
+// 起動時に毎回表示する、report を短くまとめた1行
+pub fn summary(info: &wgpu::AdapterInfo, surface: Option<&wgpu::SurfaceCapabilities>) -> String {
+    let surface = match surface {
+        Some(caps) => format!(
+            "フォーマット {:?} / 表示モード {:?} / アルファ {:?}",
+            caps.formats, caps.present_modes, caps.alpha_modes
+        ),
+        None => "サーフェイスなし".to_string(),
+    };
+    // ドライバの名前は空のことがある（GL など）
+    let driver = [info.driver.as_str(), info.driver_info.as_str()]
+        .into_iter()
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+    format!(
+        "アダプタ: {} ({:?}, {}) / {}（詳しくは --print-caps）",
+        info.name, info.backend, driver, surface
+    )
+}
+
+// `{:#?}` で1行ずつに並ぶ構造体のフィールドを `名前: 値` にする（Limits に項目を列挙する方法がないため）
+fn debug_fields(value: &impl std::fmt::Debug) -> Vec<String> {
+    format!("{:#?}", value)
+        .lines()
+        .filter_map(|line| line.strip_prefix("    "))
+        .filter(|line| !line.starts_with(' '))
+        .map(|line| line.trim_end_matches(',').to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_has_one_item_per_line() {
+        let info = |name: &str| wgpu::AdapterInfo {
+            name: name.to_string(),
+            vendor: 0x10de,
+            device: 0x2684,
+            device_type: wgpu::DeviceType::DiscreteGpu,
+            driver: "NVIDIA".to_string(),
+            driver_info: "550.54".to_string(),
+            backend: wgpu::Backend::Vulkan,
+        };
+        let caps = wgpu::SurfaceCapabilities {
+            formats: vec![
+                wgpu::TextureFormat::Bgra8UnormSrgb,
+                wgpu::TextureFormat::Bgra8Unorm,
+            ],
+            present_modes: vec![wgpu::PresentMode::Fifo],
+            alpha_modes: vec![wgpu::CompositeAlphaMode::Opaque],
+            usages: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        };
+        let lines = report(
+            &[info("llvmpipe"), info("GPU")],
+            &info("GPU"),
+            wgpu::Features::POLYGON_MODE_LINE | wgpu::Features::TIMESTAMP_QUERY,
+            &wgpu::Limits::default(),
+            Some(&caps),
+        );
+        let has = |line: &str| lines.iter().any(|l| l == line);
+        assert!(has("adapters[1]: GPU (Vulkan, DiscreteGpu) *"));
+        assert!(has("adapters[0]: llvmpipe (Vulkan, DiscreteGpu)"));
+        assert!(has("adapter.vendor: 0x10de"));
+        assert!(has("feature: TIMESTAMP_QUERY"));
+        assert!(has("feature: POLYGON_MODE_LINE"));
+        assert!(has("limit.max_texture_dimension_2d: 8192"));
+        assert!(has("surface.format: Bgra8Unorm"));
+        assert!(has("surface.present_mode: Fifo"));
+        assert!(has("surface.alpha_mode: Opaque"));
+        assert!(lines.iter().all(|line| !line.contains('\n')));
+    }
+}
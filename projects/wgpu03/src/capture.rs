@@ -0,0 +1,234 @@
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result, bail};
+use tracing::{info, warn};
+
+use crate::uniform_arena::align_to;
+
+const BYTES_PER_PIXEL: u32 = 4;
+
+// テクスチャからバッファへのコピーでは、1行のバイト数を 256 の倍数にしなければならない
+fn padded_bytes_per_row(width: u32) -> u32 {
+    align_to(width * BYTES_PER_PIXEL, wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+}
+
+// 各行の末尾の詰め物を取り除き、隙間なく並んだ画素にする
+fn strip_padding(data: &[u8], width: u32, height: u32) -> Vec<u8> {
+    let padded = padded_bytes_per_row(width) as usize;
+    let unpadded = (width * BYTES_PER_PIXEL) as usize;
+    data.chunks(padded)
+        .take(height as usize)
+        .flat_map(|row| &row[..unpadded])
+        .copied()
+        .collect()
+}
+
+// 読み出した画素を PNG に保存できる RGBA8 の並びにする
+// （8ビット以外のフォーマットは変換しない）
+fn to_rgba8(format: wgpu::TextureFormat, pixels: &mut [u8]) -> Result<()> {
+    match format {
+        wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => {}
+        wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => {
+            for pixel in pixels.chunks_exact_mut(BYTES_PER_PIXEL as usize) {
+                pixel.swap(0, 2);
+            }
+        }
+        _ => bail!("{:?} の画素は保存できません", format),
+    }
+    Ok(())
+}
+
+// size のテクスチャを、行ごとに詰め物を付けて受け取るコピー先のバッファ
+pub fn create_readback_buffer(
+    device: &wgpu::Device,
+    label: &str,
+    size: wgpu::Extent3d,
+) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some(label),
+        size: (padded_bytes_per_row(size.width) * size.height) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+// texture 全体を、create_readback_buffer で作ったバッファへコピーするコマンドを記録する
+pub fn copy_to_buffer(
+    encoder: &mut wgpu::CommandEncoder,
+    texture: &wgpu::Texture,
+    buffer: &wgpu::Buffer,
+) {
+    let size = texture.size();
+    encoder.copy_texture_to_buffer(
+        wgpu::TexelCopyTextureInfo {
+            texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        },
+        wgpu::TexelCopyBufferInfo {
+            buffer,
+            layout: wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_bytes_per_row(size.width)),
+                rows_per_image: Some(size.height),
+            },
+        },
+        size,
+    );
+}
+
+// buffer 全体をマップし、終わるまで待つ
+// map_async のコールバックはデバイスをポーリングしている間に呼ばれるので、届くまでポーリングを続ける
+pub fn map_blocking(device: &wgpu::Device, buffer: &wgpu::Buffer) -> Result<()> {
+    let (sender, receiver) = mpsc::channel();
+    buffer
+        .slice(..)
+        .map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+    let mapped = loop {
+        device.poll(wgpu::Maintain::Wait);
+        if let Ok(result) = receiver.try_recv() {
+            break result;
+        }
+    };
+    mapped.context("読み出し用のバッファをマップできませんでした")
+}
+
+// マップが終わったバッファから RGBA8 の画像を読み出し、バッファのマップを解除する
+pub fn read_mapped(
+    buffer: &wgpu::Buffer,
+    size: wgpu::Extent3d,
+    format: wgpu::TextureFormat,
+) -> Result<image::RgbaImage> {
+    let mut pixels = strip_padding(
+        &buffer.slice(..).get_mapped_range(),
+        size.width,
+        size.height,
+    );
+    buffer.unmap();
+    to_rgba8(format, &mut pixels)?;
+    image::RgbaImage::from_raw(size.width, size.height, pixels)
+        .context("描画結果の大きさが画像と一致しません")
+}
+
+// 描画先のテクスチャ（COPY_SRC を付けて、レンダーパスの描画先にできるようにする）
+pub fn create_target(
+    device: &wgpu::Device,
+    label: &str,
+    width: u32,
+    height: u32,
+    format: wgpu::TextureFormat,
+) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+    })
+}
+
+// 読み出せるように COPY_SRC を付けた描画先のテクスチャと、コピー先のバッファ
+// （サーフェイスのテクスチャはコピーできない環境があるので、シーンをこちらにもう一度描画する）
+pub struct Capture {
+    texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+    buffer: wgpu::Buffer,
+}
+
+impl Capture {
+    pub fn new(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+    ) -> Self {
+        let texture = create_target(device, "Capture Texture", width, height, format);
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let buffer = create_readback_buffer(device, "Capture Readback Buffer", texture.size());
+        Self {
+            texture,
+            view,
+            buffer,
+        }
+    }
+
+    // view に描画するコマンドの後に、テクスチャをバッファへコピーするコマンドを記録する
+    pub fn copy(&self, encoder: &mut wgpu::CommandEncoder) {
+        copy_to_buffer(encoder, &self.texture, &self.buffer);
+    }
+
+    // copy を記録したコマンドを提出した後に呼び、RGBA8 の画像として読み出す
+    pub fn read(&self, device: &wgpu::Device) -> Result<image::RgbaImage> {
+        map_blocking(device, &self.buffer)?;
+        read_mapped(&self.buffer, self.texture.size(), self.texture.format())
+    }
+}
+
+// スクリーンショットを保存するディレクトリ（実行したディレクトリからの相対パス）
+const SCREENSHOT_DIR: &str = "screenshots";
+
+// 撮影した時刻をミリ秒まで含めたファイル名（続けて撮っても上書きしない）
+fn screenshot_path(time: SystemTime) -> PathBuf {
+    let millis = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis());
+    PathBuf::from(SCREENSHOT_DIR).join(format!("screenshot-{}.png", millis))
+}
+
+// PNG へのエンコードと書き込みは別のスレッドで行い、フレームの処理を止めない
+// 失敗してもアプリケーションは続け、理由を表示するだけにする
+pub fn save_screenshot(image: image::RgbaImage) {
+    let path = screenshot_path(SystemTime::now());
+    std::thread::spawn(move || {
+        let result = std::fs::create_dir_all(SCREENSHOT_DIR)
+            .map_err(anyhow::Error::from)
+            .and_then(|()| image.save(&path).map_err(anyhow::Error::from));
+        match result {
+            Ok(()) => info!("スクリーンショットを保存しました: {}", path.display()),
+            Err(e) => warn!(
+                "スクリーンショットを保存できませんでした（{}）: {:#}",
+                path.display(),
+                e
+            ),
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rows_are_padded_to_256_bytes_and_stripped_back() {
+        assert_eq!(padded_bytes_per_row(64), 256);
+        assert_eq!(padded_bytes_per_row(65), 512);
+        // 幅 3 の画像は 12 バイトの画素の後ろに 244 バイトの詰め物が付く
+        let mut data = vec![0u8; 256 * 2];
+        data[..12].fill(1);
+        data[256..268].fill(2);
+        let pixels = strip_padding(&data, 3, 2);
+        assert_eq!(pixels.len(), 24);
+        assert_eq!(&pixels[..12], &[1; 12]);
+        assert_eq!(&pixels[12..], &[2; 12]);
+    }
+
+    #[test]
+    fn bgra_pixels_are_swapped_to_rgba() {
+        let mut pixels = [10, 20, 30, 255, 1, 2, 3, 4];
+        to_rgba8(wgpu::TextureFormat::Bgra8UnormSrgb, &mut pixels).unwrap();
+        assert_eq!(pixels, [30, 20, 10, 255, 3, 2, 1, 4]);
+        assert!(to_rgba8(wgpu::TextureFormat::Rgba16Float, &mut pixels).is_err());
+    }
+}
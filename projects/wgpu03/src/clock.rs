@@ -0,0 +1,92 @@
+use std::time::{Duration, Instant};
+
+// 1フレームの経過時間をこれ以上にしない（必要なときだけ描画する場合は、前のフレームから長く空くことがあるため）
+const MAX_DT: Duration = Duration::from_millis(100);
+
+// update に渡す、このフレームの時間
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FrameTime {
+    // 前のフレームからの実際の間隔（フレーム時間の統計に使う）
+    pub interval: Duration,
+    // カメラを動かす経過時間（秒、MAX_DT まで）
+    pub dt: f32,
+    // アニメーションを進める経過時間（MAX_DT まで。一時停止中は 0）
+    pub animation_step: Duration,
+    // 起動してからアニメーションを進めた時間（秒、シェーダーのアニメーションに使う）
+    pub time: f32,
+}
+
+// 前のフレームの時刻と、一時停止していない間に経った時間から、フレームごとの時間を求める
+pub struct FrameClock {
+    last_frame: Instant,
+    elapsed: Duration,
+    paused: bool,
+}
+
+impl Default for FrameClock {
+    fn default() -> Self {
+        Self {
+            last_frame: Instant::now(),
+            elapsed: Duration::ZERO,
+            paused: false,
+        }
+    }
+}
+
+impl FrameClock {
+    // 今のフレームの時間を求め、次のフレームの基準にする
+    pub fn tick(&mut self) -> FrameTime {
+        self.tick_at(Instant::now())
+    }
+
+    fn tick_at(&mut self, now: Instant) -> FrameTime {
+        let interval = now - self.last_frame;
+        self.last_frame = now;
+        let animation_step = if self.paused {
+            Duration::ZERO
+        } else {
+            interval.min(MAX_DT)
+        };
+        self.elapsed += animation_step;
+        FrameTime {
+            interval,
+            dt: interval.min(MAX_DT).as_secs_f32(),
+            animation_step,
+            time: self.elapsed.as_secs_f32(),
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    // 一時停止している間はアニメーションの時間を止める（カメラは動かせる）
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_gaps_between_frames_are_clamped() {
+        let mut clock = FrameClock::default();
+        let start = clock.last_frame;
+        let frame = clock.tick_at(start + Duration::from_millis(16));
+        assert_eq!(frame.interval, Duration::from_millis(16));
+        assert!((frame.dt - 0.016).abs() < 1e-6);
+        // 何もせずに待っていた後のフレームでも、カメラやアニメーションが一気に進まないようにする
+        let frame = clock.tick_at(start + Duration::from_secs(5));
+        assert_eq!(frame.interval, Duration::from_millis(4984));
+        assert!((frame.dt - 0.1).abs() < 1e-6);
+        assert!((frame.time - 0.116).abs() < 1e-6);
+        // 一時停止中はアニメーションの時間だけが止まる
+        clock.set_paused(true);
+        let frame = clock.tick_at(start + Duration::from_millis(5050));
+        assert_eq!(frame.animation_step, Duration::ZERO);
+        assert!((frame.dt - 0.05).abs() < 1e-6);
+        assert!((frame.time - 0.116).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,103 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+const FILE_NAME: &str = "config.toml";
+
+/// 実行ファイルと同じディレクトリの config.toml に保存する設定
+///
+/// 起動時に読み込んでコマンドライン引数で上書きし、ウィンドウを閉じるときに今の状態を書き戻す。
+/// 書かれていない項目は既定の値になる。
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// 前回閉じたときのウィンドウの大きさと位置
+    pub window: WindowConfig,
+    /// 垂直同期を待つか（書かれていなければサーフェイスが対応している中から選ぶ）
+    pub vsync: Option<bool>,
+    /// マルチサンプリングのサンプル数（1・2・4・8）
+    pub msaa: Option<u32>,
+    /// 背景の色（sRGB ではない 0〜1 の RGB）
+    pub clear_color: Option<[f64; 3]>,
+    /// 操作の名前とキーの名前（例: `pause = "KeyP"`）
+    pub keybindings: BTreeMap<String, String>,
+}
+
+/// config.toml の `[window]`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowConfig {
+    /// 論理ピクセルの幅
+    pub width: Option<u32>,
+    /// 論理ピクセルの高さ
+    pub height: Option<u32>,
+    /// 物理ピクセルの位置（書かれていなければウィンドウシステムに任せる）
+    pub x: Option<i32>,
+    /// 物理ピクセルの位置（書かれていなければウィンドウシステムに任せる）
+    pub y: Option<i32>,
+}
+
+fn path() -> Result<PathBuf> {
+    let exe = std::env::current_exe().context("実行ファイルの場所がわかりません")?;
+    Ok(exe.with_file_name(FILE_NAME))
+}
+
+impl Config {
+    /// config.toml を読み込む（ファイルがなければ既定の設定にする）
+    ///
+    /// 読めないか書き方が正しくなければ、警告を表示して既定の設定で起動する。
+    pub fn load() -> Self {
+        let loaded = path().and_then(|path| {
+            if !path.exists() {
+                return Ok(Self::default());
+            }
+            let text = std::fs::read_to_string(&path)
+                .with_context(|| format!("{} を読み込めませんでした", path.display()))?;
+            toml::from_str(&text)
+                .with_context(|| format!("{} の書き方が正しくありません", path.display()))
+        });
+        loaded.unwrap_or_else(|e| {
+            eprintln!("既定の設定で起動します: {:#}", e);
+            Self::default()
+        })
+    }
+
+    /// config.toml に書き戻す
+    pub fn save(&self) -> Result<()> {
+        let path = path()?;
+        std::fs::write(&path, toml::to_string_pretty(self)?)
+            .with_context(|| format!("{} に保存できませんでした", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_fields_fall_back_to_defaults_and_saved_files_round_trip() {
+        let config: Config = toml::from_str("vsync = false\n[window]\nwidth = 1024\n").unwrap();
+        assert_eq!(config.vsync, Some(false));
+        assert_eq!(config.window.width, Some(1024));
+        assert_eq!(config.window.height, None);
+        assert!(config.keybindings.is_empty());
+
+        let config = Config {
+            window: WindowConfig {
+                width: Some(1280),
+                height: Some(720),
+                x: Some(-1920),
+                y: Some(40),
+            },
+            vsync: Some(true),
+            msaa: Some(4),
+            clear_color: Some([0.1, 0.2, 0.3]),
+            keybindings: BTreeMap::from([("pause".to_string(), "KeyP".to_string())]),
+        };
+        let text = toml::to_string_pretty(&config).unwrap();
+        assert_eq!(toml::from_str::<Config>(&text).unwrap(), config);
+        assert!(toml::from_str::<Config>("msaa = \"four\"").is_err());
+    }
+}
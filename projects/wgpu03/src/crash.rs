@@ -0,0 +1,115 @@
+//! パニックしたときに、どの GPU のどのフレームのどのパスで起きたかを表示する
+//!
+//! wgpu の検証エラーによるパニックのメッセージだけでは、原因のパスやパイプラインが分かりにくいため。
+
+use std::cell::Cell;
+use std::sync::Mutex;
+
+// デバイスを作ったスレッドと描画するスレッドが異なるので、GPU の情報だけはスレッド間で共有する
+static GPU: Mutex<Option<GpuInfo>> = Mutex::new(None);
+
+thread_local! {
+    // このスレッドで描画を始めたフレームの番号（1 から数える）
+    static FRAME: Cell<u64> = const { Cell::new(0) };
+    // 最後に始めたパスと、最後に作ったパイプラインのラベル
+    // （パイプラインは作るときに記録するので、パニックしたときに使っていたものとは限らない）
+    static PASS: Cell<Option<&'static str>> = const { Cell::new(None) };
+    static CREATED_PIPELINE: Cell<Option<&'static str>> = const { Cell::new(None) };
+}
+
+struct GpuInfo {
+    adapter: String,
+    backend: wgpu::Backend,
+    format: wgpu::TextureFormat,
+}
+
+/// 状況を表示してから、既定のフック（メッセージとバックトレース）に渡すパニックフックを設定する
+pub fn install() {
+    let default = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        eprintln!("{}", report());
+        default(info);
+    }));
+}
+
+// デバイスとサーフェイスを作り直すたびに呼ぶ
+pub(crate) fn set_gpu(info: &wgpu::AdapterInfo, format: wgpu::TextureFormat) {
+    // パニックの表示には必須ではないので、ロックが壊れていても上書きする
+    let mut gpu = GPU.lock().unwrap_or_else(|e| e.into_inner());
+    *gpu = Some(GpuInfo {
+        adapter: info.name.clone(),
+        backend: info.backend,
+        format,
+    });
+}
+
+pub(crate) fn begin_frame() {
+    FRAME.set(FRAME.get() + 1);
+    PASS.set(None);
+}
+
+// このスレッドで最後に始めたフレームの番号
+pub(crate) fn frame() -> u64 {
+    FRAME.get()
+}
+
+// パスとパイプラインの記述子のラベルに使う（ラベルを記録してから、そのまま返す）
+pub(crate) fn pass(label: &'static str) -> Option<&'static str> {
+    PASS.set(Some(label));
+    Some(label)
+}
+
+pub(crate) fn created_pipeline(label: &'static str) -> Option<&'static str> {
+    CREATED_PIPELINE.set(Some(label));
+    Some(label)
+}
+
+fn report() -> String {
+    // パニックしたスレッドがロックを持ったままでも待たない
+    let gpu = match GPU.try_lock() {
+        Ok(gpu) => match gpu.as_ref() {
+            Some(gpu) => format!(
+                "{}（{:?}）、サーフェイスのフォーマット: {:?}",
+                gpu.adapter, gpu.backend, gpu.format
+            ),
+            None => "デバイスを作る前".to_string(),
+        },
+        Err(_) => "不明".to_string(),
+    };
+    format!(
+        "パニックしたときの状況:\n  GPU: {}\n  フレーム: {}\n  最後に始めたパス: {}\n  最後に作ったパイプライン: {}",
+        gpu,
+        FRAME.get(),
+        PASS.get().unwrap_or("なし"),
+        CREATED_PIPELINE.get().unwrap_or("なし"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_names_the_last_pass_and_created_pipeline_of_this_thread() {
+        // スレッドごとに記録するので、他のテストと混ざらないよう新しいスレッドで調べる
+        let report = std::thread::spawn(|| {
+            created_pipeline("Opaque Pipeline");
+            begin_frame();
+            pass(crate::state::MAIN_PASS_LABEL);
+            begin_frame();
+            let first = report();
+            pass(crate::state::SHADOW_PASS_LABEL);
+            (first, report())
+        })
+        .join()
+        .unwrap();
+        assert!(report.0.contains("フレーム: 2"));
+        assert!(report.0.contains("最後に始めたパス: なし"));
+        assert!(
+            report
+                .0
+                .contains("最後に作ったパイプライン: Opaque Pipeline")
+        );
+        assert!(report.1.contains(crate::state::SHADOW_PASS_LABEL));
+    }
+}
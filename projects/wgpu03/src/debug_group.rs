@@ -0,0 +1,98 @@
+use std::borrow::Cow;
+use std::ops::{Deref, DerefMut};
+
+// RenderDoc や Xcode の GPU キャプチャで、フレームのコマンドを区切りごとにまとめて表示する
+// リリースビルドでは、機能 debug-markers を有効にしたときだけ記録する
+const ENABLED: bool = cfg!(any(debug_assertions, feature = "debug-markers"));
+
+// push_debug_group で始めたグループを、スコープを抜けるときに必ず pop_debug_group で閉じる
+// グループの中のコマンドは、CommandEncoder と同じようにこれに記録する
+pub struct DebugGroup<'a> {
+    encoder: &'a mut wgpu::CommandEncoder,
+}
+
+impl<'a> DebugGroup<'a> {
+    pub fn new(encoder: &'a mut wgpu::CommandEncoder, label: &str) -> Self {
+        if ENABLED {
+            encoder.push_debug_group(label);
+        }
+        Self { encoder }
+    }
+}
+
+impl Deref for DebugGroup<'_> {
+    type Target = wgpu::CommandEncoder;
+
+    fn deref(&self) -> &Self::Target {
+        self.encoder
+    }
+}
+
+impl DerefMut for DebugGroup<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.encoder
+    }
+}
+
+impl Drop for DebugGroup<'_> {
+    fn drop(&mut self) {
+        if ENABLED {
+            self.encoder.pop_debug_group();
+        }
+    }
+}
+
+pub fn marker(encoder: &mut wgpu::CommandEncoder, label: &str) {
+    if ENABLED {
+        encoder.insert_debug_marker(label);
+    }
+}
+
+// キャプチャで提出したコマンドバッファを見分けられるよう、エンコーダーのラベルにフレームの番号を付ける
+// （記録しないビルドでは、毎フレーム文字列を作らないよう番号を付けない）
+pub fn frame_label(label: &'static str, frame: u64) -> Cow<'static, str> {
+    if ENABLED {
+        Cow::Owned(format!("{} #{}", label, frame))
+    } else {
+        Cow::Borrowed(label)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nested_groups_are_closed_before_the_encoder_finishes() {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+        let Some(adapter) =
+            pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default()))
+        else {
+            eprintln!("アダプタがないため飛ばします");
+            return;
+        };
+        let (device, queue) =
+            pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor::default(), None))
+                .unwrap();
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let label = frame_label("Test Encoder", 7);
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some(&label),
+        });
+        {
+            let mut scene = DebugGroup::new(&mut encoder, "scene");
+            marker(&mut scene, "clear");
+            let _inset = DebugGroup::new(&mut scene, "inset");
+        }
+        queue.submit(Some(encoder.finish()));
+        assert!(pollster::block_on(device.pop_error_scope()).is_none());
+        assert_eq!(
+            label,
+            if ENABLED {
+                "Test Encoder #7"
+            } else {
+                "Test Encoder"
+            }
+        );
+    }
+}
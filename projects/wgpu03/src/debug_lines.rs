@@ -0,0 +1,110 @@
+// 最初に確保する頂点バッファの線分の数
+const INITIAL_CAPACITY: usize = 64;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LineVertex {
+    position: [f32; 3],
+    color: [f32; 3],
+}
+
+impl LineVertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 2] =
+        wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3];
+
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+// 積み上げた線分がすべて入る大きさ（2 の累乗に切り上げて再確保の回数を抑える）
+fn capacity_for(lines: usize) -> usize {
+    lines.next_power_of_two().max(INITIAL_CAPACITY)
+}
+
+fn create_vertex_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Debug Line Vertex Buffer"),
+        size: (capacity * 2 * std::mem::size_of::<LineVertex>()) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+// フレームごとに追加したワールド座標の線分を LineList のパイプラインで描画する
+// upload で書き込んだ時点で積み上げた線分は空になるので、毎フレーム追加し直す
+pub struct DebugLines {
+    vertices: Vec<LineVertex>,
+    vertex_buffer: wgpu::Buffer,
+    // vertex_buffer に入る線分の数
+    capacity: usize,
+    // 直前の upload で書き込んだ頂点の数
+    uploaded: u32,
+}
+
+impl DebugLines {
+    pub fn new(device: &wgpu::Device) -> Self {
+        Self {
+            vertices: Vec::new(),
+            vertex_buffer: create_vertex_buffer(device, INITIAL_CAPACITY),
+            capacity: INITIAL_CAPACITY,
+            uploaded: 0,
+        }
+    }
+
+    pub fn add_line(&mut self, from: glam::Vec3, to: glam::Vec3, color: [f32; 3]) {
+        self.vertices.push(LineVertex {
+            position: from.to_array(),
+            color,
+        });
+        self.vertices.push(LineVertex {
+            position: to.to_array(),
+            color,
+        });
+    }
+
+    // 原点から X（赤）・Y（緑）・Z（青）の方向に伸びる座標軸
+    pub fn add_axes(&mut self, length: f32) {
+        self.add_line(glam::Vec3::ZERO, glam::Vec3::X * length, [1.0, 0.0, 0.0]);
+        self.add_line(glam::Vec3::ZERO, glam::Vec3::Y * length, [0.0, 1.0, 0.0]);
+        self.add_line(glam::Vec3::ZERO, glam::Vec3::Z * length, [0.0, 0.0, 1.0]);
+    }
+
+    // 積み上げた線分をバッファに書き込み、次のフレームのために空にする
+    // 入りきらない場合は捨てずにバッファを大きくしてから書き込む
+    pub fn upload(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        let lines = self.vertices.len() / 2;
+        if lines > self.capacity {
+            self.capacity = capacity_for(lines);
+            self.vertex_buffer = create_vertex_buffer(device, self.capacity);
+        }
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&self.vertices));
+        self.uploaded = self.vertices.len() as u32;
+        self.vertices.clear();
+    }
+
+    // LineList のパイプラインとカメラのバインドグループを設定してから呼ぶこと
+    pub fn draw(&self, rpass: &mut wgpu::RenderPass) {
+        if self.uploaded == 0 {
+            return;
+        }
+        rpass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        rpass.draw(0..self.uploaded, 0..1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn capacity_always_fits_all_lines() {
+        assert_eq!(capacity_for(3), INITIAL_CAPACITY);
+        assert_eq!(capacity_for(65), 128);
+        assert!((1..2000).all(|lines| capacity_for(lines) >= lines));
+    }
+}
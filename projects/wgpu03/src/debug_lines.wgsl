@@ -0,0 +1,31 @@
+struct Camera {
+    view_proj: mat4x4<f32>,
+    model: mat4x4<f32>,
+    view_position: vec4<f32>,
+};
+
+// 線分はワールド座標で指定するので、モデル行列は使わない
+@group(0) @binding(0) var<uniform> camera: Camera;
+
+struct VInput {
+    @location(0) position: vec3<f32>,
+    @location(1) color: vec3<f32>,
+};
+
+struct VOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec3<f32>,
+};
+
+@vertex
+fn vs_main(in: VInput) -> VOutput {
+    var out: VOutput;
+    out.position = camera.view_proj * vec4<f32>(in.position, 1.0);
+    out.color = in.color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(in.color, 1.0);
+}
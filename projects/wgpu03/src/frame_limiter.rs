@@ -0,0 +1,103 @@
+use std::time::{Duration, Instant};
+
+// F6 キーで順に切り替える FPS の上限（最後の次は上限なしに戻る）
+const FPS_STEPS: &[u32] = &[30, 60, 120, 144, 240];
+// スリープの精度は 1ms より粗いことがあるので、描画する時刻のこれだけ前に起きて残りは回って待つ
+const SPIN_MARGIN: Duration = Duration::from_millis(2);
+
+// FPS の上限を超えないよう、次のフレームを描画する時刻を決める
+// 待つ間はイベントループを ControlFlow::WaitUntil で眠らせ、最後の SPIN_MARGIN だけを回って待つ
+#[derive(Debug, Default)]
+pub struct FrameLimiter {
+    max_fps: Option<u32>,
+    // 次のフレームを描画する時刻（待っていなければ None）
+    deadline: Option<Instant>,
+}
+
+impl FrameLimiter {
+    pub fn new(max_fps: Option<u32>) -> Self {
+        Self {
+            max_fps: max_fps.filter(|&fps| fps > 0),
+            deadline: None,
+        }
+    }
+
+    pub fn max_fps(&self) -> Option<u32> {
+        self.max_fps
+    }
+
+    // 0 は上限なし
+    pub fn set_max_fps(&mut self, max_fps: Option<u32>) {
+        self.max_fps = max_fps.filter(|&fps| fps > 0);
+        self.deadline = None;
+    }
+
+    // 上限なし → 30 → 60 → … → 240 → 上限なし の順に切り替える
+    pub fn cycle(&mut self) {
+        let next = match self.max_fps {
+            None => FPS_STEPS.first().copied(),
+            Some(fps) => FPS_STEPS.iter().copied().find(|&step| step > fps),
+        };
+        self.set_max_fps(next);
+    }
+
+    // frame_start に描画を始めたフレームの次のフレームを描画する時刻を決める
+    // 上限がなければ何もせずに false を返す（すぐに次のフレームを描画してよい）
+    pub fn schedule(&mut self, frame_start: Instant) -> bool {
+        let Some(fps) = self.max_fps else {
+            return false;
+        };
+        self.deadline = Some(frame_start + Duration::from_secs(1) / fps);
+        true
+    }
+
+    // イベントループを眠らせておく時刻（待っていなければ None）
+    pub fn wake_time(&self) -> Option<Instant> {
+        self.deadline
+            .map(|deadline| deadline.checked_sub(SPIN_MARGIN).unwrap_or(deadline))
+    }
+
+    // wake_time を過ぎていれば描画する時刻まで回って待ち、true を返す（次のフレームを描画してよい）
+    pub fn wait(&mut self) -> bool {
+        let (Some(deadline), Some(wake)) = (self.deadline, self.wake_time()) else {
+            return false;
+        };
+        if Instant::now() < wake {
+            return false;
+        }
+        while Instant::now() < deadline {
+            std::hint::spin_loop();
+        }
+        self.deadline = None;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_are_scheduled_one_period_after_the_previous_start() {
+        let mut limiter = FrameLimiter::new(None);
+        let start = Instant::now();
+        assert!(!limiter.schedule(start));
+        assert_eq!(limiter.wake_time(), None);
+
+        limiter.cycle();
+        assert_eq!(limiter.max_fps(), Some(30));
+        limiter.set_max_fps(Some(100));
+        assert!(limiter.schedule(start));
+        assert_eq!(
+            limiter.wake_time(),
+            Some(start + Duration::from_millis(10) - SPIN_MARGIN)
+        );
+        // 144 の次は 240、240 の次は上限なし
+        limiter.set_max_fps(Some(144));
+        limiter.cycle();
+        assert_eq!(limiter.max_fps(), Some(240));
+        limiter.cycle();
+        assert_eq!(limiter.max_fps(), None);
+        assert_eq!(FrameLimiter::new(Some(0)).max_fps(), None);
+    }
+}
@@ -0,0 +1,156 @@
+use glam::{Mat4, Vec3, Vec4};
+
+// 軸に平行な箱
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    // 点が1つもなければ原点だけを囲む箱にする
+    pub fn from_points(points: impl IntoIterator<Item = Vec3>) -> Self {
+        let mut points = points.into_iter();
+        let Some(first) = points.next() else {
+            return Self {
+                min: Vec3::ZERO,
+                max: Vec3::ZERO,
+            };
+        };
+        points.fold(
+            Self {
+                min: first,
+                max: first,
+            },
+            |aabb, point| Self {
+                min: aabb.min.min(point),
+                max: aabb.max.max(point),
+            },
+        )
+    }
+
+    // matrix で変換した8つの角を囲む箱（回転すると元の箱より大きくなる）
+    pub fn transformed(&self, matrix: Mat4) -> Self {
+        Self::from_points((0..8).map(|i| {
+            let corner = Vec3::select(
+                glam::BVec3::new(i & 1 != 0, i & 2 != 0, i & 4 != 0),
+                self.max,
+                self.min,
+            );
+            matrix.transform_point3(corner)
+        }))
+    }
+}
+
+// ビュー・射影行列から取り出した左・右・下・上・近・遠の6つの平面
+// 平面 (a, b, c, d) は法線 (a, b, c) が内側を向き、a x + b y + c z + d >= 0 が視錐台の内側になる
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Frustum {
+    planes: [Vec4; 6],
+}
+
+impl Frustum {
+    // クリップ座標 (x, y, z, w) が -w <= x, y <= w かつ 0 <= z <= w になる範囲
+    // wgpu の深度は OpenGL と違って 0..w なので、近い面は r3 + r2 ではなく r2 そのものになる
+    pub fn from_view_projection(matrix: Mat4) -> Self {
+        let [r0, r1, r2, r3] = [0, 1, 2, 3].map(|i| matrix.row(i));
+        let planes = [r3 + r0, r3 - r0, r3 + r1, r3 - r1, r2, r3 - r2]
+            .map(|plane| plane / plane.truncate().length());
+        Self { planes }
+    }
+
+    // 箱がどれか1つの平面の完全に外側にあれば false
+    // 角の近くでは外側の箱も true になることがあるが、描画を省けないだけで見た目は変わらない
+    pub fn intersects(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|plane| {
+            let normal = plane.truncate();
+            // 法線の向きに最も進んだ角が外側なら、箱全体が外側にある
+            let farthest = Vec3::select(normal.cmpge(Vec3::ZERO), aabb.max, aabb.min);
+            normal.dot(farthest) + plane.w >= 0.0
+        })
+    }
+}
+
+// 視錐台の8つの角のワールド座標（番号の 1, 2, 4 のビットが x, y, z の正の側を表す）
+pub fn corners(view_projection: Mat4) -> [Vec3; 8] {
+    let inverse = view_projection.inverse();
+    [0, 1, 2, 3, 4, 5, 6, 7].map(|i| {
+        let x = if i & 1 == 0 { -1.0 } else { 1.0 };
+        let y = if i & 2 == 0 { -1.0 } else { 1.0 };
+        let z = if i & 4 == 0 { 0.0 } else { 1.0 };
+        inverse.project_point3(Vec3::new(x, y, z))
+    })
+}
+
+// 視錐台カリングで描画した数と対象の数
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CullStats {
+    pub drawn: usize,
+    pub total: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::Camera;
+
+    fn point(position: Vec3) -> Aabb {
+        Aabb::from_points([position])
+    }
+
+    // 点から各平面までの符号付きの距離（内側が正）
+    fn distances(frustum: &Frustum, point: Vec3) -> [f32; 6] {
+        frustum
+            .planes
+            .map(|plane| plane.truncate().dot(point) + plane.w)
+    }
+
+    #[test]
+    fn planes_sit_at_the_near_and_far_clip_distances_of_the_zero_to_one_depth_range() {
+        // 原点を向いて z = 2 にあるカメラ（znear 0.1, zfar 100）
+        let camera = Camera::new(800, 600);
+        let frustum = Frustum::from_view_projection(camera.build_view_projection_matrix());
+        let near = camera.eye.z - camera.znear;
+        let far = camera.eye.z - camera.zfar;
+        for (z, inside) in [
+            (near + 0.001, false),
+            (near - 0.001, true),
+            (far + 0.01, true),
+            (far - 0.01, false),
+        ] {
+            assert_eq!(frustum.intersects(&point(Vec3::new(0.0, 0.0, z))), inside);
+        }
+
+        // 正規化した平面の値はワールド座標での距離になる
+        let distances = distances(&frustum, Vec3::ZERO);
+        assert!((distances[4] - (camera.eye.z - camera.znear)).abs() < 1e-4);
+        assert!((distances[5] - (camera.zfar - camera.eye.z)).abs() < 1e-3);
+        // 左右の面は視野角の半分だけ傾いている
+        let half_width = (camera.fovy * 0.5).tan() * camera.aspect * camera.eye.z;
+        assert!(frustum.intersects(&point(Vec3::new(half_width - 0.01, 0.0, 0.0))));
+        assert!(!frustum.intersects(&point(Vec3::new(half_width + 0.01, 0.0, 0.0))));
+        // 角は逆行列で戻した近い面の上にある
+        let corners = corners(camera.build_view_projection_matrix());
+        assert!((corners[0].z - near).abs() < 1e-4);
+        assert!((corners[7].z - far).abs() < 1e-2);
+    }
+
+    #[test]
+    fn boxes_are_culled_only_when_entirely_outside_one_plane() {
+        let camera = Camera::new(800, 600);
+        let frustum = Frustum::from_view_projection(camera.build_view_projection_matrix());
+        let unit = Aabb::from_points([Vec3::splat(-0.5), Vec3::splat(0.5)]);
+        assert!(frustum.intersects(&unit));
+        // 左の面をまたぐ箱は残し、完全に左にある箱は省く
+        assert!(frustum.intersects(&unit.transformed(Mat4::from_translation(Vec3::X * -1.5))));
+        assert!(!frustum.intersects(&unit.transformed(Mat4::from_translation(Vec3::X * -3.0))));
+        // カメラの後ろと、カメラを包む箱
+        assert!(!frustum.intersects(&unit.transformed(Mat4::from_translation(Vec3::Z * 3.0))));
+        assert!(frustum.intersects(&unit.transformed(Mat4::from_scale(Vec3::splat(10.0)))));
+
+        // 回転した箱は8つの角を囲むので大きくなる
+        let rotated = unit.transformed(Mat4::from_rotation_y(std::f32::consts::FRAC_PI_4));
+        assert!((rotated.max.x - 0.5 * 2f32.sqrt()).abs() < 1e-5);
+        assert_eq!(rotated.max.y, 0.5);
+    }
+}
@@ -0,0 +1,78 @@
+use tracing::{info, warn};
+use winit::dpi::PhysicalSize;
+use winit::monitor::VideoModeHandle;
+use winit::window::{Fullscreen, Window};
+
+// 全画面表示の種類（`--fullscreen` で境界なし、`--exclusive-fullscreen` で排他的な全画面表示で起動する）
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FullscreenMode {
+    // 枠のないウィンドウでモニター全体を覆う（デスクトップの解像度のまま）
+    Borderless,
+    // モニターの表示モードを切り替えて占有する（alt-tab で抜けるとサーフェイスが Lost になることがある）
+    Exclusive,
+}
+
+// (大きさ, リフレッシュレート[mHz], 色深度) の中から、native の解像度でリフレッシュレートが最も高いものの番号
+fn best_video_mode(
+    modes: &[(PhysicalSize<u32>, u32, u16)],
+    native: PhysicalSize<u32>,
+) -> Option<usize> {
+    modes
+        .iter()
+        .enumerate()
+        .filter(|(_, (size, _, _))| *size == native)
+        .max_by_key(|(_, (_, refresh, depth))| (*refresh, *depth))
+        .map(|(i, _)| i)
+}
+
+// window のあるモニターで mode の全画面表示にする設定
+// 排他的な全画面表示にできるモードが見つからなければ、警告を表示して境界なしにする
+pub fn fullscreen(window: &Window, mode: FullscreenMode) -> Fullscreen {
+    if mode == FullscreenMode::Borderless {
+        return Fullscreen::Borderless(None);
+    }
+    let Some(monitor) = window.current_monitor() else {
+        warn!("モニターがわからないため、境界なしの全画面表示にします");
+        return Fullscreen::Borderless(None);
+    };
+    let modes: Vec<VideoModeHandle> = monitor.video_modes().collect();
+    let keys: Vec<_> = modes
+        .iter()
+        .map(|m| (m.size(), m.refresh_rate_millihertz(), m.bit_depth()))
+        .collect();
+    match best_video_mode(&keys, monitor.size()) {
+        Some(index) => {
+            let video_mode = modes[index].clone();
+            info!(
+                "表示モード {}x{} {:.2} Hz で全画面表示にします",
+                video_mode.size().width,
+                video_mode.size().height,
+                video_mode.refresh_rate_millihertz() as f64 / 1000.0
+            );
+            Fullscreen::Exclusive(video_mode)
+        }
+        None => {
+            warn!("モニターの解像度の表示モードがないため、境界なしの全画面表示にします");
+            Fullscreen::Borderless(Some(monitor))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_fastest_mode_at_native_resolution_is_chosen() {
+        let size = PhysicalSize::new;
+        let modes = [
+            (size(1280, 720), 240_000, 32),
+            (size(1920, 1080), 60_000, 32),
+            (size(1920, 1080), 144_000, 24),
+            (size(1920, 1080), 144_000, 32),
+            (size(1920, 1080), 120_000, 32),
+        ];
+        assert_eq!(best_video_mode(&modes, size(1920, 1080)), Some(3));
+        assert_eq!(best_video_mode(&modes, size(2560, 1440)), None);
+    }
+}
@@ -0,0 +1,196 @@
+use std::f32::consts::{PI, TAU};
+
+use glam::Vec3;
+
+use crate::mesh::Vertex;
+
+// 生成する図形の頂点色（テクスチャと光源の色をそのまま確認できるよう白にする）
+const WHITE: [f32; 3] = [1.0, 1.0, 1.0];
+
+fn vertex(position: Vec3, normal: Vec3, tex_coords: [f32; 2]) -> Vertex {
+    Vertex {
+        position: position.to_array(),
+        color: WHITE,
+        tex_coords,
+        normal: normal.to_array(),
+    }
+}
+
+// (columns + 1) 個ずつ並んだ頂点の格子を、外側から見て反時計回りになる三角形で埋める
+// 行 row の頂点の次の行は「下」（u が同じで v が増える向き）に並んでいるものとする
+// skip_first_row / skip_last_row は、極で潰れて面積のない三角形を省くときに使う
+fn grid_indices(rows: u32, columns: u32, skip_first_row: bool, skip_last_row: bool) -> Vec<u32> {
+    let mut indices = Vec::with_capacity((rows * columns * 6) as usize);
+    for row in 0..rows {
+        for column in 0..columns {
+            let a = row * (columns + 1) + column;
+            let b = a + columns + 1;
+            let (c, d) = (a + 1, b + 1);
+            if !(skip_first_row && row == 0) {
+                indices.extend_from_slice(&[a, b, c]);
+            }
+            if !(skip_last_row && row == rows - 1) {
+                indices.extend_from_slice(&[c, b, d]);
+            }
+        }
+    }
+    indices
+}
+
+// 原点を中心とする UV 球（rings は緯度方向、sectors は経度方向の分割数）
+pub fn uv_sphere(radius: f32, rings: u32, sectors: u32) -> (Vec<Vertex>, Vec<u32>) {
+    let (rings, sectors) = (rings.max(2), sectors.max(3));
+    let mut vertices = Vec::with_capacity(((rings + 1) * (sectors + 1)) as usize);
+    for ring in 0..=rings {
+        let v = ring as f32 / rings as f32;
+        let phi = v * PI;
+        for sector in 0..=sectors {
+            // テクスチャの継ぎ目で u が 0 と 1 の頂点を別にするため、最初と最後の経度は重複させる
+            let u = sector as f32 / sectors as f32;
+            let theta = u * TAU;
+            let normal = Vec3::new(phi.sin() * theta.sin(), phi.cos(), phi.sin() * theta.cos());
+            vertices.push(vertex(normal * radius, normal, [u, v]));
+        }
+    }
+    (vertices, grid_indices(rings, sectors, true, true))
+}
+
+// y = 0 の XZ 平面に置いた、一辺 size の正方形を subdivisions x subdivisions に分割した平面
+pub fn plane(size: f32, subdivisions: u32) -> (Vec<Vertex>, Vec<u32>) {
+    let subdivisions = subdivisions.max(1);
+    let mut vertices = Vec::with_capacity(((subdivisions + 1) * (subdivisions + 1)) as usize);
+    for row in 0..=subdivisions {
+        let v = row as f32 / subdivisions as f32;
+        for column in 0..=subdivisions {
+            let u = column as f32 / subdivisions as f32;
+            let position = Vec3::new((u - 0.5) * size, 0.0, (v - 0.5) * size);
+            vertices.push(vertex(position, Vec3::Y, [u, v]));
+        }
+    }
+    (
+        vertices,
+        grid_indices(subdivisions, subdivisions, false, false),
+    )
+}
+
+// Y 軸を囲むトーラス（major は中心から管の中心まで、minor は管の半径）
+pub fn torus(
+    major_radius: f32,
+    minor_radius: f32,
+    major_segments: u32,
+    minor_segments: u32,
+) -> (Vec<Vertex>, Vec<u32>) {
+    let (major_segments, minor_segments) = (major_segments.max(3), minor_segments.max(3));
+    let mut vertices = Vec::with_capacity(((major_segments + 1) * (minor_segments + 1)) as usize);
+    for i in 0..=major_segments {
+        let u = i as f32 / major_segments as f32;
+        let around = u * TAU;
+        // 管の中心を通る円の、この位置での外向きの向き
+        let outward = Vec3::new(around.cos(), 0.0, -around.sin());
+        for j in 0..=minor_segments {
+            let v = j as f32 / minor_segments as f32;
+            let tube = v * TAU;
+            let normal = outward * tube.cos() + Vec3::Y * tube.sin();
+            let position = outward * major_radius + normal * minor_radius;
+            vertices.push(vertex(position, normal, [u, v]));
+        }
+    }
+    (
+        vertices,
+        grid_indices(major_segments, minor_segments, false, false),
+    )
+}
+
+// Y 軸に沿った、原点を中心とする高さ height の円柱（側面と上下のふた）
+pub fn cylinder(radius: f32, height: f32, segments: u32) -> (Vec<Vertex>, Vec<u32>) {
+    let segments = segments.max(3);
+    let half = height * 0.5;
+    let rim = |i: u32| {
+        let theta = i as f32 / segments as f32 * TAU;
+        Vec3::new(theta.sin(), 0.0, theta.cos())
+    };
+
+    // 側面は上端と下端の頂点を交互に並べる（2行 × (segments + 1) 列の格子）
+    let mut vertices = Vec::with_capacity((4 * segments + 6) as usize);
+    for i in 0..=segments {
+        let u = i as f32 / segments as f32;
+        let normal = rim(i);
+        vertices.push(vertex(normal * radius + Vec3::Y * half, normal, [u, 0.0]));
+    }
+    for i in 0..=segments {
+        let u = i as f32 / segments as f32;
+        let normal = rim(i);
+        vertices.push(vertex(normal * radius - Vec3::Y * half, normal, [u, 1.0]));
+    }
+    let mut indices = grid_indices(1, segments, false, false);
+
+    // ふたは中心の頂点と周りの頂点を扇状につなぐ（側面と法線が違うので周りの頂点は別に作る）
+    for (y, normal) in [(half, Vec3::Y), (-half, Vec3::NEG_Y)] {
+        let center = vertices.len() as u32;
+        vertices.push(vertex(Vec3::Y * y, normal, [0.5, 0.5]));
+        for i in 0..=segments {
+            let direction = rim(i);
+            let tex_coords = [0.5 + direction.x * 0.5, 0.5 - direction.z * 0.5];
+            vertices.push(vertex(direction * radius + Vec3::Y * y, normal, tex_coords));
+        }
+        for i in 0..segments {
+            let (current, next) = (center + 1 + i, center + 2 + i);
+            // 下のふたは下から見て反時計回りになるよう向きを逆にする
+            if normal.y > 0.0 {
+                indices.extend_from_slice(&[center, current, next]);
+            } else {
+                indices.extend_from_slice(&[center, next, current]);
+            }
+        }
+    }
+    (vertices, indices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 法線が単位ベクトルで、面積のある三角形は外側から見て反時計回りであること
+    fn assert_well_formed((vertices, indices): &(Vec<Vertex>, Vec<u32>)) {
+        for vertex in vertices {
+            assert!((Vec3::from(vertex.normal).length() - 1.0).abs() < 1e-5);
+        }
+        assert_eq!(indices.len() % 3, 0);
+        for triangle in indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| &vertices[triangle[i] as usize]);
+            let (pa, pb, pc) = (
+                Vec3::from(a.position),
+                Vec3::from(b.position),
+                Vec3::from(c.position),
+            );
+            let face = (pb - pa).cross(pc - pa);
+            assert!(face.length() > 1e-8, "degenerate triangle {:?}", triangle);
+            let normal = Vec3::from(a.normal) + Vec3::from(b.normal) + Vec3::from(c.normal);
+            assert!(face.dot(normal) > 0.0, "inward triangle {:?}", triangle);
+        }
+    }
+
+    #[test]
+    fn generated_shapes_have_expected_counts_and_outward_unit_normals() {
+        let sphere = uv_sphere(1.0, 8, 16);
+        assert_eq!(sphere.0.len(), 9 * 17);
+        // 極の周りは1つの三角形で済むので、2行分の三角形が半分になる
+        assert_eq!(sphere.1.len(), 16 * 7 * 6);
+        assert_well_formed(&sphere);
+
+        let plane = plane(2.0, 4);
+        assert_eq!(plane.0.len(), 25);
+        assert_eq!(plane.1.len(), 4 * 4 * 6);
+        assert_well_formed(&plane);
+
+        let torus = torus(1.0, 0.25, 24, 12);
+        assert_eq!(torus.0.len(), 25 * 13);
+        assert_eq!(torus.1.len(), 24 * 12 * 6);
+        assert_well_formed(&torus);
+
+        let cylinder = cylinder(0.5, 1.0, 16);
+        assert_eq!(cylinder.0.len(), 4 * 16 + 6);
+        assert_eq!(cylinder.1.len(), 16 * 6 + 2 * 16 * 3);
+        assert_well_formed(&cylinder);
+    }
+}
@@ -0,0 +1,10 @@
+#version 450
+
+layout(location = 0) in vec3 world_normal;
+
+layout(location = 0) out vec4 color;
+
+// ワールド座標の法線の向きを -1..1 から 0..1 の色にして表示する
+void main() {
+    color = vec4(normalize(world_normal) * 0.5 + 0.5, 1.0);
+}
@@ -0,0 +1,27 @@
+#version 450
+
+// src/normals.vert.spv と src/normals.frag.spv の元のソース。変更したら src で次のどちらかで変換し直す
+// （wgpu は SPIR-V の座標系を変換せずに読み込むので、naga でも変換しない）
+//   glslc glsl/normals.vert -o normals.vert.spv
+//   naga --keep-coordinate-space --shader-stage vert glsl/normals.vert normals.vert.spv
+
+// shader.wgsl と同じカメラのユニフォーム（グループ 0 のバインディング 0）
+layout(set = 0, binding = 0) uniform Camera {
+    mat4 view_proj;
+    mat4 model;
+    vec4 view_position;
+    // モデル行列の逆転置（法線の変換に使う）
+    mat4 normal_matrix;
+} camera;
+
+layout(location = 0) in vec3 position;
+layout(location = 3) in vec3 normal;
+// インスタンスごとの位置のずれ
+layout(location = 5) in vec3 offset;
+
+layout(location = 0) out vec3 world_normal;
+
+void main() {
+    gl_Position = camera.view_proj * camera.model * vec4(position + offset, 1.0);
+    world_normal = (camera.normal_matrix * vec4(normal, 0.0)).xyz;
+}
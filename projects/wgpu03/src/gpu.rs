@@ -0,0 +1,291 @@
+//! サーフェイスのフォーマット・表示モード・サンプル数の選び方と、ウィンドウに描画するデバイスの初期化
+
+use std::sync::Arc;
+
+use tracing::Instrument;
+use winit::dpi::PhysicalSize;
+use winit::event_loop::ActiveEventLoop;
+use winit::window::Window;
+
+use crate::adapter;
+use crate::caps;
+use crate::init::{self, AppError};
+use crate::settings::Settings;
+use crate::state::State;
+use crate::texture::Texture;
+
+/// バックエンドによって formats の並び順が異なるため、ガンマ補正が自動で行われる sRGB のフォーマットを優先し、
+/// なければ先頭のものを使う
+///
+/// アダプタが表示できないサーフェイスでは formats が空になり、None を返す。
+pub fn choose_surface_format(formats: &[wgpu::TextureFormat]) -> Option<wgpu::TextureFormat> {
+    formats
+        .iter()
+        .copied()
+        .find(|format| format.is_srgb())
+        .or(formats.first().copied())
+}
+
+/// V キーで切り替える表示モードの順（サーフェイスが対応しているものだけを使う）
+/// 起動時は先頭から探して最初に対応しているものを使い、Fifo はどのサーフェイスでも使える
+pub const PRESENT_MODE_ORDER: [wgpu::PresentMode; 4] = [
+    wgpu::PresentMode::Mailbox,
+    wgpu::PresentMode::Immediate,
+    wgpu::PresentMode::Fifo,
+    wgpu::PresentMode::FifoRelaxed,
+];
+
+/// サーフェイスが対応している表示モードを PRESENT_MODE_ORDER の順に並べたもの
+pub fn supported_present_modes(caps: &[wgpu::PresentMode]) -> Vec<wgpu::PresentMode> {
+    PRESENT_MODE_ORDER
+        .into_iter()
+        .filter(|mode| caps.contains(mode))
+        .collect()
+}
+
+/// 垂直同期を待つ表示モードか（この場合は FPS の上限で待たず、表示の間隔に任せる）
+pub fn is_vsync(mode: wgpu::PresentMode) -> bool {
+    matches!(
+        mode,
+        wgpu::PresentMode::Fifo | wgpu::PresentMode::FifoRelaxed | wgpu::PresentMode::AutoVsync
+    )
+}
+
+/// modes の中で current の次の表示モード（current が modes になければ先頭）
+pub fn next_present_mode(
+    modes: &[wgpu::PresentMode],
+    current: wgpu::PresentMode,
+) -> Option<wgpu::PresentMode> {
+    let next = modes
+        .iter()
+        .position(|&mode| mode == current)
+        .map_or(0, |i| (i + 1) % modes.len());
+    modes.get(next).copied()
+}
+
+/// `--transparent` で使う、デスクトップが透けて見えるアルファの扱い（対応していなければ None）
+pub fn transparent_alpha_mode(
+    modes: &[wgpu::CompositeAlphaMode],
+) -> Option<wgpu::CompositeAlphaMode> {
+    [
+        wgpu::CompositeAlphaMode::PreMultiplied,
+        wgpu::CompositeAlphaMode::PostMultiplied,
+    ]
+    .into_iter()
+    .find(|mode| modes.contains(mode))
+}
+
+/// HDR ディスプレイ向けの線形な浮動小数点のフォーマット（1.0 を超える明るさもそのまま表示される）
+pub const HDR_SURFACE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+/// HDR 出力に使えるフォーマットがあれば返す
+pub fn choose_hdr_surface_format(formats: &[wgpu::TextureFormat]) -> Option<wgpu::TextureFormat> {
+    formats
+        .contains(&HDR_SURFACE_FORMAT)
+        .then_some(HDR_SURFACE_FORMAT)
+}
+
+/// サーフェイスへ描画するときのビューのフォーマット
+/// サーフェイスが sRGB でない場合も、view_formats に追加した sRGB のビューを通して書き込む
+pub fn surface_view_format(config: &wgpu::SurfaceConfiguration) -> wgpu::TextureFormat {
+    config
+        .view_formats
+        .first()
+        .copied()
+        .unwrap_or(config.format)
+}
+
+/// カラーフォーマットと深度フォーマットの両方が対応している場合のみ希望のサンプル数を使い、
+/// そうでなければ 1（MSAAなし）にフォールバックする
+/// 深度フォーマットは、深度テクスチャを作るときの使い方でも使えることを確かめる
+pub fn choose_sample_count(
+    adapter: &wgpu::Adapter,
+    format: wgpu::TextureFormat,
+    desired: u32,
+) -> u32 {
+    let color_flags = adapter.get_texture_format_features(format).flags;
+    let depth = adapter.get_texture_format_features(Texture::DEPTH_FORMAT);
+    if color_flags.sample_count_supported(desired)
+        && depth.flags.sample_count_supported(desired)
+        && depth.allowed_usages.contains(Texture::DEPTH_USAGE)
+    {
+        desired
+    } else {
+        1
+    }
+}
+
+/// `--print-caps` で表示する、すべてのアダプタと選んだアダプタ・サーフェイスの対応状況
+pub async fn print_caps(
+    instance: &wgpu::Instance,
+    surface: Option<&wgpu::Surface<'_>>,
+    settings: &Settings,
+) {
+    let adapter = match adapter::request(
+        instance,
+        surface,
+        settings.adapter.as_deref(),
+        settings.power,
+    )
+    .await
+    {
+        Ok(adapter) => adapter,
+        Err(e) => {
+            eprintln!("{}", init::error_chain(&e));
+            return;
+        }
+    };
+    let adapters: Vec<wgpu::AdapterInfo> = instance
+        .enumerate_adapters(wgpu::Backends::all())
+        .iter()
+        .map(|adapter| adapter.get_info())
+        .collect();
+    let surface_caps = surface.map(|surface| surface.get_capabilities(&adapter));
+    for line in caps::report(
+        &adapters,
+        &adapter.get_info(),
+        adapter.features(),
+        &adapter.limits(),
+        surface_caps.as_ref(),
+    ) {
+        println!("{}", line);
+    }
+}
+
+// ウィンドウに描画するインスタンスとサーフェイス
+// Metal のサーフェイスは UI のスレッドでしか作れないので、イベントループのスレッドで作り、
+// アダプタとデバイスを待つところからを別のスレッド（wasm では spawn_local）で行う
+pub(crate) struct SurfaceTarget {
+    instance: wgpu::Instance,
+    window: Arc<Window>,
+    surface: wgpu::Surface<'static>,
+}
+
+impl SurfaceTarget {
+    pub(crate) fn new(window: Arc<Window>, settings: &Settings) -> Result<Self, AppError> {
+        // wgpuの初期化（インスタンスの作成）
+        let instance = adapter::create_instance(settings.backends);
+        let surface = instance.create_surface(window.clone())?;
+        Ok(Self {
+            instance,
+            window,
+            surface,
+        })
+    }
+
+    // `--print-caps` で、このサーフェイスまで含めた対応状況を表示する
+    pub(crate) async fn print_caps(&self, settings: &Settings) {
+        print_caps(&self.instance, Some(&self.surface), settings).await;
+    }
+}
+
+// target のサーフェイスに描画するアダプタ・デバイスと、すべてのリソースを作る
+// 起動時と、デバイスが失われて作り直すときに呼ぶ
+pub(crate) async fn init_gpu(
+    target: SurfaceTarget,
+    settings: &Settings,
+) -> Result<State, AppError> {
+    let SurfaceTarget {
+        instance,
+        window,
+        surface,
+    } = target;
+    // Wayland では最初の configure が届くまで大きさが 0 のことがあるので、1 以上にしておく
+    // （実際の大きさは続けて届く Resized で設定し直す）
+    let size = window.inner_size();
+    let size = PhysicalSize::new(size.width.max(1), size.height.max(1));
+
+    // 初期化のログをまとめる（フォーマットと表示モードは State::new で決めたところで記録する）
+    let span = tracing::info_span!(
+        "init",
+        width = size.width,
+        height = size.height,
+        format = tracing::field::Empty,
+        present_mode = tracing::field::Empty,
+    );
+    let state = State::new(
+        &instance,
+        Some((window, surface)),
+        size.width,
+        size.height,
+        settings,
+    )
+    .instrument(span)
+    .await?;
+    Ok(state)
+}
+
+/// Linux で使っているウィンドウシステムと、表示がおかしい場合の切り替え方
+#[cfg(target_os = "linux")]
+pub fn log_window_system(event_loop: &ActiveEventLoop) {
+    use winit::platform::wayland::ActiveEventLoopExtWayland;
+
+    if event_loop.is_wayland() {
+        tracing::info!(
+            "ウィンドウシステム: Wayland（表示やスケーリングに問題があれば --x11 で XWayland を使えます）"
+        );
+    } else {
+        tracing::info!(
+            "ウィンドウシステム: X11（Wayland のセッションなら --x11 を付けずに起動すると Wayland で表示します）"
+        );
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn log_window_system(_event_loop: &ActiveEventLoop) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn present_modes_prefer_mailbox_and_cycle_through_supported_ones() {
+        use wgpu::PresentMode::*;
+
+        // Wayland や GL では Mailbox がないことが多い
+        let modes = supported_present_modes(&[Fifo, Immediate]);
+        assert_eq!(modes, [Immediate, Fifo]);
+        assert_eq!(next_present_mode(&modes, Immediate), Some(Fifo));
+        assert_eq!(next_present_mode(&modes, Fifo), Some(Immediate));
+        // UI で自動選択のモードにした後は先頭から
+        assert_eq!(next_present_mode(&modes, AutoNoVsync), Some(Immediate));
+        assert_eq!(
+            supported_present_modes(&[FifoRelaxed, Fifo, Mailbox]),
+            [Mailbox, Fifo, FifoRelaxed]
+        );
+        assert_eq!(next_present_mode(&[], Fifo), None);
+    }
+
+    #[test]
+    fn hdr_format_is_only_chosen_when_reported() {
+        use wgpu::TextureFormat::*;
+        assert_eq!(
+            choose_hdr_surface_format(&[Bgra8UnormSrgb, Rgba16Float]),
+            Some(Rgba16Float)
+        );
+        assert_eq!(
+            choose_hdr_surface_format(&[Bgra8UnormSrgb, Bgra8Unorm]),
+            None
+        );
+    }
+
+    #[test]
+    fn surface_format_prefers_srgb() {
+        use wgpu::TextureFormat::*;
+        assert_eq!(
+            choose_surface_format(&[Bgra8Unorm, Rgba8Unorm, Bgra8UnormSrgb]),
+            Some(Bgra8UnormSrgb)
+        );
+        assert_eq!(
+            choose_surface_format(&[Rgba8UnormSrgb, Bgra8UnormSrgb]),
+            Some(Rgba8UnormSrgb)
+        );
+        // sRGB のフォーマットがなければ先頭のもの
+        assert_eq!(
+            choose_surface_format(&[Rgb10a2Unorm, Bgra8Unorm]),
+            Some(Rgb10a2Unorm)
+        );
+        // 表示できないサーフェイスではパニックせずに None
+        assert_eq!(choose_surface_format(&[]), None);
+    }
+}
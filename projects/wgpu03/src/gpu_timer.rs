@@ -0,0 +1,327 @@
+use std::cell::Cell;
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
+
+use crate::capabilities::GpuCapabilities;
+
+// 所要時間を計るパス（encode_frame で記録する順）
+// ブルームと小窓はいくつかのパスに分かれるので、最初のパスの開始から最後のパスの終了までを計る
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GpuPass {
+    Simulation,
+    Shadow,
+    Main,
+    Inset,
+    Bloom,
+    Post,
+    Sprites,
+    Overlay,
+    Ui,
+}
+
+impl GpuPass {
+    pub const ALL: [GpuPass; 9] = [
+        GpuPass::Simulation,
+        GpuPass::Shadow,
+        GpuPass::Main,
+        GpuPass::Inset,
+        GpuPass::Bloom,
+        GpuPass::Post,
+        GpuPass::Sprites,
+        GpuPass::Overlay,
+        GpuPass::Ui,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            GpuPass::Simulation => "simulation",
+            GpuPass::Shadow => "shadow",
+            GpuPass::Main => "main",
+            GpuPass::Inset => "inset",
+            GpuPass::Bloom => "bloom",
+            GpuPass::Post => "post",
+            GpuPass::Sprites => "sprites",
+            GpuPass::Overlay => "overlay",
+            GpuPass::Ui => "ui",
+        }
+    }
+
+    // 開始のタイムスタンプを書き込む位置（終了はその次）
+    fn query_index(self) -> u32 {
+        Self::ALL.iter().position(|&p| p == self).unwrap() as u32 * 2
+    }
+}
+
+// 1つのパスの所要時間（開始はフレームの最初のパスの開始からの時間）
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PassTime {
+    pub pass: GpuPass,
+    pub start_ms: f32,
+    pub ms: f32,
+}
+
+// フレームごとに GPU で計ったパスの所要時間（読み出せるのは数フレーム後）
+#[derive(Clone, Debug, PartialEq)]
+pub struct GpuFrame {
+    pub frame: u64,
+    pub passes: Vec<PassTime>,
+}
+
+// パスごとの開始と終了の2回ずつ書き込む
+const QUERY_COUNT: u32 = GpuPass::ALL.len() as u32 * 2;
+const TIMESTAMPS_SIZE: wgpu::BufferAddress =
+    QUERY_COUNT as wgpu::BufferAddress * std::mem::size_of::<u64>() as wgpu::BufferAddress;
+// 書き込んだパスの組だけを読み出すので、読み出し先はパスごとに QUERY_RESOLVE_BUFFER_ALIGNMENT ずつ空ける
+// （書き込まなかったクエリを読み出すと、GL などのバックエンドではエラーになる）
+const RESOLVE_STRIDE: wgpu::BufferAddress = wgpu::QUERY_RESOLVE_BUFFER_ALIGNMENT;
+const PAIR_SIZE: wgpu::BufferAddress = 2 * std::mem::size_of::<u64>() as wgpu::BufferAddress;
+// 読み出し用のバッファの数（マップが終わるまでの数フレームの間、別のバッファに書き込む）
+const READBACK_COUNT: usize = 3;
+
+// いくつかのパスに分かれる計測の、最初のパスには開始だけ、最後のパスには終了だけを書き込む
+pub fn beginning_only(
+    writes: wgpu::RenderPassTimestampWrites<'_>,
+) -> wgpu::RenderPassTimestampWrites<'_> {
+    wgpu::RenderPassTimestampWrites {
+        end_of_pass_write_index: None,
+        ..writes
+    }
+}
+
+pub fn end_only(
+    writes: wgpu::RenderPassTimestampWrites<'_>,
+) -> wgpu::RenderPassTimestampWrites<'_> {
+    wgpu::RenderPassTimestampWrites {
+        beginning_of_pass_write_index: None,
+        ..writes
+    }
+}
+
+// 開始と終了のタイムスタンプの組をミリ秒に変換する（period は1ティックのナノ秒数）
+// そのフレームで書き込んだパス（written のビット）だけを返す
+fn pass_times(timestamps: &[u64], written: u32, period: f32) -> Vec<PassTime> {
+    let to_ms = |ticks: u64| ticks as f32 * period / 1_000_000.0;
+    let pairs: Vec<(GpuPass, &[u64])> = GpuPass::ALL
+        .iter()
+        .copied()
+        .zip(timestamps.chunks_exact(2))
+        .filter(|(pass, _)| written & (1 << (pass.query_index() / 2)) != 0)
+        .collect();
+    let Some(first) = pairs.iter().map(|(_, pair)| pair[0]).min() else {
+        return Vec::new();
+    };
+    pairs
+        .into_iter()
+        .map(|(pass, pair)| PassTime {
+            pass,
+            start_ms: to_ms(pair[0] - first),
+            // 終了が開始より前になる環境もあるので、その場合は 0 にする
+            ms: to_ms(pair[1].saturating_sub(pair[0])),
+        })
+        .collect()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ReadbackState {
+    // 次のフレームの書き込み先に使える
+    Idle,
+    // このフレームでコピーを記録したので、提出後にマップする
+    Copied,
+    // map_async の完了を待っている
+    Mapping,
+}
+
+struct Readback {
+    buffer: wgpu::Buffer,
+    state: ReadbackState,
+    // コピーしたフレームの番号と、そのフレームで書き込んだパス
+    frame: u64,
+    written: u32,
+    // map_async のコールバックで true にする
+    mapped: Arc<AtomicBool>,
+}
+
+// パスの開始と終了に書き込んだタイムスタンプを、ブロックせずに数フレーム遅れで読み出す
+pub struct GpuTimer {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    readbacks: Vec<Readback>,
+    period: f32,
+    // このフレームでタイムスタンプを書き込むパス（GpuPass::ALL の順のビット）
+    // 書き込まなかったパスの値は不定なので、読み出した後に使わない
+    written: Cell<u32>,
+}
+
+impl GpuTimer {
+    // デバイスが TIMESTAMP_QUERY に対応していなければ None（計測しない）
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        capabilities: &GpuCapabilities,
+    ) -> Option<Self> {
+        if !capabilities.timestamp_query {
+            return None;
+        }
+        let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("Timestamp Query Set"),
+            ty: wgpu::QueryType::Timestamp,
+            count: QUERY_COUNT,
+        });
+        let resolve_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Timestamp Resolve Buffer"),
+            size: RESOLVE_STRIDE * GpuPass::ALL.len() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readbacks = (0..READBACK_COUNT)
+            .map(|i| Readback {
+                buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some(&format!("Timestamp Readback Buffer {}", i)),
+                    size: TIMESTAMPS_SIZE,
+                    usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                }),
+                state: ReadbackState::Idle,
+                frame: 0,
+                written: 0,
+                mapped: Arc::new(AtomicBool::new(false)),
+            })
+            .collect();
+        Some(Self {
+            query_set,
+            resolve_buffer,
+            readbacks,
+            period: queue.get_timestamp_period(),
+            written: Cell::new(0),
+        })
+    }
+
+    // pass の開始と終了にタイムスタンプを書き込む設定（RenderPassDescriptor に渡す）
+    pub fn timestamp_writes(&self, pass: GpuPass) -> wgpu::RenderPassTimestampWrites<'_> {
+        let index = self.write(pass);
+        wgpu::RenderPassTimestampWrites {
+            query_set: &self.query_set,
+            beginning_of_pass_write_index: Some(index),
+            end_of_pass_write_index: Some(index + 1),
+        }
+    }
+
+    // コンピュートパス用（ComputePassDescriptor に渡す）
+    pub fn compute_timestamp_writes(&self, pass: GpuPass) -> wgpu::ComputePassTimestampWrites<'_> {
+        let index = self.write(pass);
+        wgpu::ComputePassTimestampWrites {
+            query_set: &self.query_set,
+            beginning_of_pass_write_index: Some(index),
+            end_of_pass_write_index: Some(index + 1),
+        }
+    }
+
+    fn write(&self, pass: GpuPass) -> u32 {
+        let index = pass.query_index();
+        self.written.set(self.written.get() | 1 << (index / 2));
+        index
+    }
+
+    // frame のすべてのパスを記録した後に呼び、空いている読み出し用のバッファへコピーする
+    // （空きがなければこのフレームの結果は捨てる）
+    pub fn resolve(&mut self, encoder: &mut wgpu::CommandEncoder, frame: u64) {
+        let written = self.written.take();
+        let Some(readback) = self
+            .readbacks
+            .iter_mut()
+            .find(|r| r.state == ReadbackState::Idle)
+        else {
+            return;
+        };
+        for pass in GpuPass::ALL {
+            let index = pass.query_index();
+            if written & (1 << (index / 2)) == 0 {
+                continue;
+            }
+            let offset = RESOLVE_STRIDE * (index / 2) as wgpu::BufferAddress;
+            encoder.resolve_query_set(
+                &self.query_set,
+                index..index + 2,
+                &self.resolve_buffer,
+                offset,
+            );
+            encoder.copy_buffer_to_buffer(
+                &self.resolve_buffer,
+                offset,
+                &readback.buffer,
+                PAIR_SIZE * (index / 2) as wgpu::BufferAddress,
+                PAIR_SIZE,
+            );
+        }
+        readback.state = ReadbackState::Copied;
+        readback.frame = frame;
+        readback.written = written;
+    }
+
+    // コマンドを提出した後に呼び、コピーしたバッファのマップを始めて、マップが終わったものを返す
+    pub fn after_submit(&mut self) -> Vec<GpuFrame> {
+        let mut finished = Vec::new();
+        for readback in &mut self.readbacks {
+            match readback.state {
+                ReadbackState::Idle => {}
+                ReadbackState::Copied => {
+                    let mapped = readback.mapped.clone();
+                    readback
+                        .buffer
+                        .slice(..)
+                        .map_async(wgpu::MapMode::Read, move |result| {
+                            // 失敗した場合はマップされないまま残るが、その場合は計測を諦める
+                            if result.is_ok() {
+                                mapped.store(true, Ordering::Release);
+                            }
+                        });
+                    readback.state = ReadbackState::Mapping;
+                }
+                ReadbackState::Mapping => {
+                    if !readback.mapped.swap(false, Ordering::Acquire) {
+                        continue;
+                    }
+                    let timestamps: Vec<u64> =
+                        bytemuck::cast_slice(&readback.buffer.slice(..).get_mapped_range())
+                            .to_vec();
+                    readback.buffer.unmap();
+                    readback.state = ReadbackState::Idle;
+                    finished.push(GpuFrame {
+                        frame: readback.frame,
+                        passes: pass_times(&timestamps, readback.written, self.period),
+                    });
+                }
+            }
+        }
+        finished
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(times: &[PassTime]) -> Vec<(GpuPass, f32, f32)> {
+        times.iter().map(|t| (t.pass, t.start_ms, t.ms)).collect()
+    }
+
+    #[test]
+    fn converts_tick_pairs_of_written_passes_to_milliseconds() {
+        // 1ティック = 1ns で、シミュレーションとシャドウのパスだけを書き込んだ
+        let mut timestamps = vec![0; GpuPass::ALL.len() * 2];
+        timestamps[..4].copy_from_slice(&[1_000_100, 3_100_100, 100, 500_100]);
+        assert_eq!(
+            ms(&pass_times(&timestamps, 0b11, 1.0)),
+            [(GpuPass::Simulation, 1.0, 2.1), (GpuPass::Shadow, 0.0, 0.5)]
+        );
+        // 1ティック = 40ns の環境で、終了が開始より前になったパスは 0 にする
+        timestamps[2..4].copy_from_slice(&[50, 10]);
+        assert_eq!(
+            ms(&pass_times(&timestamps, 0b10, 40.0)),
+            [(GpuPass::Shadow, 0.0, 0.0)]
+        );
+        assert!(pass_times(&timestamps, 0, 1.0).is_empty());
+    }
+}
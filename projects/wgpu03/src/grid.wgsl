@@ -0,0 +1,92 @@
+struct Grid {
+    view_proj: mat4x4<f32>,
+    inv_view_proj: mat4x4<f32>,
+    eye: vec3<f32>,
+    // グリッドを敷く水平面の高さ（y 座標）
+    height: f32,
+};
+
+@group(0) @binding(0) var<uniform> grid: Grid;
+
+// カメラからこの距離の範囲でグリッドを薄くしていく
+const FADE_START: f32 = 10.0;
+const FADE_END: f32 = 40.0;
+const LINE_COLOR: vec3<f32> = vec3<f32>(0.5, 0.5, 0.5);
+
+struct VOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) clip: vec2<f32>,
+};
+
+struct FOutput {
+    @location(0) color: vec4<f32>,
+    // 平面上の点の深度を書き込み、シーンの図形と正しく交わるようにする
+    @builtin(frag_depth) depth: f32,
+};
+
+// 頂点バッファを使わずに画面全体を覆う大きな三角形を描画し、平面との交点はフラグメントで求める
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    let clip = uv * 2.0 - 1.0;
+    var out: VOutput;
+    out.position = vec4<f32>(clip, 0.0, 1.0);
+    out.clip = clip;
+    return out;
+}
+
+fn unproject(clip: vec2<f32>, depth: f32) -> vec3<f32> {
+    let p = grid.inv_view_proj * vec4<f32>(clip, depth, 1.0);
+    return p.xyz / p.w;
+}
+
+// spacing 間隔の線の濃さ（0.0 〜 1.0）
+// 座標の画面上での変化量（fwidth）で割ることで、ズームしても線の太さが約1ピクセルのまま保たれる
+fn grid_lines(coord: vec2<f32>, spacing: f32) -> f32 {
+    let c = coord / spacing;
+    let derivative = max(fwidth(c), vec2<f32>(1e-6));
+    let distance = abs(fract(c - 0.5) - 0.5) / derivative;
+    let line = 1.0 - min(min(distance.x, distance.y), 1.0);
+    // 線の間隔が画面上で数ピクセルより狭くなったらモアレになるので消していく
+    let density = max(derivative.x, derivative.y);
+    return line * (1.0 - smoothstep(0.2, 0.5, density));
+}
+
+// 座標 value が 0 の線（座標軸）の濃さ
+fn axis_line(value: f32) -> f32 {
+    return 1.0 - min(abs(value) / max(fwidth(value), 1e-6), 1.0);
+}
+
+@fragment
+fn fs_main(in: VOutput) -> FOutput {
+    // ニアクリップ面とファークリップ面の点を結ぶ視線と水平面の交点を求める
+    let near = unproject(in.clip, 0.0);
+    let far = unproject(in.clip, 1.0);
+    let t = (grid.height - near.y) / (far.y - near.y);
+    let world = mix(near, far, t);
+    let coord = world.xz;
+
+    // 導関数は一様な制御フローの中で求める必要があるので、破棄するかは最後に判定する
+    let minor = grid_lines(coord, 1.0);
+    let major = grid_lines(coord, 10.0);
+    let x_axis = axis_line(coord.y);
+    let z_axis = axis_line(coord.x);
+
+    var color = LINE_COLOR;
+    var alpha = max(minor * 0.3, major * 0.6);
+    // x 軸（z = 0）は赤、z 軸（x = 0）は青で描く
+    color = mix(color, vec3<f32>(0.9, 0.2, 0.2), x_axis);
+    color = mix(color, vec3<f32>(0.2, 0.3, 0.9), z_axis);
+    alpha = max(alpha, max(x_axis, z_axis) * 0.8);
+    let fade = 1.0 - smoothstep(FADE_START, FADE_END, length(world - grid.eye));
+
+    let clip = grid.view_proj * vec4<f32>(world, 1.0);
+    var out: FOutput;
+    out.color = vec4<f32>(color, alpha * fade);
+    out.depth = clip.z / clip.w;
+    // 視線が平面と交わらない（平面と平行か、交点がクリップ面の外にある）画素は描画しない
+    if !(t > 0.0 && t <= 1.0) || out.color.a <= 0.0 {
+        discard;
+    }
+    return out;
+}
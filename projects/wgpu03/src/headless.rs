@@ -0,0 +1,113 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result, bail};
+use tracing::info;
+
+use crate::adapter;
+use crate::capture::Capture;
+use crate::clock::FrameClock;
+use crate::settings::Settings;
+use crate::state::State;
+
+// PNG にそのまま保存できるよう、sRGB にエンコードされた RGBA8 に描画する
+pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+// `--headless WIDTHxHEIGHT OUTPUT.png` で指定された描画の大きさと保存先
+#[derive(Clone, Debug, PartialEq)]
+pub struct Request {
+    width: u32,
+    height: u32,
+    output: PathBuf,
+}
+
+// `--headless` に続く2つの引数から読む
+pub fn parse_request(size: Option<String>, output: Option<String>) -> Result<Request> {
+    let (Some(size), Some(output)) = (size, output) else {
+        bail!("使い方: --headless WIDTHxHEIGHT OUTPUT.png");
+    };
+    let Some((width, height)) = parse_size(&size) else {
+        bail!("大きさは WIDTHxHEIGHT の形式で指定してください: {}", size);
+    };
+    Ok(Request {
+        width,
+        height,
+        output: PathBuf::from(output),
+    })
+}
+
+// "800x600" を (800, 600) にする（大きさが 0 の場合は None）
+fn parse_size(size: &str) -> Option<(u32, u32)> {
+    let (width, height) = size.split_once('x')?;
+    let (width, height) = (width.parse().ok()?, height.parse().ok()?);
+    (width > 0 && height > 0).then_some((width, height))
+}
+
+// ウィンドウを作らずに1フレームを描画し、PNG に保存する（ディスプレイのない CI 用）
+pub fn run(request: Request, settings: &Settings) -> Result<()> {
+    let Request {
+        width,
+        height,
+        output,
+    } = request;
+    let image = pollster::block_on(render(width, height, settings))?;
+    image
+        .save(&output)
+        .with_context(|| format!("{} に保存できませんでした", output.display()))?;
+    info!(
+        "{} に保存しました（{}x{}）",
+        output.display(),
+        width,
+        height
+    );
+    Ok(())
+}
+
+// 1フレームを描画して読み出す
+async fn render(width: u32, height: u32, settings: &Settings) -> Result<image::RgbaImage> {
+    let instance = adapter::create_instance(settings.backends);
+    let mut state = State::new(&instance, None, width, height, settings).await?;
+    state.update(FrameClock::default().tick(), 0.0);
+
+    let capture = Capture::new(&state.device, width, height, FORMAT);
+    let mut encoder = state.encode_frame(&capture.view);
+    capture.copy(&mut encoder);
+    state.queue.submit(Some(encoder.finish()));
+    state.after_submit();
+    capture.read(&state.device)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::init::AppError;
+
+    #[test]
+    fn size_is_parsed_from_width_x_height() {
+        assert_eq!(parse_size("800x600"), Some((800, 600)));
+        assert_eq!(parse_size("0x600"), None);
+        assert_eq!(parse_size("800"), None);
+    }
+
+    #[test]
+    fn scene_is_drawn_with_msaa() {
+        let settings = Settings {
+            msaa: 4,
+            ..Settings::default()
+        };
+        // アダプタがない環境だけ飛ばし、検証エラーや読み出しの失敗はテストの失敗にする
+        let image = match pollster::block_on(render(64, 48, &settings)) {
+            Ok(image) => image,
+            Err(e) if matches!(e.downcast_ref(), Some(AppError::Adapter)) => {
+                eprintln!("アダプタがないため飛ばします");
+                return;
+            }
+            Err(e) => panic!("描画できませんでした: {:#}", e),
+        };
+        // 深度テクスチャを MSAA で作れないとフレームバッファが不完全になり、
+        // クリアも描画もされずに黒いまま（または一色だけ）になる
+        let black = image.pixels().filter(|p| p.0[..3] == [0, 0, 0]).count();
+        assert!(black < image.pixels().len() / 2, "{} ピクセルが黒", black);
+        let first = image.get_pixel(0, 0);
+        assert!(image.pixels().any(|p| p != first));
+    }
+}
@@ -0,0 +1,287 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Context, Result, bail};
+use winit::event_loop::EventLoopProxy;
+
+use crate::app::UserEvent;
+use crate::preprocess::{self, Expanded};
+use crate::shader_file::{self, ShaderFile};
+
+// 実行時にシェーダーを読み込み、変更を監視するディレクトリ（cargo run したソースツリーの src）
+// #include のパスもこのディレクトリからの相対パスになる
+pub const SHADER_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src");
+// ディスクから読めない場合（実行ファイルだけを別の場所に移した場合など）に使う、ビルド時のシェーダー
+const EMBEDDED_SHADERS: &[(&str, &str)] = &[
+    ("shader.wgsl", include_str!("shader.wgsl")),
+    ("model.wgsl", include_str!("model.wgsl")),
+    ("pbr.wgsl", include_str!("pbr.wgsl")),
+    ("include/camera.wgsl", include_str!("include/camera.wgsl")),
+    (
+        "include/lighting.wgsl",
+        include_str!("include/lighting.wgsl"),
+    ),
+];
+const EMBEDDED_SPIRV: &[(&str, &[u8])] = &[
+    ("normals.vert.spv", include_bytes!("normals.vert.spv")),
+    ("normals.frag.spv", include_bytes!("normals.frag.spv")),
+];
+// 監視用のスレッドが更新日時を確かめる間隔
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+fn read_from_disk(path: &str) -> Result<String> {
+    Ok(std::fs::read_to_string(Path::new(SHADER_DIR).join(path))?)
+}
+
+fn read_embedded(path: &str) -> Result<String> {
+    match EMBEDDED_SHADERS.iter().find(|(name, _)| *name == path) {
+        Some((_, source)) => Ok(source.to_string()),
+        None => bail!("ビルド時に埋め込んでいないファイルです"),
+    }
+}
+
+// ディスク上のシェーダーを読み込む（WGSL ならインクルードを展開する）
+pub fn read_shader(entry: &str) -> Result<ShaderFile> {
+    if shader_file::is_spirv(entry) {
+        let bytes = std::fs::read(Path::new(SHADER_DIR).join(entry))
+            .with_context(|| format!("{} を読み込めませんでした", entry))?;
+        return Ok(ShaderFile::SpirV {
+            path: entry.to_string(),
+            bytes,
+        });
+    }
+    Ok(ShaderFile::Wgsl(preprocess::expand(
+        entry,
+        &read_from_disk,
+    )?))
+}
+
+// ビルド時のシェーダーを読み込む（WGSL ならインクルードを展開する）
+pub fn embedded_shader(entry: &str) -> ShaderFile {
+    if let Some((_, bytes)) = EMBEDDED_SPIRV.iter().find(|(name, _)| *name == entry) {
+        return ShaderFile::SpirV {
+            path: entry.to_string(),
+            bytes: bytes.to_vec(),
+        };
+    }
+    ShaderFile::Wgsl(
+        preprocess::expand(entry, &read_embedded).expect("Failed to expand an embedded shader"),
+    )
+}
+
+// dir の下にあるすべてのシェーダーのファイル（.wgsl と .spv）の更新日時
+fn modified_times(dir: &Path) -> HashMap<PathBuf, SystemTime> {
+    let mut times = HashMap::new();
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else if path
+                .extension()
+                .is_some_and(|ext| ext == "wgsl" || ext == "spv")
+                && let Ok(modified) = entry.metadata().and_then(|m| m.modified())
+            {
+                times.insert(path, modified);
+            }
+        }
+    }
+    times
+}
+
+// dir の下のシェーダーのファイルが変更・追加されるたびに、イベントループへ ShaderChanged を送るスレッドを起動する
+// インクルードされるファイルも監視するので、共通のファイルを変更してもそれを使うシェーダーを読み込み直せる
+// スレッドは待機しながら確かめるので、イベントループの側は送られてくるまで眠っていられる
+// イベントループが終了して送れなくなったらスレッドも終わる
+pub fn watch(dir: PathBuf, proxy: EventLoopProxy<UserEvent>) {
+    std::thread::spawn(move || {
+        let mut last = modified_times(&dir);
+        loop {
+            std::thread::sleep(POLL_INTERVAL);
+            let current = modified_times(&dir);
+            // 保存の途中でファイルが消えている間は、次に現れたときに変更として扱う
+            let changed = current
+                .iter()
+                .any(|(path, modified)| last.get(path) != Some(modified));
+            last = current;
+            if changed && proxy.send_event(UserEvent::ShaderChanged).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+// 検証エラーをパニックさせずに受け取る（f の中で起きた最初のエラーを返す）
+pub fn catch_validation_error<T>(device: &wgpu::Device, f: impl FnOnce() -> T) -> Result<T> {
+    device.push_error_scope(wgpu::ErrorFilter::Validation);
+    let value = f();
+    match pollster::block_on(device.pop_error_scope()) {
+        Some(error) => bail!("{}", error),
+        None => Ok(value),
+    }
+}
+
+// 展開後のソースのハッシュをキーにして、コンパイルしたシェーダーモジュールを使い回す
+// 変更を元に戻した場合などは、コンパイルし直さずに前のモジュールを返す
+// 編集するたびに増えないよう、読み込み直した後に使っていないモジュールを retain で捨てる
+#[derive(Default)]
+pub struct ShaderCache {
+    modules: HashMap<u64, wgpu::ShaderModule>,
+}
+
+impl ShaderCache {
+    pub fn get_or_compile(
+        &mut self,
+        device: &wgpu::Device,
+        shader: &ShaderFile,
+    ) -> Result<wgpu::ShaderModule> {
+        let hash = shader.hash();
+        if let Some(module) = self.modules.get(&hash) {
+            return Ok(module.clone());
+        }
+        let module = compile(device, shader)?;
+        self.modules.insert(hash, module.clone());
+        Ok(module)
+    }
+
+    // in_use のハッシュのモジュールだけを残す
+    pub fn retain(&mut self, in_use: &[u64]) {
+        self.modules.retain(|hash, _| in_use.contains(hash));
+    }
+}
+
+// シェーダーをコンパイルし、エラーがあれば WGSL ではインクルード元のファイルと行番号を付けたメッセージを返す
+fn compile(device: &wgpu::Device, shader: &ShaderFile) -> Result<wgpu::ShaderModule> {
+    let source = shader.source()?;
+    device.push_error_scope(wgpu::ErrorFilter::Validation);
+    let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some(shader.path()),
+        source,
+    });
+    let info = pollster::block_on(module.get_compilation_info());
+    let error = pollster::block_on(device.pop_error_scope());
+    // コンパイル情報にはエラーの位置が入っているので、エラースコープのメッセージより先に見る
+    if let ShaderFile::Wgsl(shader) = shader
+        && let Some(report) = format_errors(shader, &info.messages)
+    {
+        bail!("{}", report);
+    }
+    if let Some(error) = error {
+        bail!("{}: {}", shader.path(), error);
+    }
+    Ok(module)
+}
+
+// エラーごとに元のファイルの行番号と列、その行のソースを並べたもの（エラーがなければ None）
+// メッセージにも wgpu が付けたソースの抜粋があるが、行番号は展開後のものなので最初の行だけを使う
+fn format_errors(shader: &Expanded, messages: &[wgpu::CompilationMessage]) -> Option<String> {
+    let lines: Vec<String> = messages
+        .iter()
+        .filter(|message| message.message_type == wgpu::CompilationMessageType::Error)
+        .map(|message| {
+            let text = message.message.trim().lines().next().unwrap_or_default();
+            let Some(location) = message.location else {
+                return text.to_string();
+            };
+            let Some((file, line)) = shader.origin(location.line_number) else {
+                return text.to_string();
+            };
+            format!(
+                "{}:{}:{}: {}\n{:>5} | {}",
+                file,
+                line,
+                location.line_position,
+                text,
+                line,
+                shader
+                    .source()
+                    .lines()
+                    .nth(location.line_number as usize - 1)
+                    .unwrap_or_default()
+            )
+        })
+        .collect();
+    (!lines.is_empty()).then(|| lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn errors_point_at_the_included_file_and_line() {
+        let read = |path: &str| -> Result<String> {
+            Ok(match path {
+                "main.wgsl" => "#include \"common.wgsl\"\nfn main() {}\n",
+                _ => "struct Common {\n    x: ,\n}\n",
+            }
+            .to_string())
+        };
+        let shader = preprocess::expand("main.wgsl", &read).unwrap();
+        let message = |message_type, location| wgpu::CompilationMessage {
+            message: "\nexpected type\n   ┌─ wgsl:2:8\n".to_string(),
+            message_type,
+            location,
+        };
+        let location = wgpu::SourceLocation {
+            line_number: 2,
+            line_position: 8,
+            offset: 23,
+            length: 1,
+        };
+        assert_eq!(
+            format_errors(
+                &shader,
+                &[
+                    message(wgpu::CompilationMessageType::Warning, None),
+                    message(wgpu::CompilationMessageType::Error, Some(location)),
+                    message(wgpu::CompilationMessageType::Error, None),
+                ]
+            )
+            .as_deref(),
+            Some("common.wgsl:2:8: expected type\n    2 |     x: ,\nexpected type")
+        );
+        // 警告だけならエラーにしない
+        assert!(
+            format_errors(
+                &shader,
+                &[message(wgpu::CompilationMessageType::Warning, None)]
+            )
+            .is_none()
+        );
+    }
+
+    #[test]
+    fn embedded_shaders_are_valid() {
+        use wgpu::naga::{front, valid};
+
+        let validate = |entry: &str, module: wgpu::naga::Module, source: &str| {
+            valid::Validator::new(valid::ValidationFlags::all(), valid::Capabilities::all())
+                .validate(&module)
+                .unwrap_or_else(|e| panic!("{}: {}", entry, e.emit_to_string(source)));
+        };
+        for entry in ["shader.wgsl", "model.wgsl", "pbr.wgsl"] {
+            let ShaderFile::Wgsl(shader) = embedded_shader(entry) else {
+                panic!("{} is not WGSL", entry);
+            };
+            let module = front::wgsl::parse_str(shader.source())
+                .unwrap_or_else(|e| panic!("{}: {}", entry, e.emit_to_string(shader.source())));
+            validate(entry, module, shader.source());
+        }
+        // wgpu と同じく、座標系を変換せずに読み込む
+        let options = front::spv::Options {
+            adjust_coordinate_space: false,
+            ..Default::default()
+        };
+        for (entry, bytes) in EMBEDDED_SPIRV {
+            let module = front::spv::parse_u8_slice(bytes, &options)
+                .unwrap_or_else(|e| panic!("{}: {}", entry, e));
+            validate(entry, module, "");
+        }
+    }
+}
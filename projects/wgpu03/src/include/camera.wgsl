@@ -0,0 +1,10 @@
+// shader.wgsl・model.wgsl・pbr.wgsl で共通のカメラのユニフォーム
+struct Camera {
+    view_proj: mat4x4<f32>,
+    model: mat4x4<f32>,
+    view_position: vec4<f32>,
+    // モデル行列の逆転置（法線の変換に使う）
+    normal_matrix: mat4x4<f32>,
+};
+
+@group(0) @binding(0) var<uniform> camera: Camera;
@@ -0,0 +1,29 @@
+// shader.wgsl・model.wgsl・pbr.wgsl で共通の点光源とシャドウマップ（グループ 2）
+struct Light {
+    position: vec3<f32>,
+    color: vec3<f32>,
+};
+
+struct LightInfo {
+    // 先頭の光源から見たビュー・平行投影行列（シャドウマップの参照に使う）
+    view_proj: mat4x4<f32>,
+    count: u32,
+};
+
+@group(2) @binding(0) var<uniform> light_info: LightInfo;
+@group(2) @binding(1) var t_shadow: texture_depth_2d;
+@group(2) @binding(2) var s_shadow: sampler_comparison;
+// ストレージバッファを使えない環境では、読み込み時に固定長のユニフォーム配列の宣言に書き換える
+@group(2) @binding(3) var<storage, read> lights: array<Light>;
+
+// シャドウマップと比較して、光が届いていれば 1.0、影なら 0.0 を返す
+fn shadow_factor(world_position: vec3<f32>) -> f32 {
+    let p = light_info.view_proj * vec4<f32>(world_position, 1.0);
+    let ndc = p.xyz / p.w;
+    // テクスチャ座標は y が下向き
+    let uv = ndc.xy * vec2<f32>(0.5, -0.5) + 0.5;
+    // シャドウマップの範囲外は影にしない
+    let inside = all(uv >= vec2<f32>(0.0)) && all(uv <= vec2<f32>(1.0)) && ndc.z <= 1.0;
+    let lit = textureSampleCompareLevel(t_shadow, s_shadow, uv, ndc.z);
+    return select(1.0, lit, inside);
+}
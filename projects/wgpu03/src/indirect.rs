@@ -0,0 +1,72 @@
+use std::ops::Range;
+
+use wgpu::util::{DeviceExt, DrawIndirectArgs};
+
+// 直接描画する場合に draw に渡す頂点とインスタンスの範囲
+fn direct_ranges(args: &DrawIndirectArgs) -> (Range<u32>, Range<u32>) {
+    (
+        args.first_vertex..args.first_vertex + args.vertex_count,
+        args.first_instance..args.first_instance + args.instance_count,
+    )
+}
+
+// 描画の引数を GPU のバッファから読む描画呼び出し
+// 対応していない環境では CPU 側に持っている引数で直接描画する
+pub struct IndirectDraw {
+    args: DrawIndirectArgs,
+    // 対応している環境でのみ作成する
+    buffer: Option<wgpu::Buffer>,
+}
+
+impl IndirectDraw {
+    pub fn new(
+        device: &wgpu::Device,
+        supported: bool,
+        label: &str,
+        args: DrawIndirectArgs,
+    ) -> Self {
+        // 最初の引数は CPU から書き込み、STORAGE も付けてコンピュートシェーダーから書き換えられるようにする
+        let buffer = supported.then(|| {
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(label),
+                contents: args.as_bytes(),
+                usage: wgpu::BufferUsages::INDIRECT
+                    | wgpu::BufferUsages::STORAGE
+                    | wgpu::BufferUsages::COPY_DST,
+            })
+        });
+        Self { args, buffer }
+    }
+
+    // 頂点バッファなどは呼び出し側で設定しておく
+    pub fn draw(&self, rpass: &mut wgpu::RenderPass) {
+        match &self.buffer {
+            Some(buffer) => rpass.draw_indirect(buffer, 0),
+            None => {
+                let (vertices, instances) = direct_ranges(&self.args);
+                rpass.draw(vertices, instances);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fallback_draws_the_same_ranges_as_the_buffer() {
+        let args = DrawIndirectArgs {
+            vertex_count: 3,
+            instance_count: 2,
+            first_vertex: 6,
+            first_instance: 1,
+        };
+        assert_eq!(direct_ranges(&args), (6..9, 1..3));
+        // バッファには vertex_count, instance_count, first_vertex, first_instance の順に並ぶ
+        assert_eq!(
+            args.as_bytes(),
+            bytemuck::cast_slice::<u32, u8>(&[3, 2, 6, 1])
+        );
+    }
+}
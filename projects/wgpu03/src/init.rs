@@ -0,0 +1,103 @@
+use std::error::Error;
+
+use crate::hot_reload;
+
+// 起動時（とデバイスを作り直すとき）に起きるエラー
+// パニックさせずに resumed まで返し、メッセージを表示してイベントループを終える
+// 検証エラーはエラースコープで受け取るので、後から非同期にパニックせず、作成した時点で State::new が返す
+#[derive(Debug, thiserror::Error)]
+pub enum AppError {
+    #[error("window creation failed")]
+    Window(#[from] winit::error::OsError),
+    #[error("surface creation failed")]
+    Surface(#[from] wgpu::CreateSurfaceError),
+    // 有効なバックエンドにアダプタがないか、サーフェイスに表示できるものがない
+    #[error("no adapter can present to the surface on the enabled backends")]
+    Adapter,
+    #[error("device request failed")]
+    Device(#[from] wgpu::RequestDeviceError),
+    // シェーダーモジュールの作成（WGSL の構文や型のエラーなど）
+    #[error("shader compilation failed: {0}")]
+    Shader(String),
+    // レンダー・コンピュートパイプラインの作成
+    #[error("pipeline creation failed: {0}")]
+    Pipeline(String),
+    // テクスチャ・バッファ・バインドグループなど、それ以外のリソースの作成
+    #[error("resource creation failed: {0}")]
+    Resource(String),
+}
+
+// エラーと、その原因のエラーを順に `: ` でつないだもの
+pub fn error_chain(error: &dyn Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(error) = source {
+        message.push_str(": ");
+        message.push_str(&error.to_string());
+        source = error.source();
+    }
+    message
+}
+
+// 埋め込んだ画像の読み込みなど、anyhow などで返る失敗を、何を作っていたかとともに AppError にする
+pub fn resource<T, E: Into<anyhow::Error>>(
+    what: &str,
+    result: Result<T, E>,
+) -> Result<T, AppError> {
+    result.map_err(|e| AppError::Resource(format!("{}: {:#}", what, e.into())))
+}
+
+// エラースコープの中でシェーダーモジュールを作る
+pub fn shader_module(
+    device: &wgpu::Device,
+    descriptor: wgpu::ShaderModuleDescriptor,
+) -> Result<wgpu::ShaderModule, AppError> {
+    hot_reload::catch_validation_error(device, || device.create_shader_module(descriptor))
+        .map_err(|e| AppError::Shader(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bad_wgsl_is_returned_as_a_shader_error() {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+        let Some(adapter) =
+            pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default()))
+        else {
+            eprintln!("アダプタがないため飛ばします");
+            return;
+        };
+        let (device, _queue) =
+            pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor::default(), None))
+                .unwrap();
+        let error = shader_module(
+            &device,
+            wgpu::ShaderModuleDescriptor {
+                label: Some("Bad Shader"),
+                source: wgpu::ShaderSource::Wgsl("fn main( {}".into()),
+            },
+        )
+        .err()
+        .unwrap();
+        assert!(matches!(error, AppError::Shader(_)), "{:?}", error);
+        assert!(
+            error.to_string().starts_with("shader compilation failed: "),
+            "{}",
+            error
+        );
+        assert!(
+            shader_module(
+                &device,
+                wgpu::ShaderModuleDescriptor {
+                    label: Some("Good Shader"),
+                    source: wgpu::ShaderSource::Wgsl(
+                        "@compute @workgroup_size(1) fn main() {}".into()
+                    ),
+                },
+            )
+            .is_ok()
+        );
+    }
+}
@@ -0,0 +1,36 @@
+//! 拡大率が変わったときのウィンドウの大きさとカーソル位置の換算
+
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+
+/// 論理ピクセルの大きさを変えずに拡大率を old から new にしたときの物理ピクセルの大きさ
+pub fn rescaled_size(size: PhysicalSize<u32>, old: f64, new: f64) -> PhysicalSize<u32> {
+    size.to_logical::<f64>(old).to_physical(new)
+}
+
+/// old の拡大率で物理ピクセルにしたカーソルの位置を、new の拡大率での位置にする
+/// （CursorMoved が次に届くまでの間も、クリックした位置がずれないようにする）
+pub fn rescaled_cursor(cursor: (f32, f32), old: f64, new: f64) -> (f32, f32) {
+    let position = PhysicalPosition::new(cursor.0, cursor.1)
+        .to_logical::<f64>(old)
+        .to_physical::<f32>(new);
+    (position.x, position.y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn moving_to_a_hidpi_monitor_keeps_the_logical_size_and_cursor() {
+        assert_eq!(
+            rescaled_size(PhysicalSize::new(800, 600), 1.0, 2.0),
+            PhysicalSize::new(1600, 1200)
+        );
+        assert_eq!(
+            rescaled_size(PhysicalSize::new(1600, 1200), 2.0, 1.0),
+            PhysicalSize::new(800, 600)
+        );
+        assert_eq!(rescaled_cursor((100.0, 50.0), 1.0, 2.0), (200.0, 100.0));
+        assert_eq!(rescaled_cursor((300.0, 150.0), 2.0, 1.5), (225.0, 112.5));
+    }
+}
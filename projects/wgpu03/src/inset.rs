@@ -0,0 +1,241 @@
+use wgpu::util::DeviceExt;
+
+use crate::crash;
+use crate::pipeline_cache::PipelineCache;
+use crate::post::PostPass;
+use crate::texture::Texture;
+
+// 小窓の大きさと、ウィンドウの右下の角からの余白（物理ピクセル単位で、ウィンドウの大きさによらない）
+pub const INSET_WIDTH: u32 = 320;
+pub const INSET_HEIGHT: u32 = 200;
+const INSET_MARGIN: u32 = 16;
+
+// inset.wgsl の Inset 構造体に対応するユニフォームデータ
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct InsetUniform {
+    projection: [[f32; 4]; 4],
+    rect: [f32; 4],
+}
+
+impl InsetUniform {
+    fn new(surface_width: u32, surface_height: u32) -> Self {
+        let (width, height) = (surface_width.max(1) as f32, surface_height.max(1) as f32);
+        Self {
+            projection: glam::Mat4::orthographic_rh(0.0, width, height, 0.0, -1.0, 1.0)
+                .to_cols_array_2d(),
+            rect: inset_rect(surface_width, surface_height),
+        }
+    }
+}
+
+// 小窓をウィンドウの右下に置いたときの左上の位置と大きさ
+// ウィンドウが小窓より小さい場合は左上に寄せて、はみ出した分は切り取られる
+fn inset_rect(surface_width: u32, surface_height: u32) -> [f32; 4] {
+    let x = surface_width.saturating_sub(INSET_WIDTH + INSET_MARGIN);
+    let y = surface_height.saturating_sub(INSET_HEIGHT + INSET_MARGIN);
+    [x as f32, y as f32, INSET_WIDTH as f32, INSET_HEIGHT as f32]
+}
+
+// 2台目のカメラから見たシーンを描画するオフスクリーンのテクスチャと、
+// それをシーンのテクスチャの右下に重ねる合成パス
+pub struct Inset {
+    // MSAA の場合は解決先、そうでなければ直接の描画先
+    pub texture: Texture,
+    pub msaa_view: Option<wgpu::TextureView>,
+    pub depth_texture: Texture,
+    pipeline: wgpu::RenderPipeline,
+    bind_group: wgpu::BindGroup,
+    uniform_buffer: wgpu::Buffer,
+}
+
+impl Inset {
+    pub fn new(
+        device: &wgpu::Device,
+        pipeline_cache: &PipelineCache,
+        config: &wgpu::SurfaceConfiguration,
+        shader: &wgpu::ShaderModule,
+        sample_count: u32,
+    ) -> Self {
+        let texture = Texture::create_render_target(
+            device,
+            INSET_WIDTH,
+            INSET_HEIGHT,
+            PostPass::FORMAT,
+            "Inset Texture",
+        );
+        let (msaa_view, depth_texture) = create_attachments(device, config, sample_count);
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Inset Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[InsetUniform::new(config.width, config.height)]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Inset Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Inset Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&texture.sampler),
+                },
+            ],
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Inset Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        // シーンのテクスチャ（HDR）に重ねるので、トーンマッピングは後のポストプロセスで一緒に掛かる
+        let pipeline = pipeline_cache.create(|cache| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: crash::created_pipeline("Inset Pipeline"),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: shader,
+                    entry_point: Some("fs_main"),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: PostPass::FORMAT,
+                        blend: Some(wgpu::BlendState::REPLACE),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: Default::default(),
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache,
+            })
+        });
+
+        Self {
+            texture,
+            msaa_view,
+            depth_texture,
+            pipeline,
+            bind_group,
+            uniform_buffer,
+        }
+    }
+
+    // MSAA の切り替えに合わせて、サンプル数に依存するテクスチャを作り直す
+    pub fn set_sample_count(
+        &mut self,
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        sample_count: u32,
+    ) {
+        (self.msaa_view, self.depth_texture) = create_attachments(device, config, sample_count);
+    }
+
+    // 小窓の大きさは変えず、新しいウィンドウの右下に置き直す
+    pub fn resize(&self, queue: &wgpu::Queue, surface_width: u32, surface_height: u32) {
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[InsetUniform::new(surface_width, surface_height)]),
+        );
+    }
+
+    // 小窓に描画したシーンを view（シーンのテクスチャ）の右下に重ねる
+    pub fn composite(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        timestamp_writes: Option<wgpu::RenderPassTimestampWrites<'_>>,
+    ) {
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: crash::pass("Inset Composite Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes,
+            occlusion_query_set: None,
+        });
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_bind_group(0, &self.bind_group, &[]);
+        rpass.draw(0..6, 0..1);
+    }
+}
+
+// 小窓の大きさの MSAA のカラーテクスチャと深度テクスチャ
+fn create_attachments(
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+    sample_count: u32,
+) -> (Option<wgpu::TextureView>, Texture) {
+    let config = wgpu::SurfaceConfiguration {
+        width: INSET_WIDTH,
+        height: INSET_HEIGHT,
+        ..config.clone()
+    };
+    (
+        crate::render::create_msaa_view(device, &config, sample_count),
+        Texture::create_depth_texture(device, &config, sample_count, "Inset Depth Texture"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inset_keeps_its_size_in_the_bottom_right_corner() {
+        assert_eq!(inset_rect(800, 600), [464.0, 384.0, 320.0, 200.0]);
+        assert_eq!(inset_rect(1920, 1080), [1584.0, 864.0, 320.0, 200.0]);
+        // 小窓より小さいウィンドウでは左上に寄せる
+        assert_eq!(inset_rect(200, 100), [0.0, 0.0, 320.0, 200.0]);
+    }
+}
@@ -0,0 +1,39 @@
+struct Inset {
+    // ピクセル座標（左上が原点、y は下向き）からクリップ座標への平行投影
+    projection: mat4x4<f32>,
+    // 小窓の左上の位置と大きさ（ピクセル単位）
+    rect: vec4<f32>,
+};
+
+@group(0) @binding(0) var<uniform> inset: Inset;
+@group(0) @binding(1) var t_inset: texture_2d<f32>;
+@group(0) @binding(2) var s_inset: sampler;
+
+struct VOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+// 頂点バッファを使わず、頂点番号から小窓の四角形の2つの三角形を作る
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VOutput {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(0.0, 0.0),
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(1.0, 1.0),
+    );
+    let corner = corners[index];
+    var out: VOutput;
+    let position = inset.rect.xy + corner * inset.rect.zw;
+    out.position = inset.projection * vec4<f32>(position, 0.0, 1.0);
+    out.uv = corner;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VOutput) -> @location(0) vec4<f32> {
+    return textureSample(t_inset, s_inset, in.uv);
+}
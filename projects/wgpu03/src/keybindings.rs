@@ -0,0 +1,267 @@
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::{Result, bail};
+use tracing::warn;
+use winit::keyboard::KeyCode;
+
+// 設定ファイルで割り当てを変えられる、アプリケーション全体の操作
+// ここにないキーは今まで通り State::key_pressed が処理する
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    Quit,
+    ToggleFullscreen,
+    ToggleExclusiveFullscreen,
+    // 対応している表示モードを順に切り替えて、垂直同期を入り切りする
+    CyclePresentMode,
+    TogglePause,
+    ToggleRenderMode,
+    // RenderDoc から起動したときに、次の1フレームを取り込む
+    CaptureFrame,
+}
+
+impl Action {
+    const ALL: [Self; 7] = [
+        Self::Quit,
+        Self::ToggleFullscreen,
+        Self::ToggleExclusiveFullscreen,
+        Self::CyclePresentMode,
+        Self::TogglePause,
+        Self::ToggleRenderMode,
+        Self::CaptureFrame,
+    ];
+
+    // 設定ファイルに書く名前
+    fn name(self) -> &'static str {
+        match self {
+            Self::Quit => "quit",
+            Self::ToggleFullscreen => "fullscreen",
+            Self::ToggleExclusiveFullscreen => "exclusive_fullscreen",
+            Self::CyclePresentMode => "vsync",
+            Self::TogglePause => "pause",
+            Self::ToggleRenderMode => "render_mode",
+            Self::CaptureFrame => "capture_frame",
+        }
+    }
+
+    // 押し続けたときのキーリピートでは切り替えを繰り返さない
+    pub fn is_toggle(self) -> bool {
+        self != Self::Quit
+    }
+}
+
+// 設定ファイルで指定できるキー（名前は winit の KeyCode と同じ）
+const BINDABLE_KEYS: &[KeyCode] = &[
+    KeyCode::KeyA,
+    KeyCode::KeyB,
+    KeyCode::KeyC,
+    KeyCode::KeyD,
+    KeyCode::KeyE,
+    KeyCode::KeyF,
+    KeyCode::KeyG,
+    KeyCode::KeyH,
+    KeyCode::KeyI,
+    KeyCode::KeyJ,
+    KeyCode::KeyK,
+    KeyCode::KeyL,
+    KeyCode::KeyM,
+    KeyCode::KeyN,
+    KeyCode::KeyO,
+    KeyCode::KeyP,
+    KeyCode::KeyQ,
+    KeyCode::KeyR,
+    KeyCode::KeyS,
+    KeyCode::KeyT,
+    KeyCode::KeyU,
+    KeyCode::KeyV,
+    KeyCode::KeyW,
+    KeyCode::KeyX,
+    KeyCode::KeyY,
+    KeyCode::KeyZ,
+    KeyCode::Digit0,
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+    KeyCode::F1,
+    KeyCode::F2,
+    KeyCode::F3,
+    KeyCode::F4,
+    KeyCode::F5,
+    KeyCode::F6,
+    KeyCode::F7,
+    KeyCode::F8,
+    KeyCode::F9,
+    KeyCode::F10,
+    KeyCode::F11,
+    KeyCode::F12,
+    KeyCode::Escape,
+    KeyCode::Space,
+    KeyCode::Enter,
+    KeyCode::Backspace,
+    KeyCode::Pause,
+    KeyCode::Insert,
+    KeyCode::Delete,
+    KeyCode::Home,
+    KeyCode::End,
+    KeyCode::PageUp,
+    KeyCode::PageDown,
+];
+
+fn parse_key(name: &str) -> Option<KeyCode> {
+    BINDABLE_KEYS
+        .iter()
+        .copied()
+        .find(|key| format!("{:?}", key).eq_ignore_ascii_case(name))
+}
+
+// キーから操作への割り当て
+#[derive(Debug)]
+pub struct Keybindings {
+    actions: HashMap<KeyCode, Action>,
+}
+
+// RenderDoc に対応したビルドでは、F10 キーを排他的な全画面表示ではなくフレームの取り込みに使う
+// （排他的な全画面表示は --exclusive-fullscreen か、config.toml で別のキーに割り当てて使う）
+impl Default for Keybindings {
+    fn default() -> Self {
+        let f10 = if cfg!(feature = "renderdoc") {
+            Action::CaptureFrame
+        } else {
+            Action::ToggleExclusiveFullscreen
+        };
+        Self {
+            actions: HashMap::from([
+                (KeyCode::Escape, Action::Quit),
+                (KeyCode::F11, Action::ToggleFullscreen),
+                (KeyCode::F10, f10),
+                (KeyCode::KeyV, Action::CyclePresentMode),
+                (KeyCode::KeyP, Action::TogglePause),
+                (KeyCode::F5, Action::ToggleRenderMode),
+            ]),
+        }
+    }
+}
+
+impl Keybindings {
+    // config.toml の [keybindings] に書いた `操作 = "キー"`（例: `pause = "KeyP"`）で既定の割り当てを上書きする
+    // 割り当てたキーは State::key_pressed より先に使われる
+    // 既定で別の操作に割り当てられていたキーを指定した場合は、その操作からキーを外して警告する
+    // （2つの操作に同じキーを指定した場合はエラー）
+    pub fn with_overrides(overrides: &BTreeMap<String, String>) -> Result<Self> {
+        let mut bindings = Self::default();
+        let mut overridden = Vec::new();
+        let mut displaced = Vec::new();
+        for (name, key) in overrides {
+            let Some(action) = Action::ALL.into_iter().find(|a| a.name() == name) else {
+                bail!("知らない操作です: {}", name);
+            };
+            let Some(key_code) = parse_key(key) else {
+                bail!("{} に知らないキーが指定されています: {}", name, key);
+            };
+            // 操作ごとにキーは1つなので、前の割り当ては外す
+            bindings.actions.retain(|_, bound| *bound != action);
+            if let Some(previous) = bindings.actions.insert(key_code, action) {
+                if overridden.contains(&previous) {
+                    bail!(
+                        "{} と {} に同じキーが指定されています: {}",
+                        previous.name(),
+                        name,
+                        key
+                    );
+                }
+                displaced.push((previous, action, key));
+            }
+            overridden.push(action);
+        }
+        // キーを奪われた操作も、後から別のキーを指定されていれば問題ない
+        for (previous, action, key) in displaced {
+            if !bindings.actions.values().any(|bound| *bound == previous) {
+                warn!(
+                    "{} を {} に割り当てたため、{} にはキーが割り当てられていません",
+                    action.name(),
+                    key,
+                    previous.name()
+                );
+            }
+        }
+        Ok(bindings)
+    }
+
+    pub fn action(&self, key: KeyCode) -> Option<Action> {
+        self.actions.get(&key).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_entries_rebind_actions() {
+        let overrides = |entries: &[(&str, &str)]| {
+            let entries: BTreeMap<String, String> = entries
+                .iter()
+                .map(|(name, key)| (name.to_string(), key.to_string()))
+                .collect();
+            Keybindings::with_overrides(&entries)
+        };
+        // 大文字小文字は区別しない
+        let bindings = overrides(&[("pause", "KeyP"), ("quit", "keyq")]).unwrap();
+        assert_eq!(bindings.action(KeyCode::KeyP), Some(Action::TogglePause));
+        assert_eq!(bindings.action(KeyCode::Pause), None);
+        assert_eq!(bindings.action(KeyCode::KeyQ), Some(Action::Quit));
+        assert_eq!(bindings.action(KeyCode::Escape), None);
+        assert_eq!(
+            bindings.action(KeyCode::F11),
+            Some(Action::ToggleFullscreen)
+        );
+
+        let bindings = overrides(&[("capture_frame", "F9")]).unwrap();
+        assert_eq!(bindings.action(KeyCode::F9), Some(Action::CaptureFrame));
+
+        // 既定で別の操作に割り当てられていたキーは、その操作から外す
+        let bindings = overrides(&[("pause", "KeyV")]).unwrap();
+        assert_eq!(bindings.action(KeyCode::KeyV), Some(Action::TogglePause));
+        assert!(
+            !bindings
+                .actions
+                .values()
+                .any(|a| *a == Action::CyclePresentMode)
+        );
+        let bindings = overrides(&[("pause", "KeyV"), ("vsync", "F6")]).unwrap();
+        assert_eq!(bindings.action(KeyCode::F6), Some(Action::CyclePresentMode));
+        assert!(overrides(&[("pause", "F6"), ("quit", "F6")]).is_err());
+
+        assert!(overrides(&[("jump", "Space")]).is_err());
+        assert!(overrides(&[("pause", "Hyper")]).is_err());
+    }
+
+    #[test]
+    fn default_keys_are_not_taken_from_state() {
+        // 割り当てたキーは State::key_pressed より先に使われるので、そこで処理するキーと重なると
+        // State の操作が使えなくなる（key_pressed の中に書かれた KeyCode と比べる）
+        let source = include_str!("state/keys.rs");
+        let start = source.find("pub fn key_pressed").unwrap();
+        let body = &source[start..];
+        let body = &body[..body.find("\n    }\n").unwrap()];
+        let handled: Vec<&str> = body
+            .split("KeyCode::")
+            .skip(1)
+            .map(|rest| rest.split(|c: char| !c.is_alphanumeric()).next().unwrap())
+            .collect();
+        assert!(handled.contains(&"KeyC"));
+        for key in Keybindings::default().actions.keys() {
+            let name = format!("{:?}", key);
+            assert!(
+                !handled.contains(&name.as_str()),
+                "{} は State::key_pressed でも使われています",
+                name
+            );
+        }
+    }
+}
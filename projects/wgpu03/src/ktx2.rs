@@ -0,0 +1,353 @@
+use anyhow::{Context, Result, bail, ensure};
+
+// KTX2 ファイルの先頭12バイト（«KTX 20»\r\n\x1A\n）
+const IDENTIFIER: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+// 識別子・ヘッダー・インデックスを合わせた大きさ（この後にレベルのインデックスが並ぶ）
+const HEADER_SIZE: usize = 80;
+const LEVEL_INDEX_SIZE: usize = 24;
+
+// KTX2 のヘッダーに書かれている Vulkan の VkFormat の値
+const VK_FORMAT_UNDEFINED: u32 = 0;
+const VK_FORMAT_R8G8B8A8_UNORM: u32 = 37;
+const VK_FORMAT_R8G8B8A8_SRGB: u32 = 43;
+const VK_FORMAT_BC1_RGBA_UNORM_BLOCK: u32 = 133;
+const VK_FORMAT_BC1_RGBA_SRGB_BLOCK: u32 = 134;
+const VK_FORMAT_BC3_UNORM_BLOCK: u32 = 137;
+const VK_FORMAT_BC3_SRGB_BLOCK: u32 = 138;
+const VK_FORMAT_BC7_UNORM_BLOCK: u32 = 145;
+const VK_FORMAT_BC7_SRGB_BLOCK: u32 = 146;
+const VK_FORMAT_ETC2_R8G8B8A8_UNORM_BLOCK: u32 = 151;
+const VK_FORMAT_ETC2_R8G8B8A8_SRGB_BLOCK: u32 = 152;
+const VK_FORMAT_ASTC_4X4_UNORM_BLOCK: u32 = 157;
+const VK_FORMAT_ASTC_4X4_SRGB_BLOCK: u32 = 158;
+
+// VkFormat を対応する wgpu のフォーマットに変換する（このプログラムで扱う形式のみ）
+fn texture_format(vk_format: u32) -> Option<wgpu::TextureFormat> {
+    use wgpu::{AstcBlock, AstcChannel, TextureFormat};
+    let astc = |channel| TextureFormat::Astc {
+        block: AstcBlock::B4x4,
+        channel,
+    };
+    Some(match vk_format {
+        VK_FORMAT_R8G8B8A8_UNORM => TextureFormat::Rgba8Unorm,
+        VK_FORMAT_R8G8B8A8_SRGB => TextureFormat::Rgba8UnormSrgb,
+        VK_FORMAT_BC1_RGBA_UNORM_BLOCK => TextureFormat::Bc1RgbaUnorm,
+        VK_FORMAT_BC1_RGBA_SRGB_BLOCK => TextureFormat::Bc1RgbaUnormSrgb,
+        VK_FORMAT_BC3_UNORM_BLOCK => TextureFormat::Bc3RgbaUnorm,
+        VK_FORMAT_BC3_SRGB_BLOCK => TextureFormat::Bc3RgbaUnormSrgb,
+        VK_FORMAT_BC7_UNORM_BLOCK => TextureFormat::Bc7RgbaUnorm,
+        VK_FORMAT_BC7_SRGB_BLOCK => TextureFormat::Bc7RgbaUnormSrgb,
+        VK_FORMAT_ETC2_R8G8B8A8_UNORM_BLOCK => TextureFormat::Etc2Rgba8Unorm,
+        VK_FORMAT_ETC2_R8G8B8A8_SRGB_BLOCK => TextureFormat::Etc2Rgba8UnormSrgb,
+        VK_FORMAT_ASTC_4X4_UNORM_BLOCK => astc(AstcChannel::Unorm),
+        VK_FORMAT_ASTC_4X4_SRGB_BLOCK => astc(AstcChannel::UnormSrgb),
+        _ => return None,
+    })
+}
+
+// KTX2 ファイルから取り出した2次元テクスチャ
+pub struct Ktx2Image<'a> {
+    pub format: wgpu::TextureFormat,
+    pub width: u32,
+    pub height: u32,
+    // ミップレベル 0 から順に並んだ各レベルのデータ
+    pub levels: Vec<&'a [u8]>,
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+}
+
+// ミップレベルごとのデータを切り出す（中身の展開や変換はしない）
+// キューブマップや配列テクスチャ、超圧縮（Basis Universal の BasisLZ や Zstandard）には対応していない
+pub fn parse(bytes: &[u8]) -> Result<Ktx2Image<'_>> {
+    ensure!(
+        bytes.len() >= HEADER_SIZE && bytes[..12] == IDENTIFIER,
+        "KTX2 ファイルではありません"
+    );
+    let vk_format = read_u32(bytes, 12);
+    let width = read_u32(bytes, 20);
+    let height = read_u32(bytes, 24);
+    let depth = read_u32(bytes, 28);
+    let layers = read_u32(bytes, 32);
+    let faces = read_u32(bytes, 36);
+    // 0 のときはミップマップを含まず、読み込む側で作ることになっている
+    let level_count = read_u32(bytes, 40).max(1) as usize;
+    let supercompression = read_u32(bytes, 44);
+
+    if supercompression != 0 || vk_format == VK_FORMAT_UNDEFINED {
+        bail!(
+            "超圧縮された KTX2（Basis Universal など、方式 {}）のトランスコードには対応していません",
+            supercompression
+        );
+    }
+    let format = texture_format(vk_format)
+        .with_context(|| format!("対応していない VkFormat です: {}", vk_format))?;
+    ensure!(
+        width > 0 && height > 0 && depth == 0 && layers == 0 && faces == 1,
+        "2次元のテクスチャのみ読み込めます"
+    );
+    let (block_width, block_height) = format.block_dimensions();
+    ensure!(
+        width.is_multiple_of(block_width) && height.is_multiple_of(block_height),
+        "{}x{} は {:?} のブロックの大きさの倍数ではありません",
+        width,
+        height,
+        format
+    );
+    let size = wgpu::Extent3d {
+        width,
+        height,
+        depth_or_array_layers: 1,
+    };
+    ensure!(
+        level_count as u32 <= size.max_mips(wgpu::TextureDimension::D2),
+        "{}x{} にはミップレベルが {} 個もありません",
+        width,
+        height,
+        level_count
+    );
+    ensure!(
+        bytes.len() >= HEADER_SIZE + level_count * LEVEL_INDEX_SIZE,
+        "レベルのインデックスが途中で切れています"
+    );
+
+    let levels = (0..level_count)
+        .map(|level| {
+            let index = HEADER_SIZE + level * LEVEL_INDEX_SIZE;
+            let offset = read_u64(bytes, index);
+            let length = read_u64(bytes, index + 8) as usize;
+            let expected = level_byte_size(format, width, height, level as u32);
+            ensure!(
+                length == expected,
+                "ミップレベル {} の大きさが {} バイトではなく {} バイトです",
+                level,
+                expected,
+                length
+            );
+            // 壊れたファイルの大きなオフセットでも、足したときに溢れないようにする
+            usize::try_from(offset)
+                .ok()
+                .and_then(|offset| Some(offset..offset.checked_add(length)?))
+                .and_then(|range| bytes.get(range))
+                .with_context(|| format!("ミップレベル {} のデータが途中で切れています", level))
+        })
+        .collect::<Result<_>>()?;
+
+    Ok(Ktx2Image {
+        format,
+        width,
+        height,
+        levels,
+    })
+}
+
+// ミップレベルの物理的な大きさ（ブロックの大きさに切り上げたもの）
+pub fn level_extent(
+    format: wgpu::TextureFormat,
+    width: u32,
+    height: u32,
+    level: u32,
+) -> wgpu::Extent3d {
+    wgpu::Extent3d {
+        width,
+        height,
+        depth_or_array_layers: 1,
+    }
+    .mip_level_size(level, wgpu::TextureDimension::D2)
+    .physical_size(format)
+}
+
+// ブロック圧縮では1行が「横に並ぶブロックの数 × ブロックのバイト数」になる
+pub fn bytes_per_block_row(format: wgpu::TextureFormat, physical_width: u32) -> u32 {
+    let (block_width, _) = format.block_dimensions();
+    let block_size = format.block_copy_size(None).unwrap();
+    physical_width / block_width * block_size
+}
+
+fn level_byte_size(format: wgpu::TextureFormat, width: u32, height: u32, level: u32) -> usize {
+    let extent = level_extent(format, width, height, level);
+    let (_, block_height) = format.block_dimensions();
+    (bytes_per_block_row(format, extent.width) * (extent.height / block_height)) as usize
+}
+
+// 色の 5:6:5 ビットを 8 ビットに広げる
+fn rgb565(color: u16) -> [u8; 3] {
+    let r = (color >> 11) & 0x1f;
+    let g = (color >> 5) & 0x3f;
+    let b = color & 0x1f;
+    [
+        ((r << 3) | (r >> 2)) as u8,
+        ((g << 2) | (g >> 4)) as u8,
+        ((b << 3) | (b >> 2)) as u8,
+    ]
+}
+
+// BC1 の色ブロック（8バイト）を 4x4 の RGBA に展開する
+// BC3 の色ブロックでは c0 <= c1 でも常に4色を使う
+fn decode_bc1_block(block: &[u8], always_four_colors: bool) -> [[u8; 4]; 16] {
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+    let indices = u32::from_le_bytes(block[4..8].try_into().unwrap());
+    let (e0, e1) = (rgb565(c0), rgb565(c1));
+    let mix = |a: u32, b: u32, d: u32| -> [u8; 4] {
+        let channel = |i: usize| ((e0[i] as u32 * a + e1[i] as u32 * b) / d) as u8;
+        [channel(0), channel(1), channel(2), 255]
+    };
+    let palette = if c0 > c1 || always_four_colors {
+        [mix(1, 0, 1), mix(0, 1, 1), mix(2, 1, 3), mix(1, 2, 3)]
+    } else {
+        // 3色と透明の黒
+        [mix(1, 0, 1), mix(0, 1, 1), mix(1, 1, 2), [0, 0, 0, 0]]
+    };
+    std::array::from_fn(|i| palette[((indices >> (2 * i)) & 3) as usize])
+}
+
+// BC3 のアルファブロック（8バイト）を 4x4 のアルファ値に展開する
+fn decode_bc3_alpha(block: &[u8]) -> [u8; 16] {
+    let (a0, a1) = (block[0] as u32, block[1] as u32);
+    let palette: [u8; 8] = std::array::from_fn(|i| match i {
+        0 => a0 as u8,
+        1 => a1 as u8,
+        _ if a0 > a1 => ((a0 * (8 - i as u32) + a1 * (i as u32 - 1)) / 7) as u8,
+        6 => 0,
+        7 => 255,
+        _ => ((a0 * (6 - i as u32) + a1 * (i as u32 - 1)) / 5) as u8,
+    });
+    let mut bits = [0u8; 8];
+    bits[..6].copy_from_slice(&block[2..8]);
+    let indices = u64::from_le_bytes(bits);
+    std::array::from_fn(|i| palette[((indices >> (3 * i)) & 7) as usize])
+}
+
+// GPU が圧縮形式に対応していない場合の代わりに、CPU で RGBA8 に展開する
+// 展開できるのは BC1 と BC3 のみ（BC7・ETC2・ASTC の展開は実装していない）
+pub fn decode_to_rgba8(
+    format: wgpu::TextureFormat,
+    data: &[u8],
+    width: u32,
+    height: u32,
+) -> Result<image::RgbaImage> {
+    use wgpu::TextureFormat;
+    let block_size = match format {
+        TextureFormat::Bc1RgbaUnorm | TextureFormat::Bc1RgbaUnormSrgb => 8,
+        TextureFormat::Bc3RgbaUnorm | TextureFormat::Bc3RgbaUnormSrgb => 16,
+        _ => bail!("{:?} を RGBA8 に展開することはできません", format),
+    };
+    let blocks_per_row = width.div_ceil(4);
+    ensure!(
+        data.len() == (blocks_per_row * height.div_ceil(4)) as usize * block_size,
+        "{}x{} の {:?} のデータの大きさが合いません",
+        width,
+        height,
+        format
+    );
+
+    let mut image = image::RgbaImage::new(width, height);
+    for (i, block) in data.chunks_exact(block_size).enumerate() {
+        let texels = if block_size == 8 {
+            decode_bc1_block(block, false)
+        } else {
+            let alpha = decode_bc3_alpha(&block[..8]);
+            let mut texels = decode_bc1_block(&block[8..], true);
+            for (texel, alpha) in texels.iter_mut().zip(alpha) {
+                texel[3] = alpha;
+            }
+            texels
+        };
+        let (bx, by) = (i as u32 % blocks_per_row * 4, i as u32 / blocks_per_row * 4);
+        for (j, texel) in texels.iter().enumerate() {
+            // 大きさが4の倍数でない小さいミップレベルでは、はみ出した画素を捨てる
+            let (x, y) = (bx + j as u32 % 4, by + j as u32 / 4);
+            if x < width && y < height {
+                image.put_pixel(x, y, image::Rgba(*texel));
+            }
+        }
+    }
+    Ok(image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // BC1 の 8x8 のテクスチャ（ミップレベル 0〜3）だけを持つ最小限の KTX2 を組み立てる
+    fn bc1_ktx2() -> Vec<u8> {
+        let levels = [4 * 8, 8, 8, 8];
+        let mut bytes = IDENTIFIER.to_vec();
+        for value in [VK_FORMAT_BC1_RGBA_SRGB_BLOCK, 1, 8, 8, 0, 0, 1, 4, 0] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes.resize(HEADER_SIZE, 0);
+        let mut offset = HEADER_SIZE + levels.len() * LEVEL_INDEX_SIZE;
+        for length in levels {
+            for value in [offset, length, length] {
+                bytes.extend_from_slice(&(value as u64).to_le_bytes());
+            }
+            offset += length;
+        }
+        for (level, length) in levels.iter().enumerate() {
+            bytes.extend(std::iter::repeat_n(level as u8, *length));
+        }
+        bytes
+    }
+
+    #[test]
+    fn parses_block_compressed_mip_levels() {
+        let bytes = bc1_ktx2();
+        let image = parse(&bytes).unwrap();
+        assert_eq!(image.format, wgpu::TextureFormat::Bc1RgbaUnormSrgb);
+        assert_eq!((image.width, image.height), (8, 8));
+        // 2x2 と 1x1 のレベルも 4x4 のブロック1つ分のデータを持つ
+        let lengths: Vec<_> = image.levels.iter().map(|level| level.len()).collect();
+        assert_eq!(lengths, [32, 8, 8, 8]);
+        assert!(image.levels[3].iter().all(|&b| b == 3));
+        assert_eq!(bytes_per_block_row(image.format, 8), 16);
+
+        // 途中で切れたファイルや超圧縮されたファイルはエラーにする
+        assert!(parse(&bytes[..bytes.len() - 1]).is_err());
+        let mut supercompressed = bytes.clone();
+        supercompressed[44] = 1;
+        assert!(parse(&supercompressed).is_err());
+        // オフセットに足すと溢れる値が書かれていても、パニックせずにエラーにする
+        let mut overflowing = bytes.clone();
+        overflowing[HEADER_SIZE..HEADER_SIZE + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(parse(&overflowing).is_err());
+
+        // 同梱の KTX2 はすべてのミップレベルを RGBA8 に展開できる
+        let bricks = parse(include_bytes!("../assets/bricks.ktx2")).unwrap();
+        assert_eq!(bricks.levels.len(), 9);
+        for (level, data) in bricks.levels.iter().enumerate() {
+            let size = (bricks.width >> level).max(1);
+            assert!(decode_to_rgba8(bricks.format, data, size, size).is_ok());
+        }
+    }
+
+    #[test]
+    fn decodes_bc1_and_bc3_blocks() {
+        // c0 = 白、c1 = 黒で、1行目を 0,1,2,3 の順に並べたブロック
+        let bc1 = [0xff, 0xff, 0x00, 0x00, 0b1110_0100, 0, 0, 0];
+        let image = decode_to_rgba8(wgpu::TextureFormat::Bc1RgbaUnorm, &bc1, 2, 2).unwrap();
+        assert_eq!(image.dimensions(), (2, 2));
+        assert_eq!(image.get_pixel(0, 0).0, [255, 255, 255, 255]);
+        assert_eq!(image.get_pixel(1, 0).0, [0, 0, 0, 255]);
+        assert_eq!(image.get_pixel(0, 1).0, [255, 255, 255, 255]);
+
+        // アルファの端点が 255 と 0 で、最初の画素だけ 0 番（255）、残りは 1 番（0）
+        let indices: u64 = (1..16).map(|i| 1 << (3 * i)).sum();
+        let mut bc3 = vec![255, 0];
+        bc3.extend_from_slice(&indices.to_le_bytes()[..6]);
+        bc3.extend_from_slice(&bc1);
+        let image = decode_to_rgba8(wgpu::TextureFormat::Bc3RgbaUnorm, &bc3, 4, 4).unwrap();
+        assert_eq!(image.get_pixel(0, 0).0[3], 255);
+        assert_eq!(image.get_pixel(1, 0).0[3], 0);
+        // BC3 の色ブロックは常に4色なので、3番は透明にならない
+        assert_eq!(image.get_pixel(3, 0).0, [85, 85, 85, 0]);
+    }
+}
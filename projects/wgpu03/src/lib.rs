@@ -0,0 +1,120 @@
+//! wgpu の三角形から始めて、モデル・光源・ポストプロセスなどを1つのウィンドウに描画するサンプル
+//!
+//! バイナリは引数を [`settings::Settings`] にして [`run`] を呼ぶだけで、描画はすべてこのライブラリが行う。
+
+mod adapter;
+mod animation;
+mod app;
+mod atlas;
+mod bloom;
+mod bundle;
+pub mod camera;
+mod capabilities;
+mod caps;
+mod capture;
+mod clock;
+pub mod config;
+pub mod crash;
+mod debug_group;
+mod debug_lines;
+mod frame_limiter;
+mod frustum;
+mod fullscreen;
+mod geometry;
+pub mod gpu;
+mod gpu_timer;
+mod headless;
+mod hot_reload;
+mod indirect;
+mod init;
+pub mod input;
+mod inset;
+mod keybindings;
+mod ktx2;
+mod light;
+pub mod logging;
+mod mesh;
+mod model;
+mod occlusion;
+mod overlay;
+mod particles;
+mod per_draw;
+mod picking;
+mod pipeline_cache;
+mod post;
+mod preprocess;
+mod profiler;
+mod ray;
+mod recorder;
+pub mod render;
+#[cfg(feature = "renderdoc")]
+mod renderdoc;
+mod scene;
+pub mod settings;
+mod shader_file;
+mod shading;
+mod shape;
+mod simulation;
+mod sprite;
+mod state;
+mod stats;
+mod terrain;
+pub mod texture;
+#[cfg(feature = "ui")]
+mod ui;
+mod uniform_arena;
+mod uniforms;
+mod viewport;
+mod wave;
+mod windows;
+
+use config::Config;
+use settings::Settings;
+
+use winit::event_loop::EventLoop;
+
+/// config.toml とコマンドラインから作った settings でウィンドウを開き、閉じるまで描画する
+///
+/// `--bench-bundles` と `--headless` の場合はウィンドウを作らずに実行して戻る。
+/// 初期化に失敗した場合は、原因のエラーをたどれる [`anyhow::Error`] を返す。
+pub fn run(settings: Settings, config: Config) -> anyhow::Result<()> {
+    // `--bench-bundles` ではレンダーバンドルを使う場合と使わない場合の記録時間を比べて終了する
+    if settings.bench_bundles {
+        return bundle::run_benchmark(&settings);
+    }
+
+    // `--headless WIDTHxHEIGHT OUTPUT.png` ではイベントループもウィンドウも作らずに1フレームを描画して保存する
+    if let Some(request) = settings.headless.clone() {
+        return headless::run(request, &settings);
+    }
+
+    app::run_event_loop(EventLoop::with_user_event().build()?, settings, config)
+}
+
+/// Android のアクティビティから呼ばれる入り口（cargo-apk でビルドする。Cargo.toml を参照）
+///
+/// コマンドライン引数はないので、config.toml と既定の設定で起動する。
+#[cfg(target_os = "android")]
+#[unsafe(no_mangle)]
+fn android_main(app: winit::platform::android::activity::AndroidApp) {
+    use winit::platform::android::EventLoopBuilderExtAndroid;
+
+    let config = Config::load();
+    let settings = match Settings::from_args(&config) {
+        Ok(settings) => settings,
+        Err(e) => {
+            eprintln!("引数エラー: {:#}", e);
+            return;
+        }
+    };
+    logging::init(settings.log_format);
+    crash::install();
+    let result = EventLoop::with_user_event()
+        .with_android_app(app)
+        .build()
+        .map_err(anyhow::Error::from)
+        .and_then(|event_loop| app::run_event_loop(event_loop, settings, config));
+    if let Err(e) = result {
+        tracing::error!("アプリケーションエラー: {:#}", e);
+    }
+}
@@ -0,0 +1,207 @@
+use std::borrow::Cow;
+
+use glam::Vec3;
+
+use crate::capabilities::GpuCapabilities;
+
+// 1つの点光源のデータ（シェーダーの Light 構造体に対応）
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Light {
+    pub position: [f32; 3],
+    // WGSLの vec3 は16バイト境界に揃える必要がある
+    _padding: u32,
+    pub color: [f32; 3],
+    _padding2: u32,
+}
+
+impl Light {
+    pub fn new(position: Vec3, color: [f32; 3]) -> Self {
+        Self {
+            position: position.to_array(),
+            _padding: 0,
+            color,
+            _padding2: 0,
+        }
+    }
+}
+
+// 光源の個数と、影を落とす光源（先頭の光源）のビュー・平行投影行列
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LightsUniform {
+    pub view_proj: [[f32; 4]; 4],
+    pub count: u32,
+    _padding: [u32; 3],
+}
+
+impl LightsUniform {
+    pub fn new(view_proj: glam::Mat4, count: usize) -> Self {
+        Self {
+            view_proj: view_proj.to_cols_array_2d(),
+            count: count as u32,
+            _padding: [0; 3],
+        }
+    }
+}
+
+// 点光源はシーンの中心のまわりを周回させ、鏡面反射のハイライトが動くのを確認できるようにする
+const ORBIT_RADIUS: f32 = 2.0;
+const ORBIT_HEIGHT: f32 = 1.0;
+// 周回の角速度（ラジアン毎秒）
+const ORBIT_SPEED: f32 = 0.8;
+
+// 光源を追加したときに順番に使う色（先頭は影を落とす白色の光源）
+const PALETTE: [[f32; 3]; 6] = [
+    [1.0, 1.0, 1.0],
+    [1.0, 0.35, 0.3],
+    [0.3, 0.5, 1.0],
+    [0.4, 1.0, 0.4],
+    [1.0, 0.8, 0.3],
+    [0.8, 0.4, 1.0],
+];
+
+// count 個の光源を円周上に等間隔に並べて周回させる
+// 2番目以降の光源は高さを少しずつ変えて、重なって見えないようにする
+pub fn orbiting_lights(count: usize, time: f32) -> Vec<Light> {
+    (0..count)
+        .map(|i| {
+            let angle = time * ORBIT_SPEED + i as f32 * std::f32::consts::TAU / count as f32;
+            let height = ORBIT_HEIGHT + (i % 3) as f32 * 0.4;
+            let position = Vec3::new(
+                ORBIT_RADIUS * angle.cos(),
+                height,
+                ORBIT_RADIUS * angle.sin(),
+            );
+            Light::new(position, PALETTE[i % PALETTE.len()])
+        })
+        .collect()
+}
+
+// 光源の配列をシェーダーに渡す方法
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LightStorage {
+    // 読み取り専用のストレージバッファ（個数の上限なし）
+    Storage,
+    // ストレージバッファを使えない環境向けの固定長のユニフォーム配列
+    Uniform,
+}
+
+// ユニフォーム配列で渡せる光源の最大数
+pub const MAX_UNIFORM_LIGHTS: usize = 16;
+
+impl LightStorage {
+    // WebGL などのダウンレベルの環境では頂点・フラグメントシェーダーからストレージバッファを読めない
+    pub fn for_capabilities(capabilities: &GpuCapabilities) -> Self {
+        if capabilities.fragment_storage {
+            Self::Storage
+        } else {
+            Self::Uniform
+        }
+    }
+
+    // 光源の最大数（None なら上限なし）
+    pub fn max_lights(self) -> Option<usize> {
+        match self {
+            Self::Storage => None,
+            Self::Uniform => Some(MAX_UNIFORM_LIGHTS),
+        }
+    }
+
+    pub fn binding_type(self) -> wgpu::BindingType {
+        let ty = match self {
+            Self::Storage => wgpu::BufferBindingType::Storage { read_only: true },
+            Self::Uniform => wgpu::BufferBindingType::Uniform,
+        };
+        wgpu::BindingType::Buffer {
+            ty,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        }
+    }
+
+    // シェーダーはストレージバッファの宣言で書いておき、ユニフォーム配列を使う場合は宣言を書き換える
+    pub fn shader_source(self, source: &str) -> Cow<'_, str> {
+        const STORAGE_DECL: &str = "var<storage, read> lights: array<Light>;";
+        match self {
+            Self::Storage => Cow::Borrowed(source),
+            Self::Uniform => Cow::Owned(source.replace(
+                STORAGE_DECL,
+                &format!("var<uniform> lights: array<Light, {}>;", MAX_UNIFORM_LIGHTS),
+            )),
+        }
+    }
+}
+
+// 光源の配列を格納する GPU バッファ
+pub struct LightBuffer {
+    pub buffer: wgpu::Buffer,
+    capacity: usize,
+    storage: LightStorage,
+}
+
+impl LightBuffer {
+    pub fn new(device: &wgpu::Device, storage: LightStorage, count: usize) -> Self {
+        let capacity = match storage {
+            // 長さ 0 のバッファはバインドできないので、少なくとも1つ分は確保する
+            LightStorage::Storage => count.max(1),
+            LightStorage::Uniform => MAX_UNIFORM_LIGHTS,
+        };
+        let usage = match storage {
+            LightStorage::Storage => wgpu::BufferUsages::STORAGE,
+            LightStorage::Uniform => wgpu::BufferUsages::UNIFORM,
+        } | wgpu::BufferUsages::COPY_DST;
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Lights Buffer"),
+            size: (capacity * std::mem::size_of::<Light>()) as wgpu::BufferAddress,
+            usage,
+            mapped_at_creation: false,
+        });
+        Self {
+            buffer,
+            capacity,
+            storage,
+        }
+    }
+
+    // 光源の個数に合わせてバッファを作り直す
+    // 作り直した場合は true を返すので、呼び出し側でバインドグループも作り直すこと
+    pub fn resize(&mut self, device: &wgpu::Device, count: usize) -> bool {
+        if self.storage == LightStorage::Uniform || count.max(1) == self.capacity {
+            return false;
+        }
+        *self = Self::new(device, self.storage, count);
+        true
+    }
+
+    pub fn write(&self, queue: &wgpu::Queue, lights: &[Light]) {
+        let count = lights.len().min(self.capacity);
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&lights[..count]));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn light_matches_wgsl_array_stride() {
+        // vec3 + vec3 は 16 バイト境界に揃えられて 32 バイトになる
+        assert_eq!(std::mem::size_of::<Light>(), 32);
+        assert_eq!(std::mem::offset_of!(Light, color), 16);
+        assert_eq!(std::mem::size_of::<LightsUniform>(), 80);
+    }
+
+    #[test]
+    fn uniform_fallback_rewrites_light_array_declaration() {
+        // shader.wgsl・model.wgsl・pbr.wgsl の宣言はインクルードするファイルにある
+        for source in [
+            include_str!("include/lighting.wgsl"),
+            include_str!("light.wgsl"),
+        ] {
+            let rewritten = LightStorage::Uniform.shader_source(source);
+            assert!(!rewritten.contains("var<storage, read> lights"));
+            assert!(rewritten.contains("var<uniform> lights: array<Light, 16>;"));
+        }
+    }
+}
@@ -0,0 +1,43 @@
+struct Camera {
+    view_proj: mat4x4<f32>,
+    model: mat4x4<f32>,
+    view_position: vec4<f32>,
+};
+
+struct Light {
+    position: vec3<f32>,
+    color: vec3<f32>,
+};
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+// ストレージバッファを使えない環境では、読み込み時に固定長のユニフォーム配列の宣言に書き換える
+@group(1) @binding(3) var<storage, read> lights: array<Light>;
+
+// 目印の立方体の大きさ
+const MARKER_SCALE: f32 = 0.1;
+
+struct VInput {
+    @location(0) position: vec3<f32>,
+};
+
+struct VOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec3<f32>,
+};
+
+// インスタンス番号を光源の番号として、光源ごとに立方体を1つずつ描画する
+@vertex
+fn vs_main(in: VInput, @builtin(instance_index) index: u32) -> VOutput {
+    let light = lights[index];
+    var out: VOutput;
+    let world = in.position * MARKER_SCALE + light.position;
+    out.position = camera.view_proj * vec4<f32>(world, 1.0);
+    out.color = light.color;
+    return out;
+}
+
+// 光源自体は照らされないので光の色をそのまま出力する
+@fragment
+fn fs_main(in: VOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(in.color, 1.0);
+}
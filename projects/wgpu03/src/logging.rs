@@ -0,0 +1,486 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io::Write as _;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Level, Metadata, Subscriber};
+use tracing_core::span::Current;
+
+// RUST_LOG がなければ、このクレートのメッセージとほかのクレート（wgpu など）のエラーを表示する
+const DEFAULT_FILTER: &str = "error,wgpu03=info";
+
+/// ログの書式（`--log-format`）
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// 人が読むための1行ずつの書式
+    #[default]
+    Pretty,
+    /// 1行に1つの JSON オブジェクト（集めたログを後から調べる場合）
+    Json,
+}
+
+impl LogFormat {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "pretty" => Some(Self::Pretty),
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+}
+
+/// tracing のイベントとスパンを標準エラー出力に書き出し始める
+///
+/// RUST_LOG（env_logger と同じ書き方）で表示するものを選ぶ。
+/// log で出力するクレート（wgpu など）のレコードも、同じ書式と今のスパンで書き出す。
+pub fn init(format: LogFormat) {
+    let directives = std::env::var("RUST_LOG").unwrap_or_else(|_| DEFAULT_FILTER.to_string());
+    let logger = Arc::new(Logger::new(&directives, format));
+    let max_level = logger.filter.filter();
+    // 2回目以降の呼び出しでは最初の設定のままにする
+    if tracing::subscriber::set_global_default(logger.clone()).is_ok() {
+        let _ = log::set_boxed_logger(Box::new(logger));
+        log::set_max_level(max_level);
+    }
+}
+
+// イベントとスパンに付けた値（JSON の書式では型を保って書き出す）
+#[derive(Clone, Debug, PartialEq)]
+enum Value {
+    Str(String),
+    I64(i64),
+    U64(u64),
+    F64(f64),
+    Bool(bool),
+}
+
+impl Value {
+    fn json(&self) -> serde_json::Value {
+        match self {
+            Self::Str(s) => s.as_str().into(),
+            Self::I64(n) => (*n).into(),
+            Self::U64(n) => (*n).into(),
+            Self::F64(n) => (*n).into(),
+            Self::Bool(b) => (*b).into(),
+        }
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Str(s) => f.write_str(s),
+            Self::I64(n) => write!(f, "{}", n),
+            Self::U64(n) => write!(f, "{}", n),
+            Self::F64(n) => write!(f, "{:.3}", n),
+            Self::Bool(b) => write!(f, "{}", b),
+        }
+    }
+}
+
+type Fields = Vec<(&'static str, Value)>;
+
+// 同じ名前の値は後から記録したもので置き換える（スパンの Empty の値を record で埋める場合など）
+struct FieldVisitor<'a>(&'a mut Fields);
+
+impl FieldVisitor<'_> {
+    fn set(&mut self, field: &Field, value: Value) {
+        match self.0.iter_mut().find(|(name, _)| *name == field.name()) {
+            Some((_, old)) => *old = value,
+            None => self.0.push((field.name(), value)),
+        }
+    }
+}
+
+impl Visit for FieldVisitor<'_> {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.set(field, Value::Str(format!("{:?}", value)));
+    }
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.set(field, Value::Str(value.to_string()));
+    }
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.set(field, Value::I64(value));
+    }
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.set(field, Value::U64(value));
+    }
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.set(field, Value::F64(value));
+    }
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.set(field, Value::Bool(value));
+    }
+}
+
+struct SpanData {
+    metadata: &'static Metadata<'static>,
+    fields: Fields,
+    parent: Option<Id>,
+    start: Instant,
+    // Span のハンドルの数（0 になったら閉じる）
+    refs: usize,
+}
+
+// 1行に書き出すもの
+struct Line<'a> {
+    // 起動からの秒数
+    time: f64,
+    level: Level,
+    target: &'a str,
+    // 外側から順に、スパンの名前と値
+    scope: Vec<(&'a str, &'a [(&'static str, Value)])>,
+    message: &'a str,
+    fields: &'a [(&'static str, Value)],
+}
+
+impl Line<'_> {
+    fn format(&self, format: LogFormat) -> String {
+        match format {
+            LogFormat::Pretty => {
+                let mut line = format!("{:>9.3}s {:>5} {}: ", self.time, self.level, self.target);
+                for (name, fields) in &self.scope {
+                    line.push_str(name);
+                    if !fields.is_empty() {
+                        line.push('{');
+                        line.push_str(&join_fields(fields));
+                        line.push('}');
+                    }
+                    line.push_str(": ");
+                }
+                line.push_str(self.message);
+                if !self.fields.is_empty() {
+                    let _ = write!(line, " {}", join_fields(self.fields));
+                }
+                line
+            }
+            LogFormat::Json => {
+                let object = |fields: &[(&'static str, Value)]| -> serde_json::Value {
+                    fields
+                        .iter()
+                        .map(|(name, value)| (name.to_string(), value.json()))
+                        .collect::<serde_json::Map<_, _>>()
+                        .into()
+                };
+                let spans: Vec<serde_json::Value> = self
+                    .scope
+                    .iter()
+                    .map(|(name, fields)| serde_json::json!({ "name": name, "fields": object(fields) }))
+                    .collect();
+                serde_json::json!({
+                    "time": self.time,
+                    "level": self.level.as_str(),
+                    "target": self.target,
+                    "spans": spans,
+                    "message": self.message,
+                    "fields": object(self.fields),
+                })
+                .to_string()
+            }
+        }
+    }
+}
+
+fn join_fields(fields: &[(&'static str, Value)]) -> String {
+    fields
+        .iter()
+        .map(|(name, value)| format!("{}={}", name, value))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn log_level(level: &Level) -> log::Level {
+    match *level {
+        Level::ERROR => log::Level::Error,
+        Level::WARN => log::Level::Warn,
+        Level::INFO => log::Level::Info,
+        Level::DEBUG => log::Level::Debug,
+        Level::TRACE => log::Level::Trace,
+    }
+}
+
+fn tracing_level(level: log::Level) -> Level {
+    match level {
+        log::Level::Error => Level::ERROR,
+        log::Level::Warn => Level::WARN,
+        log::Level::Info => Level::INFO,
+        log::Level::Debug => Level::DEBUG,
+        log::Level::Trace => Level::TRACE,
+    }
+}
+
+thread_local! {
+    // このスレッドで入っているスパン（最後のものが今のスパン）
+    static STACK: RefCell<Vec<Id>> = const { RefCell::new(Vec::new()) };
+}
+
+fn current_id() -> Option<Id> {
+    STACK.with(|stack| stack.borrow().last().cloned())
+}
+
+struct Logger {
+    filter: env_filter::Filter,
+    format: LogFormat,
+    start: Instant,
+    next_id: AtomicU64,
+    spans: Mutex<HashMap<u64, SpanData>>,
+}
+
+impl Logger {
+    fn new(directives: &str, format: LogFormat) -> Self {
+        Self {
+            filter: env_filter::Builder::new().parse(directives).build(),
+            format,
+            start: Instant::now(),
+            next_id: AtomicU64::new(1),
+            spans: Mutex::default(),
+        }
+    }
+
+    // parent から外側へたどったスパンを、外側から順に並べて1行を書き出す
+    fn write(
+        &self,
+        level: Level,
+        target: &str,
+        parent: Option<Id>,
+        message: &str,
+        fields: &[(&'static str, Value)],
+    ) {
+        let spans = self.spans.lock().unwrap();
+        let mut scope = Vec::new();
+        let mut next = parent;
+        while let Some(span) = next.and_then(|id| spans.get(&id.into_u64())) {
+            scope.push((span.metadata.name(), span.fields.as_slice()));
+            next = span.parent.clone();
+        }
+        scope.reverse();
+        let line = Line {
+            time: self.start.elapsed().as_secs_f64(),
+            level,
+            target,
+            scope,
+            message,
+            fields,
+        }
+        .format(self.format);
+        let _ = writeln!(std::io::stderr().lock(), "{}", line);
+    }
+}
+
+impl Subscriber for Logger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        self.filter.enabled(
+            &log::Metadata::builder()
+                .level(log_level(metadata.level()))
+                .target(metadata.target())
+                .build(),
+        )
+    }
+
+    fn max_level_hint(&self) -> Option<tracing::level_filters::LevelFilter> {
+        Some(match self.filter.filter().to_level() {
+            Some(level) => tracing_level(level).into(),
+            None => tracing::level_filters::LevelFilter::OFF,
+        })
+    }
+
+    fn new_span(&self, attributes: &Attributes<'_>) -> Id {
+        let id = Id::from_u64(self.next_id.fetch_add(1, Ordering::Relaxed));
+        let mut fields = Fields::new();
+        attributes.record(&mut FieldVisitor(&mut fields));
+        let parent = if attributes.is_root() {
+            None
+        } else {
+            attributes.parent().cloned().or_else(current_id)
+        };
+        self.spans.lock().unwrap().insert(
+            id.into_u64(),
+            SpanData {
+                metadata: attributes.metadata(),
+                fields,
+                parent,
+                start: Instant::now(),
+                refs: 1,
+            },
+        );
+        id
+    }
+
+    fn record(&self, span: &Id, values: &Record<'_>) {
+        if let Some(span) = self.spans.lock().unwrap().get_mut(&span.into_u64()) {
+            values.record(&mut FieldVisitor(&mut span.fields));
+        }
+    }
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut fields = Fields::new();
+        event.record(&mut FieldVisitor(&mut fields));
+        let message = match fields.iter().position(|(name, _)| *name == "message") {
+            Some(index) => fields.remove(index).1.to_string(),
+            None => String::new(),
+        };
+        let parent = if event.is_root() {
+            None
+        } else {
+            event.parent().cloned().or_else(current_id)
+        };
+        let metadata = event.metadata();
+        self.write(
+            *metadata.level(),
+            metadata.target(),
+            parent,
+            &message,
+            &fields,
+        );
+    }
+
+    fn enter(&self, span: &Id) {
+        STACK.with(|stack| stack.borrow_mut().push(span.clone()));
+    }
+
+    fn exit(&self, span: &Id) {
+        STACK.with(|stack| {
+            let mut stack = stack.borrow_mut();
+            if let Some(index) = stack.iter().rposition(|id| id == span) {
+                stack.remove(index);
+            }
+        });
+    }
+
+    fn current_span(&self) -> Current {
+        let Some(id) = current_id() else {
+            return Current::none();
+        };
+        match self.spans.lock().unwrap().get(&id.into_u64()) {
+            Some(span) => Current::new(id, span.metadata),
+            None => Current::none(),
+        }
+    }
+
+    fn clone_span(&self, id: &Id) -> Id {
+        if let Some(span) = self.spans.lock().unwrap().get_mut(&id.into_u64()) {
+            span.refs += 1;
+        }
+        id.clone()
+    }
+
+    // 閉じたスパンは、かかった時間と最後に記録した値を1行に書き出す（遅いフレームを後から調べるため）
+    fn try_close(&self, id: Id) -> bool {
+        let closed = {
+            let mut spans = self.spans.lock().unwrap();
+            let Some(span) = spans.get_mut(&id.into_u64()) else {
+                return false;
+            };
+            span.refs -= 1;
+            if span.refs > 0 {
+                return false;
+            }
+            spans.remove(&id.into_u64())
+        };
+        if let Some(span) = closed {
+            let mut fields = span.fields;
+            fields.push((
+                "elapsed_ms",
+                Value::F64(span.start.elapsed().as_secs_f64() * 1000.0),
+            ));
+            let name = format!("{} を終えました", span.metadata.name());
+            self.write(
+                *span.metadata.level(),
+                span.metadata.target(),
+                span.parent,
+                &name,
+                &fields,
+            );
+        }
+        true
+    }
+}
+
+impl log::Log for Logger {
+    fn enabled(&self, metadata: &log::Metadata<'_>) -> bool {
+        self.filter.enabled(metadata)
+    }
+
+    fn log(&self, record: &log::Record<'_>) {
+        if self.filter.matches(record) {
+            self.write(
+                tracing_level(record.level()),
+                record.target(),
+                current_id(),
+                &record.args().to_string(),
+                &[],
+            );
+        }
+    }
+
+    fn flush(&self) {
+        let _ = std::io::stderr().flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line<'a>(fields: &'a [(&'static str, Value)], scope_fields: &'a Fields) -> Line<'a> {
+        Line {
+            time: 1.5,
+            level: Level::DEBUG,
+            target: "wgpu03",
+            scope: vec![("frame", scope_fields.as_slice())],
+            message: "フレーム",
+            fields,
+        }
+    }
+
+    #[test]
+    fn lines_show_spans_and_typed_fields() {
+        let scope = vec![("width", Value::U64(800)), ("acquire_ms", Value::F64(1.25))];
+        let fields = [("format", Value::Str("Bgra8UnormSrgb".into()))];
+        assert_eq!(
+            line(&fields, &scope).format(LogFormat::Pretty),
+            "    1.500s DEBUG wgpu03: frame{width=800 acquire_ms=1.250}: フレーム format=Bgra8UnormSrgb"
+        );
+        let json: serde_json::Value =
+            serde_json::from_str(&line(&fields, &scope).format(LogFormat::Json)).unwrap();
+        assert_eq!(json["level"], "DEBUG");
+        assert_eq!(json["spans"][0]["name"], "frame");
+        assert_eq!(json["spans"][0]["fields"]["width"], 800);
+        assert_eq!(json["spans"][0]["fields"]["acquire_ms"], 1.25);
+        assert_eq!(json["fields"]["format"], "Bgra8UnormSrgb");
+        assert_eq!(json["message"], "フレーム");
+    }
+
+    #[test]
+    fn spans_record_late_values_and_close_with_their_last_handle() {
+        assert_eq!(LogFormat::parse("json"), Some(LogFormat::Json));
+        assert_eq!(LogFormat::parse("xml"), None);
+
+        let logger = Arc::new(Logger::new("trace", LogFormat::Pretty));
+        tracing::subscriber::with_default(logger.clone(), || {
+            let span = tracing::debug_span!("frame", encode_ms = tracing::field::Empty);
+            let handle = span.clone();
+            span.record("encode_ms", 2.0);
+            {
+                let _entered = span.enter();
+                assert_eq!(tracing::Span::current().id(), span.id());
+            }
+            let id = span.id().unwrap().into_u64();
+            assert_eq!(
+                logger.spans.lock().unwrap()[&id].fields,
+                vec![("encode_ms", Value::F64(2.0))]
+            );
+            drop(span);
+            assert!(logger.spans.lock().unwrap().contains_key(&id));
+            drop(handle);
+            assert!(logger.spans.lock().unwrap().is_empty());
+        });
+    }
+}
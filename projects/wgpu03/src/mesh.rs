@@ -0,0 +1,523 @@
+use std::ops::Range;
+
+use wgpu::util::{DeviceExt, RenderEncoder};
+
+use crate::frustum::Aabb;
+use crate::indirect::IndirectDraw;
+
+// 頂点バッファのスロットとシェーダーのロケーションの割り当て
+//   スロット0: 頂点ごとのデータ（Vertex）         ロケーション 0〜4（現在は 0〜3 を使用）
+//   スロット1: インスタンスごとのデータ（Instance） ロケーション 5〜（現在は 5〜6 を使用）
+// 頂点属性を追加するときはロケーション 4 までに収め、インスタンス属性と重ならないようにする
+
+// 頂点データ（位置・色・テクスチャ座標・法線）
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Vertex {
+    pub position: [f32; 3],
+    pub color: [f32; 3],
+    pub tex_coords: [f32; 2],
+    pub normal: [f32; 3],
+}
+
+impl Vertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 4] = wgpu::vertex_attr_array![
+        0 => Float32x3,
+        1 => Float32x3,
+        2 => Float32x2,
+        3 => Float32x3,
+    ];
+
+    // 頂点バッファのレイアウトを返す
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+// インスタンスごとのデータ（平行移動量と色）
+// 色のアルファ値は半透明パイプラインでのみ意味を持つ
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Instance {
+    pub offset: [f32; 3],
+    color: [f32; 4],
+}
+
+impl Instance {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 2] =
+        wgpu::vertex_attr_array![5 => Float32x3, 6 => Float32x4];
+
+    // 移動なし・白色のインスタンス（インスタンス描画しない図形に使う）
+    pub const IDENTITY: Instance = Instance {
+        offset: [0.0, 0.0, 0.0],
+        color: [1.0, 1.0, 1.0, 1.0],
+    };
+
+    // インスタンスバッファのレイアウトを返す
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Instance>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+// グリッド状に並べる三角形のインスタンス数
+const INSTANCES_PER_ROW: u32 = 10;
+pub const NUM_INSTANCES: u32 = INSTANCES_PER_ROW * INSTANCES_PER_ROW;
+
+// グリッドに並べる三角形の間隔
+const INSTANCE_SPACING: f32 = 1.2;
+// グリッドを配置する奥行き（カメラから全体が見える距離）
+const GRID_DEPTH: f32 = -13.0;
+
+// 10x10のグリッドに並べたインスタンスデータを生成する
+pub fn grid_instances() -> Vec<Instance> {
+    let half = (INSTANCES_PER_ROW - 1) as f32 * INSTANCE_SPACING / 2.0;
+    (0..INSTANCES_PER_ROW)
+        .flat_map(|row| (0..INSTANCES_PER_ROW).map(move |col| (row, col)))
+        .map(|(row, col)| {
+            let u = col as f32 / (INSTANCES_PER_ROW - 1) as f32;
+            let v = row as f32 / (INSTANCES_PER_ROW - 1) as f32;
+            Instance {
+                offset: [
+                    col as f32 * INSTANCE_SPACING - half,
+                    row as f32 * INSTANCE_SPACING - half,
+                    GRID_DEPTH,
+                ],
+                color: [u, v, 1.0 - u, 1.0],
+            }
+        })
+        .collect()
+}
+
+// 不透明な図形の手前に重ねる半透明の三角形（三角形のメッシュを平行移動して使う）
+pub const TRANSLUCENT_INSTANCES: &[Instance] = &[
+    Instance {
+        offset: [0.25, -0.1, 0.3],
+        color: [0.2, 0.6, 1.0, 0.5],
+    },
+    Instance {
+        offset: [-0.2, 0.1, 0.15],
+        color: [1.0, 0.5, 0.2, 0.5],
+    },
+];
+
+// 半透明の図形を奥から手前の順に描画するためのインスタンス番号の並びを返す
+pub fn back_to_front(instances: &[Instance], model: glam::Mat4, eye: glam::Vec3) -> Vec<u32> {
+    let distance = |instance: &Instance| {
+        model
+            .transform_point3(glam::Vec3::from(instance.offset))
+            .distance_squared(eye)
+    };
+    let mut order: Vec<u32> = (0..instances.len() as u32).collect();
+    order.sort_by(|&a, &b| {
+        distance(&instances[b as usize]).total_cmp(&distance(&instances[a as usize]))
+    });
+    order
+}
+
+// 三角形の頂点データ
+pub const VERTICES: &[Vertex] = &[
+    Vertex {
+        position: [0.0, 0.5, 0.0],
+        color: [1.0, 0.0, 0.0],
+        tex_coords: [0.5, 0.0],
+        normal: [0.0, 0.0, 1.0],
+    },
+    Vertex {
+        position: [-0.5, -0.5, 0.0],
+        color: [0.0, 1.0, 0.0],
+        tex_coords: [0.0, 1.0],
+        normal: [0.0, 0.0, 1.0],
+    },
+    Vertex {
+        position: [0.5, -0.5, 0.0],
+        color: [0.0, 0.0, 1.0],
+        tex_coords: [1.0, 1.0],
+        normal: [0.0, 0.0, 1.0],
+    },
+];
+
+// 五角形の頂点データとインデックスデータ
+pub const PENTAGON_VERTICES: &[Vertex] = &[
+    Vertex {
+        position: [-0.0868241, 0.49240386, 0.0],
+        color: [1.0, 0.0, 0.0],
+        tex_coords: [0.4131759, 0.0075961],
+        normal: [0.0, 0.0, 1.0],
+    },
+    Vertex {
+        position: [-0.49513406, 0.06958647, 0.0],
+        color: [1.0, 1.0, 0.0],
+        tex_coords: [0.0048659, 0.4304135],
+        normal: [0.0, 0.0, 1.0],
+    },
+    Vertex {
+        position: [-0.21918549, -0.44939706, 0.0],
+        color: [0.0, 1.0, 0.0],
+        tex_coords: [0.2808145, 0.9493971],
+        normal: [0.0, 0.0, 1.0],
+    },
+    Vertex {
+        position: [0.35966998, -0.3473291, 0.0],
+        color: [0.0, 0.0, 1.0],
+        tex_coords: [0.85967, 0.8473291],
+        normal: [0.0, 0.0, 1.0],
+    },
+    Vertex {
+        position: [0.44147372, 0.2347359, 0.0],
+        color: [1.0, 0.0, 1.0],
+        tex_coords: [0.9414737, 0.2652641],
+        normal: [0.0, 0.0, 1.0],
+    },
+];
+
+// 奥に配置した三角形（深度テストで手前の図形に隠れることを確認する）
+pub const BACK_VERTICES: &[Vertex] = &[
+    Vertex {
+        position: [0.3, 0.7, -0.5],
+        color: [0.9, 0.9, 0.2],
+        tex_coords: [0.5, 0.0],
+        normal: [0.0, 0.0, 1.0],
+    },
+    Vertex {
+        position: [-0.3, -0.3, -0.5],
+        color: [0.9, 0.9, 0.2],
+        tex_coords: [0.0, 1.0],
+        normal: [0.0, 0.0, 1.0],
+    },
+    Vertex {
+        position: [0.9, -0.3, -0.5],
+        color: [0.9, 0.9, 0.2],
+        tex_coords: [1.0, 1.0],
+        normal: [0.0, 0.0, 1.0],
+    },
+];
+
+pub const PENTAGON_INDICES: &[u16] = &[0, 1, 4, 1, 2, 4, 2, 3, 4];
+
+// パーティクルの数と、1つの粒子として描画する小さな四角形
+pub const NUM_PARTICLES: u32 = 100_000;
+const PARTICLE_HALF_SIZE: f32 = 0.008;
+pub const PARTICLE_VERTICES: &[Vertex] = &[
+    Vertex {
+        position: [-PARTICLE_HALF_SIZE, -PARTICLE_HALF_SIZE, 0.0],
+        color: [1.0, 1.0, 1.0],
+        tex_coords: [0.0, 0.0],
+        normal: [0.0, 0.0, 1.0],
+    },
+    Vertex {
+        position: [PARTICLE_HALF_SIZE, -PARTICLE_HALF_SIZE, 0.0],
+        color: [1.0, 1.0, 1.0],
+        tex_coords: [0.0, 0.0],
+        normal: [0.0, 0.0, 1.0],
+    },
+    Vertex {
+        position: [PARTICLE_HALF_SIZE, PARTICLE_HALF_SIZE, 0.0],
+        color: [1.0, 1.0, 1.0],
+        tex_coords: [0.0, 0.0],
+        normal: [0.0, 0.0, 1.0],
+    },
+    Vertex {
+        position: [-PARTICLE_HALF_SIZE, PARTICLE_HALF_SIZE, 0.0],
+        color: [1.0, 1.0, 1.0],
+        tex_coords: [0.0, 0.0],
+        normal: [0.0, 0.0, 1.0],
+    },
+];
+pub const PARTICLE_INDICES: &[u16] = &[0, 1, 2, 0, 2, 3];
+
+// 影を受ける床（立方体やモデルの下に敷く）
+const FLOOR_HALF_SIZE: f32 = 3.0;
+pub const FLOOR_HEIGHT: f32 = -1.0;
+pub const FLOOR_VERTICES: &[Vertex] = &[
+    Vertex {
+        position: [-FLOOR_HALF_SIZE, FLOOR_HEIGHT, FLOOR_HALF_SIZE],
+        color: [0.8, 0.8, 0.8],
+        tex_coords: [0.0, 1.0],
+        normal: [0.0, 1.0, 0.0],
+    },
+    Vertex {
+        position: [FLOOR_HALF_SIZE, FLOOR_HEIGHT, FLOOR_HALF_SIZE],
+        color: [0.8, 0.8, 0.8],
+        tex_coords: [1.0, 1.0],
+        normal: [0.0, 1.0, 0.0],
+    },
+    Vertex {
+        position: [FLOOR_HALF_SIZE, FLOOR_HEIGHT, -FLOOR_HALF_SIZE],
+        color: [0.8, 0.8, 0.8],
+        tex_coords: [1.0, 0.0],
+        normal: [0.0, 1.0, 0.0],
+    },
+    Vertex {
+        position: [-FLOOR_HALF_SIZE, FLOOR_HEIGHT, -FLOOR_HALF_SIZE],
+        color: [0.8, 0.8, 0.8],
+        tex_coords: [0.0, 0.0],
+        normal: [0.0, 1.0, 0.0],
+    },
+];
+pub const FLOOR_INDICES: &[u16] = &[0, 1, 2, 0, 2, 3];
+
+// 遠くまで続く市松模様の平面（床と同じ高さで、1単位ごとにテクスチャを繰り返す）
+// 斜めから見た遠くの模様がちらつかないことで、ミップマップが効いていることを確かめる
+const PLANE_HALF_SIZE: f32 = 40.0;
+pub const PLANE_VERTICES: &[Vertex] = &[
+    Vertex {
+        position: [-PLANE_HALF_SIZE, FLOOR_HEIGHT, PLANE_HALF_SIZE],
+        color: [0.8, 0.8, 0.8],
+        tex_coords: [0.0, PLANE_HALF_SIZE * 2.0],
+        normal: [0.0, 1.0, 0.0],
+    },
+    Vertex {
+        position: [PLANE_HALF_SIZE, FLOOR_HEIGHT, PLANE_HALF_SIZE],
+        color: [0.8, 0.8, 0.8],
+        tex_coords: [PLANE_HALF_SIZE * 2.0, PLANE_HALF_SIZE * 2.0],
+        normal: [0.0, 1.0, 0.0],
+    },
+    Vertex {
+        position: [PLANE_HALF_SIZE, FLOOR_HEIGHT, -PLANE_HALF_SIZE],
+        color: [0.8, 0.8, 0.8],
+        tex_coords: [PLANE_HALF_SIZE * 2.0, 0.0],
+        normal: [0.0, 1.0, 0.0],
+    },
+    Vertex {
+        position: [-PLANE_HALF_SIZE, FLOOR_HEIGHT, -PLANE_HALF_SIZE],
+        color: [0.8, 0.8, 0.8],
+        tex_coords: [0.0, 0.0],
+        normal: [0.0, 1.0, 0.0],
+    },
+];
+
+// 面ごとにテクスチャ座標を持つ24頂点の立方体（一辺の長さは1）
+// 各面は外側から見て反時計回りになるように並べる
+pub fn cube_geometry() -> (Vec<Vertex>, Vec<u16>) {
+    // (法線, 面上のu方向, 面上のv方向)。u × v = 法線 となるように選ぶ
+    let faces = [
+        (glam::Vec3::X, glam::Vec3::NEG_Z, glam::Vec3::Y),
+        (glam::Vec3::NEG_X, glam::Vec3::Z, glam::Vec3::Y),
+        (glam::Vec3::Y, glam::Vec3::X, glam::Vec3::NEG_Z),
+        (glam::Vec3::NEG_Y, glam::Vec3::X, glam::Vec3::Z),
+        (glam::Vec3::Z, glam::Vec3::X, glam::Vec3::Y),
+        (glam::Vec3::NEG_Z, glam::Vec3::NEG_X, glam::Vec3::Y),
+    ];
+    let corners = [
+        (-1.0, -1.0, [0.0, 1.0]),
+        (1.0, -1.0, [1.0, 1.0]),
+        (1.0, 1.0, [1.0, 0.0]),
+        (-1.0, 1.0, [0.0, 0.0]),
+    ];
+
+    let mut vertices = Vec::with_capacity(24);
+    let mut indices = Vec::with_capacity(36);
+    for (normal, u, v) in faces {
+        let base = vertices.len() as u16;
+        for (su, sv, tex_coords) in corners {
+            let position = (normal + u * su + v * sv) * 0.5;
+            vertices.push(Vertex {
+                position: position.to_array(),
+                color: [1.0, 1.0, 1.0],
+                tex_coords,
+                normal: normal.to_array(),
+            });
+        }
+        indices.extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
+    }
+    (vertices, indices)
+}
+
+// GPUに転送済みのジオメトリ
+pub struct Mesh {
+    vertex_buffer: wgpu::Buffer,
+    num_vertices: u32,
+    // インデックスバッファを持つ場合のみインデックス描画を行う
+    index_buffer: Option<wgpu::Buffer>,
+    num_indices: u32,
+    index_format: wgpu::IndexFormat,
+    // モデル座標での頂点を囲む箱（視錐台カリングに使う）
+    pub bounds: Aabb,
+}
+
+impl Mesh {
+    pub fn new(
+        device: &wgpu::Device,
+        label: &str,
+        vertices: &[Vertex],
+        indices: Option<&[u16]>,
+    ) -> Self {
+        let index_buffer = indices.map(|indices| (bytemuck::cast_slice(indices), indices.len()));
+        Self::with_indices(
+            device,
+            label,
+            vertices,
+            index_buffer,
+            wgpu::IndexFormat::Uint16,
+        )
+    }
+
+    // 生成したジオメトリは頂点数が 65536 を超えることがあるので、32ビットのインデックスで描画する
+    pub fn from_geometry(
+        device: &wgpu::Device,
+        label: &str,
+        (vertices, indices): &(Vec<Vertex>, Vec<u32>),
+    ) -> Self {
+        let index_buffer = Some((bytemuck::cast_slice(indices.as_slice()), indices.len()));
+        Self::with_indices(
+            device,
+            label,
+            vertices,
+            index_buffer,
+            wgpu::IndexFormat::Uint32,
+        )
+    }
+
+    // indices はインデックスバッファの中身とインデックスの数
+    fn with_indices(
+        device: &wgpu::Device,
+        label: &str,
+        vertices: &[Vertex],
+        indices: Option<(&[u8], usize)>,
+        index_format: wgpu::IndexFormat,
+    ) -> Self {
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{} Vertex Buffer", label)),
+            contents: bytemuck::cast_slice(vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let index_buffer = indices.map(|(contents, _)| {
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(&format!("{} Index Buffer", label)),
+                contents,
+                usage: wgpu::BufferUsages::INDEX,
+            })
+        });
+
+        Self {
+            vertex_buffer,
+            num_vertices: vertices.len() as u32,
+            index_buffer,
+            num_indices: indices.map_or(0, |(_, count)| count as u32),
+            index_format,
+            bounds: Aabb::from_points(vertices.iter().map(|v| glam::Vec3::from(v.position))),
+        }
+    }
+
+    // スロット1のインスタンスバッファは呼び出し側で設定しておく
+    pub fn draw(&self, rpass: &mut wgpu::RenderPass, instances: Range<u32>) {
+        rpass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        match &self.index_buffer {
+            Some(index_buffer) => {
+                rpass.set_index_buffer(index_buffer.slice(..), self.index_format);
+                rpass.draw_indexed(0..self.num_indices, 0, instances);
+            }
+            None => rpass.draw(0..self.num_vertices, instances),
+        }
+    }
+
+    // draw と同じ描画を、レンダーバンドルにも記録できるよう RenderEncoder に対して行う
+    pub fn encode<'a>(&'a self, encoder: &mut impl RenderEncoder<'a>, instances: Range<u32>) {
+        encoder.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        match &self.index_buffer {
+            Some(index_buffer) => {
+                encoder.set_index_buffer(index_buffer.slice(..), self.index_format);
+                encoder.draw_indexed(0..self.num_indices, 0, instances);
+            }
+            None => encoder.draw(0..self.num_vertices, instances),
+        }
+    }
+
+    // 描画の引数をバッファから読んで描画する（インデックスなしのメッシュのみ）
+    pub fn draw_indirect(&self, rpass: &mut wgpu::RenderPass, indirect: &IndirectDraw) {
+        debug_assert!(self.index_buffer.is_none());
+        rpass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        indirect.draw(rpass);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vertex_and_instance_locations_are_unique() {
+        let layouts = [Vertex::desc(), Instance::desc()];
+        let mut locations: Vec<u32> = layouts
+            .iter()
+            .flat_map(|layout| layout.attributes.iter().map(|a| a.shader_location))
+            .collect();
+        let count = locations.len();
+        locations.sort_unstable();
+        locations.dedup();
+        assert_eq!(locations.len(), count);
+
+        assert_eq!(layouts[1].step_mode, wgpu::VertexStepMode::Instance);
+        assert_eq!(grid_instances().len() as u32, NUM_INSTANCES);
+    }
+
+    #[test]
+    fn translucent_instances_sort_back_to_front() {
+        let eye = glam::Vec3::new(0.0, 0.0, 2.0);
+        let order = back_to_front(TRANSLUCENT_INSTANCES, glam::Mat4::IDENTITY, eye);
+        assert_eq!(order, vec![1, 0]);
+
+        // カメラを反対側に置くと順序も逆になる
+        let order = back_to_front(TRANSLUCENT_INSTANCES, glam::Mat4::IDENTITY, -eye);
+        assert_eq!(order, vec![0, 1]);
+    }
+
+    #[test]
+    fn cube_faces_wind_counter_clockwise_from_outside() {
+        let (vertices, indices) = cube_geometry();
+        assert_eq!(vertices.len(), 24);
+        assert_eq!(indices.len(), 36);
+
+        for triangle in indices.chunks_exact(3) {
+            let [a, b, c] =
+                [0, 1, 2].map(|i| glam::Vec3::from(vertices[triangle[i] as usize].position));
+            let normal = (b - a).cross(c - a);
+            let centroid = (a + b + c) / 3.0;
+            assert!(
+                normal.dot(centroid) > 0.0,
+                "inward-facing triangle {:?}",
+                triangle
+            );
+        }
+    }
+
+    #[test]
+    fn vertex_layout_matches_struct() {
+        let layout = Vertex::desc();
+        assert_eq!(
+            layout.array_stride,
+            std::mem::size_of::<Vertex>() as wgpu::BufferAddress
+        );
+        assert_eq!(layout.attributes.len(), 4);
+        assert_eq!(
+            layout.attributes[0].offset,
+            std::mem::offset_of!(Vertex, position) as wgpu::BufferAddress
+        );
+        assert_eq!(
+            layout.attributes[1].offset,
+            std::mem::offset_of!(Vertex, color) as wgpu::BufferAddress
+        );
+        assert_eq!(
+            layout.attributes[2].offset,
+            std::mem::offset_of!(Vertex, tex_coords) as wgpu::BufferAddress
+        );
+        assert_eq!(
+            layout.attributes[3].offset,
+            std::mem::offset_of!(Vertex, normal) as wgpu::BufferAddress
+        );
+        for (i, attribute) in layout.attributes.iter().enumerate() {
+            assert_eq!(attribute.shader_location, i as u32);
+        }
+        // wave.wgsl は Vertex を 11 個の f32、particles.wgsl は Instance を 7 個の f32 として書き込む
+        assert_eq!(std::mem::size_of::<Vertex>(), 11 * 4);
+        assert_eq!(std::mem::size_of::<Instance>(), 7 * 4);
+    }
+}
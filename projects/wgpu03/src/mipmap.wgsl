@@ -0,0 +1,24 @@
+// 1つ上のミップレベル（2倍の大きさ）を線形補間で縮小して書き込む
+@group(0) @binding(0) var t_source: texture_2d<f32>;
+@group(0) @binding(1) var s_source: sampler;
+
+struct VOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+// 頂点バッファを使わずに画面全体を覆う大きな三角形を描画する
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VOutput;
+    out.position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    out.uv = vec2<f32>(uv.x, 1.0 - uv.y);
+    return out;
+}
+
+// 書き込む画素の中心は元のレベルの 2x2 画素の中央に当たるので、線形補間でその平均が得られる
+@fragment
+fn fs_main(in: VOutput) -> @location(0) vec4<f32> {
+    return textureSample(t_source, s_source, in.uv);
+}
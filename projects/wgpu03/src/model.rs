@@ -0,0 +1,1151 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use tracing::warn;
+use wgpu::util::DeviceExt;
+
+use crate::animation::{
+    Channel, Clip, Interpolation, JointLayout, JointVertex, Property, Skeleton, Skin, Transform,
+};
+use crate::frustum::Aabb;
+use crate::texture::Texture;
+
+// モデル用の頂点データ（位置・テクスチャ座標・法線・接線）
+// 接線の w は従接線の向き（UV が裏返っている場合は -1）
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ModelVertex {
+    pub position: [f32; 3],
+    pub tex_coords: [f32; 2],
+    pub normal: [f32; 3],
+    pub tangent: [f32; 4],
+}
+
+impl ModelVertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 4] = wgpu::vertex_attr_array![
+        0 => Float32x3,
+        1 => Float32x2,
+        2 => Float32x3,
+        3 => Float32x4,
+    ];
+
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<ModelVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+// 三角形の位置と UV の変化から頂点ごとの接線を求める
+// UV の面積が 0 の三角形は接線の向きが決まらないので計算から除き、
+// 最後に法線と直交化して、求まらなかった頂点には法線に垂直な任意の向きを使う
+pub fn compute_tangents(vertices: &mut [ModelVertex], indices: &[u32]) {
+    let mut tangents = vec![glam::Vec3::ZERO; vertices.len()];
+    let mut bitangents = vec![glam::Vec3::ZERO; vertices.len()];
+
+    for tri in indices.chunks_exact(3) {
+        let [i0, i1, i2] = [tri[0] as usize, tri[1] as usize, tri[2] as usize];
+        if i0.max(i1).max(i2) >= vertices.len() {
+            continue;
+        }
+        let [v0, v1, v2] = [vertices[i0], vertices[i1], vertices[i2]];
+        let e1 = glam::Vec3::from(v1.position) - glam::Vec3::from(v0.position);
+        let e2 = glam::Vec3::from(v2.position) - glam::Vec3::from(v0.position);
+        let d1 = glam::Vec2::from(v1.tex_coords) - glam::Vec2::from(v0.tex_coords);
+        let d2 = glam::Vec2::from(v2.tex_coords) - glam::Vec2::from(v0.tex_coords);
+
+        let det = d1.x * d2.y - d2.x * d1.y;
+        if det.abs() < 1e-8 {
+            continue;
+        }
+        let r = 1.0 / det;
+        let tangent = (e1 * d2.y - e2 * d1.y) * r;
+        let bitangent = (e2 * d1.x - e1 * d2.x) * r;
+        if !tangent.is_finite() || !bitangent.is_finite() {
+            continue;
+        }
+        for i in [i0, i1, i2] {
+            tangents[i] += tangent;
+            bitangents[i] += bitangent;
+        }
+    }
+
+    for (i, v) in vertices.iter_mut().enumerate() {
+        let n = glam::Vec3::from(v.normal).normalize_or(glam::Vec3::Z);
+        // グラム・シュミットの直交化
+        let t = (tangents[i] - n * n.dot(tangents[i])).normalize_or_zero();
+        let t = if t == glam::Vec3::ZERO {
+            n.any_orthonormal_vector()
+        } else {
+            t
+        };
+        let w = if n.cross(t).dot(bitangents[i]) < 0.0 {
+            -1.0
+        } else {
+            1.0
+        };
+        v.tangent = [t.x, t.y, t.z, w];
+    }
+}
+
+// マテリアルの基本色（シェーダーのユニフォームに対応）
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct MaterialUniform {
+    diffuse: [f32; 4],
+}
+
+// マテリアルの陰影付けの方法（メッシュごとに描画するパイプラインが変わる）
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Shading {
+    BlinnPhong,
+    Pbr,
+}
+
+pub struct Material {
+    pub bind_group: wgpu::BindGroup,
+    pub shading: Shading,
+}
+
+impl Material {
+    // 拡散反射テクスチャ・サンプラー・基本色・法線マップのバインドグループレイアウト
+    // 法線マップはバインディング1のサンプラーを共有する
+    pub fn bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Material Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+            ],
+        })
+    }
+
+    pub fn new(
+        device: &wgpu::Device,
+        name: &str,
+        diffuse_texture: &Texture,
+        normal_texture: &Texture,
+        diffuse: [f32; 4],
+        layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{} Material Buffer", name)),
+            contents: bytemuck::cast_slice(&[MaterialUniform { diffuse }]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some(&format!("{} Material Bind Group", name)),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&diffuse_texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&diffuse_texture.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(&normal_texture.view),
+                },
+            ],
+        });
+
+        Self {
+            bind_group,
+            shading: Shading::BlinnPhong,
+        }
+    }
+}
+
+// PBR マテリアルの係数（シェーダーのユニフォームに対応）
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct PbrUniform {
+    albedo: [f32; 4],
+    metallic: f32,
+    roughness: f32,
+    _padding: [f32; 2],
+}
+
+// メタリック・ラフネスの PBR マテリアルを作成するビルダー
+// 指定しなかったテクスチャは1x1の既定のテクスチャ（白または平らな法線）で埋める
+pub struct PbrMaterial<'a> {
+    name: &'a str,
+    albedo: Option<&'a Texture>,
+    normal: Option<&'a Texture>,
+    metallic: Option<&'a Texture>,
+    roughness: Option<&'a Texture>,
+    occlusion: Option<&'a Texture>,
+    albedo_factor: [f32; 4],
+    metallic_factor: f32,
+    roughness_factor: f32,
+}
+
+impl<'a> PbrMaterial<'a> {
+    pub fn new(name: &'a str) -> Self {
+        Self {
+            name,
+            albedo: None,
+            normal: None,
+            metallic: None,
+            roughness: None,
+            occlusion: None,
+            albedo_factor: [1.0; 4],
+            metallic_factor: 1.0,
+            roughness_factor: 1.0,
+        }
+    }
+
+    pub fn albedo(mut self, texture: &'a Texture) -> Self {
+        self.albedo = Some(texture);
+        self
+    }
+
+    pub fn normal(mut self, texture: &'a Texture) -> Self {
+        self.normal = Some(texture);
+        self
+    }
+
+    // メタリックは B チャンネルから読む（glTF のメタリック・ラフネステクスチャと同じ配置）
+    pub fn metallic(mut self, texture: &'a Texture) -> Self {
+        self.metallic = Some(texture);
+        self
+    }
+
+    // ラフネスは G チャンネルから読む
+    pub fn roughness(mut self, texture: &'a Texture) -> Self {
+        self.roughness = Some(texture);
+        self
+    }
+
+    // アンビエントオクルージョンは R チャンネルから読む
+    pub fn occlusion(mut self, texture: &'a Texture) -> Self {
+        self.occlusion = Some(texture);
+        self
+    }
+
+    pub fn albedo_factor(mut self, factor: [f32; 4]) -> Self {
+        self.albedo_factor = factor;
+        self
+    }
+
+    pub fn metallic_factor(mut self, factor: f32) -> Self {
+        self.metallic_factor = factor;
+        self
+    }
+
+    pub fn roughness_factor(mut self, factor: f32) -> Self {
+        self.roughness_factor = factor;
+        self
+    }
+
+    // アルベド・サンプラー・係数・法線・メタリック・ラフネス・AO のバインドグループレイアウト
+    pub fn bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        let texture = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            },
+            count: None,
+        };
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("PBR Material Bind Group Layout"),
+            entries: &[
+                texture(0),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                texture(3),
+                texture(4),
+                texture(5),
+                texture(6),
+            ],
+        })
+    }
+
+    pub fn build(
+        self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layout: &wgpu::BindGroupLayout,
+    ) -> Material {
+        // 足りないテクスチャがある場合だけ既定のテクスチャを作る
+        let white = [self.albedo, self.metallic, self.roughness, self.occlusion]
+            .iter()
+            .any(Option::is_none)
+            .then(|| Texture::white(device, queue));
+        let flat_normal = self
+            .normal
+            .is_none()
+            .then(|| Texture::flat_normal(device, queue));
+        let or_white = |texture: Option<&'a Texture>| texture.or(white.as_ref()).unwrap();
+        let albedo = or_white(self.albedo);
+        let normal = self.normal.or(flat_normal.as_ref()).unwrap();
+
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{} PBR Material Buffer", self.name)),
+            contents: bytemuck::cast_slice(&[PbrUniform {
+                albedo: self.albedo_factor,
+                metallic: self.metallic_factor,
+                roughness: self.roughness_factor,
+                _padding: [0.0; 2],
+            }]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        fn view(binding: u32, texture: &Texture) -> wgpu::BindGroupEntry<'_> {
+            wgpu::BindGroupEntry {
+                binding,
+                resource: wgpu::BindingResource::TextureView(&texture.view),
+            }
+        }
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some(&format!("{} PBR Material Bind Group", self.name)),
+            layout,
+            entries: &[
+                view(0, albedo),
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&albedo.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: buffer.as_entire_binding(),
+                },
+                view(3, normal),
+                view(4, or_white(self.metallic)),
+                view(5, or_white(self.roughness)),
+                view(6, or_white(self.occlusion)),
+            ],
+        });
+
+        Material {
+            bind_group,
+            shading: Shading::Pbr,
+        }
+    }
+}
+
+pub struct Mesh {
+    pub vertex_buffer: wgpu::Buffer,
+    pub index_buffer: wgpu::Buffer,
+    pub num_elements: u32,
+    pub material_id: usize,
+    // モデル座標での頂点を囲む箱（スキンメッシュは姿勢で形が変わるので視錐台カリングに使わない）
+    pub bounds: Aabb,
+    // スキンメッシュの場合のみ、スキンのパイプラインで描画する
+    pub skin: Option<MeshSkin>,
+}
+
+// スキンメッシュの関節の番号と重みの頂点バッファと、関節の行列のバインドグループ
+pub struct MeshSkin {
+    pub joint_buffer: wgpu::Buffer,
+    pub bind_group: wgpu::BindGroup,
+}
+
+// スキンと、関節の行列を書き込むバッファ
+pub struct SkinBuffer {
+    skin: Skin,
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    // バッファに書き込める関節の数
+    capacity: usize,
+}
+
+pub struct Model {
+    pub meshes: Vec<Mesh>,
+    pub materials: Vec<Material>,
+    // glTF のスキンとアニメーション（持たないモデルでは空）
+    pub skins: Vec<SkinBuffer>,
+    pub clips: Vec<Clip>,
+    skeleton: Option<Skeleton>,
+}
+
+impl Model {
+    // Wavefront OBJ ファイルを読み込む
+    // テクスチャのパスは OBJ ファイルのあるディレクトリからの相対パスとして解決する
+    pub fn load(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        path: &Path,
+        layout: &wgpu::BindGroupLayout,
+    ) -> Result<Self> {
+        let (models, obj_materials) = tobj::load_obj(path, &tobj::GPU_LOAD_OPTIONS)
+            .with_context(|| format!("Failed to load OBJ file {}", path.display()))?;
+        let obj_materials = obj_materials.unwrap_or_else(|e| {
+            warn!(
+                "マテリアルを読み込めませんでした（{}）: {}",
+                path.display(),
+                e
+            );
+            Vec::new()
+        });
+        let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
+
+        // テクスチャのないマテリアルやテクスチャが見つからない場合に使う1x1の白いテクスチャと平らな法線マップ
+        let white = Texture::white(device, queue);
+        let flat_normal = Texture::flat_normal(device, queue);
+
+        // テクスチャが見つからない場合は None を返し、呼び出し側で代用のテクスチャを使う
+        let load_texture = |file: &str, is_normal_map: bool| {
+            let texture_path = base_dir.join(file);
+            let texture = std::fs::read(&texture_path)
+                .map_err(anyhow::Error::from)
+                .and_then(|bytes| {
+                    if is_normal_map {
+                        Texture::linear_from_bytes(device, queue, &bytes, file)
+                    } else {
+                        Texture::from_bytes(device, queue, &bytes, file)
+                    }
+                });
+            match texture {
+                Ok(texture) => Some(texture),
+                Err(e) => {
+                    warn!(
+                        "テクスチャを読み込めないため代用します（{}）: {}",
+                        texture_path.display(),
+                        e
+                    );
+                    None
+                }
+            }
+        };
+
+        let mut materials = Vec::with_capacity(obj_materials.len() + 1);
+        for m in &obj_materials {
+            let texture = m
+                .diffuse_texture
+                .as_ref()
+                .and_then(|file| load_texture(file, false));
+            let normal_texture = m
+                .normal_texture
+                .as_ref()
+                .and_then(|file| load_texture(file, true));
+            let diffuse = m.diffuse.unwrap_or([1.0; 3]);
+            materials.push(Material::new(
+                device,
+                &m.name,
+                texture.as_ref().unwrap_or(&white),
+                normal_texture.as_ref().unwrap_or(&flat_normal),
+                [diffuse[0], diffuse[1], diffuse[2], 1.0],
+                layout,
+            ));
+        }
+        // マテリアルが指定されていないメッシュ用の既定のマテリアル
+        let default_material = materials.len();
+        materials.push(Material::new(
+            device,
+            "Default",
+            &white,
+            &flat_normal,
+            [1.0; 4],
+            layout,
+        ));
+
+        let meshes = models
+            .into_iter()
+            .map(|m| {
+                let mesh = &m.mesh;
+                let mut vertices: Vec<ModelVertex> = (0..mesh.positions.len() / 3)
+                    .map(|i| ModelVertex {
+                        position: [
+                            mesh.positions[i * 3],
+                            mesh.positions[i * 3 + 1],
+                            mesh.positions[i * 3 + 2],
+                        ],
+                        // OBJ のテクスチャ座標は下が原点なので上下を反転する
+                        tex_coords: if mesh.texcoords.len() >= (i + 1) * 2 {
+                            [mesh.texcoords[i * 2], 1.0 - mesh.texcoords[i * 2 + 1]]
+                        } else {
+                            [0.0, 0.0]
+                        },
+                        normal: if mesh.normals.len() >= (i + 1) * 3 {
+                            [
+                                mesh.normals[i * 3],
+                                mesh.normals[i * 3 + 1],
+                                mesh.normals[i * 3 + 2],
+                            ]
+                        } else {
+                            [0.0, 0.0, 0.0]
+                        },
+                        tangent: [0.0; 4],
+                    })
+                    .collect();
+                compute_tangents(&mut vertices, &mesh.indices);
+
+                let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some(&format!("{} Vertex Buffer", m.name)),
+                    contents: bytemuck::cast_slice(&vertices),
+                    usage: wgpu::BufferUsages::VERTEX,
+                });
+                let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some(&format!("{} Index Buffer", m.name)),
+                    contents: bytemuck::cast_slice(&mesh.indices),
+                    usage: wgpu::BufferUsages::INDEX,
+                });
+
+                Mesh {
+                    vertex_buffer,
+                    index_buffer,
+                    num_elements: mesh.indices.len() as u32,
+                    material_id: mesh
+                        .material_id
+                        .filter(|&id| id < default_material)
+                        .unwrap_or(default_material),
+                    bounds: Aabb::from_points(
+                        vertices.iter().map(|v| glam::Vec3::from(v.position)),
+                    ),
+                    skin: None,
+                }
+            })
+            .collect();
+
+        Ok(Self {
+            meshes,
+            materials,
+            skins: Vec::new(),
+            clips: Vec::new(),
+            skeleton: None,
+        })
+    }
+
+    // glTF 2.0（.gltf / .glb）ファイルを読み込む
+    // ノード階層の変換は頂点に焼き込み、マテリアルはメタリック・ラフネスの PBR マテリアルとして作成する
+    // スキンメッシュは変換を焼き込まず、関節の行列を animate で書き込んで頂点シェーダーで動かす
+    // （layout には PbrMaterial::bind_group_layout を渡す）
+    pub fn load_gltf(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        path: &Path,
+        layout: &wgpu::BindGroupLayout,
+        joint_layout: &JointLayout,
+    ) -> Result<Self> {
+        let (document, buffers, images) = gltf::import(path)
+            .with_context(|| format!("Failed to load glTF file {}", path.display()))?;
+
+        let mut materials = Vec::with_capacity(document.materials().len() + 1);
+        for m in document.materials() {
+            let name = m.name().unwrap_or("glTF Material");
+            let pbr = m.pbr_metallic_roughness();
+            let image = |texture: gltf::Texture| {
+                let index = texture.source().index();
+                let img = images.get(index).and_then(gltf_image_to_dynamic);
+                if img.is_none() {
+                    warn!(
+                        "未対応の画像形式のため代用します（{}: 画像 {}）",
+                        name, index
+                    );
+                }
+                img
+            };
+            let linear = |img: image::DynamicImage| {
+                Texture::linear_from_image(device, queue, &img, Some(name))
+            };
+            let albedo = pbr
+                .base_color_texture()
+                .and_then(|info| image(info.texture()))
+                .map(|img| Texture::from_image(device, queue, &img, Some(name)));
+            let normal = m
+                .normal_texture()
+                .and_then(|info| image(info.texture()))
+                .map(linear);
+            // メタリックとラフネスは1枚のテクスチャの B と G チャンネルに入っている
+            let metallic_roughness = pbr
+                .metallic_roughness_texture()
+                .and_then(|info| image(info.texture()))
+                .map(linear);
+            let occlusion = m
+                .occlusion_texture()
+                .and_then(|info| image(info.texture()))
+                .map(linear);
+
+            let mut material = PbrMaterial::new(name)
+                .albedo_factor(pbr.base_color_factor())
+                .metallic_factor(pbr.metallic_factor())
+                .roughness_factor(pbr.roughness_factor());
+            if let Some(texture) = &albedo {
+                material = material.albedo(texture);
+            }
+            if let Some(texture) = &normal {
+                material = material.normal(texture);
+            }
+            if let Some(texture) = &metallic_roughness {
+                material = material.metallic(texture).roughness(texture);
+            }
+            if let Some(texture) = &occlusion {
+                material = material.occlusion(texture);
+            }
+            materials.push(material.build(device, queue, layout));
+        }
+        // glTF の既定のマテリアル（メタリック 1・ラフネス 1）
+        let default_material = materials.len();
+        materials.push(PbrMaterial::new("Default").build(device, queue, layout));
+
+        let (skeleton, skins, clips) = gltf_animation(&document, &buffers);
+        let skins: Vec<SkinBuffer> = skins
+            .into_iter()
+            .enumerate()
+            .map(|(index, skin)| {
+                let label = format!("Skin {}", index);
+                let (buffer, bind_group) =
+                    joint_layout.create_bind_group(device, &label, skin.joints.len());
+                let capacity = joint_layout.capacity(skin.joints.len());
+                if skin.joints.len() > capacity {
+                    warn!(
+                        "関節が多すぎるため {} 個目以降は動かしません（{}: {} 個）",
+                        capacity + 1,
+                        label,
+                        skin.joints.len()
+                    );
+                }
+                SkinBuffer {
+                    skin,
+                    buffer,
+                    bind_group,
+                    capacity,
+                }
+            })
+            .collect();
+
+        let meshes = gltf_primitives(&document, &buffers)
+            .into_iter()
+            .map(|p| {
+                let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some(&format!("{} Vertex Buffer", p.name)),
+                    contents: bytemuck::cast_slice(&p.vertices),
+                    usage: wgpu::BufferUsages::VERTEX,
+                });
+                let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some(&format!("{} Index Buffer", p.name)),
+                    contents: bytemuck::cast_slice(&p.indices),
+                    usage: wgpu::BufferUsages::INDEX,
+                });
+
+                let skin = p.skin.and_then(|(skin, joints)| {
+                    let bind_group = skins.get(skin)?.bind_group.clone();
+                    let joint_buffer =
+                        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                            label: Some(&format!("{} Joint Vertex Buffer", p.name)),
+                            contents: bytemuck::cast_slice(&joints),
+                            usage: wgpu::BufferUsages::VERTEX,
+                        });
+                    Some(MeshSkin {
+                        joint_buffer,
+                        bind_group,
+                    })
+                });
+
+                Mesh {
+                    vertex_buffer,
+                    index_buffer,
+                    num_elements: p.indices.len() as u32,
+                    material_id: p
+                        .material_id
+                        .filter(|&id| id < default_material)
+                        .unwrap_or(default_material),
+                    bounds: Aabb::from_points(
+                        p.vertices.iter().map(|v| glam::Vec3::from(v.position)),
+                    ),
+                    skin,
+                }
+            })
+            .collect();
+
+        let model = Self {
+            meshes,
+            materials,
+            skins,
+            clips,
+            skeleton: Some(skeleton),
+        };
+        // 最初に描画するまでに静止した姿勢の行列を書き込んでおく
+        model.animate(queue, None, 0.0);
+        Ok(model)
+    }
+
+    // clips[clip] を time 秒まで再生した（最後まで再生したら最初に戻る）姿勢の関節の行列を書き込む
+    // clip が None なら静止した姿勢にする
+    pub fn animate(&self, queue: &wgpu::Queue, clip: Option<usize>, time: f32) {
+        let (Some(skeleton), false) = (&self.skeleton, self.skins.is_empty()) else {
+            return;
+        };
+        let clip = clip.and_then(|index| self.clips.get(index));
+        let pose = skeleton.pose(clip, clip.map_or(0.0, |clip| clip.looped(time)));
+        for skin in &self.skins {
+            let matrices = skin.skin.joint_matrices(&pose);
+            let count = matrices.len().min(skin.capacity);
+            queue.write_buffer(&skin.buffer, 0, bytemuck::cast_slice(&matrices[..count]));
+        }
+    }
+}
+
+// glTF のプリミティブ1つ分の頂点・インデックス（スキンメッシュ以外はワールド変換適用済み）
+struct GltfPrimitive {
+    name: String,
+    vertices: Vec<ModelVertex>,
+    indices: Vec<u32>,
+    material_id: Option<usize>,
+    // スキンの番号と、頂点ごとの関節の番号と重み
+    skin: Option<(usize, Vec<JointVertex>)>,
+}
+
+// 既定のシーンのノード階層をたどり、描画するプリミティブを集める
+// アクセサがインターリーブされていても別々のバッファでも reader が吸収する
+fn gltf_primitives(
+    document: &gltf::Document,
+    buffers: &[gltf::buffer::Data],
+) -> Vec<GltfPrimitive> {
+    let mut primitives = Vec::new();
+    let scene = document
+        .default_scene()
+        .or_else(|| document.scenes().next());
+    let mut stack: Vec<(gltf::Node, glam::Mat4)> = scene
+        .into_iter()
+        .flat_map(|s| s.nodes())
+        .map(|n| (n, glam::Mat4::IDENTITY))
+        .collect();
+
+    while let Some((node, parent)) = stack.pop() {
+        let world = parent * glam::Mat4::from_cols_array_2d(&node.transform().matrix());
+        // スキンメッシュの頂点はノードの変換を使わず、関節の行列だけで配置する
+        let skin = node.skin().map(|skin| skin.index());
+        let baked = if skin.is_some() {
+            glam::Mat4::IDENTITY
+        } else {
+            world
+        };
+        // 法線は逆転置行列で変換する（非一様スケールに対応）
+        let normal_matrix = glam::Mat3::from_mat4(baked).inverse().transpose();
+
+        if let Some(mesh) = node.mesh() {
+            let name = mesh.name().or(node.name()).unwrap_or("glTF Mesh");
+            for primitive in mesh.primitives() {
+                if primitive.mode() != gltf::mesh::Mode::Triangles {
+                    warn!("三角形以外のプリミティブは読み飛ばします（{}）", name);
+                    continue;
+                }
+                let reader = primitive.reader(|b| buffers.get(b.index()).map(|d| &d.0[..]));
+                let Some(positions) = reader.read_positions() else {
+                    continue;
+                };
+                let positions: Vec<[f32; 3]> = positions.collect();
+                let normals: Vec<[f32; 3]> = reader
+                    .read_normals()
+                    .map(|n| n.collect())
+                    .unwrap_or_default();
+                let tex_coords: Vec<[f32; 2]> = reader
+                    .read_tex_coords(0)
+                    .map(|t| t.into_f32().collect())
+                    .unwrap_or_default();
+
+                let mut vertices: Vec<ModelVertex> = positions
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &p)| ModelVertex {
+                        position: baked.transform_point3(p.into()).into(),
+                        // glTF のテクスチャ座標は左上が原点なのでそのまま使う
+                        tex_coords: tex_coords.get(i).copied().unwrap_or([0.0, 0.0]),
+                        normal: normals
+                            .get(i)
+                            .map(|&n| {
+                                (normal_matrix * glam::Vec3::from(n))
+                                    .normalize_or_zero()
+                                    .into()
+                            })
+                            .unwrap_or([0.0, 0.0, 0.0]),
+                        tangent: [0.0; 4],
+                    })
+                    .collect();
+                // インデックスを持たないプリミティブは頂点を順番に並べたものとして扱う
+                let indices: Vec<u32> = match reader.read_indices() {
+                    Some(indices) => indices.into_u32().collect(),
+                    None => (0..positions.len() as u32).collect(),
+                };
+                // TANGENT 属性の有無にかかわらず、変換後の頂点から接線を求める
+                compute_tangents(&mut vertices, &indices);
+                // 関節の番号や重みがなければ、すべての頂点を最初の関節に付ける
+                let skin = skin.map(|skin| {
+                    let joints: Vec<[u16; 4]> = reader
+                        .read_joints(0)
+                        .map(|j| j.into_u16().collect())
+                        .unwrap_or_default();
+                    let weights: Vec<[f32; 4]> = reader
+                        .read_weights(0)
+                        .map(|w| w.into_f32().collect())
+                        .unwrap_or_default();
+                    let vertices = (0..positions.len())
+                        .map(|i| JointVertex {
+                            joints: joints.get(i).copied().unwrap_or_default().map(u32::from),
+                            weights: weights.get(i).copied().unwrap_or([1.0, 0.0, 0.0, 0.0]),
+                        })
+                        .collect();
+                    (skin, vertices)
+                });
+
+                primitives.push(GltfPrimitive {
+                    name: name.to_string(),
+                    vertices,
+                    indices,
+                    material_id: primitive.material().index(),
+                    skin,
+                });
+            }
+        }
+
+        stack.extend(node.children().map(|c| (c, world)));
+    }
+
+    primitives
+}
+
+// すべてのノードの階層とスキン、アニメーションを読み込む
+// （モーフターゲットの重みのアニメーションには対応しないので読み飛ばす）
+fn gltf_animation(
+    document: &gltf::Document,
+    buffers: &[gltf::buffer::Data],
+) -> (Skeleton, Vec<Skin>, Vec<Clip>) {
+    let get_buffer = |b: gltf::Buffer| buffers.get(b.index()).map(|d| &d.0[..]);
+    let rest = document
+        .nodes()
+        .map(|node| {
+            let (translation, rotation, scale) = node.transform().decomposed();
+            Transform {
+                translation: translation.into(),
+                rotation: glam::Quat::from_array(rotation),
+                scale: scale.into(),
+            }
+        })
+        .collect();
+    let children: Vec<Vec<usize>> = document
+        .nodes()
+        .map(|node| node.children().map(|child| child.index()).collect())
+        .collect();
+    let skeleton = Skeleton::new(rest, &children);
+
+    let skins = document
+        .skins()
+        .map(|skin| {
+            let joints: Vec<usize> = skin.joints().map(|joint| joint.index()).collect();
+            // 逆バインド行列がなければ単位行列とする
+            let inverse_bind_matrices = skin
+                .reader(get_buffer)
+                .read_inverse_bind_matrices()
+                .map(|matrices| {
+                    matrices
+                        .map(|m| glam::Mat4::from_cols_array_2d(&m))
+                        .collect()
+                })
+                .unwrap_or_else(|| vec![glam::Mat4::IDENTITY; joints.len()]);
+            Skin {
+                joints,
+                inverse_bind_matrices,
+            }
+        })
+        .collect();
+
+    let clips = document
+        .animations()
+        .enumerate()
+        .map(|(index, animation)| {
+            let channels = animation
+                .channels()
+                .filter_map(|channel| {
+                    use gltf::animation::util::ReadOutputs;
+
+                    let reader = channel.reader(get_buffer);
+                    let times: Vec<f32> = reader.read_inputs()?.collect();
+                    let (property, values): (_, Vec<glam::Vec4>) = match reader.read_outputs()? {
+                        ReadOutputs::Translations(values) => (
+                            Property::Translation,
+                            values.map(|v| glam::Vec3::from(v).extend(0.0)).collect(),
+                        ),
+                        ReadOutputs::Rotations(values) => (
+                            Property::Rotation,
+                            values.into_f32().map(glam::Vec4::from).collect(),
+                        ),
+                        ReadOutputs::Scales(values) => (
+                            Property::Scale,
+                            values.map(|v| glam::Vec3::from(v).extend(0.0)).collect(),
+                        ),
+                        ReadOutputs::MorphTargetWeights(_) => return None,
+                    };
+                    let interpolation = match channel.sampler().interpolation() {
+                        gltf::animation::Interpolation::Step => Interpolation::Step,
+                        gltf::animation::Interpolation::Linear => Interpolation::Linear,
+                        gltf::animation::Interpolation::CubicSpline => Interpolation::CubicSpline,
+                    };
+                    (!times.is_empty()).then_some(Channel {
+                        node: channel.target().node().index(),
+                        property,
+                        interpolation,
+                        times,
+                        values,
+                    })
+                })
+                .collect();
+            let name = animation
+                .name()
+                .map_or_else(|| format!("Animation {}", index), str::to_string);
+            Clip::new(name, channels)
+        })
+        .collect();
+
+    (skeleton, skins, clips)
+}
+
+// glTF の画像データを image クレートの画像に変換する（8ビットの形式のみ対応）
+fn gltf_image_to_dynamic(data: &gltf::image::Data) -> Option<image::DynamicImage> {
+    use gltf::image::Format;
+
+    let (w, h, pixels) = (data.width, data.height, data.pixels.clone());
+    match data.format {
+        Format::R8 => image::GrayImage::from_raw(w, h, pixels).map(image::DynamicImage::ImageLuma8),
+        Format::R8G8 => {
+            image::GrayAlphaImage::from_raw(w, h, pixels).map(image::DynamicImage::ImageLumaA8)
+        }
+        Format::R8G8B8 => {
+            image::RgbImage::from_raw(w, h, pixels).map(image::DynamicImage::ImageRgb8)
+        }
+        Format::R8G8B8A8 => {
+            image::RgbaImage::from_raw(w, h, pixels).map(image::DynamicImage::ImageRgba8)
+        }
+        _ => None,
+    }
+}
+
+// マテリアルの陰影付けの方法ごとのパイプライン
+// スキンメッシュは関節の行列をバインドグループ3に設定し、skinned で描画する
+pub struct ModelPipelines<'a> {
+    pub blinn_phong: &'a wgpu::RenderPipeline,
+    pub pbr: &'a wgpu::RenderPipeline,
+    pub skinned: &'a wgpu::RenderPipeline,
+}
+
+// シャドウマップなど、マテリアルを使わないパス用のパイプライン
+// スキンメッシュは関節の行列をバインドグループ2に設定し、skinned で描画する
+pub struct DepthPipelines<'a> {
+    pub rigid: &'a wgpu::RenderPipeline,
+    pub skinned: &'a wgpu::RenderPipeline,
+}
+
+impl ModelPipelines<'_> {
+    fn get(&self, shading: Shading) -> &wgpu::RenderPipeline {
+        match shading {
+            Shading::BlinnPhong => self.blinn_phong,
+            Shading::Pbr => self.pbr,
+        }
+    }
+}
+
+// RenderPass でモデルを描画するための拡張
+// マテリアルはバインドグループ1に設定し、パイプラインはマテリアルの陰影付けの方法で選ぶ
+pub trait DrawModel {
+    fn draw_mesh(&mut self, mesh: &Mesh, material: &Material, pipelines: &ModelPipelines);
+    fn draw_model(&mut self, model: &Model, pipelines: &ModelPipelines);
+    // visible[i] が false のメッシュ（視錐台の外にあるもの）を省いて描画する
+    fn draw_model_culled(&mut self, model: &Model, pipelines: &ModelPipelines, visible: &[bool]);
+    fn draw_model_depth(&mut self, model: &Model, pipelines: &DepthPipelines);
+}
+
+impl DrawModel for wgpu::RenderPass<'_> {
+    fn draw_mesh(&mut self, mesh: &Mesh, material: &Material, pipelines: &ModelPipelines) {
+        match &mesh.skin {
+            Some(skin) => {
+                self.set_pipeline(pipelines.skinned);
+                self.set_vertex_buffer(1, skin.joint_buffer.slice(..));
+                self.set_bind_group(3, &skin.bind_group, &[]);
+            }
+            None => self.set_pipeline(pipelines.get(material.shading)),
+        }
+        self.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+        self.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        self.set_bind_group(1, &material.bind_group, &[]);
+        self.draw_indexed(0..mesh.num_elements, 0, 0..1);
+    }
+
+    fn draw_model(&mut self, model: &Model, pipelines: &ModelPipelines) {
+        for mesh in &model.meshes {
+            self.draw_mesh(mesh, &model.materials[mesh.material_id], pipelines);
+        }
+    }
+
+    fn draw_model_culled(&mut self, model: &Model, pipelines: &ModelPipelines, visible: &[bool]) {
+        for (mesh, _) in model
+            .meshes
+            .iter()
+            .zip(visible)
+            .filter(|(_, visible)| **visible)
+        {
+            self.draw_mesh(mesh, &model.materials[mesh.material_id], pipelines);
+        }
+    }
+
+    fn draw_model_depth(&mut self, model: &Model, pipelines: &DepthPipelines) {
+        for mesh in &model.meshes {
+            match &mesh.skin {
+                Some(skin) => {
+                    self.set_pipeline(pipelines.skinned);
+                    self.set_vertex_buffer(1, skin.joint_buffer.slice(..));
+                    self.set_bind_group(2, &skin.bind_group, &[]);
+                }
+                None => self.set_pipeline(pipelines.rigid),
+            }
+            self.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+            self.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+            self.draw_indexed(0..mesh.num_elements, 0, 0..1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_gltf_scene_flattens_hierarchy_and_generates_indices() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/scene.glb");
+        let (document, buffers, _) = gltf::import(path).unwrap();
+        let prims = gltf_primitives(&document, &buffers);
+        assert_eq!(prims.len(), 2);
+
+        let cube = prims.iter().find(|p| p.name == "InterleavedCube").unwrap();
+        assert_eq!(cube.vertices.len(), 24);
+        assert_eq!(cube.indices.len(), 36);
+        // ルート (0,-0.1,0) と子 (-0.8,0,0)・スケール 0.8 が焼き込まれている
+        let min_x = cube
+            .vertices
+            .iter()
+            .map(|v| v.position[0])
+            .fold(f32::MAX, f32::min);
+        assert!((min_x - (-0.8 - 0.4)).abs() < 1e-5);
+
+        let pyramid = prims.iter().find(|p| p.name == "SeparatePyramid").unwrap();
+        assert_eq!(pyramid.indices, (0..18).collect::<Vec<u32>>());
+        assert_eq!(pyramid.material_id, Some(1));
+    }
+
+    #[test]
+    fn animated_gltf_keeps_skinned_vertices_in_bind_space_and_reads_clips() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/animated.glb");
+        let (document, buffers, _) = gltf::import(path).unwrap();
+        let prims = gltf_primitives(&document, &buffers);
+        // 台座はノードの変換を焼き込み、柱はスキンの関節で動かす
+        let pedestal = prims.iter().find(|p| p.name == "Pedestal").unwrap();
+        assert!(pedestal.skin.is_none());
+        let bottom = pedestal
+            .vertices
+            .iter()
+            .map(|v| v.position[1])
+            .fold(f32::MAX, f32::min);
+        assert!((bottom - (-0.95 - 0.05)).abs() < 1e-5);
+        let column = prims.iter().find(|p| p.name == "Column").unwrap();
+        let (skin, joints) = column.skin.as_ref().unwrap();
+        assert_eq!(*skin, 0);
+        assert_eq!(joints.len(), column.vertices.len());
+
+        let (skeleton, skins, clips) = gltf_animation(&document, &buffers);
+        let names: Vec<_> = clips.iter().map(|clip| clip.name.as_str()).collect();
+        assert_eq!(names, ["Bend", "Twist", "Hop"]);
+        let interpolations: Vec<_> = clips
+            .iter()
+            .map(|clip| clip.channels[0].interpolation)
+            .collect();
+        assert_eq!(
+            interpolations,
+            [
+                Interpolation::Linear,
+                Interpolation::CubicSpline,
+                Interpolation::Step
+            ]
+        );
+        // 静止した姿勢では関節の行列が単位行列になり、頂点はバインドポーズのまま
+        for matrix in skins[0].joint_matrices(&skeleton.pose(None, 0.0)) {
+            assert!(matrix.abs_diff_eq(glam::Mat4::IDENTITY, 1e-5));
+        }
+    }
+
+    #[test]
+    fn tangents_follow_u_and_survive_degenerate_uvs() {
+        let vertex = |position: [f32; 3], tex_coords: [f32; 2]| ModelVertex {
+            position,
+            tex_coords,
+            normal: [0.0, 0.0, 1.0],
+            tangent: [0.0; 4],
+        };
+        let mut vertices = vec![
+            vertex([0.0, 0.0, 0.0], [0.0, 1.0]),
+            vertex([1.0, 0.0, 0.0], [1.0, 1.0]),
+            vertex([0.0, 1.0, 0.0], [0.0, 0.0]),
+            // UV がすべて同じ（面積 0）の三角形
+            vertex([2.0, 0.0, 0.0], [0.5, 0.5]),
+            vertex([3.0, 0.0, 0.0], [0.5, 0.5]),
+            vertex([2.0, 1.0, 0.0], [0.5, 0.5]),
+        ];
+        compute_tangents(&mut vertices, &[0, 1, 2, 3, 4, 5]);
+
+        assert_eq!(vertices[0].tangent, [1.0, 0.0, 0.0, -1.0]);
+        for v in &vertices[3..] {
+            let t = glam::Vec4::from(v.tangent);
+            assert!(t.is_finite());
+            assert!((t.truncate().length() - 1.0).abs() < 1e-5);
+            assert!(t.truncate().dot(glam::Vec3::Z).abs() < 1e-5);
+        }
+    }
+}
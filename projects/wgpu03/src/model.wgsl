@@ -0,0 +1,83 @@
+#include "include/camera.wgsl"
+
+struct Uniforms {
+    tint: vec4<f32>,
+    time: f32,
+    // 0 以外なら法線マップで法線を揺らす
+    normal_mapping: u32,
+};
+
+#include "include/lighting.wgsl"
+
+struct Material {
+    diffuse: vec4<f32>,
+};
+
+@group(0) @binding(1) var<uniform> uniforms: Uniforms;
+
+@group(1) @binding(0) var t_diffuse: texture_2d<f32>;
+@group(1) @binding(1) var s_diffuse: sampler;
+@group(1) @binding(2) var<uniform> material: Material;
+@group(1) @binding(3) var t_normal: texture_2d<f32>;
+
+// Blinn-Phong の環境光の強さと鏡面反射の鋭さ
+const AMBIENT_STRENGTH: f32 = 0.1;
+const SHININESS: f32 = 32.0;
+
+struct VInput {
+    @location(0) position: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) normal: vec3<f32>,
+    @location(3) tangent: vec4<f32>,
+};
+
+struct VOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) world_normal: vec3<f32>,
+    @location(2) world_tangent: vec4<f32>,
+    @location(3) world_position: vec3<f32>,
+};
+
+@vertex
+fn vs_main(in: VInput) -> VOutput {
+    var out: VOutput;
+    let world = camera.model * vec4<f32>(in.position, 1.0);
+    out.position = camera.view_proj * world;
+    out.world_position = world.xyz;
+    out.tex_coords = in.tex_coords;
+    // モデル行列は回転のみなので、法線と接線もそのまま変換できる
+    out.world_normal = (camera.normal_matrix * vec4<f32>(in.normal, 0.0)).xyz;
+    out.world_tangent = vec4<f32>((camera.model * vec4<f32>(in.tangent.xyz, 0.0)).xyz, in.tangent.w);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VOutput) -> @location(0) vec4<f32> {
+    let tex_color = textureSample(t_diffuse, s_diffuse, in.tex_coords);
+    let normal_sample = textureSample(t_normal, s_diffuse, in.tex_coords).xyz * 2.0 - 1.0;
+
+    var normal = normalize(in.world_normal);
+    if uniforms.normal_mapping != 0u {
+        // 補間で崩れた直交性を戻してから TBN 行列で接線空間の法線をワールド座標に変換する
+        let tangent = normalize(in.world_tangent.xyz - normal * dot(normal, in.world_tangent.xyz));
+        let bitangent = cross(normal, tangent) * in.world_tangent.w;
+        normal = normalize(mat3x3<f32>(tangent, bitangent, normal) * normal_sample);
+    }
+
+    let base = tex_color.rgb * material.diffuse.rgb;
+    let view_dir = normalize(camera.view_position.xyz - in.world_position);
+    // 影はシャドウマップを持つ先頭の光源についてのみ求める
+    let shadow = shadow_factor(in.world_position);
+
+    var lit = AMBIENT_STRENGTH * base;
+    for (var i = 0u; i < light_info.count; i += 1u) {
+        let light = lights[i];
+        let light_dir = normalize(light.position - in.world_position);
+        let half_dir = normalize(view_dir + light_dir);
+        let diffuse = light.color * max(dot(normal, light_dir), 0.0);
+        let specular = light.color * pow(max(dot(normal, half_dir), 0.0), SHININESS);
+        lit += (diffuse * base + specular) * select(1.0, shadow, i == 0u);
+    }
+    return vec4<f32>(lit, tex_color.a * material.diffuse.a) * uniforms.tint;
+}
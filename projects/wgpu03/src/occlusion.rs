@@ -0,0 +1,183 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
+
+use tracing::info;
+
+// この回数続けて隠れていたら描画を省く（1回だけ隠れた場合は描画を続けて、ちらつきを防ぐ）
+const HIDDEN_RESULTS_BEFORE_SKIP: u32 = 2;
+const RESULT_SIZE: wgpu::BufferAddress = std::mem::size_of::<u64>() as wgpu::BufferAddress;
+
+// オクルージョンクエリの結果から、次のフレームで描画するかを決める
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct Visibility {
+    // 続けて隠れていた回数
+    hidden_results: u32,
+}
+
+impl Visibility {
+    fn should_draw(self) -> bool {
+        self.hidden_results < HIDDEN_RESULTS_BEFORE_SKIP
+    }
+
+    // 描画するかどうかが変わったら true を返す
+    fn update(&mut self, passed_samples: u64) -> bool {
+        let before = self.should_draw();
+        if passed_samples > 0 {
+            self.hidden_results = 0;
+        } else {
+            self.hidden_results = self.hidden_results.saturating_add(1);
+        }
+        before != self.should_draw()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ReadbackState {
+    Idle,
+    // このフレームで結果のコピーを記録したので、提出後にマップする
+    Copied,
+    // map_async の完了を待っている
+    Mapping,
+}
+
+// 1つの物体の描画を囲むオクルージョンクエリ
+// 結果は数フレーム遅れで読み出し、隠れ続けている間は描画を省く
+pub struct OcclusionQuery {
+    label: &'static str,
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+    state: ReadbackState,
+    // map_async のコールバックで true にする
+    mapped: Arc<AtomicBool>,
+    visibility: Visibility,
+}
+
+impl OcclusionQuery {
+    pub fn new(device: &wgpu::Device, label: &'static str) -> Self {
+        let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some(&format!("{} Occlusion Query Set", label)),
+            ty: wgpu::QueryType::Occlusion,
+            count: 1,
+        });
+        let resolve_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(&format!("{} Occlusion Resolve Buffer", label)),
+            size: RESULT_SIZE,
+            usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(&format!("{} Occlusion Readback Buffer", label)),
+            size: RESULT_SIZE,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        Self {
+            label,
+            query_set,
+            resolve_buffer,
+            readback_buffer,
+            state: ReadbackState::Idle,
+            mapped: Arc::new(AtomicBool::new(false)),
+            visibility: Visibility::default(),
+        }
+    }
+
+    // RenderPassDescriptor の occlusion_query_set に渡す
+    pub fn query_set(&self) -> &wgpu::QuerySet {
+        &self.query_set
+    }
+
+    // false なら本来のパイプラインでは描画せず、クエリのためだけに色を書かずに描画する
+    pub fn should_draw(&self) -> bool {
+        self.visibility.should_draw()
+    }
+
+    // クエリを書き込んだフレームのレンダーパスの後に呼ぶ
+    // （前の結果をまだ読み出していなければ、このフレームの結果は捨てる）
+    pub fn resolve(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        if self.state != ReadbackState::Idle {
+            return;
+        }
+        encoder.resolve_query_set(&self.query_set, 0..1, &self.resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(
+            &self.resolve_buffer,
+            0,
+            &self.readback_buffer,
+            0,
+            RESULT_SIZE,
+        );
+        self.state = ReadbackState::Copied;
+    }
+
+    // コマンドを提出した後に呼び、コピーした結果のマップを始めるか、マップが終わった結果を読む
+    pub fn after_submit(&mut self) {
+        match self.state {
+            ReadbackState::Idle => {}
+            ReadbackState::Copied => {
+                let mapped = self.mapped.clone();
+                self.readback_buffer
+                    .slice(..)
+                    .map_async(wgpu::MapMode::Read, move |result| {
+                        if result.is_ok() {
+                            mapped.store(true, Ordering::Release);
+                        }
+                    });
+                self.state = ReadbackState::Mapping;
+            }
+            ReadbackState::Mapping => {
+                if !self.mapped.swap(false, Ordering::Acquire) {
+                    return;
+                }
+                let passed_samples: u64 = bytemuck::pod_read_unaligned(
+                    &self.readback_buffer.slice(..).get_mapped_range(),
+                );
+                self.readback_buffer.unmap();
+                self.state = ReadbackState::Idle;
+                if self.visibility.update(passed_samples) {
+                    if self.visibility.should_draw() {
+                        info!("{}: 見えている ({} サンプル)", self.label, passed_samples);
+                    } else {
+                        info!("{}: 隠れているので描画を省きます", self.label);
+                    }
+                }
+            }
+        }
+    }
+
+    // サーフェイスのサイズが変わったときに呼ぶ
+    // マップ中の読み出しを取り消し、見え方が変わるので次のフレームは必ず描画する
+    pub fn reset(&mut self) {
+        if self.state == ReadbackState::Mapping {
+            // マップが終わる前に unmap するとコールバックにはエラーが渡される
+            self.readback_buffer.unmap();
+            self.mapped.store(false, Ordering::Release);
+        }
+        self.state = ReadbackState::Idle;
+        self.visibility = Visibility::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skips_only_after_two_hidden_results_in_a_row() {
+        let mut visibility = Visibility::default();
+        // 1回だけ隠れても描画を続ける
+        assert!(!visibility.update(0));
+        assert!(visibility.should_draw());
+        assert!(!visibility.update(10));
+        assert!(!visibility.update(0));
+        // 2回続けて隠れたら省く
+        assert!(visibility.update(0));
+        assert!(!visibility.should_draw());
+        assert!(!visibility.update(0));
+        // 1サンプルでも見えたらすぐに描画に戻す
+        assert!(visibility.update(1));
+        assert!(visibility.should_draw());
+    }
+}
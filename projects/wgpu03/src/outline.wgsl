@@ -0,0 +1,35 @@
+struct Camera {
+    view_proj: mat4x4<f32>,
+    model: mat4x4<f32>,
+    view_position: vec4<f32>,
+};
+
+struct Outline {
+    color: vec4<f32>,
+    // 元の図形に対する拡大率（1.0 より大きいほど輪郭が太くなる）
+    scale: f32,
+};
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+@group(1) @binding(0) var<uniform> outline: Outline;
+
+struct VInput {
+    @location(0) position: vec3<f32>,
+};
+
+struct InstanceInput {
+    @location(5) offset: vec3<f32>,
+};
+
+// モデルの原点を中心に少し拡大した図形を描画する
+// ステンシルテストにより、元の図形からはみ出した縁の部分だけが残る
+@vertex
+fn vs_main(in: VInput, instance: InstanceInput) -> @builtin(position) vec4<f32> {
+    let world = camera.model * vec4<f32>(in.position * outline.scale + instance.offset, 1.0);
+    return camera.view_proj * world;
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return outline.color;
+}
@@ -0,0 +1,176 @@
+use glyphon::{
+    Attrs, Buffer, Cache, Color, Family, FontSystem, Metrics, Resolution, Shaping, SwashCache,
+    TextArea, TextAtlas, TextBounds, TextRenderer, Viewport,
+};
+use tracing::warn;
+
+use crate::crash;
+
+// 論理ピクセルでの文字の大きさと行の高さ（実際の大きさは拡大率を掛けて求める）
+const FONT_SIZE: f32 = 16.0;
+const LINE_HEIGHT: f32 = 20.0;
+// ウィンドウの左上から文字までの余白（論理ピクセル）
+const MARGIN: f32 = 8.0;
+
+// 高DPIのディスプレイでも同じ見た目の大きさになるよう、拡大率を掛けた物理ピクセルの大きさ
+fn scaled_metrics(scale_factor: f64) -> Metrics {
+    let scale = scale_factor as f32;
+    Metrics::new(FONT_SIZE * scale, LINE_HEIGHT * scale)
+}
+
+// シーンの上に文字を重ねて描画するオーバーレイ
+// 文字は物理ピクセル単位で配置するので、フォントの大きさに拡大率を反映させる
+pub struct TextOverlay {
+    font_system: FontSystem,
+    swash_cache: SwashCache,
+    viewport: Viewport,
+    atlas: TextAtlas,
+    renderer: TextRenderer,
+    buffer: Buffer,
+    width: u32,
+    height: u32,
+    scale_factor: f64,
+}
+
+impl TextOverlay {
+    // format は描画先のサーフェイスのビューのフォーマット
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+        scale_factor: f64,
+    ) -> Self {
+        let mut font_system = FontSystem::new();
+        let swash_cache = SwashCache::new();
+        let cache = Cache::new(device);
+        let viewport = Viewport::new(device, &cache);
+        let mut atlas = TextAtlas::new(device, queue, &cache, format);
+        let renderer =
+            TextRenderer::new(&mut atlas, device, wgpu::MultisampleState::default(), None);
+        let mut buffer = Buffer::new(&mut font_system, scaled_metrics(scale_factor));
+        buffer.set_size(&mut font_system, Some(width as f32), Some(height as f32));
+
+        Self {
+            font_system,
+            swash_cache,
+            viewport,
+            atlas,
+            renderer,
+            buffer,
+            width,
+            height,
+            scale_factor,
+        }
+    }
+
+    // ウィンドウの大きさに合わせないと文字が引き伸ばされてしまう
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.width = width;
+        self.height = height;
+        self.buffer.set_size(
+            &mut self.font_system,
+            Some(width as f32),
+            Some(height as f32),
+        );
+    }
+
+    pub fn set_scale_factor(&mut self, scale_factor: f64) {
+        self.scale_factor = scale_factor;
+        self.buffer
+            .set_metrics(&mut self.font_system, scaled_metrics(scale_factor));
+    }
+
+    pub fn set_text(&mut self, text: &str) {
+        self.buffer.set_text(
+            &mut self.font_system,
+            text,
+            Attrs::new().family(Family::Monospace),
+            Shaping::Advanced,
+        );
+        self.buffer.shape_until_scroll(&mut self.font_system, false);
+    }
+
+    // 文字の形をアトラスに書き込み、描画に使う頂点を準備する（draw より前に呼ぶこと）
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        self.viewport.update(
+            queue,
+            Resolution {
+                width: self.width,
+                height: self.height,
+            },
+        );
+        let margin = MARGIN * self.scale_factor as f32;
+        let area = TextArea {
+            buffer: &self.buffer,
+            left: margin,
+            top: margin,
+            scale: 1.0,
+            bounds: TextBounds {
+                left: 0,
+                top: 0,
+                right: self.width as i32,
+                bottom: self.height as i32,
+            },
+            default_color: Color::rgb(255, 255, 255),
+            custom_glyphs: &[],
+        };
+        if let Err(e) = self.renderer.prepare(
+            device,
+            queue,
+            &mut self.font_system,
+            &mut self.atlas,
+            &self.viewport,
+            [area],
+            &mut self.swash_cache,
+        ) {
+            warn!("文字の準備に失敗しました: {}", e);
+        }
+    }
+
+    // 描画済みの内容を残したまま（LoadOp::Load）文字を重ねる
+    pub fn draw(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        timestamp_writes: Option<wgpu::RenderPassTimestampWrites<'_>>,
+    ) {
+        {
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: crash::pass("Text Overlay Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes,
+                occlusion_query_set: None,
+            });
+            if let Err(e) = self
+                .renderer
+                .render(&self.atlas, &self.viewport, &mut rpass)
+            {
+                warn!("文字の描画に失敗しました: {}", e);
+            }
+        }
+        // 使われなくなった文字をアトラスから取り除く
+        self.atlas.trim();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn font_size_follows_scale_factor() {
+        let metrics = scaled_metrics(2.0);
+        assert_eq!(metrics.font_size, FONT_SIZE * 2.0);
+        assert_eq!(metrics.line_height, LINE_HEIGHT * 2.0);
+    }
+}
@@ -0,0 +1,202 @@
+use wgpu::util::DeviceExt;
+
+use crate::crash;
+use crate::pipeline_cache::PipelineCache;
+
+// particles.wgsl の @workgroup_size と一致させる
+const WORKGROUP_SIZE: u32 = 64;
+// 最初の粒子がすべて同時に生まれないよう、この時間の範囲に散らして生まれさせる
+const SPAWN_SPREAD: f32 = 3.0;
+// フレームが大きく遅れたときに粒子が飛び散りすぎないように経過時間を制限する
+const MAX_DT: f32 = 0.1;
+
+// particles.wgsl の Particle 構造体に対応する粒子の状態
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct Particle {
+    position: [f32; 3],
+    age: f32,
+    velocity: [f32; 3],
+    lifetime: f32,
+}
+
+// particles.wgsl の Params 構造体に対応するユニフォームデータ
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct ParticleParams {
+    emitter: [f32; 3],
+    dt: f32,
+    time: f32,
+    count: u32,
+    _padding: [u32; 2],
+}
+
+// まだ生まれていない粒子（年齢が負の間は透明で、0 になった時点で放出される）
+fn initial_particles(count: u32) -> Vec<Particle> {
+    (0..count)
+        .map(|i| Particle {
+            position: [0.0; 3],
+            age: -(i as f32 / count as f32) * SPAWN_SPREAD,
+            velocity: [0.0; 3],
+            lifetime: 0.0,
+        })
+        .collect()
+}
+
+// コンピュートシェーダーで粒子を動かし、その結果をインスタンスバッファに書き込むパーティクルシステム
+// 粒子の状態はその場で更新する（同じエンコーダーのコンピュートパスの後にレンダーパスを記録するので、
+// 描画が更新途中のバッファを読むことはない）
+pub struct ParticleSystem {
+    pipeline: wgpu::ComputePipeline,
+    bind_group: wgpu::BindGroup,
+    params_buffer: wgpu::Buffer,
+    count: u32,
+    // 描画ではインスタンスバッファ（平行移動量と色）として読み込む（STORAGE | VERTEX）
+    pub instance_buffer: wgpu::Buffer,
+}
+
+impl ParticleSystem {
+    // instance_size は描画に使うインスタンス構造体の大きさ（particles.wgsl の FLOATS_PER_INSTANCE 個の f32）
+    pub fn new(
+        device: &wgpu::Device,
+        pipeline_cache: &PipelineCache,
+        shader: &wgpu::ShaderModule,
+        count: u32,
+        instance_size: usize,
+    ) -> Self {
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Particle Params Buffer"),
+            size: std::mem::size_of::<ParticleParams>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let particle_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Particle Buffer"),
+            contents: bytemuck::cast_slice(&initial_particles(count)),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+        let instance_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Particle Instance Buffer"),
+            size: (count as usize * instance_size) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::VERTEX,
+            mapped_at_creation: false,
+        });
+
+        let storage_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Particle Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                storage_entry(1),
+                storage_entry(2),
+            ],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Particle Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: params_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: particle_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: instance_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Particle Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = pipeline_cache.create(|cache| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("Particle Pipeline"),
+                layout: Some(&layout),
+                module: shader,
+                entry_point: Some("cs_main"),
+                compilation_options: Default::default(),
+                cache,
+            })
+        });
+
+        Self {
+            pipeline,
+            bind_group,
+            params_buffer,
+            count,
+            instance_buffer,
+        }
+    }
+
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    // 前のフレームからの経過時間と、粒子を放出する位置（ワールド座標）を書き込む
+    pub fn update(&self, queue: &wgpu::Queue, dt: f32, time: f32, emitter: glam::Vec3) {
+        let params = ParticleParams {
+            emitter: emitter.to_array(),
+            dt: dt.min(MAX_DT),
+            time,
+            count: self.count,
+            _padding: [0; 2],
+        };
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::cast_slice(&[params]));
+    }
+
+    // 同じエンコーダーで、インスタンスバッファを読み込むレンダーパスより前に記録すること
+    pub fn dispatch(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        timestamp_writes: Option<wgpu::ComputePassTimestampWrites<'_>>,
+    ) {
+        let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: crash::pass("Particle Compute Pass"),
+            timestamp_writes,
+        });
+        cpass.set_pipeline(&self.pipeline);
+        cpass.set_bind_group(0, &self.bind_group, &[]);
+        cpass.dispatch_workgroups(self.count.div_ceil(WORKGROUP_SIZE), 1, 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn particle_matches_wgsl_layout_and_spawns_staggered() {
+        // vec3 + f32 が 16 バイトずつに詰まる
+        assert_eq!(std::mem::size_of::<Particle>(), 32);
+        assert_eq!(std::mem::size_of::<ParticleParams>(), 32);
+        let particles = initial_particles(4);
+        assert_eq!(particles[0].age, 0.0);
+        assert!(particles.windows(2).all(|pair| pair[1].age < pair[0].age));
+        assert!(particles.iter().all(|p| p.age > -SPAWN_SPREAD));
+    }
+}
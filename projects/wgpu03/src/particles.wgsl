@@ -0,0 +1,74 @@
+struct Particle {
+    position: vec3<f32>,
+    // 生まれてからの経過時間（負の間はまだ生まれていない）
+    age: f32,
+    velocity: vec3<f32>,
+    lifetime: f32,
+};
+
+struct Params {
+    emitter: vec3<f32>,
+    dt: f32,
+    time: f32,
+    count: u32,
+};
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var<storage, read_write> particles: array<Particle>;
+// Rust 側の Instance（平行移動量と色）を詰めた配列（vec3 の境界揃えを避けるため f32 で書き込む）
+@group(0) @binding(2) var<storage, read_write> instances: array<f32>;
+
+const FLOATS_PER_INSTANCE: u32 = 7u;
+const GRAVITY: vec3<f32> = vec3<f32>(0.0, -1.5, 0.0);
+const MIN_LIFETIME: f32 = 1.0;
+const MAX_LIFETIME: f32 = 3.0;
+const SPEED: f32 = 0.8;
+
+// 整数のハッシュから 0.0 〜 1.0 の乱数を作る（PCG）
+fn pcg(seed: u32) -> u32 {
+    let state = seed * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+fn random(seed: u32) -> f32 {
+    return f32(pcg(seed)) / 4294967295.0;
+}
+
+@compute @workgroup_size(64)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let i = id.x;
+    if i >= params.count {
+        return;
+    }
+    var p = particles[i];
+    p.age += params.dt;
+    if p.age >= p.lifetime {
+        // 寿命が尽きた粒子はカーソルの位置から新しい向きに放出し直す
+        let seed = pcg(i ^ bitcast<u32>(params.time));
+        let theta = random(seed) * 6.28318530718;
+        let z = random(seed + 1u) * 2.0 - 1.0;
+        let r = sqrt(1.0 - z * z);
+        p.position = params.emitter;
+        p.velocity = vec3<f32>(r * cos(theta), abs(z) + 0.5, r * sin(theta)) * SPEED;
+        p.age -= p.lifetime;
+        p.lifetime = mix(MIN_LIFETIME, MAX_LIFETIME, random(seed + 2u));
+    } else if p.age >= 0.0 {
+        p.velocity += GRAVITY * params.dt;
+        p.position += p.velocity * params.dt;
+    }
+    particles[i] = p;
+
+    // 寿命に合わせて色を変えながら薄くしていく（まだ生まれていない粒子は透明）
+    let t = clamp(p.age / p.lifetime, 0.0, 1.0);
+    let alpha = select(0.0, 1.0 - t, p.age >= 0.0);
+    let color = mix(vec3<f32>(1.0, 0.9, 0.4), vec3<f32>(1.0, 0.2, 0.1), t);
+    let base = i * FLOATS_PER_INSTANCE;
+    instances[base + 0u] = p.position.x;
+    instances[base + 1u] = p.position.y;
+    instances[base + 2u] = p.position.z;
+    instances[base + 3u] = color.r;
+    instances[base + 4u] = color.g;
+    instances[base + 5u] = color.b;
+    instances[base + 6u] = alpha;
+}
@@ -0,0 +1,158 @@
+#include "include/camera.wgsl"
+
+struct Uniforms {
+    tint: vec4<f32>,
+    time: f32,
+    // 0 以外なら法線マップで法線を揺らす
+    normal_mapping: u32,
+};
+
+#include "include/lighting.wgsl"
+
+struct PbrMaterial {
+    albedo: vec4<f32>,
+    metallic: f32,
+    roughness: f32,
+};
+
+@group(0) @binding(1) var<uniform> uniforms: Uniforms;
+
+@group(1) @binding(0) var t_albedo: texture_2d<f32>;
+@group(1) @binding(1) var s_material: sampler;
+@group(1) @binding(2) var<uniform> material: PbrMaterial;
+@group(1) @binding(3) var t_normal: texture_2d<f32>;
+@group(1) @binding(4) var t_metallic: texture_2d<f32>;
+@group(1) @binding(5) var t_roughness: texture_2d<f32>;
+@group(1) @binding(6) var t_occlusion: texture_2d<f32>;
+
+// スキンメッシュの関節ごとの行列（光源の配列と同じく、ユニフォーム配列に書き換えることがある）
+@group(3) @binding(0) var<storage, read> joints: array<mat4x4<f32>>;
+
+const PI: f32 = 3.14159265;
+// ラフネスが 0 だと GGX の分布関数が 0 / 0 になるので下限を設ける
+const MIN_ROUGHNESS: f32 = 0.045;
+// 誘電体の垂直入射での反射率
+const DIELECTRIC_F0: vec3<f32> = vec3<f32>(0.04, 0.04, 0.04);
+const AMBIENT_STRENGTH: f32 = 0.03;
+// 拡散反射が Blinn-Phong と同程度の明るさになるよう、光の強さを π 倍する
+const LIGHT_INTENSITY: f32 = PI;
+
+struct VInput {
+    @location(0) position: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) normal: vec3<f32>,
+    @location(3) tangent: vec4<f32>,
+};
+
+struct VOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) world_normal: vec3<f32>,
+    @location(2) world_tangent: vec4<f32>,
+    @location(3) world_position: vec3<f32>,
+};
+
+@vertex
+fn vs_main(in: VInput) -> VOutput {
+    return transform_vertex(in);
+}
+
+// モデル座標の頂点をワールド座標とクリップ座標に変換する
+fn transform_vertex(in: VInput) -> VOutput {
+    var out: VOutput;
+    let world = camera.model * vec4<f32>(in.position, 1.0);
+    out.position = camera.view_proj * world;
+    out.world_position = world.xyz;
+    out.tex_coords = in.tex_coords;
+    // モデル行列は回転のみなので、法線と接線もそのまま変換できる
+    out.world_normal = (camera.normal_matrix * vec4<f32>(in.normal, 0.0)).xyz;
+    out.world_tangent = vec4<f32>((camera.model * vec4<f32>(in.tangent.xyz, 0.0)).xyz, in.tangent.w);
+    return out;
+}
+
+struct SkinInput {
+    @location(4) joints: vec4<u32>,
+    @location(5) weights: vec4<f32>,
+};
+
+// スキンメッシュ用（関節の行列を重みで混ぜて、モデル座標の頂点を動かしてから vs_main と同じ変換をする）
+@vertex
+fn vs_skinned(in: VInput, skin: SkinInput) -> VOutput {
+    let skin_matrix = joints[skin.joints.x] * skin.weights.x
+        + joints[skin.joints.y] * skin.weights.y
+        + joints[skin.joints.z] * skin.weights.z
+        + joints[skin.joints.w] * skin.weights.w;
+    var skinned = in;
+    skinned.position = (skin_matrix * vec4<f32>(in.position, 1.0)).xyz;
+    // 関節の行列は回転と移動なので、法線と接線もそのまま変換できる
+    skinned.normal = normalize((skin_matrix * vec4<f32>(in.normal, 0.0)).xyz);
+    skinned.tangent = vec4<f32>(normalize((skin_matrix * vec4<f32>(in.tangent.xyz, 0.0)).xyz), in.tangent.w);
+    return transform_vertex(skinned);
+}
+
+// GGX（Trowbridge-Reitz）の法線分布関数
+fn distribution_ggx(n_dot_h: f32, roughness: f32) -> f32 {
+    let a = roughness * roughness;
+    let a2 = a * a;
+    let d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    return a2 / max(PI * d * d, 1e-7);
+}
+
+// Schlick-GGX による幾何減衰項（点光源用の k）
+fn geometry_schlick_ggx(n_dot_x: f32, roughness: f32) -> f32 {
+    let r = roughness + 1.0;
+    let k = r * r / 8.0;
+    return n_dot_x / (n_dot_x * (1.0 - k) + k);
+}
+
+fn fresnel_schlick(cos_theta: f32, f0: vec3<f32>) -> vec3<f32> {
+    return f0 + (1.0 - f0) * pow(clamp(1.0 - cos_theta, 0.0, 1.0), 5.0);
+}
+
+@fragment
+fn fs_main(in: VOutput) -> @location(0) vec4<f32> {
+    let albedo_sample = textureSample(t_albedo, s_material, in.tex_coords) * material.albedo;
+    let normal_sample = textureSample(t_normal, s_material, in.tex_coords).xyz * 2.0 - 1.0;
+    let metallic = textureSample(t_metallic, s_material, in.tex_coords).b * material.metallic;
+    let roughness = max(
+        textureSample(t_roughness, s_material, in.tex_coords).g * material.roughness,
+        MIN_ROUGHNESS,
+    );
+    let occlusion = textureSample(t_occlusion, s_material, in.tex_coords).r;
+    let albedo = albedo_sample.rgb;
+
+    var n = normalize(in.world_normal);
+    if uniforms.normal_mapping != 0u {
+        let t = normalize(in.world_tangent.xyz - n * dot(n, in.world_tangent.xyz));
+        let b = cross(n, t) * in.world_tangent.w;
+        n = normalize(mat3x3<f32>(t, b, n) * normal_sample);
+    }
+    let v = normalize(camera.view_position.xyz - in.world_position);
+    let n_dot_v = max(dot(n, v), 1e-4);
+    let f0 = mix(DIELECTRIC_F0, albedo, metallic);
+    // 影はシャドウマップを持つ先頭の光源についてのみ求める
+    let shadow = shadow_factor(in.world_position);
+
+    var direct = vec3<f32>(0.0);
+    for (var i = 0u; i < light_info.count; i += 1u) {
+        let light = lights[i];
+        let l = normalize(light.position - in.world_position);
+        let h = normalize(v + l);
+        let n_dot_l = max(dot(n, l), 0.0);
+        let n_dot_h = max(dot(n, h), 0.0);
+
+        // Cook-Torrance の鏡面反射 BRDF
+        let f = fresnel_schlick(max(dot(h, v), 0.0), f0);
+        let d = distribution_ggx(n_dot_h, roughness);
+        let g = geometry_schlick_ggx(n_dot_v, roughness) * geometry_schlick_ggx(n_dot_l, roughness);
+        let specular = d * g * f / (4.0 * n_dot_v * max(n_dot_l, 1e-4));
+
+        // 金属は拡散反射しない
+        let k_d = (vec3<f32>(1.0) - f) * (1.0 - metallic);
+        let radiance = light.color * LIGHT_INTENSITY;
+        direct += (k_d * albedo / PI + specular) * radiance * n_dot_l * select(1.0, shadow, i == 0u);
+    }
+    let ambient = AMBIENT_STRENGTH * albedo * occlusion;
+
+    return vec4<f32>(ambient + direct, albedo_sample.a) * uniforms.tint;
+}
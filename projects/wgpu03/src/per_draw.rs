@@ -0,0 +1,170 @@
+use std::borrow::Cow;
+
+use crate::uniform_arena::UniformArena;
+
+// 描画呼び出しごとに切り替えるデータ（シェーダーの DrawData 構造体に対応）
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct DrawData {
+    pub transform: [[f32; 4]; 4],
+    pub color: [f32; 4],
+}
+
+impl DrawData {
+    pub fn new(transform: glam::Mat4, color: [f32; 4]) -> Self {
+        Self {
+            transform: transform.to_cols_array_2d(),
+            color,
+        }
+    }
+}
+
+const DRAW_DATA_SIZE: u32 = std::mem::size_of::<DrawData>() as u32;
+// 動的オフセットのユニフォームバッファで使うバインドグループの番号（グループ0はカメラ）
+pub const DYNAMIC_UNIFORM_GROUP: u32 = 1;
+
+// 描画ごとのデータをシェーダーに渡す方法
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PerDrawStorage {
+    // コマンドに直接埋め込むプッシュ定数（バッファへの書き込みが要らない）
+    PushConstants,
+    // プッシュ定数を使えない環境向けに、1つのバッファの中の位置を描画ごとに動的オフセットで指定する
+    DynamicUniform,
+}
+
+impl PerDrawStorage {
+    // プッシュ定数はネイティブのバックエンドの拡張機能なので、アダプタが対応している場合だけ使う
+    pub fn for_adapter(adapter: &wgpu::Adapter) -> Self {
+        let supported = adapter.features().contains(wgpu::Features::PUSH_CONSTANTS)
+            && adapter.limits().max_push_constant_size >= DRAW_DATA_SIZE;
+        if supported {
+            Self::PushConstants
+        } else {
+            Self::DynamicUniform
+        }
+    }
+
+    // DeviceDescriptor で要求する機能
+    pub fn required_features(self) -> wgpu::Features {
+        match self {
+            Self::PushConstants => wgpu::Features::PUSH_CONSTANTS,
+            Self::DynamicUniform => wgpu::Features::empty(),
+        }
+    }
+
+    // DeviceDescriptor で要求する制限（既定値から変える分だけ書き換える）
+    pub fn required_limits(self, limits: wgpu::Limits) -> wgpu::Limits {
+        match self {
+            Self::PushConstants => wgpu::Limits {
+                max_push_constant_size: DRAW_DATA_SIZE,
+                ..limits
+            },
+            Self::DynamicUniform => limits,
+        }
+    }
+
+    // シェーダーはユニフォームバッファの宣言で書いておき、プッシュ定数を使う場合は宣言を書き換える
+    pub fn shader_source(self, source: &'static str) -> Cow<'static, str> {
+        const UNIFORM_DECL: &str = "@group(1) @binding(0) var<uniform> draw: DrawData;";
+        match self {
+            Self::PushConstants => {
+                Cow::Owned(source.replace(UNIFORM_DECL, "var<push_constant> draw: DrawData;"))
+            }
+            Self::DynamicUniform => Cow::Borrowed(source),
+        }
+    }
+}
+
+// 描画ごとのデータを、どちらの方法でも同じ呼び出し方（write してから描画ごとに bind）で渡す
+pub struct PerDrawBuffer {
+    storage: PerDrawStorage,
+    // プッシュ定数の場合は描画時にコマンドへ埋め込むので CPU 側に持っておく
+    draws: Vec<DrawData>,
+    // 動的オフセットのユニフォームバッファの場合のみ使う
+    uniform: Option<UniformArena<DrawData>>,
+}
+
+impl PerDrawBuffer {
+    // capacity は1フレームで描画する回数の上限
+    pub fn new(device: &wgpu::Device, storage: PerDrawStorage, capacity: usize) -> Self {
+        let uniform = match storage {
+            PerDrawStorage::PushConstants => None,
+            PerDrawStorage::DynamicUniform => {
+                Some(UniformArena::new(device, "Per Draw Buffer", capacity))
+            }
+        };
+        Self {
+            storage,
+            draws: Vec::with_capacity(capacity),
+            uniform,
+        }
+    }
+
+    // プッシュ定数の範囲か、動的オフセットのバインドグループを含むパイプラインレイアウト
+    pub fn pipeline_layout(
+        &self,
+        device: &wgpu::Device,
+        camera_layout: &wgpu::BindGroupLayout,
+    ) -> wgpu::PipelineLayout {
+        let push_constant_ranges = match self.storage {
+            PerDrawStorage::PushConstants => vec![wgpu::PushConstantRange {
+                stages: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                range: 0..DRAW_DATA_SIZE,
+            }],
+            PerDrawStorage::DynamicUniform => vec![],
+        };
+        let mut bind_group_layouts = vec![camera_layout];
+        bind_group_layouts.extend(self.uniform.as_ref().map(UniformArena::layout));
+        device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Per Draw Pipeline Layout"),
+            bind_group_layouts: &bind_group_layouts,
+            push_constant_ranges: &push_constant_ranges,
+        })
+    }
+
+    // このフレームで描画する分のデータを渡す（bind の index は draws の添字）
+    pub fn write(&mut self, queue: &wgpu::Queue, draws: &[DrawData]) {
+        self.draws.clear();
+        self.draws.extend_from_slice(draws);
+        if let Some(arena) = &mut self.uniform {
+            arena.clear();
+            for draw in draws {
+                arena.push(draw);
+            }
+            arena.upload(queue);
+        }
+    }
+
+    // index 番目のデータを次の描画呼び出しで使えるようにする
+    pub fn bind(&self, rpass: &mut wgpu::RenderPass, index: usize) {
+        match &self.uniform {
+            Some(arena) => arena.bind(rpass, DYNAMIC_UNIFORM_GROUP, arena.offset(index)),
+            None => rpass.set_push_constants(
+                wgpu::ShaderStages::VERTEX_FRAGMENT,
+                0,
+                bytemuck::bytes_of(&self.draws[index]),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dynamic_offsets_respect_alignment() {
+        use crate::uniform_arena::align_to;
+        assert_eq!(DRAW_DATA_SIZE, 80);
+        assert_eq!(align_to(DRAW_DATA_SIZE, 256), 256);
+        assert_eq!(align_to(DRAW_DATA_SIZE, 64), 128);
+        assert_eq!(align_to(DRAW_DATA_SIZE, 16), 80);
+    }
+
+    #[test]
+    fn push_constant_shader_replaces_uniform_binding() {
+        let source = PerDrawStorage::PushConstants.shader_source(include_str!("per_draw.wgsl"));
+        assert!(source.contains("var<push_constant> draw: DrawData;"));
+        assert!(!source.contains("var<uniform> draw"));
+    }
+}
@@ -0,0 +1,38 @@
+struct Camera {
+    view_proj: mat4x4<f32>,
+    model: mat4x4<f32>,
+    view_position: vec4<f32>,
+};
+
+// 描画呼び出しごとに切り替える変換行列と色
+struct DrawData {
+    transform: mat4x4<f32>,
+    color: vec4<f32>,
+};
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+// プッシュ定数を使える環境では、読み込み時に var<push_constant> の宣言に書き換える
+@group(1) @binding(0) var<uniform> draw: DrawData;
+
+struct VInput {
+    @location(0) position: vec3<f32>,
+    @location(1) color: vec3<f32>,
+};
+
+struct VOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+};
+
+@vertex
+fn vs_main(in: VInput) -> VOutput {
+    var out: VOutput;
+    out.position = camera.view_proj * draw.transform * vec4<f32>(in.position, 1.0);
+    out.color = vec4<f32>(in.color, 1.0) * draw.color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
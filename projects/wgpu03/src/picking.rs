@@ -0,0 +1,248 @@
+use anyhow::Result;
+
+use crate::capture;
+use crate::crash;
+use crate::pipeline_cache::PipelineCache;
+use crate::texture::Texture;
+use crate::uniform_arena::UniformArena;
+
+// 物体の番号を書き込む描画先のフォーマット
+const PICK_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Uint;
+// 何も描画されていない画素の値
+const NO_OBJECT: u32 = 0;
+// 1回のピックで描画する物体の描画呼び出しの数の上限
+const MAX_PICK_DRAWS: usize = 8;
+// 1x1 のコピーでも、コピー先の1行は COPY_BYTES_PER_ROW_ALIGNMENT バイトの倍数にする
+const READBACK_SIZE: u32 = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+
+// picking.wgsl の Pick 構造体に対応するユニフォームデータ
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct PickUniform {
+    base: u32,
+    _padding: [u32; 3],
+}
+
+// カーソルの位置（物理ピクセル）を、サーフェイスの中に収まる画素の位置にする
+// winit の CursorMoved は物理ピクセルで届くので、論理ピクセルの位置は拡大率を掛けてから渡す
+pub fn pick_pixel(cursor: (f32, f32), surface_width: u32, surface_height: u32) -> (u32, u32) {
+    let clamp = |v: f32, size: u32| (v.max(0.0) as u32).min(size.max(1) - 1);
+    (
+        clamp(cursor.0, surface_width),
+        clamp(cursor.1, surface_height),
+    )
+}
+
+// 読み出した画素の値を物体の番号にする（何もない画素の場合は None）
+fn object_from_value(value: u32) -> Option<u32> {
+    (value != NO_OBJECT).then(|| value - 1)
+}
+
+// 物体の番号を R32Uint のテクスチャに描画し、クリックした画素の値を読み出して物体を調べる
+pub struct Picking {
+    target: wgpu::Texture,
+    view: wgpu::TextureView,
+    depth_texture: Texture,
+    pipeline: wgpu::RenderPipeline,
+    draws: UniformArena<PickUniform>,
+    readback: wgpu::Buffer,
+}
+
+impl Picking {
+    // camera_layout は main.rs のカメラのバインドグループのレイアウト
+    // vertex_buffers は頂点バッファとインスタンスバッファのレイアウト
+    pub fn new(
+        device: &wgpu::Device,
+        pipeline_cache: &PipelineCache,
+        config: &wgpu::SurfaceConfiguration,
+        shader: &wgpu::ShaderModule,
+        camera_layout: &wgpu::BindGroupLayout,
+        vertex_buffers: &[wgpu::VertexBufferLayout],
+    ) -> Self {
+        let (target, view, depth_texture) = create_targets(device, config);
+        let draws = UniformArena::new(device, "Pick Uniform Arena", MAX_PICK_DRAWS);
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Pick Pipeline Layout"),
+            bind_group_layouts: &[camera_layout, draws.layout()],
+            push_constant_ranges: &[],
+        });
+        let pipeline = pipeline_cache.create(|cache| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: crash::created_pipeline("Pick Pipeline"),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: shader,
+                    entry_point: Some("vs_main"),
+                    buffers: vertex_buffers,
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: shader,
+                    entry_point: Some("fs_main"),
+                    // 整数のフォーマットはブレンドできない
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: PICK_FORMAT,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: Default::default(),
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                // 手前の物体の番号が残るよう、深度テストを行う
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: Texture::DEPTH_FORMAT,
+                    depth_write_enabled: true,
+                    depth_compare: wgpu::CompareFunction::Less,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache,
+            })
+        });
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Pick Readback Buffer"),
+            size: READBACK_SIZE as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        Self {
+            target,
+            view,
+            depth_texture,
+            pipeline,
+            draws,
+            readback,
+        }
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
+        (self.target, self.view, self.depth_texture) = create_targets(device, config);
+    }
+
+    // draw で物体の番号を描画し、pixel の画素に描画された物体の番号を返す
+    // （何も描画されていない画素の場合は None）
+    pub fn pick(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        pixel: (u32, u32),
+        draw: impl FnOnce(&mut PickPass),
+    ) -> Result<Option<u32>> {
+        self.draws.clear();
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Pick"),
+        });
+        {
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: crash::pass("Pick Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &self.view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.depth_texture.view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Discard,
+                    }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            rpass.set_pipeline(&self.pipeline);
+            draw(&mut PickPass {
+                rpass,
+                draws: &mut self.draws,
+            });
+        }
+        // 枠への書き込みは提出より前なので、パスを記録し終えた後でよい
+        self.draws.upload(queue);
+
+        let (x, y) = pixel;
+        encoder.copy_texture_to_buffer(
+            wgpu::TexelCopyTextureInfo {
+                texture: &self.target,
+                mip_level: 0,
+                origin: wgpu::Origin3d { x, y, z: 0 },
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::TexelCopyBufferInfo {
+                buffer: &self.readback,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(READBACK_SIZE),
+                    rows_per_image: None,
+                },
+            },
+            wgpu::Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+        );
+        queue.submit(Some(encoder.finish()));
+
+        capture::map_blocking(device, &self.readback)?;
+        let value: u32 = bytemuck::pod_read_unaligned(
+            &self.readback.slice(..).get_mapped_range()[..std::mem::size_of::<u32>()],
+        );
+        self.readback.unmap();
+        Ok(object_from_value(value))
+    }
+}
+
+// 物体の番号を描画するパス
+pub struct PickPass<'a> {
+    pub rpass: wgpu::RenderPass<'a>,
+    draws: &'a mut UniformArena<PickUniform>,
+}
+
+impl PickPass<'_> {
+    // 次の描画呼び出しの最初のインスタンスの番号を指定する
+    pub fn set_object(&mut self, base: u32) {
+        let offset = self.draws.push(&PickUniform {
+            base,
+            _padding: [0; 3],
+        });
+        self.draws.bind(&mut self.rpass, 1, offset);
+    }
+}
+
+fn create_targets(
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+) -> (wgpu::Texture, wgpu::TextureView, Texture) {
+    let target = capture::create_target(
+        device,
+        "Pick Texture",
+        config.width.max(1),
+        config.height.max(1),
+        PICK_FORMAT,
+    );
+    let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+    let depth_texture = Texture::create_depth_texture(device, config, 1, "Pick Depth Texture");
+    (target, view, depth_texture)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursor_is_clamped_and_empty_pixels_pick_nothing() {
+        assert_eq!(pick_pixel((10.7, 20.2), 800, 600), (10, 20));
+        // ウィンドウの外に出たカーソルは端の画素にする
+        assert_eq!(pick_pixel((-5.0, 900.0), 800, 600), (0, 599));
+        assert_eq!(pick_pixel((1000.0, 0.0), 800, 600), (799, 0));
+        assert_eq!(object_from_value(NO_OBJECT), None);
+        assert_eq!(object_from_value(1), Some(0));
+        assert_eq!(object_from_value(42), Some(41));
+    }
+}
@@ -0,0 +1,40 @@
+struct Camera {
+    view_proj: mat4x4<f32>,
+    model: mat4x4<f32>,
+    view_position: vec4<f32>,
+};
+
+struct Pick {
+    // この描画呼び出しの最初のインスタンスの番号（インスタンス番号を足したものが物体の番号）
+    base: u32,
+};
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+@group(1) @binding(0) var<uniform> pick: Pick;
+
+struct VInput {
+    @location(0) position: vec3<f32>,
+};
+
+struct InstanceInput {
+    @location(5) offset: vec3<f32>,
+};
+
+struct VOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) @interpolate(flat) id: u32,
+};
+
+@vertex
+fn vs_main(in: VInput, instance: InstanceInput, @builtin(instance_index) index: u32) -> VOutput {
+    var out: VOutput;
+    out.position = camera.view_proj * camera.model * vec4<f32>(in.position + instance.offset, 1.0);
+    // 0 は何も描画されていない画素を表すので、物体の番号に 1 を足して書き込む
+    out.id = pick.base + index + 1u;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VOutput) -> @location(0) u32 {
+    return in.id;
+}
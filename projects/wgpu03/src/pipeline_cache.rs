@@ -0,0 +1,214 @@
+use std::cell::{Cell, RefCell};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use tracing::warn;
+
+// 保存するファイルの先頭に付ける目印（続く 8 バイトが中身のハッシュ）
+const MAGIC: &[u8; 4] = b"WPC1";
+const HEADER_SIZE: usize = MAGIC.len() + 8;
+
+// 書き込みの途中で壊れたファイルを見分けるための FNV-1a ハッシュ
+// wgpu はキャッシュのヘッダーしか検証しないので、中身はこちらで確かめる
+fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+fn encode(data: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(HEADER_SIZE + data.len());
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&fnv1a(data).to_le_bytes());
+    bytes.extend_from_slice(data);
+    bytes
+}
+
+// 目印かハッシュが合わなければ None
+fn decode(bytes: &[u8]) -> Option<&[u8]> {
+    let (header, data) = bytes.split_at_checked(HEADER_SIZE)?;
+    let (magic, hash) = header.split_at(MAGIC.len());
+    let hash = u64::from_le_bytes(hash.try_into().ok()?);
+    (magic == MAGIC && hash == fnv1a(data)).then_some(data)
+}
+
+// プラットフォームごとのキャッシュ用ディレクトリ
+fn cache_dir() -> Option<PathBuf> {
+    let var = |name: &str| std::env::var_os(name).filter(|value| !value.is_empty());
+    let base = if cfg!(target_os = "windows") {
+        PathBuf::from(var("LOCALAPPDATA")?)
+    } else if cfg!(target_os = "macos") {
+        PathBuf::from(var("HOME")?).join("Library/Caches")
+    } else {
+        var("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| Some(PathBuf::from(var("HOME")?).join(".cache")))?
+    };
+    Some(base.join(env!("CARGO_PKG_NAME")))
+}
+
+// 起動時にファイルから読み込み、終了時に書き戻すパイプラインキャッシュ
+// アプリケーションが管理するキャッシュに対応したバックエンド（Vulkan など）でのみ有効になる
+// ファイル名はアダプタとドライバのバージョンから決まるので、ドライバが変われば別のファイルになる
+pub struct PipelineCache {
+    cache: Option<wgpu::PipelineCache>,
+    path: Option<PathBuf>,
+    // 起動時に読み込んだデータの大きさ（読み込まなかった場合は None）
+    loaded: Option<usize>,
+    // パイプラインの作成にかかった時間の合計
+    elapsed: Cell<Duration>,
+    // 起動中（finish_startup を呼ぶまで）は作成ごとにエラースコープで検証し、最初のエラーを持っておく
+    // 起動後はシェーダーの読み込み直しなどが自分でエラースコープを使うので、ここでは受け取らない
+    device: wgpu::Device,
+    startup: Cell<bool>,
+    startup_error: RefCell<Option<String>>,
+}
+
+impl PipelineCache {
+    // DeviceDescriptor で要求する機能
+    pub fn required_features(adapter: &wgpu::Adapter) -> wgpu::Features {
+        adapter.features() & wgpu::Features::PIPELINE_CACHE
+    }
+
+    pub fn load(device: &wgpu::Device, adapter: &wgpu::AdapterInfo) -> Self {
+        let path = device
+            .features()
+            .contains(wgpu::Features::PIPELINE_CACHE)
+            .then(|| Some(cache_dir()?.join(wgpu::util::pipeline_cache_key(adapter)?)))
+            .flatten();
+        let Some(path) = path else {
+            return Self {
+                cache: None,
+                path: None,
+                loaded: None,
+                elapsed: Cell::default(),
+                device: device.clone(),
+                startup: Cell::new(true),
+                startup_error: RefCell::default(),
+            };
+        };
+
+        let data = match std::fs::read(&path) {
+            Ok(bytes) => match decode(&bytes) {
+                Some(data) => Some(data.to_vec()),
+                None => {
+                    warn!(
+                        "壊れたパイプラインキャッシュを破棄しました: {}",
+                        path.display()
+                    );
+                    let _ = std::fs::remove_file(&path);
+                    None
+                }
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => {
+                warn!(
+                    "パイプラインキャッシュを読み込めませんでした: {}: {}",
+                    path.display(),
+                    e
+                );
+                None
+            }
+        };
+        // data は save で書き出したもので、ファイル名の pipeline_cache_key により同じアダプタのものに限られる
+        // wgpu やドライバのバージョンが違って使えないデータは、fallback により空のキャッシュとして扱われる
+        let cache = unsafe {
+            device.create_pipeline_cache(&wgpu::PipelineCacheDescriptor {
+                label: Some("Pipeline Cache"),
+                data: data.as_deref(),
+                fallback: true,
+            })
+        };
+        Self {
+            cache: Some(cache),
+            path: Some(path),
+            loaded: data.map(|data| data.len()),
+            elapsed: Cell::default(),
+            device: device.clone(),
+            startup: Cell::new(true),
+            startup_error: RefCell::default(),
+        }
+    }
+
+    // パイプラインの作成にかかった時間を記録しながら、キャッシュを渡して作成する
+    pub fn create<T>(&self, create: impl FnOnce(Option<&wgpu::PipelineCache>) -> T) -> T {
+        let start = Instant::now();
+        if !self.startup.get() {
+            let pipeline = create(self.cache.as_ref());
+            self.elapsed.set(self.elapsed.get() + start.elapsed());
+            return pipeline;
+        }
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let pipeline = create(self.cache.as_ref());
+        self.elapsed.set(self.elapsed.get() + start.elapsed());
+        if let Some(error) = pollster::block_on(self.device.pop_error_scope()) {
+            self.startup_error
+                .borrow_mut()
+                .get_or_insert_with(|| error.to_string());
+        }
+        pipeline
+    }
+
+    // 起動時の作成を終え、その間に起きた最初の検証エラーを返す
+    pub fn finish_startup(&self) -> Option<String> {
+        self.startup.set(false);
+        self.startup_error.take()
+    }
+
+    // 起動時のパイプラインの作成時間とキャッシュの状態
+    pub fn summary(&self) -> String {
+        let status = match (&self.cache, self.loaded) {
+            (None, _) => "非対応".to_string(),
+            (Some(_), None) => "新規".to_string(),
+            (Some(_), Some(size)) => format!("{} バイトを読み込み済み", size),
+        };
+        format!(
+            "パイプラインの作成: {:.1} ms（キャッシュ: {}）",
+            self.elapsed.get().as_secs_f32() * 1000.0,
+            status
+        )
+    }
+
+    // キャッシュの中身をファイルに書き戻す（途中で終了しても壊れたファイルが残らないよう、一時ファイルから置き換える）
+    pub fn save(&self) -> Result<()> {
+        let (Some(cache), Some(path)) = (&self.cache, &self.path) else {
+            return Ok(());
+        };
+        let Some(data) = cache.get_data() else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("{} を作成できませんでした", dir.display()))?;
+        }
+        let temp = path.with_extension("tmp");
+        std::fs::write(&temp, encode(&data))
+            .with_context(|| format!("{} に書き込めませんでした", temp.display()))?;
+        std::fs::rename(&temp, path)
+            .with_context(|| format!("{} に置き換えられませんでした", path.display()))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saved_data_round_trips_and_damaged_files_are_rejected() {
+        let data = b"pipeline cache data".to_vec();
+        let bytes = encode(&data);
+        assert_eq!(decode(&bytes), Some(data.as_slice()));
+
+        // 途中で切れたファイル、中身が書き換わったファイル、別の形式のファイル
+        assert_eq!(decode(&bytes[..bytes.len() - 1]), None);
+        assert_eq!(decode(&bytes[..HEADER_SIZE - 1]), None);
+        let mut corrupted = bytes.clone();
+        *corrupted.last_mut().unwrap() ^= 1;
+        assert_eq!(decode(&corrupted), None);
+        let mut other = bytes;
+        other[0] = b'X';
+        assert_eq!(decode(&other), None);
+    }
+}
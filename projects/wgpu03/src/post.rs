@@ -0,0 +1,387 @@
+use wgpu::util::DeviceExt;
+
+use crate::crash;
+use crate::pipeline_cache::PipelineCache;
+use crate::texture::Texture;
+
+// シーンを描画した後に画面全体に掛けるエフェクト
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PostEffect {
+    None,
+    Grayscale,
+    Invert,
+}
+
+impl PostEffect {
+    pub fn next(self) -> Self {
+        match self {
+            Self::None => Self::Grayscale,
+            Self::Grayscale => Self::Invert,
+            Self::Invert => Self::None,
+        }
+    }
+}
+
+// HDR の色を表示できる 0.0 〜 1.0 の範囲に収めるトーンマッピングの演算子
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tonemap {
+    // 1.0 を超えた分は切り捨てる
+    Clamp,
+    Reinhard,
+    // ACES Filmic の近似式（Narkowicz 2015）
+    Aces,
+}
+
+impl Tonemap {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Clamp => Self::Reinhard,
+            Self::Reinhard => Self::Aces,
+            Self::Aces => Self::Clamp,
+        }
+    }
+}
+
+// 実行時に切り替えられるポストプロセスの設定
+// 演算子はユニフォームの値で分岐するので、切り替えてもパイプラインやサーフェイスは作り直さない
+#[derive(Clone, Copy, Debug)]
+pub struct PostSettings {
+    pub effect: PostEffect,
+    pub tonemap: Tonemap,
+    // トーンマッピングの前に掛ける露出の倍率
+    pub exposure: f32,
+}
+
+impl Default for PostSettings {
+    fn default() -> Self {
+        Self {
+            effect: PostEffect::None,
+            tonemap: Tonemap::Aces,
+            exposure: 1.0,
+        }
+    }
+}
+
+// サーフェイスに書き込むアルファの扱い（post.wgsl の alpha_mode の値）
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum OutputAlpha {
+    Opaque,
+    PreMultiplied,
+    PostMultiplied,
+}
+
+impl OutputAlpha {
+    fn for_mode(mode: wgpu::CompositeAlphaMode) -> Self {
+        match mode {
+            wgpu::CompositeAlphaMode::PreMultiplied => Self::PreMultiplied,
+            wgpu::CompositeAlphaMode::PostMultiplied => Self::PostMultiplied,
+            _ => Self::Opaque,
+        }
+    }
+}
+
+// post.wgsl の Post 構造体に対応するユニフォームデータ
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct PostUniform {
+    effect: u32,
+    tonemap: u32,
+    exposure: f32,
+    hdr_output: u32,
+    alpha_mode: u32,
+    _padding: [u32; 3],
+}
+
+impl PostUniform {
+    fn new(settings: &PostSettings, hdr_output: bool, alpha: OutputAlpha) -> Self {
+        Self {
+            effect: settings.effect as u32,
+            tonemap: settings.tonemap as u32,
+            exposure: settings.exposure,
+            hdr_output: hdr_output as u32,
+            alpha_mode: alpha as u32,
+            _padding: [0; 3],
+        }
+    }
+}
+
+// オフスクリーンのテクスチャと、それをサンプリングして出力先に書き込むフルスクリーンのパス
+// シェーダーはグループ0に入力テクスチャ（0）、サンプラー（1）、ユニフォーム（2）を受け取り、
+// 頂点バッファを使わずに vs_main で画面全体を覆う三角形を描画すること
+pub struct PostPass {
+    pub pipeline: wgpu::RenderPipeline,
+    pub bind_group: wgpu::BindGroup,
+    // シーンの描画先（このパスの入力）
+    pub texture: Texture,
+    bind_group_layout: wgpu::BindGroupLayout,
+    uniform_buffer: wgpu::Buffer,
+    pub settings: PostSettings,
+    // 出力先が HDR のサーフェイスの場合はトーンマッピングを行わず、線形の色をそのまま書き込む
+    hdr_output: bool,
+    // 透明なウィンドウでは、シーンのアルファをサーフェイスのアルファの扱いに合わせて書き込む
+    alpha: OutputAlpha,
+}
+
+impl PostPass {
+    // 1.0 より明るい色も保持できるよう、オフスクリーンのテクスチャは浮動小数点にする
+    pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+    pub fn new(
+        device: &wgpu::Device,
+        pipeline_cache: &PipelineCache,
+        config: &wgpu::SurfaceConfiguration,
+        shader: &wgpu::ShaderModule,
+        output_format: wgpu::TextureFormat,
+    ) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Post Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let settings = PostSettings::default();
+        let hdr_output = output_format == Self::FORMAT;
+        let alpha = OutputAlpha::for_mode(config.alpha_mode);
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Post Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[PostUniform::new(&settings, hdr_output, alpha)]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let texture = Texture::create_render_target(
+            device,
+            config.width,
+            config.height,
+            Self::FORMAT,
+            "Scene Texture",
+        );
+        let bind_group = create_bind_group(device, &bind_group_layout, &texture, &uniform_buffer);
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Post Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = pipeline_cache.create(|cache| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: crash::created_pipeline("Post Pipeline"),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: shader,
+                    entry_point: Some("fs_main"),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: output_format,
+                        blend: Some(wgpu::BlendState::REPLACE),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: Default::default(),
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache,
+            })
+        });
+
+        Self {
+            pipeline,
+            bind_group,
+            texture,
+            bind_group_layout,
+            uniform_buffer,
+            settings,
+            hdr_output,
+            alpha,
+        }
+    }
+
+    // サーフェイスのサイズに合わせて入力テクスチャとバインドグループを作り直す
+    pub fn resize(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
+        self.texture = Texture::create_render_target(
+            device,
+            config.width,
+            config.height,
+            Self::FORMAT,
+            "Scene Texture",
+        );
+        self.bind_group = create_bind_group(
+            device,
+            &self.bind_group_layout,
+            &self.texture,
+            &self.uniform_buffer,
+        );
+    }
+
+    // settings を変更した後に呼び出してユニフォームバッファに反映する
+    pub fn write_settings(&self, queue: &wgpu::Queue) {
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[PostUniform::new(
+                &self.settings,
+                self.hdr_output,
+                self.alpha,
+            )]),
+        );
+    }
+
+    // 入力テクスチャをサンプリングして view に書き込む
+    pub fn draw(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        timestamp_writes: Option<wgpu::RenderPassTimestampWrites<'_>>,
+    ) {
+        self.draw_in(encoder, view, None, timestamp_writes);
+    }
+
+    // 大きさの違う view（width x height）に、入力テクスチャのアスペクト比を保って中央に書き込む
+    // （余白は黒にする。Ctrl+N で開いたウィンドウに同じシーンを表示するのに使う）
+    pub fn draw_fitted(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        width: u32,
+        height: u32,
+    ) {
+        let source = (self.texture.texture.width(), self.texture.texture.height());
+        self.draw_in(encoder, view, Some(fit(source, (width, height))), None);
+    }
+
+    // viewport は (x, y, 幅, 高さ)。None なら view 全体
+    fn draw_in(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        viewport: Option<[f32; 4]>,
+        timestamp_writes: Option<wgpu::RenderPassTimestampWrites<'_>>,
+    ) {
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: crash::pass("Post Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    // 画面全体を上書きするので、前の内容を読み込む必要はない
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes,
+            occlusion_query_set: None,
+        });
+        if let Some([x, y, width, height]) = viewport {
+            rpass.set_viewport(x, y, width, height, 0.0, 1.0);
+        }
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_bind_group(0, &self.bind_group, &[]);
+        rpass.draw(0..3, 0..1);
+    }
+}
+
+// source の大きさの画像を、アスペクト比を保って target に収まる最大の大きさで中央に置く範囲
+fn fit(source: (u32, u32), target: (u32, u32)) -> [f32; 4] {
+    let (sw, sh) = (source.0.max(1) as f32, source.1.max(1) as f32);
+    let (tw, th) = (target.0.max(1) as f32, target.1.max(1) as f32);
+    let scale = (tw / sw).min(th / sh);
+    let (width, height) = (sw * scale, sh * scale);
+    [(tw - width) / 2.0, (th - height) / 2.0, width, height]
+}
+
+fn create_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    texture: &Texture,
+    uniform_buffer: &wgpu::Buffer,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Post Bind Group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&texture.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(&texture.sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: uniform_buffer.as_entire_binding(),
+            },
+        ],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn effect_values_match_shader_switch() {
+        // post.wgsl の switch の case と一致させる
+        let mut settings = PostSettings {
+            effect: PostEffect::None,
+            tonemap: Tonemap::Clamp,
+            exposure: 1.0,
+        };
+        for expected in [0, 1, 2] {
+            let uniform = PostUniform::new(&settings, false, OutputAlpha::Opaque);
+            assert_eq!(uniform.effect, expected);
+            assert_eq!(uniform.tonemap, expected);
+            settings.effect = settings.effect.next();
+            settings.tonemap = settings.tonemap.next();
+        }
+        assert_eq!(settings.effect, PostEffect::None);
+        assert_eq!(settings.tonemap, Tonemap::Clamp);
+        for (mode, expected) in [
+            (wgpu::CompositeAlphaMode::Auto, 0),
+            (wgpu::CompositeAlphaMode::PreMultiplied, 1),
+            (wgpu::CompositeAlphaMode::PostMultiplied, 2),
+        ] {
+            let uniform = PostUniform::new(&settings, false, OutputAlpha::for_mode(mode));
+            assert_eq!(uniform.alpha_mode, expected);
+        }
+    }
+
+    #[test]
+    fn fitted_scene_keeps_its_aspect_ratio_and_is_centered() {
+        // 横長のウィンドウでは左右に、縦長のウィンドウでは上下に余白ができる
+        assert_eq!(fit((800, 600), (1600, 600)), [400.0, 0.0, 800.0, 600.0]);
+        assert_eq!(fit((800, 600), (400, 600)), [0.0, 150.0, 400.0, 300.0]);
+        assert_eq!(fit((800, 600), (400, 300)), [0.0, 0.0, 400.0, 300.0]);
+    }
+}
@@ -0,0 +1,115 @@
+struct Post {
+    // 0: そのまま, 1: グレースケール, 2: 色の反転
+    effect: u32,
+    // 0: クランプ, 1: Reinhard, 2: ACES（近似）
+    tonemap: u32,
+    exposure: f32,
+    // 1: HDR のサーフェイスに出力する（トーンマッピングを行わない）
+    hdr_output: u32,
+    // 0: 不透明, 1: アルファを掛けた色で出力する, 2: アルファを掛けない色で出力する
+    alpha_mode: u32,
+};
+
+@group(0) @binding(0) var t_scene: texture_2d<f32>;
+@group(0) @binding(1) var s_scene: sampler;
+@group(0) @binding(2) var<uniform> post: Post;
+
+struct VOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+// 頂点バッファを使わずに画面全体を覆う大きな三角形を描画する
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VOutput;
+    out.position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    // テクスチャ座標は上が 0 なので、クリップ座標の Y を反転させる
+    out.uv = vec2<f32>(uv.x, 1.0 - uv.y);
+    return out;
+}
+
+// Rec. 709 の輝度の重み
+const LUMA: vec3<f32> = vec3<f32>(0.2126, 0.7152, 0.0722);
+// Rgba16Float で表現できる最大値に近い値（これより明るい色は丸める）
+const MAX_HDR: f32 = 65000.0;
+
+// 指数部がすべて 1 のビットパターンは NaN か無限大
+fn is_finite(x: f32) -> bool {
+    return (bitcast<u32>(x) & 0x7f800000u) != 0x7f800000u;
+}
+
+// シーンのパスで生じた NaN や無限大の画素が表示に残らないよう、有限の範囲に収める
+fn sanitize(color: vec3<f32>) -> vec3<f32> {
+    var out = color;
+    for (var i = 0; i < 3; i += 1) {
+        out[i] = select(0.0, clamp(color[i], 0.0, MAX_HDR), is_finite(color[i]));
+    }
+    return out;
+}
+
+fn aces(x: vec3<f32>) -> vec3<f32> {
+    let a = 2.51;
+    let b = 0.03;
+    let c = 2.43;
+    let d = 0.59;
+    let e = 0.14;
+    return (x * (a * x + b)) / (x * (c * x + d) + e);
+}
+
+fn tonemap(hdr: vec3<f32>) -> vec3<f32> {
+    let color = sanitize(hdr) * post.exposure;
+    // HDR のサーフェイスは線形の色を受け取り、表示への変換は OS が行う
+    if post.hdr_output != 0u {
+        return color;
+    }
+    switch post.tonemap {
+        case 1u: {
+            return color / (1.0 + color);
+        }
+        case 2u: {
+            return clamp(aces(color), vec3<f32>(0.0), vec3<f32>(1.0));
+        }
+        default: {
+            return clamp(color, vec3<f32>(0.0), vec3<f32>(1.0));
+        }
+    }
+}
+
+fn effect(color: vec3<f32>) -> vec3<f32> {
+    switch post.effect {
+        case 1u: {
+            return vec3<f32>(dot(color, LUMA));
+        }
+        case 2u: {
+            return 1.0 - color;
+        }
+        default: {
+            return color;
+        }
+    }
+}
+
+@fragment
+fn fs_main(in: VOutput) -> @location(0) vec4<f32> {
+    let scene = textureSample(t_scene, s_scene, in.uv);
+    // 透明な背景に重ねて描画したシーンの色はアルファを掛けた値になっているので、
+    // 割り戻してから変換し、サーフェイスのアルファの扱いに合わせて掛け直す
+    var alpha = 1.0;
+    if post.alpha_mode != 0u {
+        alpha = select(0.0, clamp(scene.a, 0.0, 1.0), is_finite(scene.a));
+    }
+    let color = effect(tonemap(scene.rgb / max(alpha, 1e-4)));
+    switch post.alpha_mode {
+        case 1u: {
+            return vec4<f32>(color * alpha, alpha);
+        }
+        case 2u: {
+            return vec4<f32>(color, alpha);
+        }
+        default: {
+            return vec4<f32>(color, 1.0);
+        }
+    }
+}
@@ -0,0 +1,182 @@
+use std::borrow::Cow;
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use anyhow::{Context, Result, bail};
+
+// この行を `#include "include/camera.wgsl"` のように書くと、シェーダーのディレクトリからの
+// 相対パスのファイルの中身で置き換える
+const DIRECTIVE: &str = "#include";
+
+// インクルードをすべて展開したシェーダーのソース
+pub struct Expanded {
+    source: String,
+    // 展開したファイル（先頭が展開を始めたファイル）
+    files: Vec<String>,
+    // 展開後の各行が、どのファイル（files の番号）の何行目から来たか
+    origins: Vec<(usize, u32)>,
+}
+
+impl Expanded {
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    // 展開に使ったファイル
+    pub fn files(&self) -> &[String] {
+        &self.files
+    }
+
+    // 展開後の行番号（1 始まり）に対応する、元のファイルと行番号
+    pub fn origin(&self, line: u32) -> Option<(&str, u32)> {
+        let &(file, line) = self.origins.get((line as usize).checked_sub(1)?)?;
+        Some((&self.files[file], line))
+    }
+
+    // 展開後のソースのハッシュ（シェーダーモジュールを使い回す目印にする）
+    pub fn hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.source.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    // 展開後のソースを書き換える（行の対応が崩れないよう、行の数を変えない書き換えにだけ使う）
+    pub fn map(mut self, f: impl FnOnce(&str) -> Cow<'_, str>) -> Self {
+        self.source = f(&self.source).into_owned();
+        self
+    }
+}
+
+// entry のインクルードを再帰的に展開する（read はシェーダーのディレクトリからの相対パスで読み込む）
+// 同じファイルは最初の1回だけ展開し、2回目以降の #include は無視する（構造体などの定義が重ならないように）
+// 展開中のファイルを含めようとすると、循環としてエラーにする
+pub fn expand(entry: &str, read: &impl Fn(&str) -> Result<String>) -> Result<Expanded> {
+    let mut expanded = Expanded {
+        source: String::new(),
+        files: Vec::new(),
+        origins: Vec::new(),
+    };
+    expand_file(entry, read, &mut Vec::new(), &mut expanded)?;
+    Ok(expanded)
+}
+
+fn expand_file(
+    path: &str,
+    read: &impl Fn(&str) -> Result<String>,
+    stack: &mut Vec<String>,
+    expanded: &mut Expanded,
+) -> Result<()> {
+    if stack.iter().any(|file| file == path) {
+        bail!(
+            "インクルードが循環しています: {} -> {}",
+            stack.join(" -> "),
+            path
+        );
+    }
+    if expanded.files.iter().any(|file| file == path) {
+        return Ok(());
+    }
+    let source = read(path).with_context(|| format!("{} を読み込めませんでした", path))?;
+    let file = expanded.files.len();
+    expanded.files.push(path.to_string());
+    stack.push(path.to_string());
+    for (index, line) in source.lines().enumerate() {
+        let number = index as u32 + 1;
+        match include_path(line).with_context(|| format!("{}:{}", path, number))? {
+            Some(target) => expand_file(target, read, stack, expanded)
+                .with_context(|| format!("{}:{} でインクルードしました", path, number))?,
+            None => {
+                expanded.source.push_str(line);
+                expanded.source.push('\n');
+                expanded.origins.push((file, number));
+            }
+        }
+    }
+    stack.pop();
+    Ok(())
+}
+
+// #include の行ならファイル名を返す
+fn include_path(line: &str) -> Result<Option<&str>> {
+    let Some(rest) = line.trim().strip_prefix(DIRECTIVE) else {
+        return Ok(None);
+    };
+    match rest
+        .trim()
+        .strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+    {
+        Some(path) if !path.is_empty() => Ok(Some(path)),
+        _ => bail!(
+            "{} の後には \"ファイル名\" を書いてください: {}",
+            DIRECTIVE,
+            line
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn files<'a>(files: &'a [(&str, &str)]) -> impl Fn(&str) -> Result<String> + 'a {
+        let files: HashMap<_, _> = files.iter().copied().collect();
+        move |path| match files.get(path) {
+            Some(source) => Ok(source.to_string()),
+            None => bail!("ファイルがありません"),
+        }
+    }
+
+    #[test]
+    fn includes_are_expanded_once_and_lines_map_back_to_their_files() {
+        let read = files(&[
+            (
+                "main.wgsl",
+                "#include \"a.wgsl\"\n  #include \"b.wgsl\"  \nfn main() {}",
+            ),
+            ("a.wgsl", "#include \"common.wgsl\"\nstruct A {}"),
+            ("b.wgsl", "#include \"common.wgsl\"\nstruct B {}"),
+            ("common.wgsl", "struct Common {}"),
+        ]);
+        let expanded = expand("main.wgsl", &read).unwrap();
+        assert_eq!(
+            expanded.source(),
+            "struct Common {}\nstruct A {}\nstruct B {}\nfn main() {}\n"
+        );
+        assert_eq!(
+            expanded.files(),
+            ["main.wgsl", "a.wgsl", "common.wgsl", "b.wgsl"]
+        );
+        assert_eq!(expanded.origin(1), Some(("common.wgsl", 1)));
+        assert_eq!(expanded.origin(3), Some(("b.wgsl", 2)));
+        assert_eq!(expanded.origin(4), Some(("main.wgsl", 3)));
+        assert_eq!(expanded.origin(0), None);
+        assert_eq!(expanded.origin(5), None);
+    }
+
+    #[test]
+    fn cycles_missing_files_and_malformed_directives_are_errors() {
+        let read = files(&[
+            ("a.wgsl", "#include \"b.wgsl\""),
+            ("b.wgsl", "\n#include \"a.wgsl\""),
+            ("missing.wgsl", "#include \"nothing.wgsl\""),
+            ("malformed.wgsl", "#include common.wgsl"),
+        ]);
+        let error = format!("{:#}", expand("a.wgsl", &read).err().unwrap());
+        assert!(
+            error.starts_with("a.wgsl:1 でインクルードしました"),
+            "{}",
+            error
+        );
+        assert!(error.ends_with("a.wgsl -> b.wgsl -> a.wgsl"), "{}", error);
+        let error = format!("{:#}", expand("missing.wgsl", &read).err().unwrap());
+        assert!(
+            error.contains("nothing.wgsl を読み込めませんでした"),
+            "{}",
+            error
+        );
+        let error = format!("{:#}", expand("malformed.wgsl", &read).err().unwrap());
+        assert!(error.starts_with("malformed.wgsl:1"), "{}", error);
+    }
+}
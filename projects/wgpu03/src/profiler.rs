@@ -0,0 +1,216 @@
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde_json::json;
+use tracing::{info, warn};
+
+use crate::capabilities::GpuCapabilities;
+use crate::gpu_timer::{GpuPass, GpuTimer, PassTime};
+
+// F4 キーで書き出す、最近のフレームの数（60 fps でおよそ5秒分）
+const HISTORY_LEN: usize = 300;
+// 書き出すディレクトリ（実行したディレクトリからの相対パス）
+const PROFILE_DIR: &str = "profiles";
+
+// 1フレームの、パスごとの記録にかかった CPU の時間と GPU での所要時間
+#[derive(Clone, Debug, PartialEq)]
+pub struct FrameProfile {
+    pub frame: u64,
+    // 記録を始めた時刻（プロファイラを作ってからの時間）
+    start: Duration,
+    pub cpu: Vec<PassTime>,
+    // GPU の結果は数フレーム遅れで届き、届くまでと計測できないデバイスでは None
+    pub gpu: Option<Vec<PassTime>>,
+}
+
+// 毎フレームのパスごとの時間をまとめ、統計の表示と chrome://tracing 用の JSON の書き出しに使う
+// TIMESTAMP_QUERY に対応していないデバイスでは、CPU でコマンドを記録した時間だけを計る
+pub struct Profiler {
+    gpu_timer: Option<GpuTimer>,
+    epoch: Instant,
+    // 記録中のフレーム（encode_frame の最初に作り直す）
+    current: FrameProfile,
+    history: VecDeque<FrameProfile>,
+}
+
+impl Profiler {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, capabilities: &GpuCapabilities) -> Self {
+        Self::with_timer(GpuTimer::new(device, queue, capabilities))
+    }
+
+    fn with_timer(gpu_timer: Option<GpuTimer>) -> Self {
+        Self {
+            gpu_timer,
+            epoch: Instant::now(),
+            current: FrameProfile {
+                frame: 0,
+                start: Duration::ZERO,
+                cpu: Vec::new(),
+                gpu: None,
+            },
+            history: VecDeque::with_capacity(HISTORY_LEN),
+        }
+    }
+
+    pub fn begin_frame(&mut self, frame: u64) {
+        self.current = FrameProfile {
+            frame,
+            start: self.epoch.elapsed(),
+            cpu: Vec::new(),
+            gpu: None,
+        };
+    }
+
+    // start（pass の記録を始めた時刻）から今までを、pass を記録した CPU の時間にする
+    pub fn record(&mut self, pass: GpuPass, start: Instant) {
+        let start_ms = (start - self.epoch).saturating_sub(self.current.start);
+        self.current.cpu.push(PassTime {
+            pass,
+            start_ms: start_ms.as_secs_f32() * 1000.0,
+            ms: start.elapsed().as_secs_f32() * 1000.0,
+        });
+    }
+
+    pub fn timestamp_writes(&self, pass: GpuPass) -> Option<wgpu::RenderPassTimestampWrites<'_>> {
+        self.gpu_timer
+            .as_ref()
+            .map(|timer| timer.timestamp_writes(pass))
+    }
+
+    pub fn compute_timestamp_writes(
+        &self,
+        pass: GpuPass,
+    ) -> Option<wgpu::ComputePassTimestampWrites<'_>> {
+        self.gpu_timer
+            .as_ref()
+            .map(|timer| timer.compute_timestamp_writes(pass))
+    }
+
+    // すべてのパスを記録した後に、同じエンコーダーへタイムスタンプの読み出しを記録する
+    pub fn resolve(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        if let Some(timer) = &mut self.gpu_timer {
+            timer.resolve(encoder, self.current.frame);
+        }
+    }
+
+    // 表示するフレームを記録し終えたところで、履歴に加える
+    // （録画やスクリーンショットのためにもう一度記録したものは加えない）
+    pub fn end_frame(&mut self) {
+        if self.history.len() == HISTORY_LEN {
+            self.history.pop_front();
+        }
+        self.history.push_back(self.current.clone());
+    }
+
+    // 提出した後に呼び、読み出せた GPU の結果をそのフレームの履歴に加える
+    pub fn after_submit(&mut self) {
+        let Some(timer) = &mut self.gpu_timer else {
+            return;
+        };
+        for gpu in timer.after_submit() {
+            if let Some(profile) = self.history.iter_mut().find(|p| p.frame == gpu.frame) {
+                profile.gpu = Some(gpu.passes);
+            }
+        }
+    }
+
+    pub fn has_gpu_timer(&self) -> bool {
+        self.gpu_timer.is_some()
+    }
+
+    // 最後に記録したフレームと、GPU の結果が届いている最後のフレーム
+    pub fn latest(&self) -> (Option<&FrameProfile>, Option<&FrameProfile>) {
+        (
+            self.history.back(),
+            self.history.iter().rev().find(|p| p.gpu.is_some()),
+        )
+    }
+
+    // chrome://tracing（や Perfetto）で開ける形式にする
+    // CPU での記録はスレッド 1、GPU での実行はスレッド 2 に並べる
+    // （GPU の時刻はフレームの記録を始めた時刻にそろえるので、CPU との前後関係は正確ではない）
+    fn chrome_trace(&self) -> serde_json::Value {
+        let mut events = Vec::new();
+        for profile in &self.history {
+            let frame_us = profile.start.as_secs_f64() * 1_000_000.0;
+            let timelines = [(1, Some(&profile.cpu)), (2, profile.gpu.as_ref())];
+            for (tid, passes) in timelines {
+                for time in passes.into_iter().flatten() {
+                    events.push(json!({
+                        "name": time.pass.name(),
+                        "cat": if tid == 1 { "cpu" } else { "gpu" },
+                        "ph": "X",
+                        "ts": frame_us + f64::from(time.start_ms) * 1000.0,
+                        "dur": f64::from(time.ms) * 1000.0,
+                        "pid": 1,
+                        "tid": tid,
+                        "args": { "frame": profile.frame },
+                    }));
+                }
+            }
+        }
+        json!({
+            "traceEvents": events,
+            "displayTimeUnit": "ms",
+        })
+    }
+
+    // ファイルへの書き込みは別のスレッドで行い、フレームの処理を止めない
+    pub fn save_chrome_trace(&self) {
+        let trace = self.chrome_trace();
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_millis());
+        let path = PathBuf::from(PROFILE_DIR).join(format!("profile-{}.json", millis));
+        std::thread::spawn(move || {
+            let result = std::fs::create_dir_all(PROFILE_DIR)
+                .and_then(|()| std::fs::write(&path, trace.to_string()));
+            match result {
+                Ok(()) => info!("プロファイルを保存しました: {}", path.display()),
+                Err(e) => warn!(
+                    "プロファイルを保存できませんでした（{}）: {}",
+                    path.display(),
+                    e
+                ),
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_are_kept_in_order_and_exported_per_timeline() {
+        let mut profiler = Profiler::with_timer(None);
+        for frame in 1..=HISTORY_LEN as u64 + 2 {
+            profiler.begin_frame(frame);
+            profiler.record(GpuPass::Shadow, Instant::now());
+            profiler.record(GpuPass::Main, Instant::now());
+            profiler.end_frame();
+        }
+        assert_eq!(profiler.history.len(), HISTORY_LEN);
+        let (latest, latest_gpu) = profiler.latest();
+        assert_eq!(latest.unwrap().frame, HISTORY_LEN as u64 + 2);
+        assert!(latest_gpu.is_none());
+
+        // 届いた GPU の結果は GPU のスレッドに並ぶ
+        profiler.history.back_mut().unwrap().gpu = Some(vec![PassTime {
+            pass: GpuPass::Main,
+            start_ms: 0.25,
+            ms: 1.5,
+        }]);
+        let trace = profiler.chrome_trace();
+        let events = trace["traceEvents"].as_array().unwrap();
+        assert_eq!(events.len(), HISTORY_LEN * 2 + 1);
+        assert_eq!(events[0]["args"]["frame"], 3);
+        let gpu = events.last().unwrap();
+        assert_eq!(
+            (gpu["name"].as_str(), gpu["tid"].as_i64()),
+            (Some("main"), Some(2))
+        );
+        assert_eq!(gpu["dur"], 1500.0);
+    }
+}
@@ -0,0 +1,152 @@
+use glam::{Mat4, Vec3};
+
+use crate::camera::Camera;
+use crate::viewport::PhysicalRect;
+
+// 平行に近いとみなす行列式の大きさ
+const PARALLEL_EPSILON: f32 = 1e-7;
+
+// origin から direction の向きに伸びる半直線（点は origin + direction * t）
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ray {
+    pub origin: Vec3,
+    pub direction: Vec3,
+}
+
+impl Ray {
+    pub fn at(&self, t: f32) -> Vec3 {
+        self.origin + self.direction * t
+    }
+
+    // matrix で変換した半直線（モデル座標で交差を調べるときはモデル行列の逆行列を渡す）
+    // direction は正規化し直さないので、変換前と同じ t で同じ点を指す
+    pub fn transformed(&self, matrix: Mat4) -> Ray {
+        Ray {
+            origin: matrix.transform_point3(self.origin),
+            direction: matrix.transform_vector3(self.direction),
+        }
+    }
+}
+
+// カーソル（物理ピクセル）を通り、カメラのニアクリップ面から奥へ伸びる視線
+// viewport はシーンを描画している範囲で、その範囲を正規化デバイス座標の -1..1 に対応させる
+pub fn unproject(cursor: (f32, f32), camera: &Camera, viewport: PhysicalRect) -> Ray {
+    let ndc_x = (cursor.0 - viewport.x as f32) / viewport.width.max(1) as f32 * 2.0 - 1.0;
+    let ndc_y = 1.0 - (cursor.1 - viewport.y as f32) / viewport.height.max(1) as f32 * 2.0;
+    let inverse = camera.build_view_projection_matrix().inverse();
+    let near = inverse.project_point3(Vec3::new(ndc_x, ndc_y, 0.0));
+    let far = inverse.project_point3(Vec3::new(ndc_x, ndc_y, 1.0));
+    Ray {
+        origin: near,
+        direction: (far - near).normalize(),
+    }
+}
+
+// 三角形との交点（t と、頂点 a, b, c に対する重心座標）
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TriangleHit {
+    pub t: f32,
+    pub barycentric: Vec3,
+}
+
+// Möller–Trumbore 法で三角形との交点を求める（裏面からでも交差する）
+pub fn intersect_triangle(ray: &Ray, [a, b, c]: [Vec3; 3]) -> Option<TriangleHit> {
+    let edge1 = b - a;
+    let edge2 = c - a;
+    let p = ray.direction.cross(edge2);
+    let determinant = edge1.dot(p);
+    if determinant.abs() < PARALLEL_EPSILON {
+        return None;
+    }
+    let inverse = 1.0 / determinant;
+    let s = ray.origin - a;
+    let u = s.dot(p) * inverse;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = s.cross(edge1);
+    let v = ray.direction.dot(q) * inverse;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    let t = edge2.dot(q) * inverse;
+    (t >= 0.0).then_some(TriangleHit {
+        t,
+        barycentric: Vec3::new(1.0 - u - v, u, v),
+    })
+}
+
+// スラブ法で軸に平行な箱との交点の t を求める（origin が箱の中にあれば 0）
+pub fn intersect_aabb(ray: &Ray, min: Vec3, max: Vec3) -> Option<f32> {
+    // 軸に平行な成分は無限大になり、その軸では常に範囲内か範囲外かが決まる
+    let inverse = ray.direction.recip();
+    let t0 = (min - ray.origin) * inverse;
+    let t1 = (max - ray.origin) * inverse;
+    let near = t0.min(t1).max_element();
+    let far = t0.max(t1).min_element();
+    (near <= far && far >= 0.0).then_some(near.max(0.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn full_viewport() -> PhysicalRect {
+        PhysicalRect {
+            x: 0,
+            y: 0,
+            width: 800,
+            height: 600,
+        }
+    }
+
+    #[test]
+    fn ray_through_the_screen_center_hits_the_triangle_at_the_expected_point() {
+        // カメラは (0, 0, 2) から原点を向いている
+        let camera = Camera::new(800, 600);
+        let ray = unproject((400.0, 300.0), &camera, full_viewport());
+        assert!((ray.direction - Vec3::NEG_Z).length() < 1e-5);
+
+        let triangle = [
+            Vec3::new(-1.0, -1.0, 0.0),
+            Vec3::new(1.0, -1.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        ];
+        let hit = intersect_triangle(&ray, triangle).unwrap();
+        assert!(ray.at(hit.t).length() < 1e-4);
+        assert!((hit.t - (2.0 - camera.znear)).abs() < 1e-4);
+        // 原点は a と b の重みが 1/4、c の重みが 1/2 の点
+        assert!((hit.barycentric - Vec3::new(0.25, 0.25, 0.5)).length() < 1e-4);
+
+        // 右上の隅を通る視線は三角形から外れる
+        let corner = unproject((790.0, 10.0), &camera, full_viewport());
+        assert_eq!(intersect_triangle(&corner, triangle), None);
+    }
+
+    #[test]
+    fn ray_hits_the_near_face_of_a_box_and_respects_the_viewport() {
+        let camera = Camera::new(800, 600);
+        let ray = unproject((400.0, 300.0), &camera, full_viewport());
+        let t = intersect_aabb(&ray, Vec3::splat(-0.5), Vec3::splat(0.5)).unwrap();
+        assert!((ray.at(t).z - 0.5).abs() < 1e-4);
+        // 箱が視線の後ろにある場合は交差しない
+        let behind = Ray {
+            origin: Vec3::new(0.0, 0.0, 2.0),
+            direction: Vec3::Z,
+        };
+        assert_eq!(
+            intersect_aabb(&behind, Vec3::splat(-0.5), Vec3::splat(0.5)),
+            None
+        );
+
+        // 右側だけに描画している場合は、その範囲の中心が画面の中心になる
+        let right = PhysicalRect {
+            x: 240,
+            y: 0,
+            width: 560,
+            height: 600,
+        };
+        let offset = unproject((520.0, 300.0), &camera, right);
+        assert!((offset.direction - Vec3::NEG_Z).length() < 1e-5);
+    }
+}
@@ -0,0 +1,236 @@
+use std::path::PathBuf;
+use std::sync::{
+    Arc,
+    atomic::{AtomicU8, Ordering},
+    mpsc,
+};
+use std::thread::JoinHandle;
+
+use tracing::{info, warn};
+
+use crate::capture;
+
+// 同時にマップを待てる読み出しバッファの数（マップの完了を待たずに次のフレームへ進む）
+const STAGING_BUFFERS: usize = 3;
+// 書き込みを待つフレームの上限（超えた場合はフレームの処理が書き込みを待つので、メモリが増え続けない）
+const QUEUED_FRAMES: usize = 4;
+// 録画したフレームを書き込むディレクトリ（実行したディレクトリからの相対パス）
+const RECORDING_DIR: &str = "recording";
+
+// 表示した順に 1 から振った番号のファイル名
+fn frame_path(frame: u32) -> PathBuf {
+    PathBuf::from(RECORDING_DIR).join(format!("frame_{:05}.png", frame))
+}
+
+// map_async のコールバックが Slot::mapped に書き込む結果
+const MAP_PENDING: u8 = 0;
+const MAP_OK: u8 = 1;
+const MAP_FAILED: u8 = 2;
+
+// 読み出しバッファの状態（コピーしたフレームの番号を持ち、マップの完了順によらず番号で保存する）
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum SlotState {
+    Idle,
+    // このフレームでコピーを記録したので、提出後にマップする
+    Copied(u32),
+    // map_async の完了を待っている
+    Mapping(u32),
+}
+
+struct Slot {
+    buffer: wgpu::Buffer,
+    // コピーしたテクスチャの大きさとフォーマット
+    size: wgpu::Extent3d,
+    format: wgpu::TextureFormat,
+    state: SlotState,
+    // map_async のコールバックで MAP_OK か MAP_FAILED にする
+    mapped: Arc<AtomicU8>,
+}
+
+// `--record N` で、表示したフレームを N 枚まで連番の PNG に書き出す
+pub struct Recorder {
+    limit: u32,
+    // 次に描画するフレームの番号
+    next_frame: u32,
+    recording: bool,
+    // 読み出せるように COPY_SRC を付けた描画先（サーフェイスの大きさが変わったら作り直す）
+    target: Option<wgpu::Texture>,
+    slots: Vec<Slot>,
+    // begin_frame で選んだ読み出しバッファ
+    current: Option<usize>,
+    // PNG へのエンコードと書き込みを行うスレッド
+    sender: Option<mpsc::SyncSender<(u32, image::RgbaImage)>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl Recorder {
+    pub fn new(limit: u32) -> Self {
+        let (sender, receiver) = mpsc::sync_channel::<(u32, image::RgbaImage)>(QUEUED_FRAMES);
+        let worker = std::thread::spawn(move || {
+            if let Err(e) = std::fs::create_dir_all(RECORDING_DIR) {
+                warn!("{} を作成できませんでした: {}", RECORDING_DIR, e);
+            }
+            for (frame, image) in receiver {
+                let path = frame_path(frame);
+                if let Err(e) = image.save(&path) {
+                    warn!("{} を保存できませんでした: {}", path.display(), e);
+                }
+            }
+        });
+        Self {
+            limit,
+            next_frame: 1,
+            recording: true,
+            target: None,
+            slots: Vec::new(),
+            current: None,
+            sender: Some(sender),
+            worker: Some(worker),
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording
+    }
+
+    // 録画を止める・再開する（N 枚を書き出した後は再開しない）
+    pub fn toggle(&mut self) {
+        self.recording = !self.recording && self.next_frame <= self.limit;
+    }
+
+    // 録画中であれば、このフレームをもう一度描画する先のビューを返す
+    pub fn begin_frame(
+        &mut self,
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+    ) -> Option<wgpu::TextureView> {
+        if !self.recording {
+            return None;
+        }
+        let matches = |texture: &wgpu::Texture| {
+            texture.width() == width && texture.height() == height && texture.format() == format
+        };
+        let target = match self.target.take() {
+            Some(texture) if matches(&texture) => texture,
+            _ => capture::create_target(device, "Recording Texture", width, height, format),
+        };
+        let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+        self.current = Some(self.free_slot(device, target.size()));
+        self.target = Some(target);
+        Some(view)
+    }
+
+    // begin_frame のビューに描画したコマンドの後に呼び、読み出しバッファへのコピーを記録する
+    pub fn copy(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        let (Some(index), Some(target)) = (self.current.take(), &self.target) else {
+            return;
+        };
+        let slot = &mut self.slots[index];
+        capture::copy_to_buffer(encoder, target, &slot.buffer);
+        slot.format = target.format();
+        slot.state = SlotState::Copied(self.next_frame);
+        self.next_frame += 1;
+        if self.next_frame > self.limit {
+            self.recording = false;
+            info!("{} フレームを録画しました", self.limit);
+        }
+    }
+
+    // コマンドを提出した後に呼び、コピーしたバッファのマップを始めて、マップが終わったものを書き込みに回す
+    pub fn after_submit(&mut self) {
+        for slot in &mut self.slots {
+            match slot.state {
+                SlotState::Idle => {}
+                SlotState::Copied(frame) => {
+                    let mapped = slot.mapped.clone();
+                    slot.buffer
+                        .slice(..)
+                        .map_async(wgpu::MapMode::Read, move |result| {
+                            let state = if result.is_ok() { MAP_OK } else { MAP_FAILED };
+                            mapped.store(state, Ordering::Release);
+                        });
+                    slot.state = SlotState::Mapping(frame);
+                }
+                SlotState::Mapping(frame) => {
+                    // マップに失敗したバッファも空きに戻し、待ち続けないようにする
+                    match slot.mapped.swap(MAP_PENDING, Ordering::Acquire) {
+                        MAP_PENDING => continue,
+                        MAP_FAILED => {
+                            slot.state = SlotState::Idle;
+                            warn!(
+                                "フレーム {} の読み出しバッファをマップできませんでした",
+                                frame
+                            );
+                            continue;
+                        }
+                        _ => slot.state = SlotState::Idle,
+                    }
+                    let image = match capture::read_mapped(&slot.buffer, slot.size, slot.format) {
+                        Ok(image) => image,
+                        Err(e) => {
+                            warn!("フレーム {} を読み出せませんでした: {:#}", frame, e);
+                            continue;
+                        }
+                    };
+                    if let Some(sender) = &self.sender {
+                        // 書き込みが追いつかない間はここで待つ
+                        let _ = sender.send((frame, image));
+                    }
+                }
+            }
+        }
+    }
+
+    // 空いている読み出しバッファを探す（すべて使用中ならマップが終わるまで待つ）
+    fn free_slot(&mut self, device: &wgpu::Device, size: wgpu::Extent3d) -> usize {
+        loop {
+            if let Some(index) = self.slots.iter().position(|s| s.state == SlotState::Idle) {
+                let slot = &mut self.slots[index];
+                if slot.size != size {
+                    slot.buffer = capture::create_readback_buffer(device, "Recording Buffer", size);
+                    slot.size = size;
+                }
+                return index;
+            }
+            if self.slots.len() < STAGING_BUFFERS {
+                self.slots.push(Slot {
+                    buffer: capture::create_readback_buffer(device, "Recording Buffer", size),
+                    size,
+                    format: wgpu::TextureFormat::Rgba8UnormSrgb,
+                    state: SlotState::Idle,
+                    mapped: Arc::new(AtomicU8::new(MAP_PENDING)),
+                });
+                continue;
+            }
+            device.poll(wgpu::Maintain::Wait);
+            self.after_submit();
+        }
+    }
+
+    // 終了するときに呼び、読み出し中のフレームと書き込み待ちのフレームをすべて保存する
+    pub fn finish(&mut self, device: &wgpu::Device) {
+        self.recording = false;
+        while self.slots.iter().any(|s| s.state != SlotState::Idle) {
+            device.poll(wgpu::Maintain::Wait);
+            self.after_submit();
+        }
+        // 送信側を閉じると書き込みのスレッドは残りを保存してから終わる
+        self.sender = None;
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_are_numbered_from_one_with_five_digits() {
+        assert_eq!(frame_path(1), PathBuf::from("recording/frame_00001.png"));
+        assert_eq!(frame_path(1234), PathBuf::from("recording/frame_01234.png"));
+    }
+}
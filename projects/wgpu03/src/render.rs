@@ -0,0 +1,366 @@
+//! 描画設定ごとのレンダーパイプラインと、マルチサンプリング用のカラーテクスチャを作る
+
+use crate::crash;
+use crate::pipeline_cache::PipelineCache;
+use crate::post::PostPass;
+use crate::shading::Shading;
+use crate::texture::Texture;
+
+/// 半透明描画のブレンドモード
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlendMode {
+    Replace,
+    Alpha,
+    Additive,
+}
+
+impl BlendMode {
+    pub fn next(self) -> Self {
+        match self {
+            BlendMode::Replace => BlendMode::Alpha,
+            BlendMode::Alpha => BlendMode::Additive,
+            BlendMode::Additive => BlendMode::Replace,
+        }
+    }
+
+    fn state(self) -> wgpu::BlendState {
+        match self {
+            BlendMode::Replace => wgpu::BlendState::REPLACE,
+            BlendMode::Alpha => wgpu::BlendState::ALPHA_BLENDING,
+            BlendMode::Additive => wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::SrcAlpha,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha: wgpu::BlendComponent::OVER,
+            },
+        }
+    }
+}
+
+/// パイプラインごとに異なる描画設定
+#[derive(Clone, Copy, Debug)]
+pub struct PipelineOptions {
+    // バックエンドのエラーメッセージやキャプチャで見分けるためのラベル
+    label: &'static str,
+    // 頂点シェーダーとフラグメントシェーダーのエントリポイント
+    vertex_entry: &'static str,
+    fragment_entry: &'static str,
+    topology: wgpu::PrimitiveTopology,
+    polygon_mode: wgpu::PolygonMode,
+    cull_mode: Option<wgpu::Face>,
+    blend: wgpu::BlendState,
+    color_writes: wgpu::ColorWrites,
+    depth_write_enabled: bool,
+    depth_compare: wgpu::CompareFunction,
+    // 表面と裏面で共通のステンシルテスト（StencilState は Copy でないので分けて持つ）
+    stencil_face: wgpu::StencilFaceState,
+    stencil_write_mask: u32,
+}
+
+// 描画した画素のステンシルに参照値（set_stencil_reference）を書き込む
+const STENCIL_WRITE_FACE: wgpu::StencilFaceState = wgpu::StencilFaceState {
+    compare: wgpu::CompareFunction::Always,
+    fail_op: wgpu::StencilOperation::Keep,
+    depth_fail_op: wgpu::StencilOperation::Keep,
+    pass_op: wgpu::StencilOperation::Replace,
+};
+
+// ステンシルが参照値と異なる画素にだけ描画する（ステンシルは書き換えない）
+const STENCIL_OUTSIDE_FACE: wgpu::StencilFaceState = wgpu::StencilFaceState {
+    compare: wgpu::CompareFunction::NotEqual,
+    fail_op: wgpu::StencilOperation::Keep,
+    depth_fail_op: wgpu::StencilOperation::Keep,
+    pass_op: wgpu::StencilOperation::Keep,
+};
+
+impl PipelineOptions {
+    /// 不透明なジオメトリ用
+    /// 裏面カリングを有効にして、頂点の並び順（反時計回りが表）の誤りに気付けるようにする
+    pub const OPAQUE: PipelineOptions = PipelineOptions {
+        label: "Opaque Pipeline",
+        vertex_entry: "vs_main",
+        fragment_entry: "fs_main",
+        topology: wgpu::PrimitiveTopology::TriangleList,
+        polygon_mode: wgpu::PolygonMode::Fill,
+        cull_mode: Some(wgpu::Face::Back),
+        blend: wgpu::BlendState::REPLACE,
+        color_writes: wgpu::ColorWrites::ALL,
+        depth_write_enabled: true,
+        depth_compare: wgpu::CompareFunction::Less,
+        stencil_face: wgpu::StencilFaceState::IGNORE,
+        stencil_write_mask: 0,
+    };
+
+    /// スキンメッシュ用（関節の行列で頂点を動かしてから、不透明なジオメトリと同じく描画する）
+    pub const SKINNED: PipelineOptions = PipelineOptions {
+        label: "Skinned Pipeline",
+        vertex_entry: "vs_skinned",
+        ..Self::OPAQUE
+    };
+
+    pub const WIREFRAME: PipelineOptions = PipelineOptions {
+        label: "Wireframe Pipeline",
+        polygon_mode: wgpu::PolygonMode::Line,
+        ..Self::OPAQUE
+    };
+
+    /// シェーディングを比較するための、陰影を付けない塗りつぶし
+    pub const FLAT: PipelineOptions = PipelineOptions {
+        label: "Flat Pipeline",
+        fragment_entry: "fs_flat",
+        ..Self::OPAQUE
+    };
+
+    pub const VERTEX_COLOR: PipelineOptions = PipelineOptions {
+        label: "Vertex Color Pipeline",
+        fragment_entry: "fs_vertex_color",
+        ..Self::OPAQUE
+    };
+
+    /// GLSL から変換した SPIR-V は、どのステージもエントリーポイントが main になる
+    pub const NORMALS: PipelineOptions = PipelineOptions {
+        label: "Normals Pipeline",
+        vertex_entry: "main",
+        fragment_entry: "main",
+        ..Self::OPAQUE
+    };
+
+    /// 同じ描画設定のパイプラインを、用途の分かるラベルで作る
+    pub const fn with_label(self, label: &'static str) -> Self {
+        Self { label, ..self }
+    }
+
+    // 三角形や立方体のパイプラインは、どれも輪郭用にステンシルへ参照値を書き込む
+    pub(crate) fn for_shading(shading: Shading) -> Self {
+        let options = match shading {
+            Shading::Textured => Self::OPAQUE,
+            Shading::Flat => Self::FLAT,
+            Shading::VertexColor => Self::VERTEX_COLOR,
+            Shading::Normals => Self::NORMALS,
+            Shading::Wireframe => Self::WIREFRAME,
+        };
+        Self {
+            label: match shading {
+                Shading::Textured => "Textured Pipeline",
+                _ => options.label,
+            },
+            stencil_face: STENCIL_WRITE_FACE,
+            stencil_write_mask: 0xff,
+            ..options
+        }
+    }
+
+    /// 選択した物体の輪郭用（物体を描画した画素の外側だけに、他の物体より手前に描画する）
+    pub const OUTLINE: PipelineOptions = PipelineOptions {
+        label: "Outline Pipeline",
+        depth_write_enabled: false,
+        depth_compare: wgpu::CompareFunction::Always,
+        stencil_face: STENCIL_OUTSIDE_FACE,
+        stencil_write_mask: 0,
+        ..Self::OPAQUE
+    };
+
+    /// オクルージョンクエリのためだけの描画用（深度テストは行うが、色も深度も書き込まない）
+    pub const OCCLUSION_PROXY: PipelineOptions = PipelineOptions {
+        label: "Occlusion Proxy Pipeline",
+        color_writes: wgpu::ColorWrites::empty(),
+        depth_write_enabled: false,
+        ..Self::OPAQUE
+    };
+
+    /// 半透明なジオメトリ用（深度テストは行うが深度は書き込まない）
+    pub fn translucent(blend_mode: BlendMode) -> Self {
+        Self {
+            label: "Translucent Pipeline",
+            blend: blend_mode.state(),
+            depth_write_enabled: false,
+            ..Self::OPAQUE
+        }
+    }
+
+    /// スカイボックス用（深度 1.0 のファークリップ面に描画し、深度は書き込まない）
+    pub const SKYBOX: PipelineOptions = PipelineOptions {
+        label: "Skybox Pipeline",
+        cull_mode: None,
+        depth_write_enabled: false,
+        depth_compare: wgpu::CompareFunction::LessEqual,
+        ..Self::OPAQUE
+    };
+
+    /// 無限グリッドの床用（深度はフラグメントシェーダーで求めてテストするが、書き込まない）
+    pub const GRID: PipelineOptions = PipelineOptions {
+        label: "Grid Pipeline",
+        cull_mode: None,
+        blend: wgpu::BlendState::ALPHA_BLENDING,
+        depth_write_enabled: false,
+        ..Self::OPAQUE
+    };
+
+    /// デバッグ用の線分（2頂点ずつを1本の線として描画する）
+    pub const LINES: PipelineOptions = PipelineOptions {
+        label: "Debug Line Pipeline",
+        topology: wgpu::PrimitiveTopology::LineList,
+        cull_mode: None,
+        ..Self::OPAQUE
+    };
+}
+
+/// 頂点シェーダーとフラグメントシェーダーのモジュール
+/// WGSL は1つのモジュールに両方のエントリーポイントを書けるが、glslc が出力する SPIR-V はステージごとに別になる
+#[derive(Clone, Copy)]
+pub struct ShaderStages<'a> {
+    vertex: &'a wgpu::ShaderModule,
+    fragment: &'a wgpu::ShaderModule,
+}
+
+impl<'a> From<&'a wgpu::ShaderModule> for ShaderStages<'a> {
+    fn from(module: &'a wgpu::ShaderModule) -> Self {
+        Self {
+            vertex: module,
+            fragment: module,
+        }
+    }
+}
+
+/// ステージごとに別のファイルから読み込んだシェーダーのモジュール
+pub struct StageModules {
+    pub vertex: wgpu::ShaderModule,
+    pub fragment: wgpu::ShaderModule,
+}
+
+impl<'a> From<&'a StageModules> for ShaderStages<'a> {
+    fn from(modules: &'a StageModules) -> Self {
+        Self {
+            vertex: &modules.vertex,
+            fragment: &modules.fragment,
+        }
+    }
+}
+
+// shader は両方のステージで同じモジュールなら &wgpu::ShaderModule、別々なら ShaderStages を渡す
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn create_render_pipeline<'a>(
+    device: &wgpu::Device,
+    pipeline_cache: &PipelineCache,
+    layout: &wgpu::PipelineLayout,
+    shader: impl Into<ShaderStages<'a>>,
+    buffers: &[wgpu::VertexBufferLayout],
+    format: wgpu::TextureFormat,
+    sample_count: u32,
+    options: &PipelineOptions,
+) -> wgpu::RenderPipeline {
+    let shader = shader.into();
+    pipeline_cache.create(|cache| {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: crash::created_pipeline(options.label),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module: shader.vertex,
+                entry_point: Some(options.vertex_entry),
+                buffers,
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader.fragment,
+                entry_point: Some(options.fragment_entry),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(options.blend),
+                    write_mask: options.color_writes,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: options.topology,
+                polygon_mode: options.polygon_mode,
+                cull_mode: options.cull_mode,
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: options.depth_write_enabled,
+                depth_compare: options.depth_compare,
+                stencil: wgpu::StencilState {
+                    front: options.stencil_face,
+                    back: options.stencil_face,
+                    read_mask: 0xff,
+                    write_mask: options.stencil_write_mask,
+                },
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                ..Default::default()
+            },
+            multiview: None,
+            cache,
+        })
+    })
+}
+
+/// マルチサンプリング用の中間カラーテクスチャを作成する（サンプル数が 1 の場合は不要）
+/// オフスクリーンのテクスチャへ解決するので、フォーマットもそれに合わせる
+pub fn create_msaa_view(
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+    sample_count: u32,
+) -> Option<wgpu::TextureView> {
+    if sample_count <= 1 {
+        return None;
+    }
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("MSAA Color Texture"),
+        size: wgpu::Extent3d {
+            width: config.width.max(1),
+            height: config.height.max(1),
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count,
+        dimension: wgpu::TextureDimension::D2,
+        format: PostPass::FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        view_formats: &[],
+    });
+    Some(texture.create_view(&wgpu::TextureViewDescriptor::default()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_pipeline_gets_a_label() {
+        let presets = [
+            PipelineOptions::OPAQUE,
+            PipelineOptions::SKINNED,
+            PipelineOptions::OUTLINE,
+            PipelineOptions::OCCLUSION_PROXY,
+            PipelineOptions::SKYBOX,
+            PipelineOptions::GRID,
+            PipelineOptions::LINES,
+            PipelineOptions::translucent(BlendMode::Additive),
+        ];
+        for options in presets {
+            assert!(options.label.ends_with(" Pipeline"), "{:?}", options);
+        }
+        // シェーディングごとのパイプラインは、どれを描画しているか分かるラベルにする
+        let shadings = [
+            Shading::Textured,
+            Shading::Flat,
+            Shading::VertexColor,
+            Shading::Normals,
+            Shading::Wireframe,
+        ]
+        .map(|shading| PipelineOptions::for_shading(shading).label);
+        for (i, label) in shadings.iter().enumerate() {
+            assert!(!shadings[..i].contains(label), "{}", label);
+        }
+        assert_eq!(
+            PipelineOptions::OPAQUE.with_label("Model Pipeline").label,
+            "Model Pipeline"
+        );
+    }
+}
@@ -0,0 +1,154 @@
+use std::ffi::{c_char, c_void};
+
+use renderdoc_sys::{RENDERDOC_API_1_4_1, eRENDERDOC_API_Version_1_4_1, pRENDERDOC_GetAPI};
+use tracing::{info, warn};
+
+// RenderDoc から起動したときに読み込まれている、RenderDoc のライブラリの API
+// ライブラリは自分では読み込まず、読み込まれていなければ何もしない
+pub struct RenderDoc {
+    api: *const RENDERDOC_API_1_4_1,
+    // api の関数はライブラリの中にあるので、使い終わるまで持っておく
+    _library: libloading::Library,
+    // 次のフレームを取り込む（F10 キーで予約する）
+    requested: bool,
+    capturing: bool,
+}
+
+// RenderDoc から起動していなくても読み込まないよう、すでに読み込まれているライブラリだけを開く
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn loaded_library() -> Option<libloading::Library> {
+    use libloading::os::unix::{Library, RTLD_NOW};
+    // glibc と Bionic の dlfcn.h の値（libloading は定義していない）
+    const RTLD_NOLOAD: std::ffi::c_int = 4;
+    let name = if cfg!(target_os = "android") {
+        "libVkLayer_GLES_RenderDoc.so"
+    } else {
+        "librenderdoc.so"
+    };
+    // 安全性: RenderDoc がすでに初期化したライブラリなので、開くときに初期化の処理は走らない
+    unsafe { Library::open(Some(name), RTLD_NOW | RTLD_NOLOAD) }
+        .ok()
+        .map(Into::into)
+}
+
+#[cfg(windows)]
+fn loaded_library() -> Option<libloading::Library> {
+    libloading::os::windows::Library::open_already_loaded("renderdoc.dll")
+        .ok()
+        .map(Into::into)
+}
+
+// RenderDoc が対応していないプラットフォーム
+#[cfg(not(any(target_os = "linux", target_os = "android", windows)))]
+fn loaded_library() -> Option<libloading::Library> {
+    None
+}
+
+impl RenderDoc {
+    // RenderDoc から起動していなければ None
+    pub fn load() -> Option<Self> {
+        let library = loaded_library()?;
+        // 安全性: RENDERDOC_GetAPI は renderdoc_app.h で宣言された型の関数
+        let get_api = {
+            let symbol = unsafe { library.get::<pRENDERDOC_GetAPI>(b"RENDERDOC_GetAPI\0") }.ok()?;
+            (*symbol)?
+        };
+        let mut api: *mut c_void = std::ptr::null_mut();
+        // 安全性: 対応しているバージョンなら、api に RENDERDOC_API_1_4_1 を指すポインタが書き込まれる
+        if unsafe { get_api(eRENDERDOC_API_Version_1_4_1, &mut api) } != 1 || api.is_null() {
+            warn!("RenderDoc の API 1.4.1 を使えないため、F10 キーでの取り込みはできません");
+            return None;
+        }
+        Some(Self {
+            api: api.cast(),
+            _library: library,
+            requested: false,
+            capturing: false,
+        })
+    }
+
+    pub fn request(&mut self) {
+        self.requested = true;
+        info!("次のフレームを RenderDoc に取り込みます");
+    }
+
+    // フレームを描画する前に呼ぶ
+    pub fn begin_frame(&mut self) {
+        if !std::mem::take(&mut self.requested) {
+            return;
+        }
+        let api = self.api();
+        if let Some(start) = api.StartFrameCapture {
+            // デバイスとウィンドウに null を渡すと、RenderDoc が今使っているものを取り込む
+            // 安全性: null は renderdoc_app.h で認められた値
+            unsafe { start(std::ptr::null_mut(), std::ptr::null_mut()) };
+            self.capturing = true;
+        }
+    }
+
+    // フレームを表示した後に呼ぶ
+    pub fn end_frame(&mut self) {
+        if !std::mem::take(&mut self.capturing) {
+            return;
+        }
+        let api = self.api();
+        let Some(end) = api.EndFrameCapture else {
+            return;
+        };
+        // 安全性: begin_frame と同じ引数で、始めた取り込みを終える
+        if unsafe { end(std::ptr::null_mut(), std::ptr::null_mut()) } != 1 {
+            warn!("RenderDoc にフレームを取り込めませんでした");
+            return;
+        }
+        match self.last_capture_path() {
+            Some(path) => info!("RenderDoc にフレームを取り込みました: {}", path),
+            None => info!("RenderDoc にフレームを取り込みました"),
+        }
+    }
+
+    // 最後に取り込んだファイルのパス
+    fn last_capture_path(&self) -> Option<String> {
+        let api = self.api();
+        let (count, get) = (api.GetNumCaptures?, api.GetCapture?);
+        // 安全性: 取り込みの数と、その範囲の番号を渡す
+        let index = unsafe { count() }.checked_sub(1)?;
+        // 一度目はパスの長さ（末尾の NUL を含む）だけを受け取り、二度目にパスを書き込ませる
+        let mut length = 0;
+        let null = std::ptr::null_mut();
+        if unsafe { get(index, null, &mut length, null.cast()) } != 1 {
+            return None;
+        }
+        let mut path = vec![0u8; length as usize];
+        if unsafe {
+            get(
+                index,
+                path.as_mut_ptr().cast::<c_char>(),
+                &mut length,
+                null.cast(),
+            )
+        } != 1
+        {
+            return None;
+        }
+        let end = path.iter().position(|&b| b == 0).unwrap_or(path.len());
+        path.truncate(end);
+        Some(String::from_utf8_lossy(&path).into_owned())
+    }
+
+    fn api(&self) -> &RENDERDOC_API_1_4_1 {
+        // 安全性: load で受け取ったポインタは、ライブラリを読み込んでいる間は有効
+        unsafe { &*self.api }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nothing_is_loaded_outside_renderdoc() {
+        // テストは RenderDoc から起動しないので、ライブラリを読み込まずに None になる
+        assert!(RenderDoc::load().is_none());
+        assert!(loaded_library().is_none());
+    }
+}
@@ -0,0 +1,281 @@
+use anyhow::{Result, bail};
+use glam::Mat4;
+
+use crate::animation::Transform;
+
+// シーンの節点の番号
+// 削除した節点の枠は使い回すが、世代を進めるので古い番号で新しい節点を指すことはない
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NodeId {
+    index: u32,
+    generation: u32,
+}
+
+// 親に対する変換と、描画するメッシュ（M は呼び出し側がメッシュを指すのに使う値）
+pub struct Node<M> {
+    pub transform: Transform,
+    pub mesh: Option<M>,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+}
+
+impl<M> Node<M> {
+    // 根なら None
+    pub fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+}
+
+struct Slot<M> {
+    generation: u32,
+    node: Option<Node<M>>,
+}
+
+// 親子関係を持つ節点の木（親を持たない節点が根になる）
+pub struct Scene<M> {
+    slots: Vec<Slot<M>>,
+    // 節点が削除されて空いている枠
+    free: Vec<u32>,
+    roots: Vec<NodeId>,
+}
+
+impl<M> Default for Scene<M> {
+    fn default() -> Self {
+        Self {
+            slots: Vec::new(),
+            free: Vec::new(),
+            roots: Vec::new(),
+        }
+    }
+}
+
+impl<M> Scene<M> {
+    // 削除済みの番号なら None
+    pub fn get(&self, id: NodeId) -> Option<&Node<M>> {
+        self.slots
+            .get(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)?
+            .node
+            .as_ref()
+    }
+
+    pub fn get_mut(&mut self, id: NodeId) -> Option<&mut Node<M>> {
+        self.slots
+            .get_mut(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)?
+            .node
+            .as_mut()
+    }
+
+    // parent の子（None なら根）として節点を追加する
+    pub fn insert(
+        &mut self,
+        parent: Option<NodeId>,
+        transform: Transform,
+        mesh: Option<M>,
+    ) -> Result<NodeId> {
+        if parent.is_some_and(|parent| self.get(parent).is_none()) {
+            bail!("親の節点は削除されています: {:?}", parent);
+        }
+        let node = Node {
+            transform,
+            mesh,
+            parent: None,
+            children: Vec::new(),
+        };
+        let id = match self.free.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                slot.node = Some(node);
+                NodeId {
+                    index,
+                    generation: slot.generation,
+                }
+            }
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    node: Some(node),
+                });
+                NodeId {
+                    index: self.slots.len() as u32 - 1,
+                    generation: 0,
+                }
+            }
+        };
+        self.attach(id, parent);
+        Ok(id)
+    }
+
+    // 節点を parent の子（None なら根）に付け替える（ワールド座標ではなく親に対する変換を保つ）
+    // 自分や自分の子孫を親にすると木が輪になるので受け付けない
+    pub fn set_parent(&mut self, id: NodeId, parent: Option<NodeId>) -> Result<()> {
+        if self.get(id).is_none() {
+            bail!("節点は削除されています: {:?}", id);
+        }
+        if let Some(parent) = parent {
+            if self.get(parent).is_none() {
+                bail!("親の節点は削除されています: {:?}", parent);
+            }
+            if self.is_ancestor(id, parent) {
+                bail!("{:?} を自分の子孫 {:?} の子にはできません", id, parent);
+            }
+        }
+        self.detach(id);
+        self.attach(id, parent);
+        Ok(())
+    }
+
+    // 節点と、その子孫をすべて削除する
+    pub fn remove(&mut self, id: NodeId) -> Result<()> {
+        if self.get(id).is_none() {
+            bail!("節点は削除されています: {:?}", id);
+        }
+        self.detach(id);
+        let mut stack = vec![id];
+        while let Some(id) = stack.pop() {
+            let slot = &mut self.slots[id.index as usize];
+            if let Some(node) = slot.node.take() {
+                stack.extend(node.children);
+            }
+            slot.generation += 1;
+            self.free.push(id.index);
+        }
+        Ok(())
+    }
+
+    // 根から順にたどって求めた、メッシュを持つ節点のワールド行列（親は子より先に並ぶ）
+    pub fn world_matrices(&self) -> Vec<(&M, Mat4)> {
+        let mut matrices = Vec::new();
+        let mut stack: Vec<(NodeId, Mat4)> = self
+            .roots
+            .iter()
+            .rev()
+            .map(|&root| (root, Mat4::IDENTITY))
+            .collect();
+        while let Some((id, parent_world)) = stack.pop() {
+            let Some(node) = self.get(id) else {
+                continue;
+            };
+            let world = parent_world * node.transform.matrix();
+            if let Some(mesh) = &node.mesh {
+                matrices.push((mesh, world));
+            }
+            stack.extend(node.children.iter().rev().map(|&child| (child, world)));
+        }
+        matrices
+    }
+
+    // ancestor から親をたどって node に着くか（同じ節点も含む）
+    fn is_ancestor(&self, ancestor: NodeId, mut node: NodeId) -> bool {
+        loop {
+            if node == ancestor {
+                return true;
+            }
+            match self.get(node).and_then(|node| node.parent) {
+                Some(parent) => node = parent,
+                None => return false,
+            }
+        }
+    }
+
+    fn attach(&mut self, id: NodeId, parent: Option<NodeId>) {
+        match parent.and_then(|parent| self.get_mut(parent)) {
+            Some(parent_node) => parent_node.children.push(id),
+            None => self.roots.push(id),
+        }
+        if let Some(node) = self.get_mut(id) {
+            node.parent = parent;
+        }
+    }
+
+    // 親の子の一覧（根なら根の一覧）から外す
+    fn detach(&mut self, id: NodeId) {
+        let parent = self.get(id).and_then(|node| node.parent);
+        let siblings = match parent.and_then(|parent| self.get_mut(parent)) {
+            Some(parent_node) => &mut parent_node.children,
+            None => &mut self.roots,
+        };
+        siblings.retain(|&sibling| sibling != id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{Quat, Vec3};
+
+    use super::*;
+
+    fn translation(x: f32) -> Transform {
+        Transform {
+            translation: Vec3::X * x,
+            ..Transform::IDENTITY
+        }
+    }
+
+    fn positions(scene: &Scene<&'static str>) -> Vec<(&'static str, Vec3)> {
+        scene
+            .world_matrices()
+            .into_iter()
+            .map(|(name, world)| (*name, world.transform_point3(Vec3::ZERO)))
+            .collect()
+    }
+
+    #[test]
+    fn world_matrices_compose_parent_transforms() {
+        let mut scene = Scene::default();
+        // 原点で Z 軸まわりに 90° 回った太陽の x = 2 に惑星、惑星の x = 1 に衛星を置く
+        let sun = scene
+            .insert(
+                None,
+                Transform {
+                    rotation: Quat::from_rotation_z(std::f32::consts::FRAC_PI_2),
+                    ..Transform::IDENTITY
+                },
+                Some("sun"),
+            )
+            .unwrap();
+        let planet = scene
+            .insert(Some(sun), translation(2.0), Some("planet"))
+            .unwrap();
+        scene
+            .insert(Some(planet), translation(1.0), Some("moon"))
+            .unwrap();
+        let positions = positions(&scene);
+        assert_eq!(positions.len(), 3);
+        assert_eq!(positions[0].0, "sun");
+        assert!((positions[1].1 - Vec3::new(0.0, 2.0, 0.0)).length() < 1e-5);
+        assert!((positions[2].1 - Vec3::new(0.0, 3.0, 0.0)).length() < 1e-5);
+    }
+
+    #[test]
+    fn reparenting_and_removal_keep_other_handles_valid() {
+        let mut scene = Scene::default();
+        let a = scene.insert(None, translation(1.0), Some("a")).unwrap();
+        let b = scene.insert(None, translation(10.0), Some("b")).unwrap();
+        let child = scene
+            .insert(Some(a), translation(1.0), Some("child"))
+            .unwrap();
+
+        // 付け替えると親に対する変換はそのままで、新しい親に付いて動く
+        scene.set_parent(child, Some(b)).unwrap();
+        assert!(positions(&scene).contains(&("child", Vec3::X * 11.0)));
+        // 自分の子孫を親にはできない
+        assert!(scene.set_parent(b, Some(child)).is_err());
+
+        // b を消すと子も消え、空いた枠を使い回しても古い番号は無効のまま
+        scene.remove(b).unwrap();
+        assert!(scene.get(child).is_none());
+        let reused = scene
+            .insert(None, translation(5.0), Some("reused"))
+            .unwrap();
+        assert!(scene.get(b).is_none() && scene.get(child).is_none());
+        assert!(scene.remove(child).is_err());
+        assert_eq!(scene.get(a).unwrap().mesh, Some("a"));
+        assert_eq!(scene.get(reused).unwrap().mesh, Some("reused"));
+        assert_eq!(
+            positions(&scene),
+            [("a", Vec3::X), ("reused", Vec3::X * 5.0)]
+        );
+    }
+}
@@ -0,0 +1,364 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result, bail};
+
+use crate::adapter;
+use crate::config::Config;
+use crate::fullscreen::FullscreenMode;
+use crate::headless;
+use crate::logging::LogFormat;
+
+const DEFAULT_WIDTH: u32 = 800;
+const DEFAULT_HEIGHT: u32 = 600;
+const DEFAULT_TITLE: &str = "wgpu:03 triangle";
+// 希望するマルチサンプリングのサンプル数
+const DEFAULT_MSAA: u32 = 4;
+
+/// `--help` と引数が正しくないときに表示する使い方
+pub const USAGE: &str = "使い方: wgpu03 [オプション]
+
+ウィンドウ:
+  --width N                   ウィンドウの幅（論理ピクセル）
+  --height N                  ウィンドウの高さ（論理ピクセル）
+  --title TEXT                タイトルの先頭
+  --fullscreen                ボーダーレスの全画面表示で起動する
+  --exclusive-fullscreen      排他的な全画面表示で起動する
+  --transparent               背景を透明にしたウィンドウで表示する
+  --x11                       Wayland のセッションでも XWayland 経由の X11 で表示する
+
+描画:
+  --vsync on|off              垂直同期を待つか
+  --max-fps N                 FPS の上限
+  --msaa 1|2|4|8              マルチサンプリングのサンプル数
+  --hdr                       対応していれば HDR のサーフェイスに出力する
+  --no-push-constants         プッシュ定数を使わない
+  --no-indirect               間接描画を使わない
+
+GPU:
+  --backend vulkan|dx12|metal|gl
+                              使うバックエンド
+  --adapter NAME              名前に NAME を含むアダプタを選ぶ
+  --power low|high            GPU の電力の好み
+  --print-caps                アダプタとサーフェイスの対応状況を表示して終了する
+
+アセット:
+  --model PATH                読み込むOBJモデル
+  --gltf PATH                 読み込むglTFシーン
+  --animated PATH             アニメーションするglTFモデル
+  --heightmap PATH            地形の高さマップ
+
+そのほか:
+  --headless WxH OUTPUT.png   ウィンドウを作らずに1フレームを描画して保存する
+  --record N                  起動してから N フレームを recording/ に録画する
+  --bench-bundles             レンダーバンドルのベンチマークを実行して終了する
+  --log-format json|pretty    ログの書式
+  -h, --help                  この使い方を表示する
+";
+
+/// config.toml とコマンドラインで指定するウィンドウと描画の設定（コマンドラインの方が優先される）
+///
+/// コマンドライン引数はすべてここで一度だけ読み、知らない引数や正しくない値はエラーにする。
+#[derive(Clone, Debug, PartialEq)]
+pub struct Settings {
+    /// ウィンドウの幅（論理ピクセル）
+    pub width: u32,
+    /// ウィンドウの高さ（論理ピクセル）
+    pub height: u32,
+    /// ウィンドウの位置（物理ピクセル。config.toml で前回閉じたときの位置を指定する）
+    pub position: Option<(i32, i32)>,
+    /// タイトルの先頭（続けてシェーディングとアダプタの名前を表示する）
+    pub title: String,
+    /// 垂直同期を待つか（None ならサーフェイスが対応している中から選ぶ）
+    pub vsync: Option<bool>,
+    /// 希望するマルチサンプリングのサンプル数（対応していなければ 1 にする）
+    pub msaa: u32,
+    /// 背景の色（None なら既定の色。透明なウィンドウでは使わない）
+    pub clear_color: Option<[f64; 3]>,
+    /// 使うバックエンド（None なら環境変数 WGPU_BACKEND も見る）
+    pub backends: Option<wgpu::Backends>,
+    /// 名前にこの文字列を含むアダプタを選ぶ（None なら環境変数 WGPU03_ADAPTER も見る）
+    pub adapter: Option<String>,
+    /// 全画面表示で起動する
+    pub fullscreen: Option<FullscreenMode>,
+    /// ウィンドウを作らずに1フレームを描画して保存する（`--headless`）
+    pub headless: Option<headless::Request>,
+    /// ログの書式（`--log-format json|pretty`）
+    pub log_format: LogFormat,
+    /// GPU の電力の好み（`--power low|high`。None なら環境変数 WGPU03_POWER も見る）
+    pub power: Option<wgpu::PowerPreference>,
+    /// FPS の上限（`--max-fps N`。None なら上限なし）
+    pub max_fps: Option<u32>,
+    /// 起動してから録画するフレーム数（`--record N`）
+    pub record: Option<u32>,
+    /// 対応していれば HDR のサーフェイスに出力する（`--hdr`）
+    pub hdr: bool,
+    /// 背景を透明にしたウィンドウで表示する（`--transparent`）
+    pub transparent: bool,
+    /// X11 で表示する（`--x11`。Wayland のセッションでも XWayland 経由にする）
+    pub x11: bool,
+    /// アダプタとサーフェイスの対応状況を表示して終了する（`--print-caps`）
+    pub print_caps: bool,
+    /// レンダーバンドルのベンチマークを実行して終了する（`--bench-bundles`）
+    pub bench_bundles: bool,
+    /// 対応していてもプッシュ定数を使わない（`--no-push-constants` で false）
+    pub push_constants: bool,
+    /// 対応していても間接描画を使わない（`--no-indirect` で false）
+    pub indirect: bool,
+    /// 読み込むOBJモデル（`--model <path>`。None なら同梱のもの）
+    pub model: Option<PathBuf>,
+    /// 地形の高さマップ（`--heightmap <path>`）
+    pub heightmap: Option<PathBuf>,
+    /// 読み込むglTFシーン（`--gltf <path>`）
+    pub gltf: Option<PathBuf>,
+    /// アニメーションするglTFモデル（`--animated <path>`）
+    pub animated: Option<PathBuf>,
+    /// 使い方を表示して終了する（`--help`）
+    pub help: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            width: DEFAULT_WIDTH,
+            height: DEFAULT_HEIGHT,
+            position: None,
+            title: DEFAULT_TITLE.to_string(),
+            vsync: None,
+            msaa: DEFAULT_MSAA,
+            clear_color: None,
+            backends: None,
+            adapter: None,
+            fullscreen: None,
+            headless: None,
+            log_format: LogFormat::default(),
+            power: None,
+            max_fps: None,
+            record: None,
+            hdr: false,
+            transparent: false,
+            x11: false,
+            print_caps: false,
+            bench_bundles: false,
+            push_constants: true,
+            indirect: true,
+            model: None,
+            heightmap: None,
+            gltf: None,
+            animated: None,
+            help: false,
+        }
+    }
+}
+
+impl Settings {
+    /// config の値をコマンドライン引数で上書きする
+    ///
+    /// 引数の正しくない値や組み合わせは、イベントループを始める前にエラーにする。
+    pub fn from_args(config: &Config) -> Result<Self> {
+        Self::parse(Self::from_config(config), std::env::args().skip(1))
+    }
+
+    // config.toml の値は正しくなくても起動できるよう、警告を表示して既定の値にする
+    fn from_config(config: &Config) -> Self {
+        let mut settings = Self::default();
+        let window = config.window;
+        settings.width = window.width.filter(|&w| w > 0).unwrap_or(DEFAULT_WIDTH);
+        settings.height = window.height.filter(|&h| h > 0).unwrap_or(DEFAULT_HEIGHT);
+        settings.position = window.x.zip(window.y);
+        settings.vsync = config.vsync;
+        match config.msaa {
+            Some(msaa) if is_valid_msaa(msaa) => settings.msaa = msaa,
+            Some(msaa) => eprintln!(
+                "config.toml の msaa は 1・2・4・8 のどれかにしてください: {}",
+                msaa
+            ),
+            None => {}
+        }
+        settings.clear_color = config.clear_color;
+        settings
+    }
+
+    fn parse(mut settings: Self, args: impl IntoIterator<Item = String>) -> Result<Self> {
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .with_context(|| format!("{} には値を指定してください", arg))
+            };
+            match arg.as_str() {
+                "--width" => settings.width = parse_size(&arg, &value()?)?,
+                "--height" => settings.height = parse_size(&arg, &value()?)?,
+                "--title" => settings.title = value()?,
+                "--vsync" => {
+                    settings.vsync = match value()?.as_str() {
+                        "on" => Some(true),
+                        "off" => Some(false),
+                        other => bail!("--vsync には on か off を指定してください: {}", other),
+                    }
+                }
+                "--msaa" => {
+                    let msaa = value()?;
+                    settings.msaa = match msaa.parse() {
+                        Ok(msaa) if is_valid_msaa(msaa) => msaa,
+                        _ => bail!(
+                            "--msaa には 1・2・4・8 のどれかを指定してください: {}",
+                            msaa
+                        ),
+                    }
+                }
+                "--backend" => {
+                    let backend = value()?;
+                    settings.backends = Some(adapter::parse_backends(&backend).with_context(|| {
+                        format!(
+                            "--backend には vulkan・dx12・metal・gl のどれかを指定してください: {}",
+                            backend
+                        )
+                    })?);
+                }
+                "--adapter" => settings.adapter = Some(value()?),
+                "--fullscreen" => settings.fullscreen = Some(FullscreenMode::Borderless),
+                "--exclusive-fullscreen" => settings.fullscreen = Some(FullscreenMode::Exclusive),
+                "--log-format" => {
+                    let format = value()?;
+                    settings.log_format = LogFormat::parse(&format).with_context(|| {
+                        format!(
+                            "--log-format には json か pretty を指定してください: {}",
+                            format
+                        )
+                    })?;
+                }
+                "--headless" => {
+                    settings.headless = Some(headless::parse_request(args.next(), args.next())?)
+                }
+                "--power" => {
+                    let power = value()?;
+                    settings.power = Some(adapter::parse_power(&power).with_context(|| {
+                        format!("--power には low か high を指定してください: {}", power)
+                    })?);
+                }
+                "--max-fps" => settings.max_fps = Some(parse_count(&arg, &value()?)?),
+                "--record" => settings.record = Some(parse_count(&arg, &value()?)?),
+                "--hdr" => settings.hdr = true,
+                "--transparent" => settings.transparent = true,
+                "--x11" => settings.x11 = true,
+                "--print-caps" => settings.print_caps = true,
+                "--bench-bundles" => settings.bench_bundles = true,
+                "--no-push-constants" => settings.push_constants = false,
+                "--no-indirect" => settings.indirect = false,
+                "--model" => settings.model = Some(PathBuf::from(value()?)),
+                "--heightmap" => settings.heightmap = Some(PathBuf::from(value()?)),
+                "--gltf" => settings.gltf = Some(PathBuf::from(value()?)),
+                "--animated" => settings.animated = Some(PathBuf::from(value()?)),
+                "-h" | "--help" => settings.help = true,
+                _ => bail!("知らない引数です: {}", arg),
+            }
+        }
+        if settings.headless.is_some() && settings.fullscreen.is_some() {
+            bail!("--headless ではウィンドウを作らないので、全画面表示は指定できません");
+        }
+        Ok(settings)
+    }
+}
+
+fn is_valid_msaa(msaa: u32) -> bool {
+    matches!(msaa, 1 | 2 | 4 | 8)
+}
+
+fn parse_size(flag: &str, value: &str) -> Result<u32> {
+    match value.parse() {
+        Ok(size) if size > 0 => Ok(size),
+        _ => bail!("{} には 1 以上の大きさを指定してください: {}", flag, value),
+    }
+}
+
+// `--max-fps` の FPS と `--record` のフレーム数
+fn parse_count(flag: &str, value: &str) -> Result<u32> {
+    match value.parse() {
+        Ok(count) if count > 0 => Ok(count),
+        _ => bail!("{} には 1 以上の数を指定してください: {}", flag, value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Settings> {
+        Settings::parse(
+            Settings::default(),
+            args.split_whitespace().map(String::from),
+        )
+    }
+
+    #[test]
+    fn options_are_validated_before_the_event_loop_starts() {
+        let settings =
+            parse("--width 1280 --height 720 --vsync off --msaa 8 --model a.obj --backend gl")
+                .unwrap();
+        assert_eq!((settings.width, settings.height), (1280, 720));
+        assert_eq!(settings.vsync, Some(false));
+        assert_eq!(settings.msaa, 8);
+        assert_eq!(settings.backends, Some(wgpu::Backends::GL));
+        assert_eq!(settings.model, Some(PathBuf::from("a.obj")));
+        assert_eq!(parse("").unwrap(), Settings::default());
+
+        assert!(parse("--msaa 3").is_err());
+        assert!(parse("--vsync maybe").is_err());
+        assert!(parse("--width 0").is_err());
+        assert!(parse("--title").is_err());
+        assert!(parse("--backend webgpu").is_err());
+        assert!(parse("--headless 320x240 out.png --fullscreen").is_err());
+        assert_eq!(
+            parse("--log-format json").unwrap().log_format,
+            LogFormat::Json
+        );
+        assert!(parse("--log-format xml").is_err());
+
+        let settings = parse("--power low --max-fps 30 --hdr --no-indirect").unwrap();
+        assert_eq!(settings.power, Some(wgpu::PowerPreference::LowPower));
+        assert_eq!(settings.max_fps, Some(30));
+        assert!(settings.hdr && !settings.indirect && settings.push_constants);
+        assert_eq!(settings.model, None);
+        assert!(parse("--power medium").is_err());
+        assert!(parse("--record 0").is_err());
+        assert!(parse("--gltf").is_err());
+        // 打ち間違えた引数は無視せずにエラーにする
+        assert!(parse("--msa 4").is_err());
+        assert!(parse("-h").unwrap().help);
+    }
+
+    #[test]
+    fn usage_lists_every_option() {
+        // parse の match に書かれた引数がすべて使い方に載っているか
+        let source = include_str!("settings.rs");
+        let start = source.find("    fn parse(").unwrap();
+        let body = &source[start..source.find("fn is_valid_msaa").unwrap()];
+        let flags: Vec<&str> = body
+            .split('"')
+            .skip(1)
+            .step_by(2)
+            .filter(|s| s.starts_with('-') && !s.contains(' '))
+            .collect();
+        assert!(flags.contains(&"--animated"));
+        for flag in flags {
+            assert!(USAGE.contains(flag), "{} が使い方にありません", flag);
+        }
+    }
+
+    #[test]
+    fn command_line_overrides_the_config_file() {
+        let mut config = Config::default();
+        config.window.width = Some(1024);
+        config.window.x = Some(100);
+        config.window.y = Some(50);
+        config.msaa = Some(3);
+        config.vsync = Some(true);
+        let base = Settings::from_config(&config);
+        // 正しくない msaa は既定の値にする
+        assert_eq!(base.msaa, DEFAULT_MSAA);
+        assert_eq!(base.position, Some((100, 50)));
+        let settings = Settings::parse(base, ["--vsync", "off"].map(String::from)).unwrap();
+        assert_eq!(settings.width, 1024);
+        assert_eq!(settings.vsync, Some(false));
+    }
+}
@@ -1,31 +1,106 @@
+struct Uniforms {
+    tint: vec4<f32>,
+    time: f32,
+};
+
+#include "include/camera.wgsl"
+
+#include "include/lighting.wgsl"
+
+@group(0) @binding(1) var<uniform> uniforms: Uniforms;
+
+@group(1) @binding(0) var t_diffuse: texture_2d<f32>;
+@group(1) @binding(1) var s_diffuse: sampler;
+
+struct Reflection {
+    // 0.0 で元の色のみ、1.0 で周囲の映り込みのみ
+    reflectivity: f32,
+    // 0.0 で鏡のように映り、1.0 で最も小さいミップレベルのぼやけた映り込みになる
+    roughness: f32,
+};
+
+@group(3) @binding(0) var<uniform> reflection: Reflection;
+@group(3) @binding(1) var t_environment: texture_cube<f32>;
+@group(3) @binding(2) var s_environment: sampler;
+
+// 視線を法線で反射させた方向の周囲の色（粗いほど小さいミップレベルを参照してぼかす）
+fn environment_color(normal: vec3<f32>, view_dir: vec3<f32>) -> vec3<f32> {
+    let dir = reflect(-view_dir, normal);
+    let max_level = f32(textureNumLevels(t_environment) - 1u);
+    return textureSampleLevel(t_environment, s_environment, dir, reflection.roughness * max_level).rgb;
+}
+
+// Blinn-Phong の環境光の強さと鏡面反射の鋭さ
+const AMBIENT_STRENGTH: f32 = 0.1;
+const SHININESS: f32 = 32.0;
+
+struct VInput {
+    @location(0) position: vec3<f32>,
+    @location(1) color: vec3<f32>,
+    @location(2) tex_coords: vec2<f32>,
+    @location(3) normal: vec3<f32>,
+};
+
+// インスタンスごとのデータ（ロケーション5以降を使う）
+struct InstanceInput {
+    @location(5) offset: vec3<f32>,
+    @location(6) color: vec4<f32>,
+};
+
 struct VOutput {
     @location(0) v_color: vec4<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) world_position: vec3<f32>,
+    @location(3) world_normal: vec3<f32>,
     @builtin(position) position: vec4<f32>,
 };
 
 @vertex
-fn vs_main(
-    @builtin(vertex_index) in_vertex_index: u32
-) -> VOutput {
-    var pos = array<vec2<f32>, 3>(
-        vec2<f32>(0.0, 0.5),
-        vec2<f32>(-0.5, -0.5),
-        vec2<f32>(0.5, -0.5)
-    );
-
-    var color = array<vec3<f32>, 3>(
-        vec3<f32>(1.0, 0.0, 0.0),
-        vec3<f32>(0.0, 1.0, 0.0),
-        vec3<f32>(0.0, 0.0, 1.0)
-    );
-
+fn vs_main(in: VInput, instance: InstanceInput) -> VOutput {
     var out: VOutput;
-    out.position = vec4<f32>(pos[in_vertex_index], 0.0, 1.0);
-    out.v_color = vec4<f32>(color[in_vertex_index], 1.0);
+    let world = camera.model * vec4<f32>(in.position + instance.offset, 1.0);
+    out.position = camera.view_proj * world;
+    out.world_position = world.xyz;
+    // モデル行列は回転のみなので、法線もそのまま変換できる
+    out.world_normal = (camera.normal_matrix * vec4<f32>(in.normal, 0.0)).xyz;
+    out.v_color = vec4<f32>(in.color, 1.0) * instance.color;
+    out.tex_coords = in.tex_coords;
     return out;
 }
 
 @fragment
 fn fs_main(in: VOutput) -> @location(0) vec4<f32> {
-    return in.v_color;
-}
\ No newline at end of file
+    let tex_color = textureSample(t_diffuse, s_diffuse, in.tex_coords);
+    let base = in.v_color * tex_color;
+
+    let normal = normalize(in.world_normal);
+    let view_dir = normalize(camera.view_position.xyz - in.world_position);
+    // 影はシャドウマップを持つ先頭の光源についてのみ求める
+    let shadow = shadow_factor(in.world_position);
+
+    var lit = AMBIENT_STRENGTH * base.rgb;
+    for (var i = 0u; i < light_info.count; i += 1u) {
+        let light = lights[i];
+        let light_dir = normalize(light.position - in.world_position);
+        let half_dir = normalize(view_dir + light_dir);
+        let diffuse = light.color * max(dot(normal, light_dir), 0.0);
+        let specular = light.color * pow(max(dot(normal, half_dir), 0.0), SHININESS);
+        lit += (diffuse * base.rgb + specular) * select(1.0, shadow, i == 0u);
+    }
+    lit = mix(lit, environment_color(normal, view_dir), reflection.reflectivity);
+    return vec4<f32>(lit, base.a) * uniforms.tint;
+}
+
+// 陰影を付けずに1色で塗りつぶす（形だけを確認する）
+const FLAT_COLOR: vec4<f32> = vec4<f32>(0.8, 0.8, 0.8, 1.0);
+
+@fragment
+fn fs_flat(in: VOutput) -> @location(0) vec4<f32> {
+    return FLAT_COLOR * uniforms.tint;
+}
+
+// 頂点カラー（とインスタンスの色）の補間結果だけを表示する
+@fragment
+fn fs_vertex_color(in: VOutput) -> @location(0) vec4<f32> {
+    return in.v_color * uniforms.tint;
+}
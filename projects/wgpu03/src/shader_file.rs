@@ -0,0 +1,103 @@
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::Path;
+
+use anyhow::{Result, bail};
+
+use crate::preprocess::Expanded;
+
+// SPIR-V の先頭の1語（書き出した環境のバイト順で並ぶ）
+const SPIRV_MAGIC: u32 = 0x0723_0203;
+
+// ファイルから読み込んだシェーダー（拡張子が .spv なら SPIR-V、それ以外は WGSL）
+pub enum ShaderFile {
+    // インクルードを展開した WGSL
+    Wgsl(Expanded),
+    // glslc などで変換した SPIR-V（ステージごとに別のファイルで、エントリーポイントはどれも main になる）
+    SpirV { path: String, bytes: Vec<u8> },
+}
+
+pub fn is_spirv(path: &str) -> bool {
+    Path::new(path).extension().is_some_and(|ext| ext == "spv")
+}
+
+impl ShaderFile {
+    // 読み込んだファイル（WGSL ならインクルードを展開し始めたファイル）
+    pub fn path(&self) -> &str {
+        match self {
+            Self::Wgsl(shader) => &shader.files()[0],
+            Self::SpirV { path, .. } => path,
+        }
+    }
+
+    // シェーダーモジュールを使い回す目印にするハッシュ
+    pub fn hash(&self) -> u64 {
+        match self {
+            Self::Wgsl(shader) => shader.hash(),
+            Self::SpirV { bytes, .. } => {
+                let mut hasher = DefaultHasher::new();
+                bytes.hash(&mut hasher);
+                hasher.finish()
+            }
+        }
+    }
+
+    // WGSL のソースを書き換える（SPIR-V はそのまま）
+    pub fn map_wgsl(self, f: impl FnOnce(Expanded) -> Expanded) -> Self {
+        match self {
+            Self::Wgsl(shader) => Self::Wgsl(f(shader)),
+            spirv => spirv,
+        }
+    }
+
+    // シェーダーモジュールの作成に渡すソース
+    // make_spirv は形式が違うとパニックするので、SPIR-V はその前に確かめてエラーにする
+    pub fn source(&self) -> Result<wgpu::ShaderSource<'_>> {
+        match self {
+            Self::Wgsl(shader) => Ok(wgpu::ShaderSource::Wgsl(shader.source().into())),
+            Self::SpirV { path, bytes } => {
+                check_spirv(bytes).map_err(|e| e.context(format!("{} を読み込めません", path)))?;
+                Ok(wgpu::util::make_spirv(bytes))
+            }
+        }
+    }
+}
+
+// 長さが4バイトの倍数で、先頭がどちらかのバイト順の SPIR-V の目印になっているか
+fn check_spirv(bytes: &[u8]) -> Result<()> {
+    let Some(magic) = bytes.first_chunk::<4>() else {
+        bail!("SPIR-V のファイルが空か、短すぎます");
+    };
+    if !bytes.len().is_multiple_of(4) {
+        bail!(
+            "SPIR-V の長さが4バイトの倍数ではありません（{} バイト）",
+            bytes.len()
+        );
+    }
+    let magic = u32::from_le_bytes(*magic);
+    if magic != SPIRV_MAGIC && magic != SPIRV_MAGIC.swap_bytes() {
+        bail!(
+            "SPIR-V ではないファイルです（先頭が {:#010x}）。テキストのシェーダーなら拡張子を .wgsl にしてください",
+            magic
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn malformed_spirv_is_rejected_before_reaching_make_spirv() {
+        assert!(is_spirv("normals.vert.spv"));
+        assert!(!is_spirv("shader.wgsl"));
+
+        let header = SPIRV_MAGIC.to_le_bytes();
+        assert!(check_spirv(&[header, [0; 4]].concat()).is_ok());
+        // ビッグエンディアンで書き出したものも make_spirv が並べ替える
+        assert!(check_spirv(&SPIRV_MAGIC.to_be_bytes()).is_ok());
+        assert!(check_spirv(&[]).is_err());
+        assert!(check_spirv(&[header.as_slice(), &[0]].concat()).is_err());
+        assert!(check_spirv(b"@vertex fn main() {}").is_err());
+    }
+}
@@ -0,0 +1,149 @@
+use tracing::info;
+
+// 三角形や立方体の描画に使うシェーディングの種類（Tab キーで順に切り替える）
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Shading {
+    // テクスチャと点光源の陰影・影を付ける（通常の表示）
+    Textured,
+    // 陰影を付けずに1色で塗りつぶす
+    Flat,
+    // 頂点カラーだけを補間して表示する
+    VertexColor,
+    // 法線の向きを色で表示する（GLSL から変換した SPIR-V のシェーダーで描画する）
+    Normals,
+    Wireframe,
+}
+
+impl Shading {
+    const ALL: [Shading; 5] = [
+        Shading::Textured,
+        Shading::Flat,
+        Shading::VertexColor,
+        Shading::Normals,
+        Shading::Wireframe,
+    ];
+
+    fn index(self) -> usize {
+        Self::ALL.iter().position(|&s| s == self).unwrap()
+    }
+
+    // ウィンドウのタイトルに表示する名前
+    pub fn name(self) -> &'static str {
+        match self {
+            Shading::Textured => "Textured",
+            Shading::Flat => "Flat Color",
+            Shading::VertexColor => "Vertex Color",
+            Shading::Normals => "Normals (SPIR-V)",
+            Shading::Wireframe => "Wireframe",
+        }
+    }
+
+    // パイプラインの作成に必要なデバイスの機能
+    pub fn required_features(self) -> wgpu::Features {
+        match self {
+            Shading::Wireframe => wgpu::Features::POLYGON_MODE_LINE,
+            _ => wgpu::Features::empty(),
+        }
+    }
+}
+
+// current の次から順に探して、features で作成できる最初のシェーディング
+// （どれも作成できなければ current のまま）
+fn next_supported(current: Shading, features: wgpu::Features) -> Shading {
+    let count = Shading::ALL.len();
+    (1..=count)
+        .map(|step| Shading::ALL[(current.index() + step) % count])
+        .find(|shading| {
+            let supported = features.contains(shading.required_features());
+            if !supported {
+                info!(
+                    "{} はデバイスが {:?} に対応していないため飛ばします",
+                    shading.name(),
+                    shading.required_features()
+                );
+            }
+            supported
+        })
+        .unwrap_or(current)
+}
+
+// シェーディングごとのパイプラインを、初めて選ばれたときに作成して持っておく
+pub struct PipelineRegistry {
+    pipelines: [Option<wgpu::RenderPipeline>; Shading::ALL.len()],
+    current: Shading,
+    features: wgpu::Features,
+}
+
+impl PipelineRegistry {
+    // features はデバイスで有効になっている機能（device.features()）
+    // 最初のシェーディングのパイプラインはここで作成する
+    pub fn new(
+        features: wgpu::Features,
+        current: Shading,
+        create: impl FnOnce(Shading) -> wgpu::RenderPipeline,
+    ) -> Self {
+        let mut registry = Self {
+            pipelines: Default::default(),
+            current,
+            features,
+        };
+        registry.pipelines[current.index()] = Some(create(current));
+        registry
+    }
+
+    pub fn current(&self) -> Shading {
+        self.current
+    }
+
+    pub fn is_supported(&self, shading: Shading) -> bool {
+        self.features.contains(shading.required_features())
+    }
+
+    pub fn pipeline(&self) -> &wgpu::RenderPipeline {
+        self.pipelines[self.current.index()]
+            .as_ref()
+            .expect("the selected pipeline is created on selection")
+    }
+
+    // 対応していないシェーディングは選ばずに false を返す
+    pub fn select(
+        &mut self,
+        shading: Shading,
+        create: impl FnOnce(Shading) -> wgpu::RenderPipeline,
+    ) -> bool {
+        if !self.is_supported(shading) {
+            return false;
+        }
+        self.pipelines[shading.index()].get_or_insert_with(|| create(shading));
+        self.current = shading;
+        true
+    }
+
+    // 作成できない種類を飛ばして次のシェーディングに切り替える
+    pub fn select_next(&mut self, create: impl FnOnce(Shading) -> wgpu::RenderPipeline) {
+        self.select(next_supported(self.current, self.features), create);
+    }
+
+    // サンプル数などが変わったら作成済みのパイプラインを捨て、選択中のものだけ作り直す
+    pub fn rebuild(&mut self, create: impl FnOnce(Shading) -> wgpu::RenderPipeline) {
+        self.pipelines = Default::default();
+        self.pipelines[self.current.index()] = Some(create(self.current));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cycle_skips_shadings_without_device_features() {
+        let all = wgpu::Features::POLYGON_MODE_LINE;
+        assert_eq!(next_supported(Shading::VertexColor, all), Shading::Normals);
+        assert_eq!(next_supported(Shading::Normals, all), Shading::Wireframe);
+        assert_eq!(next_supported(Shading::Wireframe, all), Shading::Textured);
+        // ワイヤーフレームに対応していなければ法線の次は最初に戻る
+        let none = wgpu::Features::empty();
+        assert_eq!(next_supported(Shading::Normals, none), Shading::Textured);
+        assert_eq!(next_supported(Shading::Textured, none), Shading::Flat);
+    }
+}
@@ -0,0 +1,53 @@
+struct Camera {
+    view_proj: mat4x4<f32>,
+    model: mat4x4<f32>,
+    view_position: vec4<f32>,
+};
+
+struct LightInfo {
+    // 先頭の光源から見たビュー・平行投影行列
+    view_proj: mat4x4<f32>,
+    count: u32,
+};
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+@group(1) @binding(0) var<uniform> light_info: LightInfo;
+// スキンメッシュの関節ごとの行列（ストレージバッファを使えない環境ではユニフォーム配列に書き換える）
+@group(2) @binding(0) var<storage, read> joints: array<mat4x4<f32>>;
+
+// 深度だけを書き込むので、フラグメントシェーダーは持たない
+
+struct VInput {
+    @location(0) position: vec3<f32>,
+};
+
+struct InstanceInput {
+    @location(5) offset: vec3<f32>,
+};
+
+// 図形（Vertex とインスタンス）用
+@vertex
+fn vs_main(in: VInput, instance: InstanceInput) -> @builtin(position) vec4<f32> {
+    return light_info.view_proj * camera.model * vec4<f32>(in.position + instance.offset, 1.0);
+}
+
+// モデル（ModelVertex）用
+@vertex
+fn vs_model(in: VInput) -> @builtin(position) vec4<f32> {
+    return light_info.view_proj * camera.model * vec4<f32>(in.position, 1.0);
+}
+
+struct SkinInput {
+    @location(4) joints: vec4<u32>,
+    @location(5) weights: vec4<f32>,
+};
+
+// スキンメッシュ（ModelVertex と JointVertex）用
+@vertex
+fn vs_model_skinned(in: VInput, skin: SkinInput) -> @builtin(position) vec4<f32> {
+    let skin_matrix = joints[skin.joints.x] * skin.weights.x
+        + joints[skin.joints.y] * skin.weights.y
+        + joints[skin.joints.z] * skin.weights.z
+        + joints[skin.joints.w] * skin.weights.w;
+    return light_info.view_proj * camera.model * skin_matrix * vec4<f32>(in.position, 1.0);
+}
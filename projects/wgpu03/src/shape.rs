@@ -0,0 +1,311 @@
+use crate::animation::Transform;
+use crate::atlas::Atlas;
+use crate::capabilities::GpuCapabilities;
+use crate::mesh::Mesh;
+use crate::per_draw::{DYNAMIC_UNIFORM_GROUP, DrawData};
+use crate::scene::{NodeId, Scene};
+use crate::sprite::SpriteBatch;
+use crate::state::Primitive;
+use crate::uniform_arena::UniformArena;
+
+// 表示する図形
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Shape {
+    Triangle,
+    Pentagon,
+    Grid,
+    // 描画ごとに変換行列と色を切り替えて、同じ三角形を輪の形に何度も描画する
+    Ring,
+    // 1つのユニフォームバッファの動的オフセットを切り替えて、100個の三角形を描画する
+    Swarm,
+    // コンピュートシェーダーが頂点を生成する三角形の波
+    Wave,
+    // コンピュートシェーダーで動かすパーティクル（カーソルの位置から放出する）
+    Particles,
+    // ウィンドウのピクセル座標で描画する2Dのスプライト
+    Sprites,
+    // 遠くまで続く市松模様の平面（ミップマップの確認用）
+    Plane,
+    Cube,
+    // プログラムで生成した球・平面・トーラス・円柱（Q キーで切り替える）
+    Primitive,
+    Model,
+    Gltf,
+    // glTF のスキンメッシュのアニメーション（Y キーでクリップを切り替える）
+    Animated,
+    // 高さマップの画像から作った地形（PageUp / PageDown で高さを変える）
+    Terrain,
+    // 親子関係を持つ節点の木で、太陽・惑星・衛星を回す
+    SolarSystem,
+    // 動かない1024個の立方体（描画のコマンドをレンダーバンドルに記録して毎フレーム再生する）
+    StaticGrid,
+}
+
+impl Shape {
+    // M キーで次に表示する図形（capabilities で描画できないものは飛ばす）
+    pub fn toggle(self, capabilities: &GpuCapabilities) -> Self {
+        let mut next = self.next();
+        while !next.is_supported(capabilities) {
+            next = next.next();
+        }
+        next
+    }
+
+    fn is_supported(self, capabilities: &GpuCapabilities) -> bool {
+        match self {
+            Shape::Wave | Shape::Particles => capabilities.compute,
+            _ => true,
+        }
+    }
+
+    fn next(self) -> Self {
+        match self {
+            Shape::Triangle => Shape::Pentagon,
+            Shape::Pentagon => Shape::Grid,
+            Shape::Grid => Shape::Ring,
+            Shape::Ring => Shape::Swarm,
+            Shape::Swarm => Shape::Wave,
+            Shape::Wave => Shape::Particles,
+            Shape::Particles => Shape::Sprites,
+            Shape::Sprites => Shape::Plane,
+            Shape::Plane => Shape::Cube,
+            Shape::Cube => Shape::Primitive,
+            Shape::Primitive => Shape::Model,
+            Shape::Model => Shape::Gltf,
+            Shape::Gltf => Shape::Animated,
+            Shape::Animated => Shape::Terrain,
+            Shape::Terrain => Shape::SolarSystem,
+            Shape::SolarSystem => Shape::StaticGrid,
+            Shape::StaticGrid => Shape::Triangle,
+        }
+    }
+}
+
+// 輪に並べる三角形の数と並べる半径
+pub const RING_TRIANGLES: usize = 12;
+const RING_RADIUS: f32 = 0.7;
+
+// 輪の i 番目の三角形の変換行列と色（輪全体を回しながら、それぞれの三角形も回す）
+pub fn ring_draws(time: f32) -> Vec<DrawData> {
+    (0..RING_TRIANGLES)
+        .map(|i| {
+            let t = i as f32 / RING_TRIANGLES as f32;
+            let angle = t * std::f32::consts::TAU + time * 0.5;
+            let transform = glam::Mat4::from_rotation_z(angle)
+                * glam::Mat4::from_translation(glam::Vec3::new(RING_RADIUS, 0.0, 0.0))
+                * glam::Mat4::from_rotation_z(time * 2.0 + t * 4.0)
+                * glam::Mat4::from_scale(glam::Vec3::splat(0.25));
+            let hue = t * std::f32::consts::TAU;
+            let color = [
+                0.6 + 0.4 * hue.cos(),
+                0.6 + 0.4 * (hue + 2.1).cos(),
+                0.6 + 0.4 * (hue + 4.2).cos(),
+                1.0,
+            ];
+            DrawData::new(transform, color)
+        })
+        .collect()
+}
+
+// 格子状に並べる三角形の1辺の数（SWARM_SIZE × SWARM_SIZE 個描画する）
+pub const SWARM_SIZE: usize = 10;
+
+// 格子の各位置に置いた三角形の変換行列と色（位置ごとに少しずつずらして回す）
+pub fn swarm_draws(time: f32) -> impl Iterator<Item = DrawData> {
+    (0..SWARM_SIZE * SWARM_SIZE).map(move |i| {
+        let (col, row) = ((i % SWARM_SIZE) as f32, (i / SWARM_SIZE) as f32);
+        let step = 1.8 / (SWARM_SIZE - 1) as f32;
+        let position = glam::Vec3::new(-0.9 + col * step, -0.9 + row * step, 0.0);
+        let transform = glam::Mat4::from_translation(position)
+            * glam::Mat4::from_rotation_z(time + (col + row) * 0.4)
+            * glam::Mat4::from_scale(glam::Vec3::splat(0.08));
+        let color = [
+            col / (SWARM_SIZE - 1) as f32,
+            row / (SWARM_SIZE - 1) as f32,
+            0.5 + 0.5 * (time + i as f32 * 0.1).sin(),
+            1.0,
+        ];
+        DrawData::new(transform, color)
+    })
+}
+
+// 太陽系のシーンで描画する節点の数の上限
+const SOLAR_SYSTEM_CAPACITY: usize = 16;
+
+// シーンの節点に描画するメッシュと色
+struct Body {
+    primitive: Primitive,
+    color: [f32; 4],
+}
+
+// 太陽のまわりを回る惑星と、惑星のまわりを回る衛星の階層
+// 回す節点の速さをそれぞれ変えて、親の回転と拡大縮小が子に伝わることを確かめる
+pub struct SolarSystem {
+    scene: Scene<Body>,
+    sun: NodeId,
+    planet_orbit: NodeId,
+    planet: NodeId,
+    moon_orbit: NodeId,
+    // 描画する節点ごとのワールド行列と色を入れる動的オフセットのユニフォームバッファ
+    pub draws: UniformArena<DrawData>,
+    // draws の枠の順に描画するメッシュ
+    primitives: Vec<Primitive>,
+}
+
+impl SolarSystem {
+    pub fn new(device: &wgpu::Device) -> anyhow::Result<Self> {
+        let body = |primitive, color| Some(Body { primitive, color });
+        // 球の半径は 0.6 なので、拡大率の 0.6 倍が表示される半径になる
+        let scaled = |scale: glam::Vec3| Transform {
+            scale,
+            ..Transform::IDENTITY
+        };
+        let mut scene = Scene::default();
+        // 軌道が楕円に見えるよう、全体を手前に傾ける
+        let system = scene.insert(
+            None,
+            Transform {
+                rotation: glam::Quat::from_rotation_x(0.35),
+                ..Transform::IDENTITY
+            },
+            None,
+        )?;
+        let sun = scene.insert(
+            Some(system),
+            scaled(glam::Vec3::splat(0.35)),
+            body(Primitive::Sphere, [1.0, 0.8, 0.2, 1.0]),
+        )?;
+        let planet_orbit = scene.insert(Some(system), Transform::IDENTITY, None)?;
+        let planet = scene.insert(
+            Some(planet_orbit),
+            Transform {
+                translation: glam::Vec3::new(0.65, 0.0, 0.0),
+                ..scaled(glam::Vec3::splat(0.15))
+            },
+            body(Primitive::Sphere, [0.3, 0.5, 1.0, 1.0]),
+        )?;
+        // 環と衛星の軌道は惑星の子なので、惑星の自転と拡大率を受け継ぐ
+        scene.insert(
+            Some(planet),
+            Transform {
+                rotation: glam::Quat::from_rotation_x(0.4),
+                ..scaled(glam::Vec3::new(2.2, 0.25, 2.2))
+            },
+            body(Primitive::Torus, [0.8, 0.7, 0.5, 1.0]),
+        )?;
+        let moon_orbit = Self::insert_moon(&mut scene, planet)?;
+        Ok(Self {
+            scene,
+            sun,
+            planet_orbit,
+            planet,
+            moon_orbit,
+            draws: UniformArena::new(device, "Solar System Buffer", SOLAR_SYSTEM_CAPACITY),
+            primitives: Vec::new(),
+        })
+    }
+
+    // 衛星の軌道の節点を parent の子として追加する
+    fn insert_moon(scene: &mut Scene<Body>, parent: NodeId) -> anyhow::Result<NodeId> {
+        let moon_orbit = scene.insert(Some(parent), Transform::IDENTITY, None)?;
+        scene.insert(
+            Some(moon_orbit),
+            Transform {
+                translation: glam::Vec3::new(1.6, 0.0, 0.0),
+                scale: glam::Vec3::splat(0.3),
+                ..Transform::IDENTITY
+            },
+            Some(Body {
+                primitive: Primitive::Sphere,
+                color: [0.7, 0.7, 0.7, 1.0],
+            }),
+        )?;
+        Ok(moon_orbit)
+    }
+
+    // 衛星を惑星の子 → 太陽の子 → 削除 → 惑星の子の順に切り替え、新しい状態を返す
+    // 付け替えても親に対する変換は変わらないので、太陽の子にすると太陽の拡大率で軌道が広がる
+    pub fn cycle_moon(&mut self) -> anyhow::Result<&'static str> {
+        let Some(moon_orbit) = self.scene.get(self.moon_orbit) else {
+            self.moon_orbit = Self::insert_moon(&mut self.scene, self.planet)?;
+            return Ok("惑星の子");
+        };
+        if moon_orbit.parent() == Some(self.planet) {
+            self.scene.set_parent(self.moon_orbit, Some(self.sun))?;
+            Ok("太陽の子")
+        } else {
+            // 削除した節点の番号は無効になり、update では何もしない
+            self.scene.remove(self.moon_orbit)?;
+            Ok("削除")
+        }
+    }
+
+    // time 秒の角度に節点を回し、木をたどって求めたワールド行列を書き込む
+    pub fn update(&mut self, queue: &wgpu::Queue, time: f32) {
+        for (node, rate) in [
+            (self.sun, 0.2),
+            (self.planet_orbit, 0.6),
+            (self.planet, 1.5),
+            (self.moon_orbit, 3.0),
+        ] {
+            if let Some(node) = self.scene.get_mut(node) {
+                node.transform.rotation = glam::Quat::from_rotation_y(time * rate);
+            }
+        }
+        self.draws.clear();
+        self.primitives.clear();
+        for (body, world) in self.scene.world_matrices() {
+            self.draws.push(&DrawData::new(world, body.color));
+            self.primitives.push(body.primitive);
+        }
+        self.draws.upload(queue);
+    }
+
+    // meshes は Primitive::ALL の順に並んだメッシュ
+    pub fn draw(&self, rpass: &mut wgpu::RenderPass, meshes: &[Mesh]) {
+        for (index, primitive) in self.primitives.iter().enumerate() {
+            self.draws
+                .bind(rpass, DYNAMIC_UNIFORM_GROUP, self.draws.offset(index));
+            meshes[*primitive as usize].draw(rpass, 0..1);
+        }
+    }
+}
+
+// スプライトのデモで散らばらせる数
+const NUM_DEMO_SPRITES: u32 = 48;
+
+// アトラスに詰め込んだ図形をウィンドウ全体に散らばらせ、ゆっくり揺らす
+pub fn push_demo_sprites(
+    batch: &mut SpriteBatch,
+    atlas: &Atlas,
+    width: u32,
+    height: u32,
+    time: f32,
+) {
+    let window = glam::Vec2::new(width as f32, height as f32);
+    for i in 0..NUM_DEMO_SPRITES {
+        // 2次元の低食い違い量列で重なりにくく並べる
+        let scatter = glam::Vec2::new(
+            (0.5 + i as f32 * 0.754_877_7).fract(),
+            (0.5 + i as f32 * 0.569_840_3).fract(),
+        );
+        let phase = i as f32 * 1.7;
+        let wobble = glam::Vec2::new((time + phase).sin(), (time * 1.3 + phase).cos()) * 12.0;
+        // 図形ごとに元の画像の縦横比を保ったまま大きさを変える
+        let image = i as usize % atlas.uv_rects.len();
+        let size = atlas.sizes[image] * (0.5 + (i % 3) as f32 * 0.25);
+        let hue = i as f32 / NUM_DEMO_SPRITES as f32 * std::f32::consts::TAU;
+        let color = [
+            0.6 + 0.4 * hue.cos(),
+            0.6 + 0.4 * (hue + 2.1).cos(),
+            0.6 + 0.4 * (hue + 4.2).cos(),
+            1.0,
+        ];
+        batch.push(
+            scatter * (window - size) + wobble,
+            size,
+            atlas.uv_rects[image],
+            color,
+        );
+    }
+}
@@ -0,0 +1,83 @@
+use std::time::Duration;
+
+// シミュレーションを進める間隔（描画の頻度によらず 60 Hz で進める）
+pub const STEP: Duration = Duration::from_nanos(1_000_000_000 / 60);
+// 1フレームで追いつく段階の上限（ウィンドウをドラッグしていた間などの遅れは捨てる）
+const MAX_STEPS_PER_FRAME: u32 = 8;
+// 図形を回す速さ（ラジアン毎秒）
+const ANGULAR_SPEED: f32 = 1.0;
+
+// 固定の間隔で進めるアニメーションの状態
+// 描画するときは、前の段階と今の段階を FixedTimestep::alpha で補間した値を使う
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Simulation {
+    // 図形の回転角（ラジアン）
+    angle: f32,
+    previous_angle: f32,
+}
+
+impl Simulation {
+    // STEP だけ進める
+    pub fn step(&mut self) {
+        self.previous_angle = self.angle;
+        self.angle += ANGULAR_SPEED * STEP.as_secs_f32();
+    }
+
+    // 前の段階と今の段階の間を alpha（0〜1）で補間した回転角
+    pub fn angle(&self, alpha: f32) -> f32 {
+        self.previous_angle + (self.angle - self.previous_angle) * alpha
+    }
+}
+
+// 前のフレームから経った時間をためて、STEP ごとにシミュレーションを進める回数を決める
+#[derive(Debug, Default)]
+pub struct FixedTimestep {
+    accumulator: Duration,
+}
+
+impl FixedTimestep {
+    // このフレームで進める段階の数（多くても MAX_STEPS_PER_FRAME）
+    pub fn advance(&mut self, interval: Duration) -> u32 {
+        self.accumulator = (self.accumulator + interval).min(STEP * MAX_STEPS_PER_FRAME);
+        let steps = (self.accumulator.as_nanos() / STEP.as_nanos()) as u32;
+        self.accumulator -= STEP * steps;
+        steps
+    }
+
+    // 進めきれずに残った時間の STEP に対する割合（描画の補間に使う）
+    pub fn alpha(&self) -> f32 {
+        self.accumulator.as_secs_f32() / STEP.as_secs_f32()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steps_are_fixed_and_long_pauses_are_clamped() {
+        let mut timestep = FixedTimestep::default();
+        // 144 Hz の表示では、ほとんどのフレームで進めない
+        assert_eq!(timestep.advance(Duration::from_micros(6944)), 0);
+        assert_eq!(timestep.advance(Duration::from_micros(6944)), 0);
+        assert_eq!(timestep.advance(Duration::from_micros(6944)), 1);
+        assert!(
+            (timestep.alpha() - 0.25).abs() < 0.01,
+            "{}",
+            timestep.alpha()
+        );
+        // 長く止まっていても、追いつくのは MAX_STEPS_PER_FRAME 段階まで
+        assert_eq!(
+            timestep.advance(Duration::from_secs(10)),
+            MAX_STEPS_PER_FRAME
+        );
+        assert!(timestep.alpha() < 1.0);
+
+        let mut simulation = Simulation::default();
+        simulation.step();
+        let step = STEP.as_secs_f32() * ANGULAR_SPEED;
+        assert_eq!(simulation.angle(1.0), step);
+        assert_eq!(simulation.angle(0.0), 0.0);
+        assert!((simulation.angle(0.5) - step / 2.0).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,33 @@
+struct Sky {
+    // 平行移動を取り除いたビュー・射影行列の逆行列
+    inv_view_proj: mat4x4<f32>,
+};
+
+@group(0) @binding(0) var<uniform> sky: Sky;
+@group(0) @binding(1) var t_sky: texture_cube<f32>;
+@group(0) @binding(2) var s_sky: sampler;
+
+struct VOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) clip: vec2<f32>,
+};
+
+// 頂点バッファを使わずに画面全体を覆う大きな三角形を描画する
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    let clip = uv * 2.0 - 1.0;
+    var out: VOutput;
+    // 深度を 1.0（ファークリップ面）にして、すべてのジオメトリの奥に描画する
+    out.position = vec4<f32>(clip, 1.0, 1.0);
+    out.clip = clip;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VOutput) -> @location(0) vec4<f32> {
+    // クリップ座標から視線方向を復元してキューブマップをサンプリングする
+    let world = sky.inv_view_proj * vec4<f32>(in.clip, 1.0, 1.0);
+    let dir = normalize(world.xyz / world.w);
+    return textureSample(t_sky, s_sky, dir);
+}
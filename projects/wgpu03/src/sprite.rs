@@ -0,0 +1,277 @@
+use crate::crash;
+use crate::pipeline_cache::PipelineCache;
+use crate::texture::Texture;
+
+// 最初に確保する頂点バッファのスプライト数
+const INITIAL_CAPACITY: usize = 64;
+// 1枚のスプライトを2つの三角形で描画する
+const VERTICES_PER_SPRITE: usize = 6;
+
+// テクスチャアトラス上の範囲（左上と右下のUV座標）
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct UvRect {
+    pub min: glam::Vec2,
+    pub max: glam::Vec2,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct SpriteVertex {
+    // ウィンドウの左上を原点とするピクセル座標
+    position: [f32; 2],
+    uv: [f32; 2],
+    color: [f32; 4],
+}
+
+impl SpriteVertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 3] =
+        wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2, 2 => Float32x4];
+
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+// 足りなくなったときは2倍ずつ増やし、再確保の回数を抑える
+fn grown_capacity(capacity: usize, required: usize) -> usize {
+    let mut capacity = capacity.max(1);
+    while capacity < required {
+        capacity *= 2;
+    }
+    capacity
+}
+
+// ウィンドウのピクセル座標（左上が原点、下向きが +y）をクリップ座標に変換する正射影
+fn screen_projection(width: u32, height: u32) -> glam::Mat4 {
+    glam::Mat4::orthographic_rh(0.0, width as f32, height as f32, 0.0, -1.0, 1.0)
+}
+
+fn create_vertex_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Sprite Vertex Buffer"),
+        size: (capacity * VERTICES_PER_SPRITE * std::mem::size_of::<SpriteVertex>())
+            as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+// CPU で毎フレーム積み上げた四角形を、1つのテクスチャアトラスから1回の描画呼び出しで描画する
+pub struct SpriteBatch {
+    pipeline: wgpu::RenderPipeline,
+    projection_buffer: wgpu::Buffer,
+    projection_bind_group: wgpu::BindGroup,
+    texture_bind_group: wgpu::BindGroup,
+    vertices: Vec<SpriteVertex>,
+    vertex_buffer: wgpu::Buffer,
+    // vertex_buffer に確保してあるスプライトの数（小さくすることはない）
+    capacity: usize,
+    // 直前の upload で書き込んだ頂点の数
+    uploaded: u32,
+}
+
+impl SpriteBatch {
+    // format は描画先のテクスチャのフォーマット
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        device: &wgpu::Device,
+        pipeline_cache: &PipelineCache,
+        queue: &wgpu::Queue,
+        shader: &wgpu::ShaderModule,
+        atlas: &Texture,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        let projection_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Sprite Projection Buffer"),
+            size: std::mem::size_of::<[[f32; 4]; 4]>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let projection_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Sprite Projection Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let projection_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Sprite Projection Bind Group"),
+            layout: &projection_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: projection_buffer.as_entire_binding(),
+            }],
+        });
+        let texture_layout = Texture::bind_group_layout(device);
+        let texture_bind_group = atlas.create_bind_group(device, &texture_layout);
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Sprite Pipeline Layout"),
+            bind_group_layouts: &[&projection_layout, &texture_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = pipeline_cache.create(|cache| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: crash::created_pipeline("Sprite Pipeline"),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[SpriteVertex::desc()],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: shader,
+                    entry_point: Some("fs_main"),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format,
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: Default::default(),
+                }),
+                // 2D の四角形は裏返ることがないのでカリングしない
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache,
+            })
+        });
+
+        let batch = Self {
+            pipeline,
+            projection_buffer,
+            projection_bind_group,
+            texture_bind_group,
+            vertices: Vec::new(),
+            vertex_buffer: create_vertex_buffer(device, INITIAL_CAPACITY),
+            capacity: INITIAL_CAPACITY,
+            uploaded: 0,
+        };
+        batch.resize(queue, width, height);
+        batch
+    }
+
+    // ウィンドウの大きさが変わったら正射影行列を作り直す
+    pub fn resize(&self, queue: &wgpu::Queue, width: u32, height: u32) {
+        let matrix = screen_projection(width, height).to_cols_array_2d();
+        queue.write_buffer(&self.projection_buffer, 0, bytemuck::cast_slice(&[matrix]));
+    }
+
+    // このフレームで描画するスプライトを積み直す前に呼ぶ
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+    }
+
+    // position は左上の角のピクセル座標、size はピクセル単位の幅と高さ
+    pub fn push(&mut self, position: glam::Vec2, size: glam::Vec2, uv: UvRect, color: [f32; 4]) {
+        // (x, y) は四角形の中での位置（0.0 〜 1.0）
+        let vertex = |x: f32, y: f32| {
+            let t = glam::Vec2::new(x, y);
+            SpriteVertex {
+                position: (position + size * t).to_array(),
+                uv: (uv.min + (uv.max - uv.min) * t).to_array(),
+                color,
+            }
+        };
+        let quad = [
+            vertex(0.0, 0.0),
+            vertex(0.0, 1.0),
+            vertex(1.0, 1.0),
+            vertex(0.0, 0.0),
+            vertex(1.0, 1.0),
+            vertex(1.0, 0.0),
+        ];
+        self.vertices.extend_from_slice(&quad);
+    }
+
+    // 積み上げた頂点をバッファに書き込む（足りない場合はバッファを大きくしてから書き込む）
+    pub fn upload(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        let sprites = self.vertices.len() / VERTICES_PER_SPRITE;
+        if sprites > self.capacity {
+            self.capacity = grown_capacity(self.capacity, sprites);
+            self.vertex_buffer = create_vertex_buffer(device, self.capacity);
+        }
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&self.vertices));
+        self.uploaded = self.vertices.len() as u32;
+    }
+
+    // 送ったスプライトがなければ、draw は何も記録しない
+    pub fn is_empty(&self) -> bool {
+        self.uploaded == 0
+    }
+
+    // 描画済みの内容を残したまま（LoadOp::Load）スプライトを重ねる
+    pub fn draw(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        timestamp_writes: Option<wgpu::RenderPassTimestampWrites<'_>>,
+    ) {
+        if self.is_empty() {
+            return;
+        }
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: crash::pass("Sprite Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes,
+            occlusion_query_set: None,
+        });
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_bind_group(0, &self.projection_bind_group, &[]);
+        rpass.set_bind_group(1, &self.texture_bind_group, &[]);
+        rpass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        rpass.draw(0..self.uploaded, 0..1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn capacity_grows_geometrically() {
+        assert_eq!(grown_capacity(64, 10), 64);
+        assert_eq!(grown_capacity(64, 65), 128);
+        assert_eq!(grown_capacity(64, 300), 512);
+        assert_eq!(grown_capacity(0, 3), 4);
+    }
+
+    #[test]
+    fn projection_maps_window_corners_to_clip_space() {
+        let projection = screen_projection(800, 600);
+        let top_left = projection.project_point3(glam::Vec3::ZERO);
+        let bottom_right = projection.project_point3(glam::Vec3::new(800.0, 600.0, 0.0));
+        assert!(
+            top_left
+                .truncate()
+                .abs_diff_eq(glam::Vec2::new(-1.0, 1.0), 1e-6)
+        );
+        assert!(
+            bottom_right
+                .truncate()
+                .abs_diff_eq(glam::Vec2::new(1.0, -1.0), 1e-6)
+        );
+    }
+}
@@ -0,0 +1,30 @@
+// ウィンドウのピクセル座標をクリップ座標に変換する正射影行列
+@group(0) @binding(0) var<uniform> projection: mat4x4<f32>;
+@group(1) @binding(0) var t_atlas: texture_2d<f32>;
+@group(1) @binding(1) var s_atlas: sampler;
+
+struct VInput {
+    @location(0) position: vec2<f32>,
+    @location(1) uv: vec2<f32>,
+    @location(2) color: vec4<f32>,
+};
+
+struct VOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+};
+
+@vertex
+fn vs_main(in: VInput) -> VOutput {
+    var out: VOutput;
+    out.position = projection * vec4<f32>(in.position, 0.0, 1.0);
+    out.uv = in.uv;
+    out.color = in.color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VOutput) -> @location(0) vec4<f32> {
+    return textureSample(t_atlas, s_atlas, in.uv) * in.color;
+}
@@ -0,0 +1,560 @@
+use std::time::{Duration, Instant};
+
+use tracing::warn;
+
+use crate::capture::Capture;
+use crate::clock::FrameTime;
+use crate::debug_group::DebugGroup;
+use crate::frustum::CullStats;
+use crate::gpu::surface_view_format;
+use crate::gpu_timer::{GpuPass, PassTime};
+use crate::light::{LightsUniform, orbiting_lights};
+use crate::mesh::FLOOR_HEIGHT;
+use crate::profiler::{FrameProfile, Profiler};
+use crate::shape::{Shape, push_demo_sprites, ring_draws, swarm_draws};
+use crate::stats::FrameStats;
+use crate::{capture, crash, debug_group, frustum, gpu_timer};
+
+use super::{
+    AXIS_LENGTH, CameraMode, FRAME_ENCODER_LABEL, FROZEN_FRUSTUM_COLOR, SceneTarget, State,
+    light_view_projection,
+};
+
+// フレーム時間の表示を更新する間隔（毎フレーム書き換えると読めないので 4 Hz 程度にする）
+const STATS_REFRESH_INTERVAL: Duration = Duration::from_millis(250);
+
+// 画面に重ねて表示するフレーム時間の統計
+// GPU の計測ができない場合は、パスごとに記録にかかった時間を表示する
+// timings が false（ヘッドレスで1フレームだけ描画する場合）は、意味のない時間を表示せず画像が毎回同じになるようにする
+fn stats_text(
+    intervals: &FrameStats,
+    cpu: &FrameStats,
+    profiler: &Profiler,
+    culling: CullStats,
+    shape: Shape,
+    timings: bool,
+) -> String {
+    let ms = |d: Duration| d.as_secs_f32() * 1000.0;
+    let mut text = String::from("wgpu:03 triangle\n");
+    if let Some(frame) = intervals.summary().filter(|_| timings) {
+        text += &format!(
+            "{:.0} fps ({:.2} ms)\n",
+            1.0 / frame.average.as_secs_f32().max(f32::EPSILON),
+            ms(frame.average)
+        );
+    }
+    if let Some(cpu) = cpu.summary().filter(|_| timings) {
+        text += &format!(
+            "CPU avg {:.2} / min {:.2} / max {:.2} / p99 {:.2} ms\n",
+            ms(cpu.average),
+            ms(cpu.min),
+            ms(cpu.max),
+            ms(cpu.p99)
+        );
+    }
+    let passes = |times: &[PassTime]| {
+        times
+            .iter()
+            .map(|time| format!("{} {:.2}", time.pass.name(), time.ms))
+            .collect::<Vec<_>>()
+            .join(" / ")
+    };
+    match profiler.latest() {
+        _ if !timings => {}
+        (_, Some(FrameProfile { gpu: Some(gpu), .. })) => {
+            text += &format!("GPU {} ms\n", passes(gpu));
+        }
+        (Some(latest), _) if !profiler.has_gpu_timer() => {
+            text += &format!("Encode {} ms\n", passes(&latest.cpu));
+        }
+        _ => {}
+    }
+    // 視錐台カリングの対象がない図形では表示しない
+    if culling.total > 0 {
+        text += &format!("Drawn {} / {} meshes\n", culling.drawn, culling.total);
+    }
+    text + &format!("{:?}", shape)
+}
+
+impl State {
+    // カメラを更新し、経過時間とMVP行列をユニフォームバッファに書き込む
+    // angle は固定の間隔で進めた Simulation の回転角を、描画する時点に補間したもの
+    pub fn update(&mut self, frame: FrameTime, angle: f32) {
+        let now = Instant::now();
+        let dt = frame.dt;
+        self.frame_stats.push(frame.interval);
+        match self.camera_mode {
+            CameraMode::Fly => self.camera_controller.update_camera(&mut self.camera, dt),
+            CameraMode::Orbit => self.orbit_controller.update_camera(&mut self.camera),
+        }
+
+        self.uniforms.time = frame.time;
+        self.queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[self.uniforms]),
+        );
+
+        if let Some(wave) = &mut self.wave {
+            wave.update(&self.queue, self.uniforms.time);
+        }
+        // カーソルを通る視線と z = 0 の平面の交点から粒子を放出する
+        let emitter = self
+            .cursor
+            .and_then(|(x, y)| {
+                self.camera
+                    .cursor_to_plane(x, y, self.config.width, self.config.height, 0.0)
+            })
+            .unwrap_or(glam::Vec3::ZERO);
+        if let Some(particles) = &self.particles {
+            particles.update(
+                &self.queue,
+                frame.animation_step.as_secs_f32(),
+                self.uniforms.time,
+                emitter,
+            );
+        }
+
+        if self.show_axes {
+            self.debug_lines.add_axes(AXIS_LENGTH);
+        }
+        // 固定した視錐台の辺を描いて、カリングされる境界を見えるようにする
+        if let Some(view_projection) = self.frozen_view_projection {
+            let corners = frustum::corners(view_projection);
+            for (i, corner) in corners.iter().enumerate() {
+                for bit in [1, 2, 4] {
+                    if i & bit == 0 {
+                        self.debug_lines
+                            .add_line(*corner, corners[i | bit], FROZEN_FRUSTUM_COLOR);
+                    }
+                }
+            }
+        }
+        self.debug_lines.upload(&self.device, &self.queue);
+
+        if self.shape == Shape::Ring {
+            self.per_draw
+                .write(&self.queue, &ring_draws(self.uniforms.time));
+        }
+
+        if self.shape == Shape::Swarm {
+            self.swarm.clear();
+            for draw in swarm_draws(self.uniforms.time) {
+                self.swarm.push(&draw);
+            }
+            self.swarm.upload(&self.queue);
+        }
+
+        if self.shape == Shape::SolarSystem {
+            self.solar_system.update(&self.queue, self.uniforms.time);
+        }
+
+        if self.shape == Shape::Sprites {
+            self.sprites.clear();
+            push_demo_sprites(
+                &mut self.sprites,
+                &self.sprite_atlas,
+                self.config.width,
+                self.config.height,
+                self.uniforms.time,
+            );
+            self.sprites.upload(&self.device, &self.queue);
+        }
+
+        if self
+            .stats_refreshed
+            .is_none_or(|refreshed| now - refreshed >= STATS_REFRESH_INTERVAL)
+        {
+            self.overlay.set_text(&stats_text(
+                &self.frame_stats,
+                &self.cpu_stats,
+                &self.profiler,
+                self.cull_stats,
+                self.shape,
+                self.window.is_some(),
+            ));
+            self.stats_refreshed = Some(now);
+        }
+        self.overlay.prepare(&self.device, &self.queue);
+
+        // 回転角に応じてZ軸まわりに回転させる（描画の頻度によらず同じ速さで回る）
+        // 立方体は斜めの軸まわりに回転させて、すべての面が見えるようにする
+        // 波とパーティクルは頂点や粒子そのものが動くので回転させない
+        self.model = match self.shape {
+            Shape::Ring
+            | Shape::Swarm
+            | Shape::Wave
+            | Shape::Particles
+            | Shape::Sprites
+            | Shape::Plane
+            | Shape::SolarSystem
+            | Shape::StaticGrid => glam::Mat4::IDENTITY,
+            Shape::Primitive | Shape::Model | Shape::Gltf | Shape::Animated => {
+                glam::Mat4::from_rotation_y(angle)
+            }
+            Shape::Cube => {
+                glam::Mat4::from_axis_angle(glam::Vec3::new(1.0, 1.0, 0.0).normalize(), angle)
+            }
+            // 地形は床の高さから、高さマップの 0〜1 を terrain_height 倍して盛り上げる
+            Shape::Terrain => {
+                glam::Mat4::from_translation(glam::Vec3::Y * FLOOR_HEIGHT)
+                    * glam::Mat4::from_scale(glam::Vec3::new(1.0, self.terrain_height, 1.0))
+            }
+            _ => glam::Mat4::from_rotation_z(angle),
+        };
+        self.camera_bindings
+            .write(&self.queue, &self.camera, self.model);
+        self.cull();
+        if self.shape == Shape::Animated
+            && let Some(model) = &self.animated_model
+        {
+            let time = self.uniforms.time - self.animation_start;
+            model.animate(&self.queue, Some(self.animation_clip), time);
+        }
+        if self.show_inset {
+            self.inset_bindings
+                .write(&self.queue, &self.inset_camera, self.model);
+        }
+
+        let lights = orbiting_lights(self.light_count, self.uniforms.time);
+        self.lights_buffer.write(&self.queue, &lights);
+        // 影は先頭の光源だけが落とす
+        let light_info = LightsUniform::new(
+            light_view_projection(glam::Vec3::from(lights[0].position)),
+            self.light_count,
+        );
+        self.queue.write_buffer(
+            &self.light_info_buffer,
+            0,
+            bytemuck::cast_slice(&[light_info]),
+        );
+    }
+
+    // 各パスを順番にコマンドエンコーダーへ記録して送信する
+    // パスを追加するときは、前のパスの出力を参照するパスがその後ろに来るように並べる
+    fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
+        // ヘッドレスの場合は headless::run が encode_frame で描画する
+        let Some(surface) = &self.surface else {
+            return Ok(());
+        };
+        // フレームの取得（表示を待つ時間）と記録にかかった時間を、frame のスパンに記録する
+        let span = tracing::Span::current();
+        let start = Instant::now();
+        let frame = surface.get_current_texture()?;
+        span.record("acquire_ms", start.elapsed().as_secs_f64() * 1000.0);
+        let view = frame.texture.create_view(&wgpu::TextureViewDescriptor {
+            format: Some(surface_view_format(&self.config)),
+            ..Default::default()
+        });
+        let start = Instant::now();
+        let encoder = self.encode_frame(&view);
+        span.record("encode_ms", start.elapsed().as_secs_f64() * 1000.0);
+        self.profiler.end_frame();
+        self.queue.submit(Some(encoder.finish()));
+        frame.present();
+        self.after_submit();
+        // 録画中は読み出し用のテクスチャにもう一度描画する（読み出しは次以降のフレームで終わる）
+        let (width, height, format) = (
+            self.config.width,
+            self.config.height,
+            surface_view_format(&self.config),
+        );
+        if let Some(view) = self
+            .recorder
+            .as_mut()
+            .and_then(|recorder| recorder.begin_frame(&self.device, width, height, format))
+        {
+            let mut encoder = self.encode_frame(&view);
+            if let Some(recorder) = &mut self.recorder {
+                recorder.copy(&mut encoder);
+            }
+            self.queue.submit(Some(encoder.finish()));
+            self.after_submit();
+        }
+        if std::mem::take(&mut self.screenshot_requested) {
+            match self.capture_frame() {
+                Ok(image) => capture::save_screenshot(image),
+                Err(e) => warn!("スクリーンショットを撮れませんでした: {:#}", e),
+            }
+        }
+        Ok(())
+    }
+
+    // 表示したフレームと同じ内容を読み出せるテクスチャにもう一度描画して読み出す
+    // （設定パネルは表示したフレームで描画済みなので、スクリーンショットには含まれない）
+    fn capture_frame(&mut self) -> anyhow::Result<image::RgbaImage> {
+        let capture = Capture::new(
+            &self.device,
+            self.config.width,
+            self.config.height,
+            surface_view_format(&self.config),
+        );
+        let mut encoder = self.encode_frame(&capture.view);
+        capture.copy(&mut encoder);
+        self.queue.submit(Some(encoder.finish()));
+        self.after_submit();
+        capture.read(&self.device)
+    }
+
+    // view（サーフェイスかヘッドレスの描画先のテクスチャ）へ1フレームを描画するコマンドを記録する
+    pub fn encode_frame(&mut self, view: &wgpu::TextureView) -> wgpu::CommandEncoder {
+        let mut encoder = self.frame_encoder();
+        self.encode_scene(&mut encoder);
+        {
+            let mut post = DebugGroup::new(&mut encoder, "post");
+            let start = Instant::now();
+            self.post.draw(
+                &mut post,
+                view,
+                self.profiler.timestamp_writes(GpuPass::Post),
+            );
+            self.profiler.record(GpuPass::Post, start);
+        }
+        {
+            let mut ui = DebugGroup::new(&mut encoder, "ui");
+            if self.shape == Shape::Sprites && !self.sprites.is_empty() {
+                let start = Instant::now();
+                self.sprites.draw(
+                    &mut ui,
+                    view,
+                    self.profiler.timestamp_writes(GpuPass::Sprites),
+                );
+                self.profiler.record(GpuPass::Sprites, start);
+            }
+            let start = Instant::now();
+            self.overlay.draw(
+                &mut ui,
+                view,
+                self.profiler.timestamp_writes(GpuPass::Overlay),
+            );
+            self.profiler.record(GpuPass::Overlay, start);
+            #[cfg(feature = "ui")]
+            if let Some(panel) = &mut self.ui
+                && panel.is_ready()
+            {
+                let start = Instant::now();
+                panel.draw(
+                    &self.device,
+                    &self.queue,
+                    &mut ui,
+                    view,
+                    [self.config.width, self.config.height],
+                    self.profiler.timestamp_writes(GpuPass::Ui),
+                );
+                self.profiler.record(GpuPass::Ui, start);
+            }
+        }
+        self.resolve_queries(&mut encoder);
+        encoder
+    }
+
+    // メインのウィンドウが最小化されたり隠れたりして描画できない間に、
+    // ほかのウィンドウに表示するシーン（ポストプロセスの入力）だけを描画する
+    // （文字と設定パネルはメインのウィンドウにだけ表示するので記録しない）
+    pub fn render_scene(&mut self, frame: FrameTime, angle: f32) {
+        let _span = tracing::debug_span!("scene_frame").entered();
+        crash::begin_frame();
+        self.reload_shaders();
+        self.update(frame, angle);
+        let mut encoder = self.frame_encoder();
+        self.encode_scene(&mut encoder);
+        self.resolve_queries(&mut encoder);
+        self.queue.submit(Some(encoder.finish()));
+        self.after_submit();
+    }
+
+    // パスごとに記録にかかった時間を計り、GPU での所要時間はタイムスタンプで計る
+    fn frame_encoder(&mut self) -> wgpu::CommandEncoder {
+        let label = debug_group::frame_label(FRAME_ENCODER_LABEL, crash::frame());
+        self.profiler.begin_frame(crash::frame());
+        self.device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some(&label),
+            })
+    }
+
+    // シミュレーションからブルームまで、ポストプロセスの入力のテクスチャへシーンを描画するコマンドを記録する
+    fn encode_scene(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        // 波の頂点はそれを読み込むレンダーパスより前に生成しておく
+        let start = Instant::now();
+        let simulated = match (self.shape, &self.wave, &self.particles) {
+            (Shape::Wave, Some(wave), _) => {
+                wave.dispatch(
+                    &mut DebugGroup::new(encoder, "simulation"),
+                    self.profiler.compute_timestamp_writes(GpuPass::Simulation),
+                );
+                true
+            }
+            (Shape::Particles, _, Some(particles)) => {
+                particles.dispatch(
+                    &mut DebugGroup::new(encoder, "simulation"),
+                    self.profiler.compute_timestamp_writes(GpuPass::Simulation),
+                );
+                true
+            }
+            _ => false,
+        };
+        if simulated {
+            self.profiler.record(GpuPass::Simulation, start);
+        }
+        let start = Instant::now();
+        self.shadow_pass(&mut DebugGroup::new(encoder, "shadows"));
+        self.profiler.record(GpuPass::Shadow, start);
+        // メインのパスの最初に背景の色で塗りつぶしてから、ジオメトリを描画する
+        let start = Instant::now();
+        let mut scene = DebugGroup::new(encoder, "scene");
+        debug_group::marker(&mut scene, "clear");
+        self.main_pass(
+            &mut scene,
+            &SceneTarget {
+                view: &self.post.texture.view,
+                msaa_view: self.msaa_view.as_ref(),
+                depth_view: &self.depth_texture.view,
+                bindings: &self.camera_bindings,
+                eye: self.camera.eye,
+                primary: true,
+            },
+        );
+        self.profiler.record(GpuPass::Main, start);
+        if self.show_inset {
+            // 同じシーンを固定のカメラから小窓のテクスチャに描画し、シーンのテクスチャの右下に重ねる
+            let start = Instant::now();
+            let mut inset = DebugGroup::new(&mut scene, "inset");
+            self.main_pass(
+                &mut inset,
+                &SceneTarget {
+                    view: &self.inset.texture.view,
+                    msaa_view: self.inset.msaa_view.as_ref(),
+                    depth_view: &self.inset.depth_texture.view,
+                    bindings: &self.inset_bindings,
+                    eye: self.inset_camera.eye,
+                    primary: false,
+                },
+            );
+            self.inset.composite(
+                &mut inset,
+                &self.post.texture.view,
+                self.profiler
+                    .timestamp_writes(GpuPass::Inset)
+                    .map(gpu_timer::end_only),
+            );
+            drop(inset);
+            self.profiler.record(GpuPass::Inset, start);
+        }
+        drop(scene);
+        if self.bloom.is_enabled() {
+            let start = Instant::now();
+            self.bloom.draw(
+                &mut DebugGroup::new(encoder, "bloom"),
+                &self.post.texture.view,
+                self.profiler.timestamp_writes(GpuPass::Bloom),
+            );
+            self.profiler.record(GpuPass::Bloom, start);
+        }
+    }
+
+    // タイムスタンプの読み出しも同じエンコーダーに記録し、1回の提出で済ませる
+    fn resolve_queries(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        debug_group::marker(encoder, "queries");
+        self.profiler.resolve(encoder);
+        if self.shows_overlap_demo() {
+            self.back_occlusion.resolve(encoder);
+        }
+    }
+
+    // encode_frame で記録したコマンドを提出した後に呼ぶ
+    // GPU の完了は待たずに（待つと CPU と GPU が交互にしか動かない）、終わっている map_async のコールバックだけを呼ぶ
+    // 先に進みすぎないよう、サーフェイスの desired_maximum_frame_latency で get_current_texture が待つ
+    pub fn after_submit(&mut self) {
+        self.device.poll(wgpu::Maintain::Poll);
+        // マップが終わった前のフレームの結果を読み、このフレームの結果は次以降のフレームで読む
+        self.profiler.after_submit();
+        self.back_occlusion.after_submit();
+        if let Some(recorder) = &mut self.recorder {
+            recorder.after_submit();
+        }
+    }
+
+    // 設定パネルを組み立て、変更された値を反映する
+    #[cfg(feature = "ui")]
+    fn run_ui(&mut self) {
+        use crate::gpu::is_vsync;
+        use crate::shading::Shading;
+        use crate::ui;
+
+        let before = ui::PanelSettings {
+            clear_color: [
+                self.clear_color.r as f32,
+                self.clear_color.g as f32,
+                self.clear_color.b as f32,
+            ],
+            wireframe: self.shading_pipelines.current() == Shading::Wireframe,
+            wireframe_supported: self.shading_pipelines.is_supported(Shading::Wireframe),
+            vsync: is_vsync(self.config.present_mode),
+            max_fps: self.frame_limiter.max_fps().unwrap_or(0),
+        };
+        let mut settings = before;
+        if let (Some(ui), Some(window)) = (&mut self.ui, &self.window) {
+            ui.run(window, &mut settings);
+        }
+        if settings == before {
+            return;
+        }
+        let [r, g, b] = settings.clear_color.map(f64::from);
+        self.clear_color = wgpu::Color {
+            r,
+            g,
+            b,
+            a: self.clear_color.a,
+        };
+        if settings.wireframe != before.wireframe {
+            self.select_shading(if settings.wireframe {
+                Shading::Wireframe
+            } else {
+                Shading::Textured
+            });
+            self.update_title();
+        }
+        if settings.vsync != before.vsync {
+            self.set_vsync(settings.vsync);
+        }
+        if settings.max_fps != before.max_fps {
+            self.frame_limiter.set_max_fps(Some(settings.max_fps));
+        }
+    }
+
+    // 1フレームを更新して描画する
+    // サーフェイスが使えなくなっただけなら設定し直して続け、続けられないエラーだけを返す
+    pub fn frame(&mut self, frame: FrameTime, angle: f32) -> Result<(), wgpu::SurfaceError> {
+        let _span = tracing::debug_span!(
+            "frame",
+            width = self.config.width,
+            height = self.config.height,
+            acquire_ms = tracing::field::Empty,
+            encode_ms = tracing::field::Empty,
+        )
+        .entered();
+        crash::begin_frame();
+        self.reload_shaders();
+        self.update(frame, angle);
+        #[cfg(feature = "ui")]
+        self.run_ui();
+        match self.render() {
+            Ok(()) => Ok(()),
+            // モニターを外した場合などにサーフェイスが使えなくなるので、設定し直して次のフレームで描画する
+            Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                if let Some(window) = &self.window {
+                    let size = window.inner_size();
+                    self.resize(size);
+                }
+                Ok(())
+            }
+            // 表示が間に合わなかったフレームは飛ばす
+            Err(wgpu::SurfaceError::Timeout) => {
+                warn!("フレームの取得がタイムアウトしたため、描画を飛ばしました");
+                Ok(())
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
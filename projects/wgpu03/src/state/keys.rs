@@ -0,0 +1,484 @@
+use tracing::{info, warn};
+use winit::{
+    event::{DeviceEvent, WindowEvent},
+    keyboard::KeyCode,
+};
+
+use crate::gpu::is_vsync;
+use crate::mesh::{Instance, Vertex};
+use crate::post::PostPass;
+use crate::render::{PipelineOptions, create_msaa_view, create_render_pipeline};
+use crate::shading::Shading;
+use crate::texture::Texture;
+use crate::uniforms::{
+    REFLECTIVITY_PRESETS, ROUGHNESS_PRESETS, TINT_PRESETS, create_light_bind_group,
+    create_plane_bind_group, next_preset,
+};
+
+use super::shaders::create_shading_pipeline;
+use super::{CameraMode, MAX_ZFAR, MIN_TERRAIN_HEIGHT, MIN_ZFAR, State, TERRAIN_HEIGHT_STEP};
+
+impl State {
+    // 設定パネルかカメラ操作に使われた入力の場合は true を返す
+    pub fn input(&mut self, event: &WindowEvent) -> bool {
+        // 設定パネルが使ったイベントはカメラの操作やキー入力には渡さない
+        #[cfg(feature = "ui")]
+        if let (Some(ui), Some(window)) = (&mut self.ui, &self.window)
+            && ui.on_window_event(window, event)
+        {
+            return true;
+        }
+        match self.camera_mode {
+            CameraMode::Fly => self.camera_controller.process_window_event(event),
+            CameraMode::Orbit => self.orbit_controller.process_window_event(event),
+        }
+    }
+
+    pub fn device_input(&mut self, event: &DeviceEvent) {
+        match self.camera_mode {
+            CameraMode::Fly => self.camera_controller.process_device_event(event),
+            CameraMode::Orbit => self.orbit_controller.process_device_event(event),
+        }
+    }
+
+    // キー入力を処理し、再描画が必要な場合は true を返す
+    pub fn key_pressed(&mut self, code: KeyCode) -> bool {
+        match code {
+            KeyCode::KeyM => {
+                // 三角形（非インデックス描画）・五角形（インデックス描画）・
+                // 三角形のグリッド（インスタンス描画）・三角形の波（コンピュートシェーダー）・
+                // 立方体・OBJモデル・glTFシーンを順に切り替える
+                self.shape = self.shape.toggle(&self.capabilities);
+                info!("表示する図形: {:?}", self.shape);
+                true
+            }
+            KeyCode::Digit1
+            | KeyCode::Digit2
+            | KeyCode::Digit3
+            | KeyCode::Digit4
+            | KeyCode::Digit5 => {
+                // 数字キーで色味を切り替え、ユニフォームバッファを更新する
+                let index = match code {
+                    KeyCode::Digit1 => 0,
+                    KeyCode::Digit2 => 1,
+                    KeyCode::Digit3 => 2,
+                    KeyCode::Digit4 => 3,
+                    _ => 4,
+                };
+                self.uniforms.tint = TINT_PRESETS[index];
+                self.queue.write_buffer(
+                    &self.uniform_buffer,
+                    0,
+                    bytemuck::cast_slice(&[self.uniforms]),
+                );
+                true
+            }
+            KeyCode::KeyT => {
+                // 法線マップの有無を切り替えて、ライティングの違いを比較できるようにする
+                self.uniforms.normal_mapping ^= 1;
+                self.queue.write_buffer(
+                    &self.uniform_buffer,
+                    0,
+                    bytemuck::cast_slice(&[self.uniforms]),
+                );
+                info!(
+                    "法線マップ: {}",
+                    if self.uniforms.normal_mapping != 0 {
+                        "有効"
+                    } else {
+                        "無効"
+                    }
+                );
+                true
+            }
+            KeyCode::KeyR | KeyCode::KeyU => {
+                // 立方体の映り込みの割合（R）と粗さ（U）を切り替える
+                if code == KeyCode::KeyR {
+                    self.reflection.reflectivity =
+                        next_preset(&REFLECTIVITY_PRESETS, self.reflection.reflectivity);
+                } else {
+                    self.reflection.roughness =
+                        next_preset(&ROUGHNESS_PRESETS, self.reflection.roughness);
+                }
+                self.queue.write_buffer(
+                    &self.reflection_buffer,
+                    0,
+                    bytemuck::cast_slice(&[self.reflection]),
+                );
+                info!(
+                    "映り込み: 反射率 {:.1} / 粗さ {:.1}",
+                    self.reflection.reflectivity, self.reflection.roughness
+                );
+                true
+            }
+            KeyCode::KeyC => {
+                // フライカメラとオービットカメラを切り替える
+                // 切り替え前のコントローラーに残った入力状態は解除しておく
+                self.camera_mode = match self.camera_mode {
+                    CameraMode::Fly => {
+                        self.camera_controller.reset();
+                        CameraMode::Orbit
+                    }
+                    CameraMode::Orbit => {
+                        self.orbit_controller.reset();
+                        CameraMode::Fly
+                    }
+                };
+                info!("カメラモード: {:?}", self.camera_mode);
+                true
+            }
+            KeyCode::KeyN => {
+                // MSAAの有効・無効を切り替え、パイプラインとサンプル数依存のテクスチャを作り直す
+                if self.max_sample_count == 1 {
+                    info!("このアダプタはMSAAに対応していません");
+                    return false;
+                }
+                self.sample_count = if self.sample_count == 1 {
+                    self.max_sample_count
+                } else {
+                    1
+                };
+                self.rebuild_pipelines();
+                self.depth_texture = Texture::create_depth_texture(
+                    &self.device,
+                    &self.config,
+                    self.sample_count,
+                    "Depth Texture",
+                );
+                self.msaa_view = create_msaa_view(&self.device, &self.config, self.sample_count);
+                self.inset
+                    .set_sample_count(&self.device, &self.config, self.sample_count);
+                info!("MSAAサンプル数: {}", self.sample_count);
+                true
+            }
+            KeyCode::KeyW => {
+                // ワイヤーフレーム表示を切り替える
+                // （フライカメラ操作中は W キーが前進に使われるため、オービットカメラ時のみ届く）
+                let wireframe = self.shading_pipelines.current() != Shading::Wireframe;
+                if !self.select_shading(if wireframe {
+                    Shading::Wireframe
+                } else {
+                    Shading::Textured
+                }) {
+                    info!(
+                        "このアダプタはワイヤーフレーム表示（POLYGON_MODE_LINE）に対応していません"
+                    );
+                    return false;
+                }
+                info!("ワイヤーフレーム表示: {}", wireframe);
+                true
+            }
+            KeyCode::Tab => {
+                // シェーディングを順に切り替える（デバイスが対応していないものは飛ばす）
+                self.shading_pipelines.select_next(|shading| {
+                    create_shading_pipeline(
+                        &self.device,
+                        &self.pipeline_cache,
+                        &self.pipeline_layout,
+                        &self.shader,
+                        &self.normals_shader,
+                        self.sample_count,
+                        shading,
+                    )
+                });
+                info!(
+                    "シェーディング: {}",
+                    self.shading_pipelines.current().name()
+                );
+                true
+            }
+            KeyCode::KeyB => {
+                // 半透明の図形のブレンドモードを切り替える
+                self.blend_mode = self.blend_mode.next();
+                self.translucent_pipeline = create_render_pipeline(
+                    &self.device,
+                    &self.pipeline_cache,
+                    &self.pipeline_layout,
+                    &self.shader,
+                    &[Vertex::desc(), Instance::desc()],
+                    PostPass::FORMAT,
+                    self.sample_count,
+                    &PipelineOptions::translucent(self.blend_mode),
+                );
+                info!("ブレンドモード: {:?}", self.blend_mode);
+                true
+            }
+            KeyCode::BracketLeft | KeyCode::BracketRight => {
+                // シャドウマップの深度バイアスを調整する
+                // 小さすぎると縞模様（シャドウアクネ）、大きすぎると影が浮く（ピーターパン）
+                let step = if code == KeyCode::BracketRight { 1 } else { -1 };
+                self.depth_bias.constant = (self.depth_bias.constant + step).max(0);
+                self.rebuild_shadow_pipelines();
+                info!(
+                    "深度バイアス: constant = {}, slope_scale = {}",
+                    self.depth_bias.constant, self.depth_bias.slope_scale
+                );
+                true
+            }
+            KeyCode::Equal | KeyCode::Minus => {
+                // 点光源の数を増減する（ユニフォーム配列の場合は上限あり）
+                let max = self.light_storage.max_lights().unwrap_or(usize::MAX);
+                let count = if code == KeyCode::Equal {
+                    (self.light_count + 1).min(max)
+                } else {
+                    self.light_count.saturating_sub(1).max(1)
+                };
+                if count == self.light_count {
+                    return false;
+                }
+                self.light_count = count;
+                if self.lights_buffer.resize(&self.device, count) {
+                    self.light_bind_group = create_light_bind_group(
+                        &self.device,
+                        &self.light_bind_group_layout,
+                        &self.light_info_buffer,
+                        &self.shadow_map,
+                        &self.lights_buffer,
+                    );
+                }
+                info!("点光源の数: {}", self.light_count);
+                true
+            }
+            KeyCode::KeyG => {
+                // 原点の座標軸の表示を切り替える
+                self.show_axes = !self.show_axes;
+                info!(
+                    "座標軸: {}",
+                    if self.show_axes {
+                        "表示"
+                    } else {
+                        "非表示"
+                    }
+                );
+                true
+            }
+            KeyCode::KeyF => {
+                // 無限グリッドの床の表示を切り替える
+                self.show_grid = !self.show_grid;
+                info!(
+                    "グリッド: {}",
+                    if self.show_grid {
+                        "表示"
+                    } else {
+                        "非表示"
+                    }
+                );
+                true
+            }
+            KeyCode::F2 => {
+                // ウィンドウの左側を空けて、右側だけにシーンを描画するかを切り替える
+                self.show_sidebar = !self.show_sidebar;
+                self.layout_scene_viewport();
+                info!("シーンの描画範囲: {:?}", self.scene_viewport.physical());
+                true
+            }
+            KeyCode::PageUp | KeyCode::PageDown => {
+                // 地形の高さの拡大率を変える（モデル行列の Y の拡大率になる）
+                let step = if code == KeyCode::PageUp {
+                    TERRAIN_HEIGHT_STEP
+                } else {
+                    -TERRAIN_HEIGHT_STEP
+                };
+                self.terrain_height = (self.terrain_height + step).max(MIN_TERRAIN_HEIGHT);
+                info!("地形の高さ: {}", self.terrain_height);
+                true
+            }
+            KeyCode::Home | KeyCode::End => {
+                // 遠クリップ面を遠ざける・近づける（地形の奥まで表示するため）
+                let zfar = if code == KeyCode::Home {
+                    self.camera.zfar * 2.0
+                } else {
+                    self.camera.zfar * 0.5
+                };
+                self.camera.zfar = zfar.clamp(MIN_ZFAR, MAX_ZFAR);
+                info!("遠クリップ面: {}", self.camera.zfar);
+                true
+            }
+            KeyCode::KeyY => {
+                // 再生するアニメーションのクリップを切り替え、最初から再生する
+                let Some(clips) = self.animated_model.as_ref().map(|model| &model.clips) else {
+                    return false;
+                };
+                if clips.is_empty() {
+                    return false;
+                }
+                self.animation_clip = (self.animation_clip + 1) % clips.len();
+                self.animation_start = self.uniforms.time;
+                info!("アニメーション: {}", clips[self.animation_clip].name);
+                true
+            }
+            KeyCode::KeyQ => {
+                // 生成した図形のデモで表示する図形を切り替える
+                self.primitive = self.primitive.next();
+                info!("生成した図形: {:?}", self.primitive);
+                true
+            }
+            KeyCode::KeyX => {
+                // クリックした物体を調べる方法を切り替える
+                self.pick_method = self.pick_method.next();
+                info!("物体の選択方法: {:?}", self.pick_method);
+                true
+            }
+            KeyCode::KeyE => {
+                // 太陽系のシーンで衛星の親を付け替える・削除する
+                match self.solar_system.cycle_moon() {
+                    Ok(state) => info!("衛星: {}", state),
+                    Err(e) => warn!("衛星を切り替えられませんでした: {:#}", e),
+                }
+                true
+            }
+            KeyCode::KeyZ => {
+                // カリングに使う視錐台を今のカメラの位置で固定する・固定をやめる
+                self.frozen_view_projection = match self.frozen_view_projection {
+                    Some(_) => None,
+                    None => Some(self.camera.build_view_projection_matrix()),
+                };
+                info!(
+                    "カリングの視錐台: {}",
+                    if self.frozen_view_projection.is_some() {
+                        "固定"
+                    } else {
+                        "カメラに追従"
+                    }
+                );
+                true
+            }
+            KeyCode::F7 => {
+                // デバッグ用: デバイスを破棄して、失われたときの作り直しを確かめる
+                info!("デバイスを破棄します");
+                self.device.destroy();
+                self.device.poll(wgpu::Maintain::Wait);
+                true
+            }
+            KeyCode::F6 => {
+                self.frame_limiter.cycle();
+                match self.frame_limiter.max_fps() {
+                    Some(fps) if is_vsync(self.config.present_mode) => info!(
+                        "FPS の上限: {}（{:?} では垂直同期に合わせるため、V キーで表示モードを変えると有効になります）",
+                        fps, self.config.present_mode
+                    ),
+                    Some(fps) => info!("FPS の上限: {}", fps),
+                    None => info!("FPS の上限: なし"),
+                }
+                true
+            }
+            KeyCode::F9 => {
+                // 録画を止める・再開する
+                let Some(recorder) = &mut self.recorder else {
+                    info!("録画するには --record N を指定してください");
+                    return false;
+                };
+                recorder.toggle();
+                info!(
+                    "録画: {}",
+                    if recorder.is_recording() {
+                        "再開"
+                    } else {
+                        "停止"
+                    }
+                );
+                true
+            }
+            KeyCode::F4 => {
+                // 最近のフレームのパスごとの時間を、chrome://tracing で開ける JSON に書き出す
+                self.profiler.save_chrome_trace();
+                true
+            }
+            KeyCode::F12 => {
+                // 次に表示するフレームを screenshots/ に保存する
+                self.screenshot_requested = true;
+                true
+            }
+            KeyCode::KeyJ => {
+                // 右下の小窓に、固定のカメラから見たシーンを描画するかを切り替える
+                self.show_inset = !self.show_inset;
+                info!(
+                    "小窓: {}",
+                    if self.show_inset {
+                        "表示"
+                    } else {
+                        "非表示"
+                    }
+                );
+                true
+            }
+            KeyCode::KeyH => {
+                // 立方体の輪郭の表示を切り替える
+                self.show_outline = !self.show_outline;
+                info!(
+                    "輪郭: {}",
+                    if self.show_outline {
+                        "表示"
+                    } else {
+                        "非表示"
+                    }
+                );
+                true
+            }
+            KeyCode::KeyI => {
+                // 遠くまで続く平面のフィルタリングを切り替える
+                let filter = self.plane_filter.next();
+                match create_plane_bind_group(
+                    &self.device,
+                    &self.texture_bind_group_layout,
+                    &self.plane_texture,
+                    filter,
+                    self.max_anisotropy,
+                ) {
+                    Ok(bind_group) => {
+                        self.plane_texture_bind_group = bind_group;
+                        self.plane_filter = filter;
+                        info!("平面のフィルタリング: {:?}", filter);
+                    }
+                    Err(e) => warn!("サンプラーを作成できませんでした: {:#}", e),
+                }
+                true
+            }
+            KeyCode::F3 => {
+                // ポストプロセスのエフェクトを切り替える
+                self.post.settings.effect = self.post.settings.effect.next();
+                self.post.write_settings(&self.queue);
+                info!("ポストプロセス: {:?}", self.post.settings.effect);
+                true
+            }
+            KeyCode::KeyO => {
+                // トーンマッピングの演算子を切り替える
+                self.post.settings.tonemap = self.post.settings.tonemap.next();
+                self.post.write_settings(&self.queue);
+                info!("トーンマッピング: {:?}", self.post.settings.tonemap);
+                true
+            }
+            KeyCode::NumpadAdd | KeyCode::NumpadSubtract => {
+                // 露出を 1/4 段ずつ調整する（= / - キーは点光源の数の増減に使っている）
+                let stops = if code == KeyCode::NumpadAdd {
+                    0.25
+                } else {
+                    -0.25
+                };
+                let exposure = self.post.settings.exposure * 2f32.powf(stops);
+                self.post.settings.exposure = exposure.clamp(1.0 / 16.0, 16.0);
+                self.post.write_settings(&self.queue);
+                info!("露出: {:.2}", self.post.settings.exposure);
+                true
+            }
+            KeyCode::Comma | KeyCode::Period => {
+                // ブルームのぼかしの半径を調整する
+                let step = if code == KeyCode::Period { 0.25 } else { -0.25 };
+                self.bloom.settings.radius = (self.bloom.settings.radius + step).clamp(0.25, 4.0);
+                self.bloom.write_settings(&self.queue);
+                info!("ブルームの半径: {}", self.bloom.settings.radius);
+                true
+            }
+            KeyCode::KeyK | KeyCode::KeyL => {
+                // ブルームの強さを調整する（0 にするとブルームのパスを省略する）
+                let step = if code == KeyCode::KeyL { 0.1 } else { -0.1 };
+                self.bloom.settings.intensity =
+                    (self.bloom.settings.intensity + step).clamp(0.0, 2.0);
+                self.bloom.write_settings(&self.queue);
+                info!("ブルームの強さ: {:.1}", self.bloom.settings.intensity);
+                true
+            }
+            _ => false,
+        }
+    }
+}
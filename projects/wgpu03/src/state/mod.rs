@@ -0,0 +1,646 @@
+mod frame;
+mod keys;
+mod passes;
+mod pick;
+mod setup;
+mod shaders;
+
+use std::{
+    sync::{Arc, atomic::AtomicBool},
+    time::Instant,
+};
+
+use tracing::{info, warn};
+use winit::{dpi::PhysicalSize, event::InnerSizeWriter, window::Window};
+
+use crate::atlas::Atlas;
+use crate::bloom::Bloom;
+use crate::bundle::StaticScene;
+use crate::camera::{Camera, CameraController, OrbitCameraController};
+use crate::capabilities::GpuCapabilities;
+use crate::debug_lines::DebugLines;
+use crate::frame_limiter::FrameLimiter;
+use crate::frustum::CullStats;
+use crate::gpu::{is_vsync, next_present_mode};
+use crate::hot_reload::ShaderCache;
+use crate::indirect::IndirectDraw;
+use crate::input::{rescaled_cursor, rescaled_size};
+use crate::inset::{INSET_HEIGHT, INSET_WIDTH, Inset};
+use crate::light::{LightBuffer, LightStorage};
+use crate::mesh::Mesh;
+use crate::model::Model;
+use crate::occlusion::OcclusionQuery;
+use crate::overlay::TextOverlay;
+use crate::particles::ParticleSystem;
+use crate::per_draw::{DrawData, PerDrawBuffer};
+use crate::picking::Picking;
+use crate::pipeline_cache::PipelineCache;
+use crate::post::PostPass;
+use crate::profiler::Profiler;
+use crate::recorder::Recorder;
+use crate::render::{BlendMode, StageModules, create_msaa_view};
+use crate::shading::{PipelineRegistry, Shading};
+use crate::shape::{Shape, SolarSystem};
+use crate::sprite::SpriteBatch;
+use crate::stats::FrameStats;
+use crate::texture::{MAX_ANISOTROPY, SamplerOptions, Texture};
+use crate::uniform_arena::UniformArena;
+use crate::uniforms::{CameraBindings, ReflectionUniform, Uniforms};
+use crate::viewport::{LogicalRect, Viewport};
+use crate::wave::WaveCompute;
+use crate::windows::WindowState;
+
+use shaders::create_shading_pipeline;
+
+pub use pick::{PickMethod, Primitive};
+
+// 既定で読み込むOBJモデルとglTFシーン
+const DEFAULT_MODEL_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/scene.obj");
+const DEFAULT_GLTF_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/scene.glb");
+// `--animated` で差し替えられる、スキンとアニメーションを持つ glTF
+const DEFAULT_ANIMATED_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/animated.glb");
+// `--heightmap` で差し替えられる地形の高さマップ（グレースケールの画像）
+const DEFAULT_HEIGHTMAP_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/heightmap.png");
+// 地形を分割するセル数（1つのチャンクの頂点数を抑えて、画面外のチャンクを捨てられるようにする）
+const TERRAIN_CHUNK_SIZE: u32 = 64;
+// 高さマップの隣り合う画素の間隔
+const TERRAIN_SPACING: f32 = 0.125;
+// PageUp / PageDown で1回に変える地形の高さと、その下限
+const TERRAIN_HEIGHT_STEP: f32 = 0.5;
+const MIN_TERRAIN_HEIGHT: f32 = 0.5;
+// Home / End で変えるカメラの遠クリップ面の範囲
+const MIN_ZFAR: f32 = 10.0;
+const MAX_ZFAR: f32 = 1000.0;
+
+// バックエンドのエラーメッセージやキャプチャで見分けるためのラベル
+const FRAME_ENCODER_LABEL: &str = "Frame Encoder";
+pub const MAIN_PASS_LABEL: &str = "Main Pass";
+pub const SHADOW_PASS_LABEL: &str = "Shadow Pass";
+const SCENE_PIPELINE_LAYOUT_LABEL: &str = "Scene Pipeline Layout";
+
+// コンピュートシェーダーで頂点を生成する三角形の数と、1行に並べる数
+// （三角形の数はワークグループの大きさの倍数でなくてもよい）
+const WAVE_TRIANGLES: u32 = 1000;
+const WAVE_COLUMNS: u32 = 40;
+
+// KTX2 の圧縮テクスチャをそのまま使うために要求するデバイスの機能
+// BC はデスクトップ、ETC2 と ASTC はモバイルの GPU でおもに使える
+const TEXTURE_COMPRESSION_FEATURES: wgpu::Features = wgpu::Features::TEXTURE_COMPRESSION_BC
+    .union(wgpu::Features::TEXTURE_COMPRESSION_ETC2)
+    .union(wgpu::Features::TEXTURE_COMPRESSION_ASTC);
+
+// 起動時の背景色（設定パネルから変更できる）
+const CLEAR_COLOR: wgpu::Color = wgpu::Color {
+    r: 0.05,
+    g: 0.062,
+    b: 0.08,
+    a: 1.0,
+};
+
+// サイドバーを空ける場合に、シーンを描画するウィンドウの右側の割合（F2 キーで切り替える）
+const SCENE_WIDTH_FRACTION: f32 = 0.7;
+
+// ウィンドウの左側をサイドバー用に空け、右側にシーンを描画する範囲
+fn scene_rect(surface: LogicalRect) -> LogicalRect {
+    let width = surface.width * SCENE_WIDTH_FRACTION;
+    LogicalRect {
+        x: surface.x + surface.width - width,
+        width,
+        ..surface
+    }
+}
+
+// 原点に描画する座標軸の長さ
+const AXIS_LENGTH: f32 = 1.5;
+// Z キーで固定したカリングの視錐台の辺の色
+const FROZEN_FRUSTUM_COLOR: [f32; 3] = [1.0, 0.8, 0.1];
+
+// 起動時の点光源の数（= / - キーで増減できる）
+const INITIAL_LIGHT_COUNT: usize = 3;
+
+// シャドウマップの解像度と、光源からの平行投影で影を落とす範囲
+const SHADOW_MAP_SIZE: u32 = 2048;
+const SHADOW_HALF_EXTENT: f32 = 4.5;
+// 光源の方向にこの距離だけ離れた位置から投影し、床の手前側が near 面で切れないようにする
+const SHADOW_DISTANCE: f32 = 6.0;
+const SHADOW_NEAR: f32 = 0.1;
+const SHADOW_FAR: f32 = 14.0;
+// シャドウアクネとピーターパンのバランスを取るための深度バイアスの初期値
+const SHADOW_DEPTH_BIAS: wgpu::DepthBiasState = wgpu::DepthBiasState {
+    constant: 2,
+    slope_scale: 2.0,
+    clamp: 0.0,
+};
+
+// 点光源の方向からシーンの中心を見下ろす平行投影の行列を作成する
+// （点光源だが、影は平行光源とみなして平行投影で求める）
+fn light_view_projection(position: glam::Vec3) -> glam::Mat4 {
+    let eye = position.normalize() * SHADOW_DISTANCE;
+    let view = glam::Mat4::look_at_rh(eye, glam::Vec3::ZERO, glam::Vec3::Y);
+    let projection = glam::Mat4::orthographic_rh(
+        -SHADOW_HALF_EXTENT,
+        SHADOW_HALF_EXTENT,
+        -SHADOW_HALF_EXTENT,
+        SHADOW_HALF_EXTENT,
+        SHADOW_NEAR,
+        SHADOW_FAR,
+    );
+    projection * view
+}
+
+// 小窓に描画する固定のカメラ（斜め上からシーン全体を見下ろす。J キーで表示を切り替える）
+fn inset_camera() -> Camera {
+    let mut camera = Camera::new(INSET_WIDTH, INSET_HEIGHT);
+    camera.eye = glam::Vec3::new(2.5, 2.0, 2.5);
+    camera.target = glam::Vec3::ZERO;
+    camera
+}
+
+// シーンを描画する先と、そのときの視点
+pub struct SceneTarget<'a> {
+    // MSAA の場合は解決先
+    view: &'a wgpu::TextureView,
+    msaa_view: Option<&'a wgpu::TextureView>,
+    depth_view: &'a wgpu::TextureView,
+    bindings: &'a CameraBindings,
+    // 半透明の図形をカメラから遠い順に並べるための視点の位置
+    eye: glam::Vec3,
+    // メインのカメラの場合のみ、描画範囲の指定・GPU の計測・オクルージョンクエリを行う
+    primary: bool,
+}
+
+// スカイボックスのキューブマップを読み込むディレクトリ
+const DEFAULT_SKYBOX_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/skybox");
+
+// カメラの操作方法
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum CameraMode {
+    Fly,
+    Orbit,
+}
+
+// 遠くまで続く平面のテクスチャのフィルタリング
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlaneFilter {
+    Nearest,
+    Anisotropic,
+}
+
+impl PlaneFilter {
+    fn next(self) -> Self {
+        match self {
+            PlaneFilter::Nearest => PlaneFilter::Anisotropic,
+            PlaneFilter::Anisotropic => PlaneFilter::Nearest,
+        }
+    }
+
+    pub fn sampler_options(self) -> SamplerOptions {
+        match self {
+            PlaneFilter::Nearest => SamplerOptions::nearest(),
+            PlaneFilter::Anisotropic => SamplerOptions::anisotropic(MAX_ANISOTROPY),
+        }
+    }
+}
+
+// ウィンドウのタイトルに選択中のシェーディングを表示する
+fn window_title(title: &str, shading: Shading, adapter: &str) -> String {
+    format!("{} - {} - {}", title, shading.name(), adapter)
+}
+
+pub struct State {
+    // 描画するウィンドウ（ヘッドレスの場合は None）
+    // サーフェイスが Arc で持つので、サーフェイスはウィンドウより長く生きる 'static にできる
+    pub window: Option<Arc<Window>>,
+    pub config: wgpu::SurfaceConfiguration,
+    // ヘッドレスの場合と、アプリケーションが中断されている間は None
+    // （config は描画先のテクスチャの設定として使う）
+    surface: Option<wgpu::Surface<'static>>,
+    // 再開したときと、Ctrl+N でウィンドウを開いたときにサーフェイスを作るために持っておく
+    instance: wgpu::Instance,
+    adapter: wgpu::Adapter,
+    pub device: wgpu::Device,
+    pub queue: wgpu::Queue,
+    // ウィンドウのタイトルの先頭（`--title`）と、続けて表示するアダプタの名前
+    title: String,
+    adapter_name: String,
+    // 起動時にディスクから読み込み、閉じるときに書き戻す（対応していないバックエンドでは何もしない）
+    pub pipeline_cache: PipelineCache,
+    // Tab キーで切り替えるシェーディングのパイプライン（初めて選んだときに作成する）
+    shading_pipelines: PipelineRegistry,
+    pub clear_color: wgpu::Color,
+    // `--transparent` で、サーフェイスがアルファでデスクトップと合成される（スカイボックスは描画しない）
+    pub transparent: bool,
+    translucent_pipeline: wgpu::RenderPipeline,
+    // 隠れている奥の三角形を、クエリのためだけに描画するパイプライン
+    occlusion_proxy_pipeline: wgpu::RenderPipeline,
+    blend_mode: BlendMode,
+    model_pipeline: wgpu::RenderPipeline,
+    model_pipeline_layout: wgpu::PipelineLayout,
+    model_shader: wgpu::ShaderModule,
+    // glTF のメタリック・ラフネスのマテリアルを描画する PBR パイプライン
+    pbr_pipeline: wgpu::RenderPipeline,
+    pbr_pipeline_layout: wgpu::PipelineLayout,
+    pbr_shader: wgpu::ShaderModule,
+    // Shading::Normals で使う、GLSL から変換した SPIR-V のシェーダー
+    normals_shader: StageModules,
+    // スキンメッシュを描画するパイプライン（PBR のレイアウトに関節の行列のバインドグループを加える）
+    skinned_pipeline: wgpu::RenderPipeline,
+    skinned_pipeline_layout: wgpu::PipelineLayout,
+    // 読み込みに失敗した場合は None
+    obj_model: Option<Model>,
+    gltf_model: Option<Model>,
+    animated_model: Option<Model>,
+    // 再生中のクリップの番号と、再生を始めた時刻（起動からの経過時間）
+    animation_clip: usize,
+    animation_start: f32,
+    // 点光源とその位置を示す立方体を描画するパイプライン
+    light_pipeline: wgpu::RenderPipeline,
+    light_pipeline_layout: wgpu::PipelineLayout,
+    light_shader: wgpu::ShaderModule,
+    // 座標軸などのデバッグ用の線分（カメラのバインドグループを共有する）
+    debug_lines: DebugLines,
+    debug_line_pipeline: wgpu::RenderPipeline,
+    debug_line_pipeline_layout: wgpu::PipelineLayout,
+    debug_line_shader: wgpu::ShaderModule,
+    per_draw: PerDrawBuffer,
+    per_draw_pipeline: wgpu::RenderPipeline,
+    per_draw_pipeline_layout: wgpu::PipelineLayout,
+    per_draw_shader: wgpu::ShaderModule,
+    swarm: UniformArena<DrawData>,
+    swarm_pipeline: wgpu::RenderPipeline,
+    swarm_pipeline_layout: wgpu::PipelineLayout,
+    swarm_shader: wgpu::ShaderModule,
+    // 太陽系のシーン（群れと同じシェーダーで、節点ごとに動的オフセットを切り替えて描画する）
+    solar_system: SolarSystem,
+    solar_system_pipeline: wgpu::RenderPipeline,
+    solar_system_pipeline_layout: wgpu::PipelineLayout,
+    pub static_scene: StaticScene,
+    show_axes: bool,
+    // 点光源の数と、光源の配列をシェーダーに渡す方法
+    light_count: usize,
+    light_storage: LightStorage,
+    // デバイスで使える機能（起動時とデバイスを作り直したときに調べる）
+    capabilities: GpuCapabilities,
+    light_info_buffer: wgpu::Buffer,
+    lights_buffer: LightBuffer,
+    light_bind_group_layout: wgpu::BindGroupLayout,
+    light_bind_group: wgpu::BindGroup,
+    // シャドウマップと、それに深度を書き込むパイプライン（図形用とモデル用）
+    shadow_map: Texture,
+    shadow_pipeline: wgpu::RenderPipeline,
+    model_shadow_pipeline: wgpu::RenderPipeline,
+    skinned_shadow_pipeline: wgpu::RenderPipeline,
+    shadow_pipeline_layout: wgpu::PipelineLayout,
+    skinned_shadow_pipeline_layout: wgpu::PipelineLayout,
+    shadow_shader: wgpu::ShaderModule,
+    // シャドウパスではシャドウマップ自体を参照できないので、光源のユニフォームだけを持つ
+    shadow_bind_group: wgpu::BindGroup,
+    depth_bias: wgpu::DepthBiasState,
+    // 影を受ける床（モデル行列を単位行列にしたカメラのバインドグループで描画する）
+    floor: Mesh,
+    skybox_pipeline: wgpu::RenderPipeline,
+    skybox_pipeline_layout: wgpu::PipelineLayout,
+    skybox_shader: wgpu::ShaderModule,
+    reflection: ReflectionUniform,
+    reflection_buffer: wgpu::Buffer,
+    reflection_bind_group: wgpu::BindGroup,
+    no_reflection_bind_group: wgpu::BindGroup,
+    // 画面全体を覆う三角形から水平面との交点を求めて描画する無限グリッドの床
+    grid_pipeline: wgpu::RenderPipeline,
+    grid_pipeline_layout: wgpu::PipelineLayout,
+    grid_shader: wgpu::ShaderModule,
+    show_grid: bool,
+    // メインのカメラのユニフォームとバインドグループ
+    pub camera_bindings: CameraBindings,
+    // 右下の小窓に、固定のカメラから見たシーンを描画する
+    inset: Inset,
+    inset_camera: Camera,
+    inset_bindings: CameraBindings,
+    show_inset: bool,
+    // シーンを描画するウィンドウの中の範囲と、左側にサイドバーを空けるか
+    scene_viewport: Viewport,
+    show_sidebar: bool,
+    // 選択した物体（立方体）の輪郭
+    outline_pipeline: wgpu::RenderPipeline,
+    outline_pipeline_layout: wgpu::PipelineLayout,
+    outline_shader: wgpu::ShaderModule,
+    outline_bind_group: wgpu::BindGroup,
+    show_outline: bool,
+    pipeline_layout: wgpu::PipelineLayout,
+    shader: wgpu::ShaderModule,
+    // シェーダーのファイルが変更されたことが通知され、次のフレームで読み込み直す
+    pub shader_changed: bool,
+    // デバイスが失われたときにコールバックが true にする（F7 キーで破棄しても true になる）
+    pub device_lost: Arc<AtomicBool>,
+    // RELOADABLE_SHADERS の順に、使っているシェーダーのソースのハッシュ
+    shader_hashes: [u64; 5],
+    shader_cache: ShaderCache,
+    // アダプタが対応している最大のサンプル数と現在のサンプル数
+    max_sample_count: u32,
+    pub sample_count: u32,
+    pub msaa_view: Option<wgpu::TextureView>,
+    // シーンの描画先のオフスクリーンテクスチャと、それをサーフェイスに書き込むポストプロセス
+    pub post: PostPass,
+    // オフスクリーンのテクスチャに光のにじみを加算するブルーム
+    bloom: Bloom,
+    // 最後にサーフェイスへ重ねる文字（フレーム時間などの情報）
+    overlay: TextOverlay,
+    // パスごとの時間（TIMESTAMP_QUERY に対応していなければ、CPU で記録した時間だけ）
+    profiler: Profiler,
+    // F12 キーで true にし、次に表示するフレームをスクリーンショットとして保存する
+    screenshot_requested: bool,
+    // V キーで順に切り替える、サーフェイスが対応している表示モード
+    present_modes: Vec<wgpu::PresentMode>,
+    // `--max-fps N` で指定した FPS の上限（F6 キーと設定パネルで変更できる）
+    pub frame_limiter: FrameLimiter,
+    // `--record N` を指定した場合のみ（F9 キーで止める・再開する）
+    pub recorder: Option<Recorder>,
+    // egui の設定パネル（ui フィーチャーが有効な場合のみ。ヘッドレスの場合は None）
+    #[cfg(feature = "ui")]
+    ui: Option<crate::ui::Ui>,
+    // 直近のフレームの間隔と、RedrawRequested の処理にかかった CPU 時間
+    frame_stats: FrameStats,
+    pub cpu_stats: FrameStats,
+    // 最後に統計の表示を更新した時刻（まだ更新していなければ None）
+    stats_refreshed: Option<Instant>,
+    triangle: Mesh,
+    // 三角形を描画するときの描画の引数（draw_indirect を使えなければ直接描画する）
+    triangle_indirect: IndirectDraw,
+    pentagon: Mesh,
+    back_triangle: Mesh,
+    // 奥の三角形が手前の図形に隠れているかを調べるクエリ
+    back_occlusion: OcclusionQuery,
+    pub cube: Mesh,
+    // Primitive::ALL の順に生成した図形のメッシュ
+    primitive_meshes: Vec<Mesh>,
+    primitive: Primitive,
+    // 地形のチャンクごとのメッシュ（高さマップを読み込めなければ空）
+    terrain: Vec<Mesh>,
+    terrain_height: f32,
+    // 視錐台カリングで表示中のメッシュ（地形のチャンクやモデルのメッシュ）ごとに描画するか
+    // Z キーで固定した場合は、その時点のビュー・射影行列の視錐台でカリングを続ける
+    visible: Vec<bool>,
+    cull_stats: CullStats,
+    frozen_view_projection: Option<glam::Mat4>,
+    // コンピュートパスを使えないデバイスでは None
+    wave: Option<WaveCompute>,
+    particles: Option<ParticleSystem>,
+    particle_quad: Mesh,
+    sprites: SpriteBatch,
+    sprite_atlas: Atlas,
+    // ウィンドウ内のカーソルの位置（パーティクルの放出位置に使う）
+    pub cursor: Option<(f32, f32)>,
+    // 左ボタンを押した位置（オービットカメラでは左ドラッグで回転するので、動かさずに離したときだけ物体を選ぶ）
+    click_start: Option<(f32, f32)>,
+    // 左クリックした位置の物体を調べる
+    picking: Picking,
+    pub pick_method: PickMethod,
+    // スロット1に設定するインスタンスバッファ
+    identity_instance_buffer: wgpu::Buffer,
+    instance_buffer: wgpu::Buffer,
+    translucent_instance_buffer: wgpu::Buffer,
+    model: glam::Mat4,
+    pub shape: Shape,
+    pub depth_texture: Texture,
+    uniforms: Uniforms,
+    uniform_buffer: wgpu::Buffer,
+    texture_bind_group: wgpu::BindGroup,
+    plane: Mesh,
+    plane_texture_bind_group: wgpu::BindGroup,
+    plane_filter: PlaneFilter,
+    max_anisotropy: u16,
+    // 平面のサンプラーを切り替えたときにバインドグループを作り直すために持っておく
+    plane_texture: Texture,
+    texture_bind_group_layout: wgpu::BindGroupLayout,
+    camera: Camera,
+    camera_mode: CameraMode,
+    camera_controller: CameraController,
+    orbit_controller: OrbitCameraController,
+}
+
+impl State {
+    // 描画に使うパイプラインを返す
+    fn active_pipeline(&self) -> &wgpu::RenderPipeline {
+        self.shading_pipelines.pipeline()
+    }
+
+    fn window_title(&self) -> String {
+        window_title(
+            &self.title,
+            self.shading_pipelines.current(),
+            &self.adapter_name,
+        )
+    }
+
+    // シェーディングが変わったときなどに、ウィンドウのタイトルを今の状態にする
+    pub fn update_title(&self) {
+        if let Some(window) = &self.window {
+            window.set_title(&self.window_title());
+        }
+    }
+
+    pub fn request_redraw(&self) {
+        if let Some(window) = &self.window {
+            window.request_redraw();
+        }
+    }
+
+    // まだ作成していないシェーディングはここで作成する（対応していなければ false）
+    fn select_shading(&mut self, shading: Shading) -> bool {
+        self.shading_pipelines.select(shading, |shading| {
+            create_shading_pipeline(
+                &self.device,
+                &self.pipeline_cache,
+                &self.pipeline_layout,
+                &self.shader,
+                &self.normals_shader,
+                self.sample_count,
+                shading,
+            )
+        })
+    }
+
+    // サーフェイスが対応している表示モードを順に切り替える（垂直同期の有無と FPS の上限が変わる）
+    pub fn cycle_present_mode(&mut self) {
+        let (Some(surface), Some(mode)) = (
+            &self.surface,
+            next_present_mode(&self.present_modes, self.config.present_mode),
+        ) else {
+            info!("表示モードを切り替えられません");
+            return;
+        };
+        self.config.present_mode = mode;
+        surface.configure(&self.device, &self.config);
+        info!("表示モード: {:?}", mode);
+    }
+
+    // 垂直同期を待つかを切り替える（設定パネルから使う）
+    // 対応していない表示モードを指定しないよう、自動選択のモードを使う
+    #[cfg(feature = "ui")]
+    pub fn set_vsync(&mut self, vsync: bool) {
+        self.config.present_mode = if vsync {
+            wgpu::PresentMode::AutoVsync
+        } else {
+            wgpu::PresentMode::AutoNoVsync
+        };
+        if let Some(surface) = &self.surface {
+            surface.configure(&self.device, &self.config);
+        }
+        info!("表示モード: {:?}", self.config.present_mode);
+    }
+
+    // 次のフレームを描画する前に FPS の上限まで待つか
+    // 垂直同期を待つ表示モードでは、上限があっても表示の間隔で描画する
+    pub fn limits_frame_rate(&self) -> bool {
+        self.frame_limiter.max_fps().is_some() && !is_vsync(self.config.present_mode)
+    }
+
+    // メインのウィンドウと同じデバイスで描画するウィンドウのサーフェイスを作る
+    pub fn open_window(&self, window: Arc<Window>) -> anyhow::Result<WindowState> {
+        WindowState::new(
+            &self.instance,
+            &self.adapter,
+            &self.device,
+            window,
+            &self.config,
+        )
+    }
+
+    // メインのウィンドウを閉じたときに、残っているウィンドウをメインのウィンドウにする
+    // 前のウィンドウはここで捨てられて閉じ、大きさで決まるテクスチャは新しいウィンドウに合わせて作り直す
+    pub fn adopt_window(&mut self, window_state: WindowState) {
+        let (window, surface, config) = window_state.into_parts();
+        #[cfg(feature = "ui")]
+        if self.ui.is_some() {
+            self.ui = Some(crate::ui::Ui::new(
+                &self.device,
+                crate::gpu::surface_view_format(&self.config),
+                &window,
+            ));
+        }
+        let size = window.inner_size();
+        self.config.alpha_mode = config.alpha_mode;
+        self.surface = Some(surface);
+        self.window = Some(window);
+        self.resize(size);
+        self.update_title();
+    }
+
+    // 中断されたらサーフェイスを捨てる（Android では中断中にウィンドウが破棄される）
+    // デバイスやパイプラインは残しておき、再開したら resume でサーフェイスだけを作り直す
+    pub fn suspend(&mut self) {
+        self.surface = None;
+    }
+
+    pub fn resume(&mut self) -> Result<(), wgpu::CreateSurfaceError> {
+        let Some(window) = self.window.clone() else {
+            return Ok(());
+        };
+        let size = window.inner_size();
+        self.surface = Some(self.instance.create_surface(window)?);
+        // 中断中にウィンドウの大きさが変わっていることがあるので、今の大きさで設定する
+        self.resize(size);
+        Ok(())
+    }
+
+    // サーフェイスと、その大きさに合わせたテクスチャを作り直す
+    // ウィンドウの大きさが変わったときと、サーフェイスが使えなくなったときに呼ぶ
+    pub fn resize(&mut self, size: PhysicalSize<u32>) {
+        let _span =
+            tracing::debug_span!("resize", width = size.width, height = size.height).entered();
+        self.config.width = size.width.max(1);
+        self.config.height = size.height.max(1);
+        let config = &self.config;
+        if let Some(surface) = &self.surface {
+            surface.configure(&self.device, config);
+        }
+        // 深度テクスチャもサーフェイスのサイズに合わせて作り直す
+        self.depth_texture =
+            Texture::create_depth_texture(&self.device, config, self.sample_count, "Depth Texture");
+        self.msaa_view = create_msaa_view(&self.device, config, self.sample_count);
+        self.post.resize(&self.device, config);
+        self.picking.resize(&self.device, config);
+        self.bloom.resize(
+            &self.device,
+            &self.post.texture,
+            config.width,
+            config.height,
+        );
+        self.overlay.resize(config.width, config.height);
+        self.sprites
+            .resize(&self.queue, config.width, config.height);
+        self.inset.resize(&self.queue, config.width, config.height);
+        self.back_occlusion.reset();
+        // 新しいアスペクト比をカメラに反映する
+        self.camera.set_aspect(config.width, config.height);
+        self.scene_viewport.resize(config.width, config.height);
+        // サイドバーを空けている場合は、新しい大きさに合わせて範囲を決め直す
+        self.layout_scene_viewport();
+    }
+
+    // サイドバーの有無に合わせてシーンを描画する範囲を決め、カメラのアスペクト比を合わせる
+    // 拡大率に合わせて大きさが決まるもの（文字・ビューポート）を更新する
+    // 設定パネルの拡大率は egui_winit が ScaleFactorChanged を受け取って更新する
+    pub fn set_scale_factor(&mut self, scale_factor: f64) {
+        self.overlay.set_scale_factor(scale_factor);
+        self.scene_viewport.set_scale_factor(scale_factor);
+        self.layout_scene_viewport();
+    }
+
+    // 拡大率の違うモニターに移ると物理ピクセルの大きさが変わるが、この時点の inner_size は前の大きさのこともある
+    // 論理ピクセルの大きさを保った新しい大きさを自分で求め、Resized を待たずにサーフェイスを設定し直す
+    pub fn scale_factor_changed(
+        &mut self,
+        old: f64,
+        scale_factor: f64,
+        inner_size_writer: &mut InnerSizeWriter,
+    ) {
+        let Some(window) = self.window.clone() else {
+            return;
+        };
+        let size = rescaled_size(window.inner_size(), old, scale_factor);
+        if let Err(e) = inner_size_writer.request_inner_size(size) {
+            warn!("ウィンドウの大きさを変更できませんでした: {}", e);
+        }
+        self.cursor = self
+            .cursor
+            .map(|cursor| rescaled_cursor(cursor, old, scale_factor));
+        self.set_scale_factor(scale_factor);
+        if size.width > 0 && size.height > 0 {
+            self.resize(size);
+        }
+        window.request_redraw();
+    }
+
+    fn layout_scene_viewport(&mut self) {
+        let surface = self.scene_viewport.surface_logical_rect();
+        let rect = if self.show_sidebar {
+            scene_rect(surface)
+        } else {
+            surface
+        };
+        self.scene_viewport.set_logical(rect);
+        let physical = self.scene_viewport.physical();
+        self.camera.set_aspect(physical.width, physical.height);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::light::orbiting_lights;
+    use crate::mesh::FLOOR_VERTICES;
+
+    #[test]
+    fn floor_fits_inside_light_frustum() {
+        for time in [0.0, 1.0, 2.5, 4.0] {
+            let position = orbiting_lights(1, time)[0].position;
+            let matrix = light_view_projection(glam::Vec3::from(position));
+            for vertex in FLOOR_VERTICES {
+                let p = matrix.project_point3(glam::Vec3::from(vertex.position));
+                assert!(p.x.abs() <= 1.0 && p.y.abs() <= 1.0, "outside: {}", p);
+                assert!((0.0..=1.0).contains(&p.z), "depth out of range: {}", p.z);
+            }
+        }
+    }
+}
@@ -0,0 +1,377 @@
+use crate::frustum::{Aabb, CullStats, Frustum};
+use crate::gpu_timer::GpuPass;
+use crate::mesh::{Mesh, NUM_INSTANCES, TRANSLUCENT_INSTANCES, back_to_front};
+use crate::model::{DepthPipelines, DrawModel, Model, ModelPipelines};
+use crate::per_draw::DYNAMIC_UNIFORM_GROUP;
+use crate::shape::{RING_TRIANGLES, Shape};
+use crate::uniforms::OUTLINE_STENCIL_REFERENCE;
+use crate::{crash, gpu_timer};
+
+use super::{MAIN_PASS_LABEL, SHADOW_PASS_LABEL, SceneTarget, State};
+
+impl State {
+    pub fn shows_overlap_demo(&self) -> bool {
+        matches!(self.shape, Shape::Triangle | Shape::Pentagon | Shape::Grid)
+    }
+
+    // モデルのデモで表示するモデル（読み込めなかった場合は None）
+    fn displayed_model(&self) -> Option<&Model> {
+        match self.shape {
+            Shape::Model => self.obj_model.as_ref(),
+            Shape::Gltf => self.gltf_model.as_ref(),
+            Shape::Animated => self.animated_model.as_ref(),
+            _ => None,
+        }
+    }
+
+    fn primitive_mesh(&self) -> &Mesh {
+        &self.primitive_meshes[self.primitive as usize]
+    }
+
+    // 表示中の図形のメッシュごとに、ワールド座標の箱が視錐台と重なるかを調べ直す
+    pub fn cull(&mut self) {
+        let bounds: Vec<Option<Aabb>> = match self.shape {
+            Shape::Cube => vec![Some(self.cube.bounds)],
+            Shape::Primitive => vec![Some(self.primitive_mesh().bounds)],
+            Shape::Terrain => self
+                .terrain
+                .iter()
+                .map(|chunk| Some(chunk.bounds))
+                .collect(),
+            _ => self.displayed_model().map_or_else(Vec::new, |model| {
+                model
+                    .meshes
+                    .iter()
+                    .map(|mesh| mesh.skin.is_none().then_some(mesh.bounds))
+                    .collect()
+            }),
+        };
+        let view_projection = self
+            .frozen_view_projection
+            .unwrap_or_else(|| self.camera.build_view_projection_matrix());
+        let frustum = Frustum::from_view_projection(view_projection);
+        self.visible = bounds
+            .iter()
+            .map(|bounds| bounds.is_none_or(|b| frustum.intersects(&b.transformed(self.model))))
+            .collect();
+        self.cull_stats = CullStats {
+            drawn: self.visible.iter().filter(|visible| **visible).count(),
+            total: self.visible.len(),
+        };
+    }
+
+    // メインの描画でカリングの結果を使うか（小窓は別の方向から見ているので、すべて描画する）
+    fn is_visible(&self, target: &SceneTarget, index: usize) -> bool {
+        !target.primary || self.visible.get(index).copied().unwrap_or(true)
+    }
+
+    // 影を受ける床を描画するか（立方体やモデルのデモでのみ床を敷く）
+    fn shows_floor(&self) -> bool {
+        matches!(
+            self.shape,
+            Shape::Cube | Shape::Primitive | Shape::Model | Shape::Gltf | Shape::Animated
+        )
+    }
+
+    pub fn shadow_pass(&self, encoder: &mut wgpu::CommandEncoder) {
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: crash::pass(SHADOW_PASS_LABEL),
+            color_attachments: &[],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &self.shadow_map.view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            timestamp_writes: self.profiler.timestamp_writes(GpuPass::Shadow),
+            occlusion_query_set: None,
+        });
+        rpass.set_bind_group(0, &self.camera_bindings.uniform_bind_group, &[]);
+        rpass.set_bind_group(1, &self.shadow_bind_group, &[]);
+        rpass.set_pipeline(&self.shadow_pipeline);
+        rpass.set_vertex_buffer(1, self.identity_instance_buffer.slice(..));
+        match self.shape {
+            Shape::Triangle => self.triangle.draw(&mut rpass, 0..1),
+            Shape::Pentagon => self.pentagon.draw(&mut rpass, 0..1),
+            Shape::Grid => {
+                rpass.set_vertex_buffer(1, self.instance_buffer.slice(..));
+                self.triangle.draw(&mut rpass, 0..NUM_INSTANCES);
+                rpass.set_vertex_buffer(1, self.identity_instance_buffer.slice(..));
+            }
+            Shape::Wave => {
+                if let Some(wave) = &self.wave {
+                    rpass.set_vertex_buffer(0, wave.vertex_buffer.slice(..));
+                    rpass.draw(0..wave.vertex_count(), 0..1);
+                }
+            }
+            // パーティクルは半透明なので、後で半透明の図形と一緒に描画する
+            Shape::Particles => {}
+            // スプライトはポストプロセスの後にサーフェイスへ直接描画する
+            Shape::Ring | Shape::Swarm => {}
+            Shape::Sprites => {}
+            // 平面は床と同じ高さにあり、他の図形に影を落とさない
+            Shape::Plane => {}
+            // 節点ごとの行列はカメラのユニフォームではなく動的オフセットの枠にあるので、影は落とさない
+            Shape::SolarSystem | Shape::StaticGrid => {}
+            Shape::Cube => self.cube.draw(&mut rpass, 0..1),
+            Shape::Primitive => self.primitive_mesh().draw(&mut rpass, 0..1),
+            Shape::Terrain => {
+                for chunk in &self.terrain {
+                    chunk.draw(&mut rpass, 0..1);
+                }
+            }
+            Shape::Model | Shape::Gltf | Shape::Animated => {
+                if let Some(model) = self.displayed_model() {
+                    let pipelines = DepthPipelines {
+                        rigid: &self.model_shadow_pipeline,
+                        skinned: &self.skinned_shadow_pipeline,
+                    };
+                    rpass.draw_model_depth(model, &pipelines);
+                }
+            }
+        }
+        if self.shows_overlap_demo() {
+            self.back_triangle.draw(&mut rpass, 0..1);
+        }
+    }
+
+    pub fn main_pass(&self, encoder: &mut wgpu::CommandEncoder, target: &SceneTarget) {
+        let bindings = target.bindings;
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: crash::pass(MAIN_PASS_LABEL),
+            // MSAAが有効な場合は中間テクスチャに描画し、サーフェイスのテクスチャへ解決する
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target.msaa_view.unwrap_or(target.view),
+                resolve_target: target.msaa_view.map(|_| target.view),
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(self.clear_color),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: target.depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: wgpu::StoreOp::Store,
+                }),
+                // 輪郭を付ける物体の範囲をフレームごとに記録し直す
+                stencil_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(0),
+                    store: wgpu::StoreOp::Discard,
+                }),
+            }),
+            // 小窓のパスは開始だけを書き込み、重ねるパスで終了を書き込む
+            timestamp_writes: if target.primary {
+                self.profiler.timestamp_writes(GpuPass::Main)
+            } else {
+                self.profiler
+                    .timestamp_writes(GpuPass::Inset)
+                    .map(gpu_timer::beginning_only)
+            },
+            occlusion_query_set: target.primary.then(|| self.back_occlusion.query_set()),
+        });
+        if target.primary {
+            self.scene_viewport.apply(&mut rpass);
+        }
+        rpass.set_pipeline(self.active_pipeline());
+        rpass.set_bind_group(0, &bindings.uniform_bind_group, &[]);
+        rpass.set_bind_group(1, &self.texture_bind_group, &[]);
+        rpass.set_bind_group(2, &self.light_bind_group, &[]);
+        rpass.set_bind_group(3, &self.no_reflection_bind_group, &[]);
+        rpass.set_vertex_buffer(1, self.identity_instance_buffer.slice(..));
+        if self.shows_floor() {
+            rpass.set_bind_group(0, &bindings.floor_bind_group, &[]);
+            self.floor.draw(&mut rpass, 0..1);
+            rpass.set_bind_group(0, &bindings.uniform_bind_group, &[]);
+        }
+        match self.shape {
+            Shape::Triangle => self
+                .triangle
+                .draw_indirect(&mut rpass, &self.triangle_indirect),
+            Shape::Pentagon => self.pentagon.draw(&mut rpass, 0..1),
+            Shape::Grid => {
+                // 1回の描画呼び出しで100個の三角形を描画する
+                rpass.set_vertex_buffer(1, self.instance_buffer.slice(..));
+                self.triangle.draw(&mut rpass, 0..NUM_INSTANCES);
+                rpass.set_vertex_buffer(1, self.identity_instance_buffer.slice(..));
+            }
+            Shape::Wave => {
+                if let Some(wave) = &self.wave {
+                    rpass.set_vertex_buffer(0, wave.vertex_buffer.slice(..));
+                    rpass.draw(0..wave.vertex_count(), 0..1);
+                }
+            }
+            // パーティクルは半透明なので、後で半透明の図形と一緒に描画する
+            Shape::Particles => {}
+            Shape::Ring => {
+                // 同じ三角形を、描画呼び出しごとに別の変換行列と色で描画する
+                rpass.set_pipeline(&self.per_draw_pipeline);
+                for index in 0..RING_TRIANGLES {
+                    self.per_draw.bind(&mut rpass, index);
+                    self.triangle.draw(&mut rpass, 0..1);
+                }
+            }
+            Shape::Swarm => {
+                // バインドグループは1つのまま、オフセットだけを変えて別の枠のデータを読ませる
+                rpass.set_pipeline(&self.swarm_pipeline);
+                for index in 0..self.swarm.len() {
+                    self.swarm
+                        .bind(&mut rpass, DYNAMIC_UNIFORM_GROUP, self.swarm.offset(index));
+                    self.triangle.draw(&mut rpass, 0..1);
+                }
+            }
+            // スプライトはポストプロセスの後にサーフェイスへ直接描画する
+            Shape::Sprites => {}
+            Shape::SolarSystem => {
+                rpass.set_pipeline(&self.solar_system_pipeline);
+                self.solar_system.draw(&mut rpass, &self.primitive_meshes);
+            }
+            Shape::StaticGrid => {
+                // バンドルはメインのカメラで記録したので、小窓では描画呼び出しをその場で記録する
+                if target.primary {
+                    self.static_scene.execute(&mut rpass);
+                    // 再生の後はパイプラインとバインドグループが未設定に戻るので、設定し直す
+                    rpass.set_pipeline(self.active_pipeline());
+                    rpass.set_bind_group(0, &bindings.uniform_bind_group, &[]);
+                    rpass.set_bind_group(1, &self.texture_bind_group, &[]);
+                    rpass.set_bind_group(2, &self.light_bind_group, &[]);
+                    rpass.set_bind_group(3, &self.no_reflection_bind_group, &[]);
+                    rpass.set_vertex_buffer(1, self.identity_instance_buffer.slice(..));
+                } else {
+                    self.static_scene
+                        .draw(&mut rpass, &bindings.uniform_bind_group, &self.cube);
+                }
+            }
+            Shape::Plane => {
+                // 模様を繰り返すサンプラーのバインドグループに差し替えて描画する
+                rpass.set_bind_group(1, &self.plane_texture_bind_group, &[]);
+                self.plane.draw(&mut rpass, 0..1);
+                rpass.set_bind_group(1, &self.texture_bind_group, &[]);
+            }
+            Shape::Cube => {
+                // 立方体だけは周囲のキューブマップを映り込ませる
+                // 輪郭を付ける場合は、立方体を描画した範囲のステンシルに参照値を書き込む
+                rpass.set_bind_group(3, &self.reflection_bind_group, &[]);
+                if self.show_outline {
+                    rpass.set_stencil_reference(OUTLINE_STENCIL_REFERENCE);
+                }
+                if self.is_visible(target, 0) {
+                    self.cube.draw(&mut rpass, 0..1);
+                }
+                rpass.set_stencil_reference(0);
+                rpass.set_bind_group(3, &self.no_reflection_bind_group, &[]);
+            }
+            Shape::Primitive => {
+                if self.is_visible(target, 0) {
+                    self.primitive_mesh().draw(&mut rpass, 0..1);
+                }
+            }
+            Shape::Terrain => {
+                // 平面と同じく、模様を繰り返すサンプラーのバインドグループで描画する
+                rpass.set_bind_group(1, &self.plane_texture_bind_group, &[]);
+                for (index, chunk) in self.terrain.iter().enumerate() {
+                    if self.is_visible(target, index) {
+                        chunk.draw(&mut rpass, 0..1);
+                    }
+                }
+                rpass.set_bind_group(1, &self.texture_bind_group, &[]);
+            }
+            Shape::Model | Shape::Gltf | Shape::Animated => {
+                if let Some(model) = self.displayed_model() {
+                    let pipelines = ModelPipelines {
+                        blinn_phong: &self.model_pipeline,
+                        pbr: &self.pbr_pipeline,
+                        skinned: &self.skinned_pipeline,
+                    };
+                    if target.primary {
+                        rpass.draw_model_culled(model, &pipelines, &self.visible);
+                    } else {
+                        rpass.draw_model(model, &pipelines);
+                    }
+                    // スキンメッシュで関節の行列に差し替えたバインドグループ3を戻す
+                    rpass.set_bind_group(3, &self.no_reflection_bind_group, &[]);
+                }
+            }
+        }
+
+        // 立方体やモデルのデモでは重なりを確認するための図形は描画しない
+        if self.shows_overlap_demo() {
+            // 奥の三角形は後から描画するが、深度テストにより手前の図形と重なる部分は隠れる
+            // すべて隠れている間は色を書かずに描画し、再び見えるようになったかだけを調べる
+            // 小窓では別の方向から見ているので、クエリの結果を使わずに描画する
+            if target.primary {
+                rpass.begin_occlusion_query(0);
+                if !self.back_occlusion.should_draw() {
+                    rpass.set_pipeline(&self.occlusion_proxy_pipeline);
+                }
+            }
+            self.back_triangle.draw(&mut rpass, 0..1);
+            if target.primary {
+                rpass.end_occlusion_query();
+            }
+        }
+
+        // 各点光源の位置に目印の立方体を描画する（このパイプラインではグループ1が点光源）
+        // インスタンス番号で光源の配列を参照する
+        rpass.set_pipeline(&self.light_pipeline);
+        rpass.set_bind_group(1, &self.light_bind_group, &[]);
+        self.cube.draw(&mut rpass, 0..self.light_count as u32);
+
+        rpass.set_pipeline(&self.debug_line_pipeline);
+        self.debug_lines.draw(&mut rpass);
+
+        // スカイボックスは不透明な図形の後に描画し、何も描かれていない画素だけを塗る
+        // （半透明の図形は深度を書き込まないので、その前に描画しておく必要がある）
+        if let Some(bind_group) = &bindings.skybox_bind_group
+            && !self.transparent
+        {
+            rpass.set_pipeline(&self.skybox_pipeline);
+            rpass.set_bind_group(0, bind_group, &[]);
+            rpass.draw(0..3, 0..1);
+            rpass.set_bind_group(0, &bindings.uniform_bind_group, &[]);
+        }
+
+        // シェーダーが書き込む平面の深度でテストするので、平面より手前にある図形がグリッドを隠す
+        if self.show_grid {
+            rpass.set_pipeline(&self.grid_pipeline);
+            rpass.set_bind_group(0, &bindings.grid_bind_group, &[]);
+            rpass.draw(0..3, 0..1);
+            rpass.set_bind_group(0, &bindings.uniform_bind_group, &[]);
+        }
+
+        // 拡大した立方体を、ステンシルが参照値でない（立方体からはみ出した）画素にだけ描画する
+        // スカイボックスやグリッドに塗りつぶされないよう、背景を描画した後に描画する
+        if self.show_outline && self.shape == Shape::Cube {
+            rpass.set_pipeline(&self.outline_pipeline);
+            rpass.set_bind_group(1, &self.outline_bind_group, &[]);
+            rpass.set_stencil_reference(OUTLINE_STENCIL_REFERENCE);
+            self.cube.draw(&mut rpass, 0..1);
+            rpass.set_stencil_reference(0);
+            rpass.set_bind_group(1, &self.texture_bind_group, &[]);
+        }
+
+        if self.shows_overlap_demo() {
+            // 半透明の図形は不透明な図形をすべて描画した後に、カメラから遠い順に描画する
+            // （深度を書き込まないため、手前の半透明の図形が奥の図形を隠すことはない）
+            rpass.set_pipeline(&self.translucent_pipeline);
+            rpass.set_bind_group(1, &self.texture_bind_group, &[]);
+            rpass.set_vertex_buffer(1, self.translucent_instance_buffer.slice(..));
+            for index in back_to_front(TRANSLUCENT_INSTANCES, self.model, target.eye) {
+                self.triangle.draw(&mut rpass, index..index + 1);
+            }
+        }
+
+        if self.shape == Shape::Particles
+            && let Some(particles) = &self.particles
+        {
+            // コンピュートシェーダーが書き込んだインスタンスバッファで粒子の数だけ描画する
+            // （粒子の並べ替えは行わないので、加算合成のブレンドモードで見るのがよい）
+            rpass.set_pipeline(&self.translucent_pipeline);
+            rpass.set_bind_group(1, &self.texture_bind_group, &[]);
+            rpass.set_vertex_buffer(1, particles.instance_buffer.slice(..));
+            self.particle_quad.draw(&mut rpass, 0..particles.count());
+        }
+    }
+}
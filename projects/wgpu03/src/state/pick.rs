@@ -0,0 +1,313 @@
+use tracing::info;
+use winit::event::{ElementState, MouseButton, WindowEvent};
+
+use crate::mesh::{
+    BACK_VERTICES, FLOOR_INDICES, NUM_INSTANCES, PENTAGON_INDICES, PENTAGON_VERTICES,
+    PLANE_VERTICES, VERTICES, Vertex, cube_geometry, grid_instances,
+};
+use crate::ray::Ray;
+use crate::shape::Shape;
+use crate::{geometry, picking, ray};
+
+use super::State;
+
+// クリックで選べる物体（ピック用のテクスチャにはこの番号を書き込む）
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PickObject {
+    // 三角形・五角形のデモの手前の図形
+    Shape,
+    BackTriangle,
+    Cube,
+    Plane,
+    // グリッドの何番目の三角形か
+    GridTriangle(u32),
+}
+
+// クリックした物体を調べる方法（X キーで切り替える）
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PickMethod {
+    // 物体の番号を描画したテクスチャを読み出す
+    Gpu,
+    // 視線と CPU 側にある図形の頂点データとの交差を調べる
+    Cpu,
+}
+
+// Shape::Primitive で表示する生成した図形
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Primitive {
+    Sphere,
+    Plane,
+    Torus,
+    Cylinder,
+}
+
+impl Primitive {
+    pub const ALL: [Primitive; 4] = [
+        Primitive::Sphere,
+        Primitive::Plane,
+        Primitive::Torus,
+        Primitive::Cylinder,
+    ];
+
+    pub fn next(self) -> Self {
+        match self {
+            Primitive::Sphere => Primitive::Plane,
+            Primitive::Plane => Primitive::Torus,
+            Primitive::Torus => Primitive::Cylinder,
+            Primitive::Cylinder => Primitive::Sphere,
+        }
+    }
+
+    pub fn geometry(self) -> (Vec<Vertex>, Vec<u32>) {
+        match self {
+            Primitive::Sphere => geometry::uv_sphere(0.6, 24, 48),
+            Primitive::Plane => geometry::plane(1.2, 8),
+            Primitive::Torus => geometry::torus(0.5, 0.2, 48, 24),
+            Primitive::Cylinder => geometry::cylinder(0.4, 1.0, 32),
+        }
+    }
+}
+
+impl PickMethod {
+    pub fn next(self) -> Self {
+        match self {
+            PickMethod::Gpu => PickMethod::Cpu,
+            PickMethod::Cpu => PickMethod::Gpu,
+        }
+    }
+}
+
+// offset だけずらした図形の三角形と ray の交点のうち、最も手前のものの t
+fn intersect_mesh(
+    ray: &Ray,
+    vertices: &[Vertex],
+    indices: Option<&[u16]>,
+    offset: glam::Vec3,
+) -> Option<f32> {
+    let position = |index: usize| glam::Vec3::from(vertices[index].position) + offset;
+    let corners: Vec<usize> = match indices {
+        Some(indices) => indices.iter().map(|&i| i as usize).collect(),
+        None => (0..vertices.len()).collect(),
+    };
+    corners
+        .chunks_exact(3)
+        .filter_map(|c| {
+            ray::intersect_triangle(ray, [position(c[0]), position(c[1]), position(c[2])])
+        })
+        .map(|hit| hit.t)
+        .min_by(f32::total_cmp)
+}
+
+// 押してから離すまでにカーソルがこれ以上動いたら、クリックではなくドラッグとみなす（物理ピクセル）
+const CLICK_SLOP: f32 = 4.0;
+
+fn is_click(start: (f32, f32), end: (f32, f32)) -> bool {
+    (end.0 - start.0).hypot(end.1 - start.1) <= CLICK_SLOP
+}
+
+impl PickObject {
+    // グリッドの三角形はインスタンス番号を足した番号になる
+    const GRID_BASE: u32 = 4;
+
+    fn id(self) -> u32 {
+        match self {
+            PickObject::Shape => 0,
+            PickObject::BackTriangle => 1,
+            PickObject::Cube => 2,
+            PickObject::Plane => 3,
+            PickObject::GridTriangle(index) => Self::GRID_BASE + index,
+        }
+    }
+
+    fn from_id(id: u32) -> Option<Self> {
+        match id {
+            0 => Some(PickObject::Shape),
+            1 => Some(PickObject::BackTriangle),
+            2 => Some(PickObject::Cube),
+            3 => Some(PickObject::Plane),
+            id if (Self::GRID_BASE..Self::GRID_BASE + NUM_INSTANCES).contains(&id) => {
+                Some(PickObject::GridTriangle(id - Self::GRID_BASE))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl State {
+    // 左ボタンをほとんど動かさずに離したら、その位置を返す
+    // カメラの操作より先に呼び、オービットカメラの左ドラッグによる回転と区別する
+    pub fn click(&mut self, event: &WindowEvent) -> Option<(f32, f32)> {
+        let WindowEvent::MouseInput {
+            state,
+            button: MouseButton::Left,
+            ..
+        } = event
+        else {
+            return None;
+        };
+        match state {
+            ElementState::Pressed => {
+                self.click_start = self.cursor;
+                None
+            }
+            ElementState::Released => match (self.click_start.take(), self.cursor) {
+                (Some(start), Some(end)) => is_click(start, end).then_some(end),
+                _ => {
+                    info!("カーソルの位置が分からないため、物体を選べません");
+                    None
+                }
+            },
+        }
+    }
+
+    // 今表示している図形に、クリックで選べる物体があるか
+    pub fn has_pickable_objects(&self) -> bool {
+        self.shows_overlap_demo() || matches!(self.shape, Shape::Plane | Shape::Cube)
+    }
+
+    // cursor（物理ピクセル）の位置に描画されている物体を調べる
+    // 三角形・五角形・グリッド・平面・立方体のデモの図形だけを選べる
+    pub fn pick_object(&mut self, cursor: (f32, f32)) -> anyhow::Result<Option<PickObject>> {
+        let pixel = picking::pick_pixel(cursor, self.config.width, self.config.height);
+        let shape = self.shape;
+        let overlap_demo = self.shows_overlap_demo();
+        let id = self
+            .picking
+            .pick(&self.device, &self.queue, pixel, |pass| {
+                // メインのパスと同じ範囲に描画し、サイドバーの部分では何も選ばれないようにする
+                self.scene_viewport.apply(&mut pass.rpass);
+                pass.rpass
+                    .set_bind_group(0, &self.camera_bindings.uniform_bind_group, &[]);
+                pass.rpass
+                    .set_vertex_buffer(1, self.identity_instance_buffer.slice(..));
+                match shape {
+                    Shape::Triangle | Shape::Pentagon => {
+                        pass.set_object(PickObject::Shape.id());
+                        let mesh = if shape == Shape::Triangle {
+                            &self.triangle
+                        } else {
+                            &self.pentagon
+                        };
+                        mesh.draw(&mut pass.rpass, 0..1);
+                    }
+                    Shape::Grid => {
+                        pass.set_object(PickObject::GridTriangle(0).id());
+                        pass.rpass
+                            .set_vertex_buffer(1, self.instance_buffer.slice(..));
+                        self.triangle.draw(&mut pass.rpass, 0..NUM_INSTANCES);
+                        pass.rpass
+                            .set_vertex_buffer(1, self.identity_instance_buffer.slice(..));
+                    }
+                    Shape::Plane => {
+                        pass.set_object(PickObject::Plane.id());
+                        self.plane.draw(&mut pass.rpass, 0..1);
+                    }
+                    Shape::Cube => {
+                        pass.set_object(PickObject::Cube.id());
+                        self.cube.draw(&mut pass.rpass, 0..1);
+                    }
+                    _ => {}
+                }
+                if overlap_demo {
+                    pass.set_object(PickObject::BackTriangle.id());
+                    self.back_triangle.draw(&mut pass.rpass, 0..1);
+                }
+            })?;
+        Ok(id.and_then(PickObject::from_id))
+    }
+
+    // cursor を通る視線と交差する物体のうち最も手前のものと、その交点（ワールド座標）
+    // pick_object と同じ図形を、GPU を使わずに頂点データから調べる
+    pub fn ray_pick(&self, cursor: (f32, f32)) -> Option<(PickObject, glam::Vec3)> {
+        let ray = ray::unproject(cursor, &self.camera, self.scene_viewport.physical());
+        // 図形はモデル座標で調べる（変換した視線でも t は同じ点を指す）
+        let local = ray.transformed(self.model.inverse());
+        let mut closest: Option<(f32, PickObject)> = None;
+        let mut consider = |t: Option<f32>, object: PickObject| {
+            if let Some(t) = t
+                && closest.is_none_or(|(best, _)| t < best)
+            {
+                closest = Some((t, object));
+            }
+        };
+        match self.shape {
+            Shape::Triangle => consider(
+                intersect_mesh(&local, VERTICES, None, glam::Vec3::ZERO),
+                PickObject::Shape,
+            ),
+            Shape::Pentagon => consider(
+                intersect_mesh(
+                    &local,
+                    PENTAGON_VERTICES,
+                    Some(PENTAGON_INDICES),
+                    glam::Vec3::ZERO,
+                ),
+                PickObject::Shape,
+            ),
+            Shape::Grid => {
+                for (index, instance) in grid_instances().iter().enumerate() {
+                    consider(
+                        intersect_mesh(&local, VERTICES, None, instance.offset.into()),
+                        PickObject::GridTriangle(index as u32),
+                    );
+                }
+            }
+            Shape::Plane => consider(
+                intersect_mesh(
+                    &local,
+                    PLANE_VERTICES,
+                    Some(FLOOR_INDICES),
+                    glam::Vec3::ZERO,
+                ),
+                PickObject::Plane,
+            ),
+            Shape::Cube => {
+                // 立方体は三角形ごとではなく、頂点を囲む箱と交差を調べる
+                let (vertices, _) = cube_geometry();
+                let (min, max) = vertices.iter().fold(
+                    (glam::Vec3::splat(f32::MAX), glam::Vec3::splat(f32::MIN)),
+                    |(min, max), v| (min.min(v.position.into()), max.max(v.position.into())),
+                );
+                consider(ray::intersect_aabb(&local, min, max), PickObject::Cube);
+            }
+            _ => {}
+        }
+        if self.shows_overlap_demo() {
+            consider(
+                intersect_mesh(&local, BACK_VERTICES, None, glam::Vec3::ZERO),
+                PickObject::BackTriangle,
+            );
+        }
+        closest.map(|(t, object)| (object, ray.at(t)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pick_ids_round_trip_and_unknown_ids_pick_nothing() {
+        for object in [
+            PickObject::Shape,
+            PickObject::BackTriangle,
+            PickObject::Cube,
+            PickObject::Plane,
+            PickObject::GridTriangle(0),
+            PickObject::GridTriangle(NUM_INSTANCES - 1),
+        ] {
+            assert_eq!(PickObject::from_id(object.id()), Some(object));
+        }
+        assert_eq!(
+            PickObject::from_id(PickObject::GRID_BASE + NUM_INSTANCES),
+            None
+        );
+    }
+
+    #[test]
+    fn only_a_release_near_the_press_is_a_click() {
+        assert!(is_click((10.0, 10.0), (10.0, 10.0)));
+        assert!(is_click((10.0, 10.0), (13.0, 12.0)));
+        assert!(!is_click((10.0, 10.0), (20.0, 10.0)));
+    }
+}
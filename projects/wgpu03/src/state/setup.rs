@@ -0,0 +1,1455 @@
+use std::{
+    borrow::Cow,
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use tracing::{error, info, warn};
+use wgpu::util::DeviceExt;
+use winit::window::Window;
+
+use crate::animation::{JointLayout, JointVertex};
+use crate::atlas::AtlasBuilder;
+use crate::bloom::Bloom;
+use crate::bundle::StaticScene;
+use crate::camera::{Camera, CameraController, OrbitCameraController};
+use crate::capabilities::GpuCapabilities;
+use crate::debug_lines::{DebugLines, LineVertex};
+use crate::frame_limiter::FrameLimiter;
+use crate::frustum::CullStats;
+use crate::gpu::{
+    choose_hdr_surface_format, choose_sample_count, choose_surface_format, is_vsync,
+    supported_present_modes, surface_view_format, transparent_alpha_mode,
+};
+use crate::hot_reload::ShaderCache;
+use crate::indirect::IndirectDraw;
+use crate::init::AppError;
+use crate::inset::Inset;
+use crate::light::{LightBuffer, LightStorage, LightsUniform};
+use crate::mesh::{
+    BACK_VERTICES, FLOOR_INDICES, FLOOR_VERTICES, Instance, Mesh, NUM_PARTICLES, PARTICLE_INDICES,
+    PARTICLE_VERTICES, PENTAGON_INDICES, PENTAGON_VERTICES, PLANE_VERTICES, TRANSLUCENT_INSTANCES,
+    VERTICES, Vertex, cube_geometry, grid_instances,
+};
+use crate::model::{Material, Model, ModelVertex, PbrMaterial};
+use crate::occlusion::OcclusionQuery;
+use crate::overlay::TextOverlay;
+use crate::particles::ParticleSystem;
+use crate::per_draw::{PerDrawBuffer, PerDrawStorage};
+use crate::picking::Picking;
+use crate::pipeline_cache::PipelineCache;
+use crate::post::PostPass;
+use crate::profiler::Profiler;
+use crate::recorder::Recorder;
+use crate::render::{
+    BlendMode, PipelineOptions, StageModules, create_msaa_view, create_render_pipeline,
+};
+use crate::settings::Settings;
+use crate::shading::{PipelineRegistry, Shading};
+use crate::shape::{RING_TRIANGLES, SWARM_SIZE, Shape, SolarSystem};
+use crate::sprite::SpriteBatch;
+use crate::stats::FrameStats;
+use crate::terrain::Heightmap;
+use crate::texture::{Texture, max_anisotropy};
+use crate::uniform_arena::UniformArena;
+use crate::uniforms::{
+    CameraBindings, FALLBACK_ENVIRONMENT_COLOR, OUTLINE_COLOR, OUTLINE_SCALE, OutlineUniform,
+    REFLECTIVITY_PRESETS, ROUGHNESS_PRESETS, ReflectionUniform, TINT_PRESETS, Uniforms,
+    create_light_bind_group, create_plane_bind_group, create_reflection_bind_group,
+};
+use crate::viewport::Viewport;
+use crate::wave::WaveCompute;
+use crate::{adapter, animation, capabilities, caps, crash, headless, init};
+
+use super::pick::{PickMethod, Primitive};
+use super::shaders::{
+    RELOADABLE_SHADERS, create_shading_pipeline, create_shadow_pipeline, load_shader,
+};
+use super::{
+    CLEAR_COLOR, CameraMode, DEFAULT_ANIMATED_PATH, DEFAULT_GLTF_PATH, DEFAULT_HEIGHTMAP_PATH,
+    DEFAULT_MODEL_PATH, DEFAULT_SKYBOX_DIR, INITIAL_LIGHT_COUNT, PlaneFilter,
+    SCENE_PIPELINE_LAYOUT_LABEL, SHADOW_DEPTH_BIAS, SHADOW_MAP_SIZE, State, TERRAIN_CHUNK_SIZE,
+    TERRAIN_SPACING, TEXTURE_COMPRESSION_FEATURES, WAVE_COLUMNS, WAVE_TRIANGLES, inset_camera,
+};
+
+impl State {
+    // target はウィンドウと、イベントループのスレッドでそのウィンドウに作ったサーフェイス
+    // None の場合はウィンドウを使わずに描画する（ヘッドレス）
+    pub async fn new(
+        instance: &wgpu::Instance,
+        target: Option<(Arc<Window>, wgpu::Surface<'static>)>,
+        width: u32,
+        height: u32,
+        settings: &Settings,
+    ) -> Result<Self, AppError> {
+        let (window, surface) = target.unzip();
+        let scale_factor = window.as_ref().map_or(1.0, |window| window.scale_factor());
+
+        // アダプタの取得
+        let adapter = adapter::request(
+            instance,
+            surface.as_ref(),
+            settings.adapter.as_deref(),
+            settings.power,
+        )
+        .await?;
+        let surface_caps = surface
+            .as_ref()
+            .map(|surface| surface.get_capabilities(&adapter));
+        info!(
+            "{}",
+            caps::summary(&adapter.get_info(), surface_caps.as_ref())
+        );
+
+        // デバイスの作成
+        // ワイヤーフレーム表示はアダプタが対応している場合のみ有効にする
+        let wireframe_supported = adapter
+            .features()
+            .contains(wgpu::Features::POLYGON_MODE_LINE);
+        let mut required_features = if wireframe_supported {
+            wgpu::Features::POLYGON_MODE_LINE
+        } else {
+            info!("POLYGON_MODE_LINE に対応していないため、ワイヤーフレーム表示は無効です");
+            wgpu::Features::empty()
+        };
+        // 圧縮テクスチャの形式はアダプタが対応しているものだけを有効にする
+        // （どれにも対応していなければ KTX2 を読み込むときに RGBA8 に展開する）
+        let compression_features = adapter.features() & TEXTURE_COMPRESSION_FEATURES;
+        info!("圧縮テクスチャ: {:?}", compression_features);
+        required_features |= compression_features;
+        // 描画ごとのデータはプッシュ定数で渡し、使えなければ動的オフセットのユニフォームバッファで渡す
+        // （`--no-push-constants` で対応している環境でもユニフォームバッファを使う）
+        let per_draw_storage = if !settings.push_constants {
+            PerDrawStorage::DynamicUniform
+        } else {
+            PerDrawStorage::for_adapter(&adapter)
+        };
+        info!("描画ごとのデータ: {:?}", per_draw_storage);
+        required_features |= per_draw_storage.required_features();
+        // GPU の所要時間は TIMESTAMP_QUERY に対応している場合だけ計測する
+        required_features |= adapter.features() & wgpu::Features::TIMESTAMP_QUERY;
+        required_features |= PipelineCache::required_features(&adapter);
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    required_features,
+                    required_limits: per_draw_storage
+                        .required_limits(capabilities::required_limits(&adapter.limits())),
+                    ..Default::default()
+                },
+                None,
+            )
+            .await?;
+        // GPU のリセットなどでデバイスが失われたら、App が次のフレームの前にすべて作り直す
+        let device_lost = Arc::new(AtomicBool::new(false));
+        let lost = device_lost.clone();
+        device.set_device_lost_callback(move |reason, message| {
+            error!("デバイスが失われました（{:?}）: {}", reason, message);
+            lost.store(true, Ordering::Relaxed);
+        });
+        // エラースコープで受け取らなかった検証エラーは、パニックさせずに表示して描画を続ける
+        device.on_uncaptured_error(Box::new(|error| error!("GPU のエラー: {}", error)));
+        // これ以降は、アダプタではなく実際に作ったデバイスで使えるかを見て作り方を選ぶ
+        let capabilities = GpuCapabilities::new(&device, &adapter);
+        info!("GPU の機能: {:?}", capabilities);
+        if capabilities.downlevel {
+            info!("WebGPU の既定の制限を満たさないため、ダウンレベルの制限でデバイスを作りました");
+        }
+        // `--no-indirect` で対応している環境でも直接描画する
+        let indirect_supported = capabilities.indirect && settings.indirect;
+        info!(
+            "間接描画: {}",
+            if indirect_supported {
+                "有効"
+            } else {
+                "無効"
+            }
+        );
+        // 起動時に作るリソースの検証エラーは、最後に AppError として返す
+        // （シェーダーモジュールとパイプラインは、それぞれ作成したときに種類ごとに受け取る）
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        // これ以降に作るパイプラインはすべてこのキャッシュを通す
+        let pipeline_cache = PipelineCache::load(&device, &adapter.get_info());
+
+        // ヘッドレスの場合は表示しないので空のまま
+        let mut present_modes = Vec::new();
+        let config = match (&surface, surface_caps) {
+            (Some(surface), Some(caps)) => {
+                // get_preferred_formatの代わりにget_capabilitiesを使用
+                // `--hdr` が指定されていて HDR のフォーマットに対応していればそれを使い、
+                // 対応していなければ SDR のフォーマットにフォールバックする
+                let hdr_format = if settings.hdr {
+                    let hdr_format = choose_hdr_surface_format(&caps.formats);
+                    if hdr_format.is_none() {
+                        info!("このサーフェイスは HDR に対応していないため、SDR で表示します");
+                    }
+                    hdr_format
+                } else {
+                    None
+                };
+                // アダプタが表示できないサーフェイスではフォーマットがないので、アダプタのエラーにする
+                let format = match hdr_format {
+                    Some(format) => format,
+                    None => choose_surface_format(&caps.formats).ok_or(AppError::Adapter)?,
+                };
+                // sRGB でないフォーマットしか選べなかった場合は、対応する sRGB のビューで書き込めるようにする
+                // （sRGB のフォーマットか、対応する sRGB 版がなければ同じフォーマットが返る）
+                let srgb_format = format.add_srgb_suffix();
+                let view_formats = if srgb_format == format {
+                    vec![]
+                } else {
+                    vec![srgb_format]
+                };
+                info!(
+                    "サーフェイスのフォーマット: {:?}（ビュー: {:?}）",
+                    format,
+                    view_formats.first().unwrap_or(&format)
+                );
+
+                // 対応していない表示モードを設定すると検証エラーになるので、対応しているものから選ぶ
+                present_modes = supported_present_modes(&caps.present_modes);
+                // `--vsync on|off` が指定されていれば、垂直同期を待つかどうかが合う最初のモードにする
+                let present_mode = present_modes
+                    .iter()
+                    .copied()
+                    .find(|&mode| settings.vsync.is_none_or(|vsync| is_vsync(mode) == vsync))
+                    .unwrap_or_else(|| {
+                        if settings.vsync == Some(false) {
+                            warn!("垂直同期を待たない表示モードがないため、Fifo で表示します");
+                        }
+                        wgpu::PresentMode::Fifo
+                    });
+                info!(
+                    "表示モード: {:?}（対応: {:?}）",
+                    present_mode, present_modes
+                );
+                // `--transparent` ではシーンのアルファでデスクトップを透けさせる
+                let alpha_mode = if settings.transparent {
+                    transparent_alpha_mode(&caps.alpha_modes).unwrap_or_else(|| {
+                        warn!(
+                            "コンポジタが透明なウィンドウに対応していないため、不透明で表示します（対応: {:?}）",
+                            caps.alpha_modes
+                        );
+                        // Opaque がなく Inherit だけのサーフェイスもあるので、どちらかを wgpu に選ばせる
+                        wgpu::CompositeAlphaMode::Auto
+                    })
+                } else {
+                    wgpu::CompositeAlphaMode::default()
+                };
+                info!("アルファの扱い: {:?}", alpha_mode);
+
+                // サーフェイスの設定
+                let config = wgpu::SurfaceConfiguration {
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                    format,
+                    width,
+                    height,
+                    present_mode,
+                    desired_maximum_frame_latency: 2,
+                    alpha_mode,
+                    view_formats,
+                };
+
+                // サーフェイスの設定を適用
+                surface.configure(&device, &config);
+                let span = tracing::Span::current();
+                span.record("format", tracing::field::debug(format));
+                span.record("present_mode", tracing::field::debug(present_mode));
+                info!(
+                    ?format,
+                    ?present_mode,
+                    width,
+                    height,
+                    "サーフェイスを設定しました"
+                );
+                config
+            }
+            // ヘッドレスの場合は PNG に保存するので、sRGB の RGBA8 に描画して読み出す
+            _ => wgpu::SurfaceConfiguration {
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+                format: headless::FORMAT,
+                width,
+                height,
+                present_mode: wgpu::PresentMode::Fifo,
+                desired_maximum_frame_latency: 2,
+                alpha_mode: wgpu::CompositeAlphaMode::default(),
+                view_formats: vec![],
+            },
+        };
+        crash::set_gpu(&adapter.get_info(), config.format);
+
+        // 光源の配列はストレージバッファで渡し、使えない環境では固定長のユニフォーム配列で渡す
+        let light_storage = LightStorage::for_capabilities(&capabilities);
+        info!("光源の配列: {:?}", light_storage);
+        let joint_layout = JointLayout::new(&device, light_storage);
+
+        // シェーダーモジュールの作成（実行時にディスクから読み込み、変更されたら作り直す）
+        let mut shader_cache = ShaderCache::default();
+        let [
+            shader,
+            model_shader,
+            pbr_shader,
+            normals_vertex,
+            normals_fragment,
+        ] = RELOADABLE_SHADERS
+            .map(|entry| load_shader(&device, &mut shader_cache, light_storage, entry));
+        let (shader, shader_hash) = shader?;
+        let (model_shader, model_shader_hash) = model_shader?;
+        let (pbr_shader, pbr_shader_hash) = pbr_shader?;
+        let (normals_vertex, normals_vertex_hash) = normals_vertex?;
+        let (normals_fragment, normals_fragment_hash) = normals_fragment?;
+        let normals_shader = StageModules {
+            vertex: normals_vertex,
+            fragment: normals_fragment,
+        };
+
+        // テクスチャの読み込みとバインドグループの作成
+        let texture = init::resource(
+            "checker texture",
+            Texture::from_bytes(
+                &device,
+                &queue,
+                include_bytes!("../../assets/checker.png"),
+                "Checker Texture",
+            ),
+        )?;
+        let texture_bind_group_layout = Texture::bind_group_layout(&device);
+        let texture_bind_group = texture.create_bind_group(&device, &texture_bind_group_layout);
+        // 遠くまで続く平面では、ミップレベルを含む圧縮テクスチャを切り替え可能なフィルタリングのサンプラーで参照する
+        let plane_texture = init::resource(
+            "plane texture",
+            Texture::from_ktx2(
+                &device,
+                &queue,
+                include_bytes!("../../assets/bricks.ktx2"),
+                "Bricks Texture",
+            ),
+        )?;
+        info!(
+            "平面のテクスチャのフォーマット: {:?}",
+            plane_texture.texture.format()
+        );
+        let max_anisotropy = max_anisotropy(&capabilities);
+        info!("異方性フィルタリングの上限: {}", max_anisotropy);
+        let plane_texture_bind_group = init::resource(
+            "plane sampler",
+            create_plane_bind_group(
+                &device,
+                &texture_bind_group_layout,
+                &plane_texture,
+                PlaneFilter::Anisotropic,
+                max_anisotropy,
+            ),
+        )?;
+
+        // ユニフォームバッファとバインドグループの作成
+        let uniforms = Uniforms {
+            tint: TINT_PRESETS[0],
+            time: 0.0,
+            normal_mapping: 1,
+            _padding: [0.0; 2],
+        };
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[uniforms]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let uniform_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Uniform Bind Group Layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+
+        // 点光源のユニフォームバッファとシャドウマップ、バインドグループの作成
+        let light_info_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Light Info Buffer"),
+            contents: bytemuck::cast_slice(&[LightsUniform::new(
+                glam::Mat4::IDENTITY,
+                INITIAL_LIGHT_COUNT,
+            )]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let light_count = INITIAL_LIGHT_COUNT.min(light_storage.max_lights().unwrap_or(usize::MAX));
+        let lights_buffer = LightBuffer::new(&device, light_storage, light_count);
+        let shadow_map = Texture::create_shadow_map(&device, SHADOW_MAP_SIZE, "Shadow Map");
+        let light_uniform_entry = wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let light_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Light Bind Group Layout"),
+                entries: &[
+                    light_uniform_entry,
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Depth,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                        ty: light_storage.binding_type(),
+                        count: None,
+                    },
+                ],
+            });
+        let light_bind_group = create_light_bind_group(
+            &device,
+            &light_bind_group_layout,
+            &light_info_buffer,
+            &shadow_map,
+            &lights_buffer,
+        );
+
+        // シャドウパス用（描画先のシャドウマップを同時に参照することはできないので光源のユニフォームだけ）
+        let shadow_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Shadow Bind Group Layout"),
+                entries: &[light_uniform_entry],
+            });
+        let shadow_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Shadow Bind Group"),
+            layout: &shadow_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: light_info_buffer.as_entire_binding(),
+            }],
+        });
+        let shadow_shader = init::shader_module(
+            &device,
+            wgpu::ShaderModuleDescriptor {
+                label: Some("Shadow Shader"),
+                source: wgpu::ShaderSource::Wgsl(animation::shader_source(
+                    light_storage,
+                    Cow::Borrowed(include_str!("../shadow.wgsl")),
+                )),
+            },
+        )?;
+        let shadow_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Shadow Pipeline Layout"),
+                bind_group_layouts: &[&uniform_bind_group_layout, &shadow_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        let shadow_pipeline = create_shadow_pipeline(
+            &device,
+            &pipeline_cache,
+            &shadow_pipeline_layout,
+            &shadow_shader,
+            "vs_main",
+            &[Vertex::desc(), Instance::desc()],
+            SHADOW_DEPTH_BIAS,
+        );
+        let model_shadow_pipeline = create_shadow_pipeline(
+            &device,
+            &pipeline_cache,
+            &shadow_pipeline_layout,
+            &shadow_shader,
+            "vs_model",
+            &[ModelVertex::desc()],
+            SHADOW_DEPTH_BIAS,
+        );
+        let skinned_shadow_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Skinned Shadow Pipeline Layout"),
+                bind_group_layouts: &[
+                    &uniform_bind_group_layout,
+                    &shadow_bind_group_layout,
+                    &joint_layout.layout,
+                ],
+                push_constant_ranges: &[],
+            });
+        let skinned_shadow_pipeline = create_shadow_pipeline(
+            &device,
+            &pipeline_cache,
+            &skinned_shadow_pipeline_layout,
+            &shadow_shader,
+            "vs_model_skinned",
+            &[ModelVertex::desc(), JointVertex::desc()],
+            SHADOW_DEPTH_BIAS,
+        );
+
+        // 床のメッシュと、モデル行列を単位行列にしたカメラのバインドグループ
+        let floor = Mesh::new(&device, "Floor", FLOOR_VERTICES, Some(FLOOR_INDICES));
+        let plane = Mesh::new(&device, "Plane", PLANE_VERTICES, Some(FLOOR_INDICES));
+
+        // 周囲の映り込みに使うキューブマップ（スカイボックスと共有する）
+        let (environment, skybox_loaded) =
+            match Texture::cubemap_from_dir(&device, &queue, Path::new(DEFAULT_SKYBOX_DIR)) {
+                Ok(cubemap) => (cubemap, true),
+                Err(e) => {
+                    warn!("スカイボックスを読み込めませんでした: {:#}", e);
+                    let solid = Texture::solid_cubemap(&device, &queue, FALLBACK_ENVIRONMENT_COLOR);
+                    (solid, false)
+                }
+            };
+        let reflection_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Reflection Bind Group Layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::Cube,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                ],
+            });
+        // 映り込ませるのは立方体だけなので、他の図形には反射率 0 のバインドグループを使う
+        let reflection = ReflectionUniform::new(REFLECTIVITY_PRESETS[1], ROUGHNESS_PRESETS[0]);
+        let reflection_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Reflection Buffer"),
+            contents: bytemuck::cast_slice(&[reflection]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let reflection_bind_group = create_reflection_bind_group(
+            &device,
+            &reflection_bind_group_layout,
+            &reflection_buffer,
+            &environment,
+            "Reflection Bind Group",
+        );
+        let no_reflection_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("No Reflection Buffer"),
+            contents: bytemuck::cast_slice(&[ReflectionUniform::new(0.0, 0.0)]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let no_reflection_bind_group = create_reflection_bind_group(
+            &device,
+            &reflection_bind_group_layout,
+            &no_reflection_buffer,
+            &environment,
+            "No Reflection Bind Group",
+        );
+
+        // グループ0: カメラ・ユニフォーム、グループ1: テクスチャ、グループ2: 点光源、グループ3: 映り込み
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(SCENE_PIPELINE_LAYOUT_LABEL),
+            bind_group_layouts: &[
+                &uniform_bind_group_layout,
+                &texture_bind_group_layout,
+                &light_bind_group_layout,
+                &reflection_bind_group_layout,
+            ],
+            push_constant_ranges: &[],
+        });
+
+        // マルチサンプリングのサンプル数をアダプタの対応状況から決める
+        let max_sample_count = choose_sample_count(&adapter, PostPass::FORMAT, settings.msaa);
+        info!("MSAAサンプル数: {}", max_sample_count);
+
+        let shading_pipelines =
+            PipelineRegistry::new(device.features(), Shading::Textured, |shading| {
+                create_shading_pipeline(
+                    &device,
+                    &pipeline_cache,
+                    &pipeline_layout,
+                    &shader,
+                    &normals_shader,
+                    max_sample_count,
+                    shading,
+                )
+            });
+        let translucent_pipeline = create_render_pipeline(
+            &device,
+            &pipeline_cache,
+            &pipeline_layout,
+            &shader,
+            &[Vertex::desc(), Instance::desc()],
+            PostPass::FORMAT,
+            max_sample_count,
+            &PipelineOptions::translucent(BlendMode::Alpha),
+        );
+        let occlusion_proxy_pipeline = create_render_pipeline(
+            &device,
+            &pipeline_cache,
+            &pipeline_layout,
+            &shader,
+            &[Vertex::desc(), Instance::desc()],
+            PostPass::FORMAT,
+            max_sample_count,
+            &PipelineOptions::OCCLUSION_PROXY,
+        );
+
+        // 点光源の位置に小さな立方体を描画するパイプライン
+        // カメラのバインドグループレイアウトは他のパイプラインと共有する
+        let light_shader = init::shader_module(
+            &device,
+            wgpu::ShaderModuleDescriptor {
+                label: Some("Light Shader"),
+                source: wgpu::ShaderSource::Wgsl(
+                    light_storage.shader_source(include_str!("../light.wgsl")),
+                ),
+            },
+        )?;
+        let light_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Light Pipeline Layout"),
+                bind_group_layouts: &[&uniform_bind_group_layout, &light_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        let light_pipeline = create_render_pipeline(
+            &device,
+            &pipeline_cache,
+            &light_pipeline_layout,
+            &light_shader,
+            &[Vertex::desc()],
+            PostPass::FORMAT,
+            max_sample_count,
+            &PipelineOptions::OPAQUE.with_label("Light Pipeline"),
+        );
+
+        // デバッグ用の線分を描画するパイプライン
+        let debug_line_shader = init::shader_module(
+            &device,
+            wgpu::ShaderModuleDescriptor {
+                label: Some("Debug Line Shader"),
+                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!(
+                    "../debug_lines.wgsl"
+                ))),
+            },
+        )?;
+        let debug_line_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Debug Line Pipeline Layout"),
+                bind_group_layouts: &[&uniform_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        let debug_line_pipeline = create_render_pipeline(
+            &device,
+            &pipeline_cache,
+            &debug_line_pipeline_layout,
+            &debug_line_shader,
+            &[LineVertex::desc()],
+            PostPass::FORMAT,
+            max_sample_count,
+            &PipelineOptions::LINES,
+        );
+        let debug_lines = DebugLines::new(&device);
+
+        // 描画ごとに変換行列と色を渡して三角形の輪を描画するパイプライン
+        let per_draw = PerDrawBuffer::new(&device, per_draw_storage, RING_TRIANGLES);
+        let per_draw_shader = init::shader_module(
+            &device,
+            wgpu::ShaderModuleDescriptor {
+                label: Some("Per Draw Shader"),
+                source: wgpu::ShaderSource::Wgsl(
+                    per_draw_storage.shader_source(include_str!("../per_draw.wgsl")),
+                ),
+            },
+        )?;
+        let per_draw_pipeline_layout =
+            per_draw.pipeline_layout(&device, &uniform_bind_group_layout);
+        let per_draw_pipeline = create_render_pipeline(
+            &device,
+            &pipeline_cache,
+            &per_draw_pipeline_layout,
+            &per_draw_shader,
+            &[Vertex::desc()],
+            PostPass::FORMAT,
+            max_sample_count,
+            &PipelineOptions::OPAQUE.with_label("Per Draw Pipeline"),
+        );
+
+        // ユニフォームバッファの動的オフセットで100個の三角形を描画するパイプライン
+        // （プッシュ定数に対応していても、常にユニフォームバッファから読む）
+        let swarm = UniformArena::new(&device, "Swarm Buffer", SWARM_SIZE * SWARM_SIZE);
+        let swarm_shader = init::shader_module(
+            &device,
+            wgpu::ShaderModuleDescriptor {
+                label: Some("Swarm Shader"),
+                source: wgpu::ShaderSource::Wgsl(include_str!("../per_draw.wgsl").into()),
+            },
+        )?;
+        let swarm_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Swarm Pipeline Layout"),
+                bind_group_layouts: &[&uniform_bind_group_layout, swarm.layout()],
+                push_constant_ranges: &[],
+            });
+        let swarm_pipeline = create_render_pipeline(
+            &device,
+            &pipeline_cache,
+            &swarm_pipeline_layout,
+            &swarm_shader,
+            &[Vertex::desc()],
+            PostPass::FORMAT,
+            max_sample_count,
+            &PipelineOptions::OPAQUE.with_label("Swarm Pipeline"),
+        );
+
+        let solar_system = init::resource("solar system scene", SolarSystem::new(&device))?;
+        let solar_system_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Solar System Pipeline Layout"),
+                bind_group_layouts: &[&uniform_bind_group_layout, solar_system.draws.layout()],
+                push_constant_ranges: &[],
+            });
+        let solar_system_pipeline = create_render_pipeline(
+            &device,
+            &pipeline_cache,
+            &solar_system_pipeline_layout,
+            &swarm_shader,
+            &[Vertex::desc()],
+            PostPass::FORMAT,
+            max_sample_count,
+            &PipelineOptions::OPAQUE.with_label("Solar System Pipeline"),
+        );
+
+        // 頂点バッファ・インデックスバッファの作成
+        let triangle = Mesh::new(&device, "Triangle", VERTICES, None);
+        let triangle_indirect = IndirectDraw::new(
+            &device,
+            indirect_supported,
+            "Triangle Indirect Buffer",
+            wgpu::util::DrawIndirectArgs {
+                vertex_count: VERTICES.len() as u32,
+                instance_count: 1,
+                first_vertex: 0,
+                first_instance: 0,
+            },
+        );
+        let pentagon = Mesh::new(
+            &device,
+            "Pentagon",
+            PENTAGON_VERTICES,
+            Some(PENTAGON_INDICES),
+        );
+
+        let back_triangle = Mesh::new(&device, "Back Triangle", BACK_VERTICES, None);
+        let back_occlusion = OcclusionQuery::new(&device, "奥の三角形");
+        let (cube_vertices, cube_indices) = cube_geometry();
+        let cube = Mesh::new(&device, "Cube", &cube_vertices, Some(&cube_indices));
+        let primitive_meshes = Primitive::ALL
+            .iter()
+            .map(|primitive| {
+                let label = format!("{:?}", primitive);
+                Mesh::from_geometry(&device, &label, &primitive.geometry())
+            })
+            .collect();
+
+        // インスタンスバッファの作成
+        let identity_instance_buffer =
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Identity Instance Buffer"),
+                contents: bytemuck::cast_slice(&[Instance::IDENTITY]),
+                usage: wgpu::BufferUsages::VERTEX,
+            });
+        let instance_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Instance Buffer"),
+            contents: bytemuck::cast_slice(&grid_instances()),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let translucent_instance_buffer =
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Translucent Instance Buffer"),
+                contents: bytemuck::cast_slice(TRANSLUCENT_INSTANCES),
+                usage: wgpu::BufferUsages::VERTEX,
+            });
+
+        // 頂点を生成するコンピュートパイプライン
+        // コンピュートパスを使えないデバイスでは作らず、M キーでも波とパーティクルを飛ばす
+        let mut wave = None;
+        let mut particles = None;
+        if capabilities.compute {
+            let wave_shader = init::shader_module(
+                &device,
+                wgpu::ShaderModuleDescriptor {
+                    label: Some("Wave Shader"),
+                    source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("../wave.wgsl"))),
+                },
+            )?;
+            wave = Some(WaveCompute::new(
+                &device,
+                &pipeline_cache,
+                &wave_shader,
+                WAVE_TRIANGLES,
+                WAVE_COLUMNS,
+                std::mem::size_of::<Vertex>(),
+            ));
+
+            // 粒子を動かすコンピュートパイプライン
+            let particle_shader = init::shader_module(
+                &device,
+                wgpu::ShaderModuleDescriptor {
+                    label: Some("Particle Shader"),
+                    source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!(
+                        "../particles.wgsl"
+                    ))),
+                },
+            )?;
+            particles = Some(ParticleSystem::new(
+                &device,
+                &pipeline_cache,
+                &particle_shader,
+                NUM_PARTICLES,
+                std::mem::size_of::<Instance>(),
+            ));
+        }
+        let particle_quad = Mesh::new(
+            &device,
+            "Particle",
+            PARTICLE_VERTICES,
+            Some(PARTICLE_INDICES),
+        );
+
+        // 幾つかの小さな画像を1枚のテクスチャアトラスに詰め込み、そこから切り出したスプライトをまとめて描画する
+        let sprite_shader = init::shader_module(
+            &device,
+            wgpu::ShaderModuleDescriptor {
+                label: Some("Sprite Shader"),
+                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("../sprite.wgsl"))),
+            },
+        )?;
+        let mut atlas_builder = AtlasBuilder::default();
+        for bytes in [
+            &include_bytes!("../../assets/sprites/circle.png")[..],
+            include_bytes!("../../assets/sprites/ring.png"),
+            include_bytes!("../../assets/sprites/diamond.png"),
+            include_bytes!("../../assets/sprites/star.png"),
+        ] {
+            let img = init::resource("sprite image", image::load_from_memory(bytes))?;
+            atlas_builder.add(&img);
+        }
+        let sprite_atlas = init::resource(
+            "sprite atlas",
+            atlas_builder.build(&device, &queue, "Sprite Atlas"),
+        )?;
+        let sprites = SpriteBatch::new(
+            &device,
+            &pipeline_cache,
+            &queue,
+            &sprite_shader,
+            &sprite_atlas.texture,
+            surface_view_format(&config),
+            config.width,
+            config.height,
+        );
+
+        // 深度テクスチャの作成
+        let depth_texture =
+            Texture::create_depth_texture(&device, &config, max_sample_count, "Depth Texture");
+        let msaa_view = create_msaa_view(&device, &config, max_sample_count);
+
+        // オフスクリーンのテクスチャをサーフェイスへ書き込むポストプロセスのパス
+        let post_shader = init::shader_module(
+            &device,
+            wgpu::ShaderModuleDescriptor {
+                label: Some("Post Shader"),
+                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("../post.wgsl"))),
+            },
+        )?;
+        let post = PostPass::new(
+            &device,
+            &pipeline_cache,
+            &config,
+            &post_shader,
+            surface_view_format(&config),
+        );
+        let overlay = TextOverlay::new(
+            &device,
+            &queue,
+            surface_view_format(&config),
+            config.width,
+            config.height,
+            scale_factor,
+        );
+        // TIMESTAMP_QUERY に対応していないデバイスでは、GPU での所要時間の計測を省く
+        let profiler = Profiler::new(&device, &queue, &capabilities);
+        let scene_viewport = Viewport::full(scale_factor, config.width, config.height);
+        let bloom_shader = init::shader_module(
+            &device,
+            wgpu::ShaderModuleDescriptor {
+                label: Some("Bloom Shader"),
+                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("../bloom.wgsl"))),
+            },
+        )?;
+        let bloom = Bloom::new(
+            &device,
+            &pipeline_cache,
+            &bloom_shader,
+            &post.texture,
+            config.width,
+            config.height,
+        );
+
+        // OBJモデル・glTFシーンの読み込みとモデル用パイプラインの作成
+        let material_bind_group_layout = Material::bind_group_layout(&device);
+        let pbr_bind_group_layout = PbrMaterial::bind_group_layout(&device);
+        let model_path = settings
+            .model
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_MODEL_PATH));
+        let obj_model = match Model::load(&device, &queue, &model_path, &material_bind_group_layout)
+        {
+            Ok(model) => Some(model),
+            Err(e) => {
+                warn!("モデルを読み込めませんでした: {:#}", e);
+                None
+            }
+        };
+        let heightmap_path = settings
+            .heightmap
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_HEIGHTMAP_PATH));
+        let terrain = match Heightmap::load(&heightmap_path) {
+            Ok(heightmap) => heightmap
+                .chunks(TERRAIN_CHUNK_SIZE, TERRAIN_SPACING)
+                .iter()
+                .enumerate()
+                .map(|(index, chunk)| {
+                    Mesh::from_geometry(&device, &format!("Terrain Chunk {}", index), chunk)
+                })
+                .collect(),
+            Err(e) => {
+                warn!("地形の高さマップを読み込めませんでした: {:#}", e);
+                Vec::new()
+            }
+        };
+        let gltf_path = settings
+            .gltf
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_GLTF_PATH));
+        let load_gltf = |path: &Path| {
+            Model::load_gltf(&device, &queue, path, &pbr_bind_group_layout, &joint_layout)
+        };
+        let gltf_model = match load_gltf(&gltf_path) {
+            Ok(model) => Some(model),
+            Err(e) => {
+                warn!("glTFシーンを読み込めませんでした: {:#}", e);
+                None
+            }
+        };
+        let animated_path = settings
+            .animated
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_ANIMATED_PATH));
+        let animated_model = match load_gltf(&animated_path) {
+            Ok(model) => Some(model),
+            Err(e) => {
+                warn!("アニメーションの glTF を読み込めませんでした: {:#}", e);
+                None
+            }
+        };
+        let model_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Model Pipeline Layout"),
+                bind_group_layouts: &[
+                    &uniform_bind_group_layout,
+                    &material_bind_group_layout,
+                    &light_bind_group_layout,
+                ],
+                push_constant_ranges: &[],
+            });
+        let model_pipeline = create_render_pipeline(
+            &device,
+            &pipeline_cache,
+            &model_pipeline_layout,
+            &model_shader,
+            &[ModelVertex::desc()],
+            PostPass::FORMAT,
+            max_sample_count,
+            &PipelineOptions::OPAQUE.with_label("Model Pipeline"),
+        );
+        let pbr_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("PBR Pipeline Layout"),
+            bind_group_layouts: &[
+                &uniform_bind_group_layout,
+                &pbr_bind_group_layout,
+                &light_bind_group_layout,
+            ],
+            push_constant_ranges: &[],
+        });
+        let pbr_pipeline = create_render_pipeline(
+            &device,
+            &pipeline_cache,
+            &pbr_pipeline_layout,
+            &pbr_shader,
+            &[ModelVertex::desc()],
+            PostPass::FORMAT,
+            max_sample_count,
+            &PipelineOptions::OPAQUE.with_label("PBR Pipeline"),
+        );
+        let skinned_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Skinned Pipeline Layout"),
+                bind_group_layouts: &[
+                    &uniform_bind_group_layout,
+                    &pbr_bind_group_layout,
+                    &light_bind_group_layout,
+                    &joint_layout.layout,
+                ],
+                push_constant_ranges: &[],
+            });
+        let skinned_pipeline = create_render_pipeline(
+            &device,
+            &pipeline_cache,
+            &skinned_pipeline_layout,
+            &pbr_shader,
+            &[ModelVertex::desc(), JointVertex::desc()],
+            PostPass::FORMAT,
+            max_sample_count,
+            &PipelineOptions::SKINNED,
+        );
+
+        // スカイボックスのキューブマップとパイプラインの作成
+        let skybox_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Skybox Bind Group Layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::Cube,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                ],
+            });
+        let skybox_shader = init::shader_module(
+            &device,
+            wgpu::ShaderModuleDescriptor {
+                label: Some("Skybox Shader"),
+                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("../skybox.wgsl"))),
+            },
+        )?;
+        let skybox_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Skybox Pipeline Layout"),
+                bind_group_layouts: &[&skybox_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        let skybox_pipeline = create_render_pipeline(
+            &device,
+            &pipeline_cache,
+            &skybox_pipeline_layout,
+            &skybox_shader,
+            &[],
+            PostPass::FORMAT,
+            max_sample_count,
+            &PipelineOptions::SKYBOX,
+        );
+
+        // 無限グリッドの床のユニフォームとパイプラインの作成
+        let grid_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Grid Bind Group Layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            });
+        let grid_shader = init::shader_module(
+            &device,
+            wgpu::ShaderModuleDescriptor {
+                label: Some("Grid Shader"),
+                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("../grid.wgsl"))),
+            },
+        )?;
+        let grid_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Grid Pipeline Layout"),
+            bind_group_layouts: &[&grid_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let grid_pipeline = create_render_pipeline(
+            &device,
+            &pipeline_cache,
+            &grid_pipeline_layout,
+            &grid_shader,
+            &[],
+            PostPass::FORMAT,
+            max_sample_count,
+            &PipelineOptions::GRID,
+        );
+
+        // メインのカメラと小窓のカメラそれぞれのユニフォームとバインドグループ
+        let skybox_environment = skybox_loaded.then_some(&environment);
+        let camera_bindings = CameraBindings::new(
+            &device,
+            &uniform_bind_group_layout,
+            &uniform_buffer,
+            &skybox_bind_group_layout,
+            skybox_environment,
+            &grid_bind_group_layout,
+            "Main",
+        );
+        let inset_bindings = CameraBindings::new(
+            &device,
+            &uniform_bind_group_layout,
+            &uniform_buffer,
+            &skybox_bind_group_layout,
+            skybox_environment,
+            &grid_bind_group_layout,
+            "Inset",
+        );
+        let inset_shader = init::shader_module(
+            &device,
+            wgpu::ShaderModuleDescriptor {
+                label: Some("Inset Shader"),
+                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("../inset.wgsl"))),
+            },
+        )?;
+        let inset = Inset::new(
+            &device,
+            &pipeline_cache,
+            &config,
+            &inset_shader,
+            max_sample_count,
+        );
+        // 記録するコマンドはメインのカメラのバインドグループを参照する
+        let static_scene = StaticScene::new(
+            &device,
+            &pipeline_cache,
+            &queue,
+            &uniform_bind_group_layout,
+            &camera_bindings.uniform_bind_group,
+            &cube,
+            max_sample_count,
+        );
+
+        // 立方体の輪郭の色と拡大率、パイプラインの作成
+        let outline_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Outline Buffer"),
+            contents: bytemuck::cast_slice(&[OutlineUniform {
+                color: OUTLINE_COLOR,
+                scale: OUTLINE_SCALE,
+                _padding: [0.0; 3],
+            }]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let outline_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Outline Bind Group Layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            });
+        let outline_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Outline Bind Group"),
+            layout: &outline_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: outline_buffer.as_entire_binding(),
+            }],
+        });
+        let outline_shader = init::shader_module(
+            &device,
+            wgpu::ShaderModuleDescriptor {
+                label: Some("Outline Shader"),
+                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("../outline.wgsl"))),
+            },
+        )?;
+        let outline_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Outline Pipeline Layout"),
+                bind_group_layouts: &[&uniform_bind_group_layout, &outline_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        let outline_pipeline = create_render_pipeline(
+            &device,
+            &pipeline_cache,
+            &outline_pipeline_layout,
+            &outline_shader,
+            &[Vertex::desc(), Instance::desc()],
+            PostPass::FORMAT,
+            max_sample_count,
+            &PipelineOptions::OUTLINE,
+        );
+
+        let picking_shader = init::shader_module(
+            &device,
+            wgpu::ShaderModuleDescriptor {
+                label: Some("Picking Shader"),
+                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("../picking.wgsl"))),
+            },
+        )?;
+        let picking = Picking::new(
+            &device,
+            &pipeline_cache,
+            &config,
+            &picking_shader,
+            &uniform_bind_group_layout,
+            &[Vertex::desc(), Instance::desc()],
+        );
+
+        let transparent = matches!(
+            config.alpha_mode,
+            wgpu::CompositeAlphaMode::PreMultiplied | wgpu::CompositeAlphaMode::PostMultiplied
+        );
+
+        #[cfg(feature = "ui")]
+        let ui = window
+            .as_deref()
+            .map(|window| crate::ui::Ui::new(&device, surface_view_format(&config), window));
+
+        info!("{}", pipeline_cache.summary());
+        if let Some(error) = pipeline_cache.finish_startup() {
+            return Err(AppError::Pipeline(error));
+        }
+        if let Some(error) = device.pop_error_scope().await {
+            return Err(AppError::Resource(error.to_string()));
+        }
+
+        Ok(State {
+            window,
+            config,
+            surface,
+            instance: instance.clone(),
+            adapter: adapter.clone(),
+            device,
+            queue,
+            title: settings.title.clone(),
+            adapter_name: adapter.get_info().name,
+            pipeline_cache,
+            shading_pipelines,
+            // 透明なウィンドウでは、何も描画しない画素からデスクトップが見えるよう透明にする
+            clear_color: match settings.clear_color {
+                _ if transparent => wgpu::Color::TRANSPARENT,
+                Some([r, g, b]) => wgpu::Color { r, g, b, a: 1.0 },
+                None => CLEAR_COLOR,
+            },
+            transparent,
+            translucent_pipeline,
+            occlusion_proxy_pipeline,
+            blend_mode: BlendMode::Alpha,
+            model_pipeline,
+            model_pipeline_layout,
+            model_shader,
+            pbr_pipeline,
+            pbr_pipeline_layout,
+            pbr_shader,
+            normals_shader,
+            obj_model,
+            gltf_model,
+            animated_model,
+            animation_clip: 0,
+            animation_start: 0.0,
+            skinned_pipeline,
+            skinned_pipeline_layout,
+            light_pipeline,
+            light_pipeline_layout,
+            light_shader,
+            debug_lines,
+            debug_line_pipeline,
+            debug_line_pipeline_layout,
+            debug_line_shader,
+            per_draw,
+            per_draw_pipeline,
+            per_draw_pipeline_layout,
+            per_draw_shader,
+            swarm,
+            swarm_pipeline,
+            swarm_pipeline_layout,
+            swarm_shader,
+            solar_system,
+            solar_system_pipeline,
+            solar_system_pipeline_layout,
+            static_scene,
+            show_axes: true,
+            light_count,
+            light_storage,
+            capabilities,
+            light_info_buffer,
+            lights_buffer,
+            light_bind_group_layout,
+            light_bind_group,
+            shadow_map,
+            shadow_pipeline,
+            model_shadow_pipeline,
+            skinned_shadow_pipeline,
+            shadow_pipeline_layout,
+            skinned_shadow_pipeline_layout,
+            shadow_shader,
+            shadow_bind_group,
+            depth_bias: SHADOW_DEPTH_BIAS,
+            floor,
+            skybox_pipeline,
+            skybox_pipeline_layout,
+            skybox_shader,
+            reflection,
+            reflection_buffer,
+            reflection_bind_group,
+            no_reflection_bind_group,
+            grid_pipeline,
+            grid_pipeline_layout,
+            grid_shader,
+            show_grid: true,
+            camera_bindings,
+            inset,
+            inset_camera: inset_camera(),
+            inset_bindings,
+            show_inset: false,
+            scene_viewport,
+            show_sidebar: false,
+            outline_pipeline,
+            outline_pipeline_layout,
+            outline_shader,
+            outline_bind_group,
+            show_outline: false,
+            pipeline_layout,
+            shader,
+            shader_changed: false,
+            device_lost,
+            shader_hashes: [
+                shader_hash,
+                model_shader_hash,
+                pbr_shader_hash,
+                normals_vertex_hash,
+                normals_fragment_hash,
+            ],
+            shader_cache,
+            max_sample_count,
+            sample_count: max_sample_count,
+            msaa_view,
+            post,
+            bloom,
+            overlay,
+            profiler,
+            screenshot_requested: false,
+            present_modes,
+            frame_limiter: FrameLimiter::new(settings.max_fps),
+            recorder: settings.record.map(Recorder::new),
+            #[cfg(feature = "ui")]
+            ui,
+            frame_stats: FrameStats::default(),
+            cpu_stats: FrameStats::default(),
+            stats_refreshed: None,
+            triangle,
+            triangle_indirect,
+            pentagon,
+            back_triangle,
+            back_occlusion,
+            cube,
+            primitive_meshes,
+            primitive: Primitive::Sphere,
+            terrain,
+            terrain_height: 3.0,
+            visible: Vec::new(),
+            cull_stats: CullStats::default(),
+            frozen_view_projection: None,
+            wave,
+            particles,
+            particle_quad,
+            sprites,
+            sprite_atlas,
+            cursor: None,
+            click_start: None,
+            picking,
+            pick_method: PickMethod::Gpu,
+            identity_instance_buffer,
+            instance_buffer,
+            translucent_instance_buffer,
+            model: glam::Mat4::IDENTITY,
+            shape: Shape::Triangle,
+            depth_texture,
+            uniforms,
+            uniform_buffer,
+            texture_bind_group,
+            plane,
+            plane_texture_bind_group,
+            plane_filter: PlaneFilter::Anisotropic,
+            max_anisotropy,
+            plane_texture,
+            texture_bind_group_layout,
+            camera: Camera::new(width, height),
+            camera_mode: CameraMode::Fly,
+            camera_controller: CameraController::new(1.5, 0.004),
+            orbit_controller: OrbitCameraController::new(0.005, 0.1),
+        })
+    }
+}
@@ -0,0 +1,394 @@
+use tracing::{info, warn};
+
+use crate::animation::JointVertex;
+use crate::debug_lines::LineVertex;
+use crate::hot_reload::ShaderCache;
+use crate::init::AppError;
+use crate::light::LightStorage;
+use crate::mesh::{Instance, Vertex};
+use crate::model::ModelVertex;
+use crate::pipeline_cache::PipelineCache;
+use crate::post::PostPass;
+use crate::render::{PipelineOptions, ShaderStages, StageModules, create_render_pipeline};
+use crate::shader_file::ShaderFile;
+use crate::shading::Shading;
+use crate::texture::Texture;
+use crate::{animation, crash, hot_reload, init};
+
+use super::State;
+
+// 実行時にディスクから読み込み、変更されたら作り直すシェーダー
+// （State の shader・model_shader・pbr_shader と、normals_shader の頂点・フラグメント）
+pub(super) const RELOADABLE_SHADERS: [&str; 5] = [
+    "shader.wgsl",
+    "model.wgsl",
+    "pbr.wgsl",
+    "normals.vert.spv",
+    "normals.frag.spv",
+];
+
+// インクルードを展開した WGSL の、光源と関節の配列の宣言をデバイスに合わせて書き換える
+fn prepare_shader(shader: ShaderFile, light_storage: LightStorage) -> ShaderFile {
+    shader.map_wgsl(|shader| {
+        shader.map(|source| {
+            animation::shader_source(light_storage, light_storage.shader_source(source))
+        })
+    })
+}
+
+// シェーダーをディスクから読み込んでコンパイルし、モジュールとソースのハッシュを返す
+// 読み込みやコンパイルに失敗したら、エラーを表示してビルド時のシェーダーを使う
+pub fn load_shader(
+    device: &wgpu::Device,
+    cache: &mut ShaderCache,
+    light_storage: LightStorage,
+    entry: &str,
+) -> Result<(wgpu::ShaderModule, u64), AppError> {
+    let loaded = hot_reload::read_shader(entry).and_then(|shader| {
+        let shader = prepare_shader(shader, light_storage);
+        Ok((cache.get_or_compile(device, &shader)?, shader.hash()))
+    });
+    loaded.or_else(|e| {
+        warn!(
+            "{} を読み込めないため、ビルド時のシェーダーを使います:\n{:#}",
+            entry, e
+        );
+        let shader = prepare_shader(hot_reload::embedded_shader(entry), light_storage);
+        let module = init::shader_module(
+            device,
+            wgpu::ShaderModuleDescriptor {
+                label: Some(entry),
+                source: shader.source().expect("Failed to load an embedded shader"),
+            },
+        )?;
+        Ok((module, shader.hash()))
+    })
+}
+
+// 三角形や立方体の描画に使う、シェーディングの種類ごとのパイプライン
+pub fn create_shading_pipeline(
+    device: &wgpu::Device,
+    pipeline_cache: &PipelineCache,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    normals_shader: &StageModules,
+    sample_count: u32,
+    shading: Shading,
+) -> wgpu::RenderPipeline {
+    create_render_pipeline(
+        device,
+        pipeline_cache,
+        layout,
+        match shading {
+            Shading::Normals => normals_shader.into(),
+            _ => ShaderStages::from(shader),
+        },
+        &[Vertex::desc(), Instance::desc()],
+        PostPass::FORMAT,
+        sample_count,
+        &PipelineOptions::for_shading(shading),
+    )
+}
+
+pub fn create_shadow_pipeline(
+    device: &wgpu::Device,
+    pipeline_cache: &PipelineCache,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    entry_point: &str,
+    buffers: &[wgpu::VertexBufferLayout],
+    bias: wgpu::DepthBiasState,
+) -> wgpu::RenderPipeline {
+    pipeline_cache.create(|cache| {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: crash::created_pipeline("Shadow Pipeline"),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: Some(entry_point),
+                buffers,
+                compilation_options: Default::default(),
+            },
+            fragment: None,
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                // 三角形や五角形のような薄い図形も影を落とすよう、裏面も描画する
+                cull_mode: None,
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: Texture::SHADOW_MAP_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
+                bias,
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache,
+        })
+    })
+}
+
+impl State {
+    pub fn rebuild_shadow_pipelines(&mut self) {
+        self.shadow_pipeline = create_shadow_pipeline(
+            &self.device,
+            &self.pipeline_cache,
+            &self.shadow_pipeline_layout,
+            &self.shadow_shader,
+            "vs_main",
+            &[Vertex::desc(), Instance::desc()],
+            self.depth_bias,
+        );
+        self.model_shadow_pipeline = create_shadow_pipeline(
+            &self.device,
+            &self.pipeline_cache,
+            &self.shadow_pipeline_layout,
+            &self.shadow_shader,
+            "vs_model",
+            &[ModelVertex::desc()],
+            self.depth_bias,
+        );
+        self.skinned_shadow_pipeline = create_shadow_pipeline(
+            &self.device,
+            &self.pipeline_cache,
+            &self.skinned_shadow_pipeline_layout,
+            &self.shadow_shader,
+            "vs_model_skinned",
+            &[ModelVertex::desc(), JointVertex::desc()],
+            self.depth_bias,
+        );
+    }
+
+    // シェーダーのファイルが変更されたら、展開後のソースが変わったものだけコンパイルし直し、
+    // それを使うパイプラインを作り直す（インクルードされるファイルの変更も含む）
+    pub fn reload_shaders(&mut self) {
+        if !std::mem::take(&mut self.shader_changed) {
+            return;
+        }
+        let mut modules = [
+            self.shader.clone(),
+            self.model_shader.clone(),
+            self.pbr_shader.clone(),
+            self.normals_shader.vertex.clone(),
+            self.normals_shader.fragment.clone(),
+        ];
+        let mut hashes = self.shader_hashes;
+        let mut reloaded = Vec::new();
+        for (i, entry) in RELOADABLE_SHADERS.into_iter().enumerate() {
+            let shader = match hot_reload::read_shader(entry) {
+                Ok(shader) => prepare_shader(shader, self.light_storage),
+                Err(e) => {
+                    warn!("シェーダーを読み込めませんでした: {:#}", e);
+                    return;
+                }
+            };
+            if shader.hash() == hashes[i] {
+                continue;
+            }
+            match self.shader_cache.get_or_compile(&self.device, &shader) {
+                Ok(module) => modules[i] = module,
+                Err(e) => {
+                    warn!("{} のコンパイルに失敗しました:\n{:#}", entry, e);
+                    return;
+                }
+            }
+            hashes[i] = shader.hash();
+            reloaded.push(entry);
+        }
+        // 使っていないシェーダーのファイルが変更された場合
+        if reloaded.is_empty() {
+            return;
+        }
+        let previous = self.replace_reloadable_shaders(modules);
+        let device = self.device.clone();
+        if let Err(e) =
+            hot_reload::catch_validation_error(&device, || self.rebuild_reloadable_pipelines())
+        {
+            warn!("パイプラインを作成できませんでした:\n{:#}", e);
+            self.replace_reloadable_shaders(previous);
+            self.rebuild_reloadable_pipelines();
+            return;
+        }
+        self.shader_hashes = hashes;
+        self.shader_cache.retain(&hashes);
+        info!("シェーダーを読み込み直しました: {}", reloaded.join(", "));
+    }
+
+    // RELOADABLE_SHADERS の順にシェーダーを入れ替え、前のものを返す
+    fn replace_reloadable_shaders(
+        &mut self,
+        [
+            shader,
+            model_shader,
+            pbr_shader,
+            normals_vertex,
+            normals_fragment,
+        ]: [wgpu::ShaderModule; 5],
+    ) -> [wgpu::ShaderModule; 5] {
+        [
+            std::mem::replace(&mut self.shader, shader),
+            std::mem::replace(&mut self.model_shader, model_shader),
+            std::mem::replace(&mut self.pbr_shader, pbr_shader),
+            std::mem::replace(&mut self.normals_shader.vertex, normals_vertex),
+            std::mem::replace(&mut self.normals_shader.fragment, normals_fragment),
+        ]
+    }
+
+    // 実行時に読み込み直すシェーダーを使うパイプラインを作り直す
+    fn rebuild_reloadable_pipelines(&mut self) {
+        self.shading_pipelines.rebuild(|shading| {
+            create_shading_pipeline(
+                &self.device,
+                &self.pipeline_cache,
+                &self.pipeline_layout,
+                &self.shader,
+                &self.normals_shader,
+                self.sample_count,
+                shading,
+            )
+        });
+        self.translucent_pipeline = create_render_pipeline(
+            &self.device,
+            &self.pipeline_cache,
+            &self.pipeline_layout,
+            &self.shader,
+            &[Vertex::desc(), Instance::desc()],
+            PostPass::FORMAT,
+            self.sample_count,
+            &PipelineOptions::translucent(self.blend_mode),
+        );
+        self.occlusion_proxy_pipeline = create_render_pipeline(
+            &self.device,
+            &self.pipeline_cache,
+            &self.pipeline_layout,
+            &self.shader,
+            &[Vertex::desc(), Instance::desc()],
+            PostPass::FORMAT,
+            self.sample_count,
+            &PipelineOptions::OCCLUSION_PROXY,
+        );
+        self.model_pipeline = create_render_pipeline(
+            &self.device,
+            &self.pipeline_cache,
+            &self.model_pipeline_layout,
+            &self.model_shader,
+            &[ModelVertex::desc()],
+            PostPass::FORMAT,
+            self.sample_count,
+            &PipelineOptions::OPAQUE.with_label("Model Pipeline"),
+        );
+        self.pbr_pipeline = create_render_pipeline(
+            &self.device,
+            &self.pipeline_cache,
+            &self.pbr_pipeline_layout,
+            &self.pbr_shader,
+            &[ModelVertex::desc()],
+            PostPass::FORMAT,
+            self.sample_count,
+            &PipelineOptions::OPAQUE.with_label("PBR Pipeline"),
+        );
+        self.skinned_pipeline = create_render_pipeline(
+            &self.device,
+            &self.pipeline_cache,
+            &self.skinned_pipeline_layout,
+            &self.pbr_shader,
+            &[ModelVertex::desc(), JointVertex::desc()],
+            PostPass::FORMAT,
+            self.sample_count,
+            &PipelineOptions::SKINNED,
+        );
+    }
+
+    pub fn rebuild_pipelines(&mut self) {
+        self.rebuild_reloadable_pipelines();
+        self.light_pipeline = create_render_pipeline(
+            &self.device,
+            &self.pipeline_cache,
+            &self.light_pipeline_layout,
+            &self.light_shader,
+            &[Vertex::desc()],
+            PostPass::FORMAT,
+            self.sample_count,
+            &PipelineOptions::OPAQUE.with_label("Light Pipeline"),
+        );
+        self.debug_line_pipeline = create_render_pipeline(
+            &self.device,
+            &self.pipeline_cache,
+            &self.debug_line_pipeline_layout,
+            &self.debug_line_shader,
+            &[LineVertex::desc()],
+            PostPass::FORMAT,
+            self.sample_count,
+            &PipelineOptions::LINES,
+        );
+        self.per_draw_pipeline = create_render_pipeline(
+            &self.device,
+            &self.pipeline_cache,
+            &self.per_draw_pipeline_layout,
+            &self.per_draw_shader,
+            &[Vertex::desc()],
+            PostPass::FORMAT,
+            self.sample_count,
+            &PipelineOptions::OPAQUE.with_label("Per Draw Pipeline"),
+        );
+        self.swarm_pipeline = create_render_pipeline(
+            &self.device,
+            &self.pipeline_cache,
+            &self.swarm_pipeline_layout,
+            &self.swarm_shader,
+            &[Vertex::desc()],
+            PostPass::FORMAT,
+            self.sample_count,
+            &PipelineOptions::OPAQUE.with_label("Swarm Pipeline"),
+        );
+        self.static_scene.rebuild(
+            &self.device,
+            &self.pipeline_cache,
+            &self.camera_bindings.uniform_bind_group,
+            &self.cube,
+            self.sample_count,
+        );
+        self.solar_system_pipeline = create_render_pipeline(
+            &self.device,
+            &self.pipeline_cache,
+            &self.solar_system_pipeline_layout,
+            &self.swarm_shader,
+            &[Vertex::desc()],
+            PostPass::FORMAT,
+            self.sample_count,
+            &PipelineOptions::OPAQUE.with_label("Solar System Pipeline"),
+        );
+        self.skybox_pipeline = create_render_pipeline(
+            &self.device,
+            &self.pipeline_cache,
+            &self.skybox_pipeline_layout,
+            &self.skybox_shader,
+            &[],
+            PostPass::FORMAT,
+            self.sample_count,
+            &PipelineOptions::SKYBOX,
+        );
+        self.grid_pipeline = create_render_pipeline(
+            &self.device,
+            &self.pipeline_cache,
+            &self.grid_pipeline_layout,
+            &self.grid_shader,
+            &[],
+            PostPass::FORMAT,
+            self.sample_count,
+            &PipelineOptions::GRID,
+        );
+        self.outline_pipeline = create_render_pipeline(
+            &self.device,
+            &self.pipeline_cache,
+            &self.outline_pipeline_layout,
+            &self.outline_shader,
+            &[Vertex::desc(), Instance::desc()],
+            PostPass::FORMAT,
+            self.sample_count,
+            &PipelineOptions::OUTLINE,
+        );
+    }
+}
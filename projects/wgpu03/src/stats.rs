@@ -0,0 +1,80 @@
+use std::time::Duration;
+
+// 統計を取る直近のフレーム数
+pub const FRAME_HISTORY: usize = 120;
+
+// 直近のフレームの所要時間の集計結果
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FrameTimeSummary {
+    pub average: Duration,
+    pub min: Duration,
+    pub max: Duration,
+    // 99 パーセンタイル（たまに起きるカクつきの目安）
+    pub p99: Duration,
+}
+
+// 直近 FRAME_HISTORY フレームの所要時間をリングバッファに記録する
+#[derive(Clone, Debug)]
+pub struct FrameStats {
+    samples: [Duration; FRAME_HISTORY],
+    // 次に書き込む位置と、記録済みのフレーム数
+    next: usize,
+    len: usize,
+}
+
+impl Default for FrameStats {
+    fn default() -> Self {
+        Self {
+            samples: [Duration::ZERO; FRAME_HISTORY],
+            next: 0,
+            len: 0,
+        }
+    }
+}
+
+impl FrameStats {
+    // いっぱいになったら最も古いフレームを上書きする
+    pub fn push(&mut self, frame_time: Duration) {
+        self.samples[self.next] = frame_time;
+        self.next = (self.next + 1) % FRAME_HISTORY;
+        self.len = (self.len + 1).min(FRAME_HISTORY);
+    }
+
+    // まだ1フレームも記録していない場合は None
+    pub fn summary(&self) -> Option<FrameTimeSummary> {
+        if self.len == 0 {
+            return None;
+        }
+        let mut sorted = self.samples[..self.len].to_vec();
+        sorted.sort_unstable();
+        let total: Duration = sorted.iter().sum();
+        // 最近順位法（上位 1% に入る最初のサンプル）
+        let p99_index = (self.len * 99).div_ceil(100) - 1;
+        Some(FrameTimeSummary {
+            average: total / self.len as u32,
+            min: sorted[0],
+            max: sorted[self.len - 1],
+            p99: sorted[p99_index],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summary_covers_only_the_latest_frames() {
+        let mut stats = FrameStats::default();
+        assert_eq!(stats.summary(), None);
+        // 最初の 100 フレームは上書きされて集計から外れる
+        for ms in 1..=(FRAME_HISTORY as u64 + 100) {
+            stats.push(Duration::from_millis(ms));
+        }
+        let summary = stats.summary().unwrap();
+        assert_eq!(summary.min, Duration::from_millis(101));
+        assert_eq!(summary.max, Duration::from_millis(220));
+        assert_eq!(summary.p99, Duration::from_millis(219));
+        assert_eq!(summary.average, Duration::from_micros(160_500));
+    }
+}
@@ -0,0 +1,149 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use glam::Vec3;
+
+use crate::mesh::Vertex;
+
+// 低い所と高い所の頂点色（高さに応じて補間する）
+const LOW_COLOR: Vec3 = Vec3::new(0.35, 0.55, 0.25);
+const HIGH_COLOR: Vec3 = Vec3::new(0.9, 0.88, 0.85);
+// テクスチャを何セルごとに繰り返すか
+const CELLS_PER_TEXTURE: f32 = 16.0;
+
+// グレースケール画像の明るさを 0〜1 の高さとして持つ
+pub struct Heightmap {
+    width: u32,
+    depth: u32,
+    heights: Vec<f32>,
+}
+
+impl Heightmap {
+    // 16ビットのグレースケールはそのままの精度で、それ以外の画像は明るさに変換して読み込む
+    pub fn load(path: &Path) -> Result<Self> {
+        let image = image::open(path)
+            .with_context(|| format!("{} を読み込めませんでした", path.display()))?
+            .to_luma16();
+        Ok(Self::from_image(&image))
+    }
+
+    pub fn from_image(image: &image::ImageBuffer<image::Luma<u16>, Vec<u16>>) -> Self {
+        Self {
+            width: image.width(),
+            depth: image.height(),
+            heights: image
+                .pixels()
+                .map(|pixel| pixel.0[0] as f32 / u16::MAX as f32)
+                .collect(),
+        }
+    }
+
+    // 範囲外の座標は端の値を使う
+    fn height(&self, x: i64, z: i64) -> f32 {
+        let x = x.clamp(0, self.width as i64 - 1) as usize;
+        let z = z.clamp(0, self.depth as i64 - 1) as usize;
+        self.heights[z * self.width as usize + x]
+    }
+
+    // 両隣との中心差分で求めた傾きから作る法線（spacing は隣の頂点との間隔）
+    // 端では片側の差分になるので、実際に離れている距離で割る
+    fn normal(&self, x: u32, z: u32, spacing: f32) -> Vec3 {
+        let (x, z) = (x as i64, z as i64);
+        let slope = |before: i64, after: i64, limit: u32, sample: &dyn Fn(i64) -> f32| {
+            let (before, after) = (before.max(0), after.min(limit as i64 - 1));
+            let distance = (after - before).max(1) as f32 * spacing;
+            (sample(after) - sample(before)) / distance
+        };
+        let dx = slope(x - 1, x + 1, self.width, &|x| self.height(x, z));
+        let dz = slope(z - 1, z + 1, self.depth, &|z| self.height(x, z));
+        Vec3::new(-dx, 1.0, -dz).normalize()
+    }
+
+    // chunk_size x chunk_size セルごとに分けたメッシュ（隣のチャンクとは端の頂点を重複させる）
+    // 頂点は原点を中心とする XZ 平面に spacing 間隔で並び、y が 0〜1 の高さになる
+    pub fn chunks(&self, chunk_size: u32, spacing: f32) -> Vec<(Vec<Vertex>, Vec<u32>)> {
+        let chunk_size = chunk_size.max(1);
+        let (cells_x, cells_z) = (self.width.saturating_sub(1), self.depth.saturating_sub(1));
+        let origin = Vec3::new(cells_x as f32, 0.0, cells_z as f32) * spacing * -0.5;
+        let mut chunks = Vec::new();
+        for z0 in (0..cells_z).step_by(chunk_size as usize) {
+            for x0 in (0..cells_x).step_by(chunk_size as usize) {
+                let columns = chunk_size.min(cells_x - x0);
+                let rows = chunk_size.min(cells_z - z0);
+                let mut vertices = Vec::with_capacity(((columns + 1) * (rows + 1)) as usize);
+                for z in z0..=z0 + rows {
+                    for x in x0..=x0 + columns {
+                        let height = self.height(x as i64, z as i64);
+                        let position =
+                            origin + Vec3::new(x as f32 * spacing, height, z as f32 * spacing);
+                        vertices.push(Vertex {
+                            position: position.to_array(),
+                            color: LOW_COLOR.lerp(HIGH_COLOR, height).to_array(),
+                            tex_coords: [
+                                x as f32 / CELLS_PER_TEXTURE,
+                                z as f32 / CELLS_PER_TEXTURE,
+                            ],
+                            normal: self.normal(x, z, spacing).to_array(),
+                        });
+                    }
+                }
+                chunks.push((vertices, grid_indices(columns, rows)));
+            }
+        }
+        chunks
+    }
+}
+
+// (columns + 1) x (rows + 1) の頂点の格子を、上から見て反時計回りの三角形で埋める
+fn grid_indices(columns: u32, rows: u32) -> Vec<u32> {
+    let mut indices = Vec::with_capacity((columns * rows * 6) as usize);
+    for row in 0..rows {
+        for column in 0..columns {
+            let a = row * (columns + 1) + column;
+            let b = a + columns + 1;
+            indices.extend_from_slice(&[a, b, a + 1, a + 1, b, b + 1]);
+        }
+    }
+    indices
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // x 方向にだけ一定の割合で高くなる高さマップ
+    fn slope(width: u32, depth: u32) -> Heightmap {
+        let image = image::ImageBuffer::from_fn(width, depth, |x, _| {
+            image::Luma([(x * u16::MAX as u32 / (width - 1)) as u16])
+        });
+        Heightmap::from_image(&image)
+    }
+
+    #[test]
+    fn chunks_cover_the_grid_and_share_edge_vertices() {
+        // 4 x 4 セルを 3 セルごとに分けると、3・1 セルの幅のチャンクが 2 x 2 個できる
+        let chunks = slope(5, 5).chunks(3, 1.0);
+        let sizes: Vec<_> = chunks.iter().map(|(v, i)| (v.len(), i.len())).collect();
+        assert_eq!(sizes, [(16, 54), (8, 18), (8, 18), (4, 6)]);
+        // 隣のチャンクの端の頂点は同じ位置にある
+        assert_eq!(chunks[0].0[3].position, chunks[1].0[0].position);
+        assert_eq!(chunks[0].0[15].position[0], 1.0);
+    }
+
+    #[test]
+    fn normals_follow_the_central_difference_including_edges() {
+        // 4 セルで高さが 1 上がるので、間隔 0.5 の場合の傾きは 0.5
+        let heightmap = slope(5, 3);
+        let expected = Vec3::new(-0.5, 1.0, 0.0).normalize();
+        for x in 0..5 {
+            assert!((heightmap.normal(x, 1, 0.5) - expected).length() < 1e-4);
+        }
+        for (vertices, indices) in heightmap.chunks(2, 0.5) {
+            for triangle in indices.chunks_exact(3) {
+                let [a, b, c] =
+                    [0, 1, 2].map(|i| Vec3::from(vertices[triangle[i] as usize].position));
+                assert!((b - a).cross(c - a).y > 0.0);
+            }
+        }
+    }
+}
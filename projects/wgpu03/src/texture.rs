@@ -0,0 +1,830 @@
+//! 画像・KTX2・キューブマップからのテクスチャ、深度テクスチャ、シャドウマップとサンプラーの作成
+
+use std::borrow::Cow;
+
+use anyhow::{Context, Result, bail};
+use image::GenericImageView;
+
+use crate::capabilities::GpuCapabilities;
+use crate::crash;
+use crate::ktx2;
+
+/// GPU上のテクスチャとそのビュー・サンプラー
+pub struct Texture {
+    #[allow(dead_code)]
+    pub texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+    pub sampler: wgpu::Sampler,
+}
+
+/// 異方性フィルタリングの度合いの上限（wgpu が受け付ける最大値）
+pub const MAX_ANISOTROPY: u16 = 16;
+
+// デバイスが異方性フィルタリングに対応していなければ 1（無効）
+pub(crate) fn max_anisotropy(capabilities: &GpuCapabilities) -> u16 {
+    if capabilities.anisotropic_filtering {
+        MAX_ANISOTROPY
+    } else {
+        1
+    }
+}
+
+/// テクスチャの読み込み時やサンプラーの作り直しに使うサンプラーの設定
+/// 既定値は線形補間と繰り返し
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SamplerOptions {
+    pub mag: wgpu::FilterMode,
+    pub min: wgpu::FilterMode,
+    pub mipmap: wgpu::FilterMode,
+    pub address_mode: wgpu::AddressMode,
+    /// 1 で無効、2 以上で異方性フィルタリングを使う
+    pub anisotropy_clamp: u16,
+}
+
+impl Default for SamplerOptions {
+    fn default() -> Self {
+        Self {
+            mag: wgpu::FilterMode::Linear,
+            min: wgpu::FilterMode::Linear,
+            mipmap: wgpu::FilterMode::Linear,
+            address_mode: wgpu::AddressMode::Repeat,
+            anisotropy_clamp: 1,
+        }
+    }
+}
+
+impl SamplerOptions {
+    /// 補間をせずに最も近い画素とミップレベルを使う
+    pub fn nearest() -> Self {
+        Self {
+            mag: wgpu::FilterMode::Nearest,
+            min: wgpu::FilterMode::Nearest,
+            mipmap: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        }
+    }
+
+    /// 斜めから見た面をぼかさずに縮小する異方性フィルタリング（補間はすべて線形にする）
+    pub fn anisotropic(anisotropy_clamp: u16) -> Self {
+        Self {
+            anisotropy_clamp,
+            ..Default::default()
+        }
+    }
+
+    /// アダプタが対応している度合いまでに抑える（max_anisotropy の戻り値を渡す）
+    pub fn capped(self, max_anisotropy: u16) -> Self {
+        Self {
+            anisotropy_clamp: self.anisotropy_clamp.min(max_anisotropy.max(1)),
+            ..self
+        }
+    }
+
+    /// wgpu の検証で失敗する組み合わせを、サンプラーを作成する前にエラーとして返す
+    pub fn validate(&self) -> Result<()> {
+        if !(1..=MAX_ANISOTROPY).contains(&self.anisotropy_clamp) {
+            bail!(
+                "異方性フィルタリングの度合いは 1 から {} の範囲で指定してください: {}",
+                MAX_ANISOTROPY,
+                self.anisotropy_clamp
+            );
+        }
+        let all_linear = [self.mag, self.min, self.mipmap]
+            .iter()
+            .all(|&filter| filter == wgpu::FilterMode::Linear);
+        if self.anisotropy_clamp > 1 && !all_linear {
+            bail!(
+                "異方性フィルタリングを使う場合は mag・min・mipmap をすべて Linear にしてください"
+            );
+        }
+        Ok(())
+    }
+
+    fn descriptor<'a>(&self, label: Option<&'a str>) -> wgpu::SamplerDescriptor<'a> {
+        wgpu::SamplerDescriptor {
+            label,
+            address_mode_u: self.address_mode,
+            address_mode_v: self.address_mode,
+            address_mode_w: self.address_mode,
+            mag_filter: self.mag,
+            min_filter: self.min,
+            mipmap_filter: self.mipmap,
+            anisotropy_clamp: self.anisotropy_clamp,
+            ..Default::default()
+        }
+    }
+
+    pub fn create_sampler(&self, device: &wgpu::Device, label: &str) -> Result<wgpu::Sampler> {
+        self.validate()?;
+        Ok(device.create_sampler(&self.descriptor(Some(label))))
+    }
+}
+
+/// 1行あたりのバイト数を COPY_BYTES_PER_ROW_ALIGNMENT（256バイト）の倍数に切り上げる
+pub fn padded_bytes_per_row(width: u32, bytes_per_pixel: u32) -> u32 {
+    let unpadded = width * bytes_per_pixel;
+    let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    unpadded.div_ceil(align) * align
+}
+
+/// 各行の末尾に詰め物をして、行ピッチが256バイトの倍数になるように並べ直す
+pub fn pad_rows(data: &[u8], width: u32, height: u32, bytes_per_pixel: u32) -> Vec<u8> {
+    let unpadded = (width * bytes_per_pixel) as usize;
+    let padded = padded_bytes_per_row(width, bytes_per_pixel) as usize;
+    let mut out = vec![0; padded * height as usize];
+    for (src, dst) in data
+        .chunks_exact(unpadded)
+        .zip(out.chunks_exact_mut(padded))
+    {
+        dst[..unpadded].copy_from_slice(src);
+    }
+    out
+}
+
+// 前のミップレベルを縮小して次のレベルに描画することを、最も小さいレベルまで繰り返す
+// キューブマップなどの配列テクスチャではレイヤーごとに繰り返す
+// 読み込み時に一度だけ呼ぶので、パイプラインはその都度作成する
+fn generate_mipmaps(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+    format: wgpu::TextureFormat,
+) {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Mipmap Shader"),
+        source: wgpu::ShaderSource::Wgsl(include_str!("mipmap.wgsl").into()),
+    });
+    let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: crash::created_pipeline("Mipmap Pipeline"),
+        // バインドグループレイアウトはシェーダーから自動で決める
+        layout: None,
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: Some("vs_main"),
+            buffers: &[],
+            compilation_options: Default::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: Some("fs_main"),
+            targets: &[Some(format.into())],
+            compilation_options: Default::default(),
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+        cache: None,
+    });
+    let bind_group_layout = pipeline.get_bind_group_layout(0);
+    let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+        label: Some("Mipmap Sampler"),
+        mag_filter: wgpu::FilterMode::Linear,
+        min_filter: wgpu::FilterMode::Linear,
+        ..Default::default()
+    });
+    let level_view = |level, layer| {
+        texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2),
+            base_mip_level: level,
+            mip_level_count: Some(1),
+            base_array_layer: layer,
+            array_layer_count: Some(1),
+            ..Default::default()
+        })
+    };
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Mipmap Encoder"),
+    });
+    for layer in 0..texture.depth_or_array_layers() {
+        for level in 1..texture.mip_level_count() {
+            let source = level_view(level - 1, layer);
+            let target = level_view(level, layer);
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Mipmap Bind Group"),
+                layout: &bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&source),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&sampler),
+                    },
+                ],
+            });
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: crash::pass("Mipmap Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &target,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            rpass.set_pipeline(&pipeline);
+            rpass.set_bind_group(0, &bind_group, &[]);
+            rpass.draw(0..3, 0..1);
+        }
+    }
+    queue.submit(Some(encoder.finish()));
+}
+
+// KTX2 のフォーマットをデバイスで使えなければ、同じ色空間の RGBA8 に展開して使う
+fn ktx2_upload_format(
+    format: wgpu::TextureFormat,
+    features: wgpu::Features,
+) -> wgpu::TextureFormat {
+    if features.contains(format.required_features()) {
+        format
+    } else if format.is_srgb() {
+        wgpu::TextureFormat::Rgba8UnormSrgb
+    } else {
+        wgpu::TextureFormat::Rgba8Unorm
+    }
+}
+
+impl Texture {
+    /// メインのパスの深度テクスチャ（輪郭の描画に使うステンシルも持つ）
+    pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth24PlusStencil8;
+    /// シャドウマップは比較サンプラーで深度を読むので、深度だけのフォーマットにする
+    pub const SHADOW_MAP_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+    /// メインのパスの深度テクスチャはアタッチメントにだけ使う
+    /// （GLES では TEXTURE_BINDING を付けるとレンダーバッファでなくテクスチャになり、MSAA で作れない）
+    pub const DEPTH_USAGE: wgpu::TextureUsages = wgpu::TextureUsages::RENDER_ATTACHMENT;
+
+    /// サーフェイスと同じサイズ・サンプル数の深度・ステンシルテクスチャを作成する
+    pub fn create_depth_texture(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        sample_count: u32,
+        label: &str,
+    ) -> Self {
+        // 最小化などでサイズが 0 になっても作成に失敗しないよう 1 以上にする
+        let size = wgpu::Extent3d {
+            width: config.width.max(1),
+            height: config.height.max(1),
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format: Self::DEPTH_FORMAT,
+            usage: Self::DEPTH_USAGE,
+            view_formats: &[],
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            compare: Some(wgpu::CompareFunction::LessEqual),
+            lod_min_clamp: 0.0,
+            lod_max_clamp: 100.0,
+            ..Default::default()
+        });
+
+        Self {
+            texture,
+            view,
+            sampler,
+        }
+    }
+
+    /// シーンを描画してから後段のパスでサンプリングするオフスクリーンのカラーテクスチャ
+    /// サイズはサーフェイスに合わせて決め、ウィンドウのリサイズ時に作り直す
+    pub fn create_render_target(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        label: &str,
+    ) -> Self {
+        let size = wgpu::Extent3d {
+            width: width.max(1),
+            height: height.max(1),
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Self {
+            texture,
+            view,
+            sampler,
+        }
+    }
+
+    /// 光源から見た深度を書き込むシャドウマップ（正方形・サンプル数1）
+    /// サンプラーは比較サンプラーで、シェーダーでは textureSampleCompare で影の判定に使う
+    pub fn create_shadow_map(device: &wgpu::Device, size: u32, label: &str) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: Self::SHADOW_MAP_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Shadow Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            compare: Some(wgpu::CompareFunction::LessEqual),
+            ..Default::default()
+        });
+
+        Self {
+            texture,
+            view,
+            sampler,
+        }
+    }
+
+    /// PNGなどの画像データをデコードしてテクスチャを作成する
+    pub fn from_bytes(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        bytes: &[u8],
+        label: &str,
+    ) -> Result<Self> {
+        let img = image::load_from_memory(bytes)?;
+        Ok(Self::from_image(device, queue, &img, Some(label)))
+    }
+
+    /// 法線マップやメタリック・ラフネスなど色以外のデータ用（sRGB の変換をしない）
+    pub fn linear_from_bytes(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        bytes: &[u8],
+        label: &str,
+    ) -> Result<Self> {
+        let img = image::load_from_memory(bytes)?;
+        Ok(Self::linear_from_image(device, queue, &img, Some(label)))
+    }
+
+    /// KTX2 に入っている圧縮済みのミップレベルをそのまま転送してテクスチャを作成する
+    /// デバイスがその圧縮形式に対応していなければ、CPU で RGBA8 に展開してから転送する
+    pub fn from_ktx2(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        bytes: &[u8],
+        label: &str,
+    ) -> Result<Self> {
+        let image = ktx2::parse(bytes).with_context(|| format!("{} を読み込めません", label))?;
+        let format = ktx2_upload_format(image.format, device.features());
+        let size = wgpu::Extent3d {
+            width: image.width,
+            height: image.height,
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: image.levels.len() as u32,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        for (level, &data) in image.levels.iter().enumerate() {
+            let level = level as u32;
+            let data = if format == image.format {
+                Cow::Borrowed(data)
+            } else {
+                let extent = size.mip_level_size(level, wgpu::TextureDimension::D2);
+                let rgba = ktx2::decode_to_rgba8(image.format, data, extent.width, extent.height)?;
+                Cow::Owned(rgba.into_raw())
+            };
+            // 圧縮形式ではブロック単位で転送するので、大きさも行のバイト数もブロックに切り上げる
+            let extent = ktx2::level_extent(format, image.width, image.height, level);
+            let (_, block_height) = format.block_dimensions();
+            queue.write_texture(
+                wgpu::TexelCopyTextureInfo {
+                    texture: &texture,
+                    mip_level: level,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                &data,
+                wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(ktx2::bytes_per_block_row(format, extent.width)),
+                    rows_per_image: Some(extent.height / block_height),
+                },
+                extent,
+            );
+        }
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&SamplerOptions::default().descriptor(Some(label)));
+
+        Ok(Self {
+            texture,
+            view,
+            sampler,
+        })
+    }
+
+    pub fn from_image(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        img: &image::DynamicImage,
+        label: Option<&str>,
+    ) -> Self {
+        Self::from_image_with_format(
+            device,
+            queue,
+            img,
+            label,
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+        )
+    }
+
+    pub fn linear_from_image(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        img: &image::DynamicImage,
+        label: Option<&str>,
+    ) -> Self {
+        Self::from_image_with_format(device, queue, img, label, wgpu::TextureFormat::Rgba8Unorm)
+    }
+
+    fn from_image_with_format(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        img: &image::DynamicImage,
+        label: Option<&str>,
+        format: wgpu::TextureFormat,
+    ) -> Self {
+        let rgba = img.to_rgba8();
+        let (width, height) = img.dimensions();
+
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        // 幅と高さの大きい方が 1 になるまで半分にしていったレベルの数（奇数の大きさは切り捨てる）
+        let mip_level_count = size.max_mips(wgpu::TextureDimension::D2);
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label,
+            size,
+            mip_level_count,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            // ミップマップの生成で下位のレベルに描画する
+            usage: wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_DST
+                | wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+
+        // 幅が 2 の累乗でない場合も行ピッチが256バイトの倍数になるよう詰め物をしてから転送する
+        // （write_texture 自体は詰め物なしでも受け付けるが、バッファ経由のコピーと同じ配置に揃えておく）
+        let bytes_per_row = padded_bytes_per_row(width, 4);
+        let data = pad_rows(&rgba, width, height, 4);
+        queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            &data,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(bytes_per_row),
+                rows_per_image: Some(height),
+            },
+            size,
+        );
+        generate_mipmaps(device, queue, &texture, format);
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        // 隣り合うミップレベルの間も補間して、遠くの模様がちらつかないようにする
+        let sampler = device.create_sampler(&SamplerOptions::default().descriptor(label));
+
+        Self {
+            texture,
+            view,
+            sampler,
+        }
+    }
+
+    /// 1x1の白いテクスチャ（テクスチャを持たないマテリアルの代用）
+    pub fn white(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let img = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+            1,
+            1,
+            image::Rgba([255, 255, 255, 255]),
+        ));
+        Self::from_image(device, queue, &img, Some("White Texture"))
+    }
+
+    /// 1x1の平らな法線マップ（接線空間の +Z。法線マップを持たないマテリアルの代用）
+    pub fn flat_normal(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let img = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+            1,
+            1,
+            image::Rgba([128, 128, 255, 255]),
+        ));
+        Self::linear_from_image(device, queue, &img, Some("Flat Normal Texture"))
+    }
+
+    /// 1色で塗りつぶした1x1のキューブマップ（スカイボックスを読み込めなかったときの映り込みの代用）
+    pub fn solid_cubemap(device: &wgpu::Device, queue: &wgpu::Queue, color: [u8; 4]) -> Self {
+        let face =
+            image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(1, 1, image::Rgba(color)));
+        let faces = std::array::from_fn(|_| face.clone());
+        Self::cubemap_from_images(device, queue, &faces, Some("Solid Cubemap"))
+            .expect("1x1 cubemap faces are always valid")
+    }
+
+    /// 6枚の画像からキューブマップを作成する
+    /// 画像の順番は +X, -X, +Y, -Y, +Z, -Z（配列レイヤーの順番と同じ）で、すべて同じ正方形のサイズであること
+    pub fn cubemap_from_images(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        faces: &[image::DynamicImage; 6],
+        label: Option<&str>,
+    ) -> Result<Self> {
+        let (width, height) = faces[0].dimensions();
+        anyhow::ensure!(width == height, "cubemap faces must be square");
+        anyhow::ensure!(
+            faces.iter().all(|f| f.dimensions() == (width, height)),
+            "cubemap faces must all have the same size"
+        );
+
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 6,
+        };
+        let format = wgpu::TextureFormat::Rgba8UnormSrgb;
+        // 粗い面の映り込みでは、ぼかした周囲の代わりに小さいミップレベルを参照する
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label,
+            size,
+            mip_level_count: size.max_mips(wgpu::TextureDimension::D2),
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_DST
+                | wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+
+        // 面ごとに配列レイヤーへ転送する
+        let bytes_per_row = padded_bytes_per_row(width, 4);
+        for (layer, face) in faces.iter().enumerate() {
+            let data = pad_rows(&face.to_rgba8(), width, height, 4);
+            queue.write_texture(
+                wgpu::TexelCopyTextureInfo {
+                    texture: &texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d {
+                        x: 0,
+                        y: 0,
+                        z: layer as u32,
+                    },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                &data,
+                wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(bytes_per_row),
+                    rows_per_image: Some(height),
+                },
+                wgpu::Extent3d {
+                    depth_or_array_layers: 1,
+                    ..size
+                },
+            );
+        }
+        generate_mipmaps(device, queue, &texture, format);
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::Cube),
+            ..Default::default()
+        });
+        // 映り込みの粗さを連続的に変えられるよう、ミップレベルの間も補間する
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Ok(Self {
+            texture,
+            view,
+            sampler,
+        })
+    }
+
+    /// ディレクトリ内の px, nx, py, ny, pz, nz.png からキューブマップを作成する
+    pub fn cubemap_from_dir(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        dir: &std::path::Path,
+    ) -> Result<Self> {
+        let load = |name: &str| {
+            let path = dir.join(format!("{}.png", name));
+            image::open(&path).with_context(|| format!("Failed to load {}", path.display()))
+        };
+        let faces = [
+            load("px")?,
+            load("nx")?,
+            load("py")?,
+            load("ny")?,
+            load("pz")?,
+            load("nz")?,
+        ];
+        Self::cubemap_from_images(device, queue, &faces, Some("Skybox Cubemap"))
+    }
+
+    /// テクスチャとサンプラーのバインドグループレイアウト
+    pub fn bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Texture Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        })
+    }
+
+    pub fn create_bind_group(
+        &self,
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+    ) -> wgpu::BindGroup {
+        self.create_bind_group_with_sampler(device, layout, &self.sampler)
+    }
+
+    /// 同じテクスチャを別のサンプラーで参照するバインドグループ
+    pub fn create_bind_group_with_sampler(
+        &self,
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        sampler: &wgpu::Sampler,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Texture Bind Group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&self.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+            ],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rows_are_padded_to_copy_alignment() {
+        assert_eq!(padded_bytes_per_row(64, 4), 256);
+        assert_eq!(padded_bytes_per_row(200, 4), 1024);
+        assert_eq!(padded_bytes_per_row(1, 4), 256);
+        assert_eq!(padded_bytes_per_row(65, 4), 512);
+    }
+
+    #[test]
+    fn pad_rows_keeps_pixels_in_place() {
+        let (width, height) = (3, 2);
+        let data: Vec<u8> = (0..width * height * 4).map(|i| i as u8).collect();
+        let padded = pad_rows(&data, width, height, 4);
+
+        assert_eq!(padded.len(), 256 * 2);
+        assert_eq!(&padded[..12], &data[..12]);
+        assert!(padded[12..256].iter().all(|&b| b == 0));
+        assert_eq!(&padded[256..268], &data[12..24]);
+    }
+
+    #[test]
+    fn ktx2_falls_back_to_rgba8_without_compression_features() {
+        let bc1 = wgpu::TextureFormat::Bc1RgbaUnormSrgb;
+        assert_eq!(
+            ktx2_upload_format(bc1, wgpu::Features::TEXTURE_COMPRESSION_BC),
+            bc1
+        );
+        assert_eq!(
+            ktx2_upload_format(bc1, wgpu::Features::TEXTURE_COMPRESSION_ETC2),
+            wgpu::TextureFormat::Rgba8UnormSrgb
+        );
+        assert_eq!(
+            ktx2_upload_format(wgpu::TextureFormat::Bc3RgbaUnorm, wgpu::Features::empty()),
+            wgpu::TextureFormat::Rgba8Unorm
+        );
+    }
+
+    #[test]
+    fn anisotropy_requires_linear_filters() {
+        assert!(SamplerOptions::default().validate().is_ok());
+        assert!(SamplerOptions::anisotropic(16).validate().is_ok());
+        assert!(SamplerOptions::anisotropic(0).validate().is_err());
+        let nearest_anisotropic = SamplerOptions {
+            anisotropy_clamp: 16,
+            ..SamplerOptions::nearest()
+        };
+        assert!(nearest_anisotropic.validate().is_err());
+        assert_eq!(
+            SamplerOptions::anisotropic(16).capped(1).anisotropy_clamp,
+            1
+        );
+    }
+
+    #[test]
+    fn mip_chain_handles_odd_non_square_sizes() {
+        let size = wgpu::Extent3d {
+            width: 300,
+            height: 7,
+            depth_or_array_layers: 1,
+        };
+        let dimension = wgpu::TextureDimension::D2;
+        assert_eq!(size.max_mips(dimension), 9);
+        let level = |level| {
+            let mip = size.mip_level_size(level, dimension);
+            (mip.width, mip.height)
+        };
+        assert_eq!(level(1), (150, 3));
+        assert_eq!(level(3), (37, 1));
+        assert_eq!(level(8), (1, 1));
+    }
+}
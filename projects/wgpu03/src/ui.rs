@@ -0,0 +1,135 @@
+use winit::{event::WindowEvent, window::Window};
+
+use crate::crash;
+
+// 設定パネルで変更できる値（State の値をコピーして渡し、変更があれば State 側で反映する）
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PanelSettings {
+    pub clear_color: [f32; 3],
+    pub wireframe: bool,
+    // アダプタがワイヤーフレーム表示に対応していない場合はチェックボックスを無効にする
+    pub wireframe_supported: bool,
+    pub vsync: bool,
+    // FPS の上限（0 は上限なし。垂直同期が有効な間は使われない）
+    pub max_fps: u32,
+}
+
+// シーンの上に egui の設定パネルを重ねて表示する
+pub struct Ui {
+    state: egui_winit::State,
+    renderer: egui_wgpu::Renderer,
+    // run で作成し、draw で描画するまで保持しておく
+    output: Option<egui::FullOutput>,
+}
+
+impl Ui {
+    // format は描画先のサーフェイスのビューのフォーマット
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat, window: &Window) -> Self {
+        let ctx = egui::Context::default();
+        let state = egui_winit::State::new(
+            ctx,
+            egui::ViewportId::ROOT,
+            window,
+            Some(window.scale_factor() as f32),
+            None,
+            Some(device.limits().max_texture_dimension_2d as usize),
+        );
+        let renderer = egui_wgpu::Renderer::new(device, format, None, 1, false);
+        Self {
+            state,
+            renderer,
+            output: None,
+        }
+    }
+
+    // egui が使ったイベント（パネル上のクリックや入力欄へのキー入力など）の場合は true を返す
+    // 拡大率の変更もここで egui の pixels_per_point に反映される
+    pub fn on_window_event(&mut self, window: &Window, event: &WindowEvent) -> bool {
+        self.state.on_window_event(window, event).consumed
+    }
+
+    // パネルを組み立てる（描画は draw で行う）
+    pub fn run(&mut self, window: &Window, settings: &mut PanelSettings) {
+        let input = self.state.take_egui_input(window);
+        // egui の組み込みフォントには日本語の字形がないので、パネルの表記は英語にする
+        let output = self.state.egui_ctx().run(input, |ctx| {
+            egui::Window::new("Settings").show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Clear color");
+                    ui.color_edit_button_rgb(&mut settings.clear_color);
+                });
+                ui.add_enabled(
+                    settings.wireframe_supported,
+                    egui::Checkbox::new(&mut settings.wireframe, "Wireframe"),
+                );
+                ui.checkbox(&mut settings.vsync, "VSync");
+                ui.add_enabled(
+                    !settings.vsync,
+                    egui::Slider::new(&mut settings.max_fps, 0..=240)
+                        .text("Max FPS (0 = unlimited)"),
+                );
+            });
+        });
+        self.state
+            .handle_platform_output(window, output.platform_output.clone());
+        self.output = Some(output);
+    }
+
+    // run で組み立てたパネルがあり、draw で描画する
+    pub fn is_ready(&self) -> bool {
+        self.output.is_some()
+    }
+
+    // 描画済みの内容を残したまま（LoadOp::Load）パネルを重ねる
+    pub fn draw(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        size_in_pixels: [u32; 2],
+        timestamp_writes: Option<wgpu::RenderPassTimestampWrites<'_>>,
+    ) {
+        let Some(output) = self.output.take() else {
+            return;
+        };
+        let screen = egui_wgpu::ScreenDescriptor {
+            size_in_pixels,
+            pixels_per_point: output.pixels_per_point,
+        };
+        let paint_jobs = self
+            .state
+            .egui_ctx()
+            .tessellate(output.shapes, output.pixels_per_point);
+        for (id, delta) in &output.textures_delta.set {
+            self.renderer.update_texture(device, queue, *id, delta);
+        }
+        // ペイントコールバックを使わないので、追加のコマンドバッファは返ってこない
+        self.renderer
+            .update_buffers(device, queue, encoder, &paint_jobs, &screen);
+
+        {
+            let mut rpass = encoder
+                .begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: crash::pass("Egui Pass"),
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Load,
+                            store: wgpu::StoreOp::Store,
+                        },
+                    })],
+                    depth_stencil_attachment: None,
+                    timestamp_writes,
+                    occlusion_query_set: None,
+                })
+                .forget_lifetime();
+            self.renderer.render(&mut rpass, &paint_jobs, &screen);
+        }
+
+        for id in &output.textures_delta.free {
+            self.renderer.free_texture(id);
+        }
+    }
+}
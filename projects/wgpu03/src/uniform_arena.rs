@@ -0,0 +1,137 @@
+use std::marker::PhantomData;
+
+// size を alignment（2の累乗）の倍数に切り上げる
+pub fn align_to(size: u32, alignment: u32) -> u32 {
+    debug_assert!(alignment.is_power_of_two());
+    (size + alignment - 1) & !(alignment - 1)
+}
+
+// 1つの大きなユニフォームバッファを T ごとの枠に区切り、フレームごとに先頭から枠を割り当てる
+// バインドグループは1つだけで、どの枠を読むかは set_bind_group の動的オフセットで指定する
+pub struct UniformArena<T> {
+    layout: wgpu::BindGroupLayout,
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    // 枠の間隔（min_uniform_buffer_offset_alignment の倍数）
+    stride: u32,
+    capacity: usize,
+    // このフレームで割り当てた枠の内容（upload でまとめてバッファに書き込む）
+    staging: Vec<u8>,
+    _marker: PhantomData<T>,
+}
+
+impl<T: bytemuck::Pod> UniformArena<T> {
+    const SLOT_SIZE: u32 = std::mem::size_of::<T>() as u32;
+
+    // capacity は1フレームで割り当てる枠の数の上限
+    pub fn new(device: &wgpu::Device, label: &str, capacity: usize) -> Self {
+        let stride = align_to(
+            Self::SLOT_SIZE,
+            device.limits().min_uniform_buffer_offset_alignment,
+        );
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some(&format!("{} Bind Group Layout", label)),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: true,
+                    min_binding_size: wgpu::BufferSize::new(Self::SLOT_SIZE as u64),
+                },
+                count: None,
+            }],
+        });
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: stride as wgpu::BufferAddress * capacity as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        // バインドするのは1枠分の大きさだけ
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some(&format!("{} Bind Group", label)),
+            layout: &layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: &buffer,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(Self::SLOT_SIZE as u64),
+                }),
+            }],
+        });
+        Self {
+            layout,
+            buffer,
+            bind_group,
+            stride,
+            capacity,
+            staging: Vec::with_capacity(stride as usize * capacity),
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn layout(&self) -> &wgpu::BindGroupLayout {
+        &self.layout
+    }
+
+    // bind を使えないレンダーバンドルの記録では、このバインドグループに動的オフセットを指定する
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+
+    // 割り当て済みの枠の数
+    pub fn len(&self) -> usize {
+        self.staging.len() / self.stride as usize
+    }
+
+    // index 番目の枠の動的オフセット
+    pub fn offset(&self, index: usize) -> u32 {
+        self.stride * index as u32
+    }
+
+    // フレームの始めに呼び、前のフレームで割り当てた枠をすべて返す
+    pub fn clear(&mut self) {
+        self.staging.clear();
+    }
+
+    // 次の枠に value を書き込み、その枠の動的オフセットを返す
+    pub fn push(&mut self, value: &T) -> u32 {
+        let index = self.len();
+        assert!(index < self.capacity, "uniform arena is full");
+        self.staging.extend_from_slice(bytemuck::bytes_of(value));
+        // 次の枠がアラインメントの位置から始まるよう、残りを 0 で埋める
+        self.staging.resize(self.stride as usize * (index + 1), 0);
+        self.offset(index)
+    }
+
+    // 割り当てた枠の内容をまとめてバッファに書き込む
+    pub fn upload(&self, queue: &wgpu::Queue) {
+        if !self.staging.is_empty() {
+            queue.write_buffer(&self.buffer, 0, &self.staging);
+        }
+    }
+
+    // offset の枠を group 番のバインドグループとして次の描画呼び出しで使う
+    pub fn bind(&self, rpass: &mut wgpu::RenderPass, group: u32, offset: u32) {
+        rpass.set_bind_group(group, &self.bind_group, &[offset]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rounds_slot_size_up_to_the_offset_alignment() {
+        assert_eq!(align_to(0, 256), 0);
+        assert_eq!(align_to(1, 256), 256);
+        assert_eq!(align_to(80, 256), 256);
+        assert_eq!(align_to(256, 256), 256);
+        assert_eq!(align_to(257, 256), 512);
+        // アラインメントが小さいデバイスでは枠を詰めて並べられる
+        assert_eq!(align_to(80, 64), 128);
+        assert_eq!(align_to(80, 16), 80);
+    }
+}
@@ -0,0 +1,361 @@
+use crate::camera::Camera;
+use crate::light::LightBuffer;
+use crate::mesh::FLOOR_HEIGHT;
+use crate::state::PlaneFilter;
+use crate::texture::Texture;
+
+// シェーダーに渡すユニフォームデータ
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Uniforms {
+    pub tint: [f32; 4],
+    // 起動からの経過時間（秒）
+    pub time: f32,
+    // 0 以外ならモデルの描画で法線マップを使う
+    pub normal_mapping: u32,
+    // WGSLのユニフォーム構造体は16バイト境界に揃える必要がある
+    pub _padding: [f32; 2],
+}
+
+// 数字キー（1〜5）で選択できる色味のプリセット
+pub const TINT_PRESETS: [[f32; 4]; 5] = [
+    [1.0, 1.0, 1.0, 1.0],
+    [1.0, 0.3, 0.3, 1.0],
+    [0.3, 1.0, 0.3, 1.0],
+    [0.3, 0.3, 1.0, 1.0],
+    [0.5, 0.5, 0.5, 1.0],
+];
+
+// ビュー・プロジェクション行列・モデル行列・カメラ位置のユニフォームデータ
+// （モデル行列は接線を、法線の変換行列は法線をワールド座標に、カメラ位置は鏡面反射の計算に使う）
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct CameraUniform {
+    view_proj: [[f32; 4]; 4],
+    model: [[f32; 4]; 4],
+    // WGSLの vec3 は16バイト境界に揃えるので w を含めて渡す
+    view_position: [f32; 4],
+    // 法線をワールド座標に変換する行列（モデル行列の逆転置。拡大率が軸ごとに違っても法線が面に垂直なままになる）
+    normal_matrix: [[f32; 4]; 4],
+}
+
+// 立方体に映り込む周囲の割合と粗さ（R キーと U キーで順に切り替える）
+pub const REFLECTIVITY_PRESETS: [f32; 3] = [0.0, 0.5, 1.0];
+pub const ROUGHNESS_PRESETS: [f32; 4] = [0.0, 0.3, 0.6, 1.0];
+// スカイボックスを読み込めなかった場合に映り込ませる色
+pub const FALLBACK_ENVIRONMENT_COLOR: [u8; 4] = [40, 44, 52, 255];
+
+// 環境マップの映り込みのユニフォームデータ
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ReflectionUniform {
+    pub reflectivity: f32,
+    pub roughness: f32,
+    // WGSLのユニフォーム構造体は16バイト境界に揃える必要がある
+    _padding: [f32; 2],
+}
+
+impl ReflectionUniform {
+    pub fn new(reflectivity: f32, roughness: f32) -> Self {
+        Self {
+            reflectivity,
+            roughness,
+            _padding: [0.0; 2],
+        }
+    }
+}
+
+// プリセットの中で今の値の次に大きい値（最後まで来たら最初の値に戻る）
+pub fn next_preset(presets: &[f32], current: f32) -> f32 {
+    presets
+        .iter()
+        .copied()
+        .find(|&value| value > current)
+        .unwrap_or(presets[0])
+}
+
+// 映り込みの設定と環境マップのバインドグループ（グループ3）
+pub fn create_reflection_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    buffer: &wgpu::Buffer,
+    environment: &Texture,
+    label: &str,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some(label),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::TextureView(&environment.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::Sampler(&environment.sampler),
+            },
+        ],
+    })
+}
+
+// スカイボックスの視線方向を復元するためのユニフォームデータ
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct SkyUniform {
+    inv_view_proj: [[f32; 4]; 4],
+}
+
+// 無限グリッドの床で、平面上の点を求めて深度を書き込むためのユニフォームデータ
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct GridUniform {
+    view_proj: [[f32; 4]; 4],
+    inv_view_proj: [[f32; 4]; 4],
+    eye: [f32; 3],
+    height: f32,
+}
+
+// グリッドは影を受ける床のわずかに上に敷き、床と重なってちらつかないようにする
+const GRID_HEIGHT: f32 = FLOOR_HEIGHT + 0.005;
+
+// 1台のカメラからシーンを描画するのに使う、カメラごとのユニフォームバッファとバインドグループ
+// （メインのカメラと、右下の小窓に描画する固定のカメラがそれぞれ持つ）
+pub struct CameraBindings {
+    camera_buffer: wgpu::Buffer,
+    pub uniform_bind_group: wgpu::BindGroup,
+    // 影を受ける床（モデル行列を単位行列にしたカメラのバインドグループで描画する）
+    floor_camera_buffer: wgpu::Buffer,
+    pub floor_bind_group: wgpu::BindGroup,
+    skybox_buffer: wgpu::Buffer,
+    // キューブマップの読み込みに失敗した場合は None
+    pub skybox_bind_group: Option<wgpu::BindGroup>,
+    grid_buffer: wgpu::Buffer,
+    pub grid_bind_group: wgpu::BindGroup,
+}
+
+impl CameraBindings {
+    // environment はスカイボックスのキューブマップ（読み込めなかった場合は None）
+    pub fn new(
+        device: &wgpu::Device,
+        uniform_layout: &wgpu::BindGroupLayout,
+        uniform_buffer: &wgpu::Buffer,
+        skybox_layout: &wgpu::BindGroupLayout,
+        environment: Option<&Texture>,
+        grid_layout: &wgpu::BindGroupLayout,
+        label: &str,
+    ) -> Self {
+        let camera_uniform_bind_group = |buffer: &wgpu::Buffer, name: &str| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some(&format!("{} {} Bind Group", label, name)),
+                layout: uniform_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: uniform_buffer.as_entire_binding(),
+                    },
+                ],
+            })
+        };
+        let uniform_buffer_of = |size: usize, name: &str| {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(&format!("{} {} Buffer", label, name)),
+                size: size as wgpu::BufferAddress,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            })
+        };
+
+        let camera_buffer = uniform_buffer_of(std::mem::size_of::<CameraUniform>(), "Camera");
+        let uniform_bind_group = camera_uniform_bind_group(&camera_buffer, "Uniform");
+        let floor_camera_buffer =
+            uniform_buffer_of(std::mem::size_of::<CameraUniform>(), "Floor Camera");
+        let floor_bind_group = camera_uniform_bind_group(&floor_camera_buffer, "Floor");
+        let skybox_buffer = uniform_buffer_of(std::mem::size_of::<SkyUniform>(), "Skybox");
+        let skybox_bind_group = environment.map(|environment| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some(&format!("{} Skybox Bind Group", label)),
+                layout: skybox_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: skybox_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(&environment.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::Sampler(&environment.sampler),
+                    },
+                ],
+            })
+        });
+        let grid_buffer = uniform_buffer_of(std::mem::size_of::<GridUniform>(), "Grid");
+        let grid_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some(&format!("{} Grid Bind Group", label)),
+            layout: grid_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: grid_buffer.as_entire_binding(),
+            }],
+        });
+        Self {
+            camera_buffer,
+            uniform_bind_group,
+            floor_camera_buffer,
+            floor_bind_group,
+            skybox_buffer,
+            skybox_bind_group,
+            grid_buffer,
+            grid_bind_group,
+        }
+    }
+
+    // camera から見たときの各ユニフォームを書き込む（model は三角形や立方体のモデル行列）
+    pub fn write(&self, queue: &wgpu::Queue, camera: &Camera, model: glam::Mat4) {
+        let view_proj = camera.build_view_projection_matrix();
+        let camera_uniform = CameraUniform {
+            view_proj: view_proj.to_cols_array_2d(),
+            model: model.to_cols_array_2d(),
+            view_position: camera.eye.extend(1.0).to_array(),
+            normal_matrix: model.inverse().transpose().to_cols_array_2d(),
+        };
+        let floor_camera = CameraUniform {
+            model: glam::Mat4::IDENTITY.to_cols_array_2d(),
+            normal_matrix: glam::Mat4::IDENTITY.to_cols_array_2d(),
+            ..camera_uniform
+        };
+        queue.write_buffer(
+            &self.floor_camera_buffer,
+            0,
+            bytemuck::cast_slice(&[floor_camera]),
+        );
+        queue.write_buffer(
+            &self.camera_buffer,
+            0,
+            bytemuck::cast_slice(&[camera_uniform]),
+        );
+        let sky_uniform = SkyUniform {
+            inv_view_proj: camera.build_skybox_matrix().to_cols_array_2d(),
+        };
+        queue.write_buffer(&self.skybox_buffer, 0, bytemuck::cast_slice(&[sky_uniform]));
+        let grid_uniform = GridUniform {
+            view_proj: view_proj.to_cols_array_2d(),
+            inv_view_proj: view_proj.inverse().to_cols_array_2d(),
+            eye: camera.eye.to_array(),
+            height: GRID_HEIGHT,
+        };
+        queue.write_buffer(&self.grid_buffer, 0, bytemuck::cast_slice(&[grid_uniform]));
+    }
+}
+
+pub fn create_plane_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    texture: &Texture,
+    filter: PlaneFilter,
+    max_anisotropy: u16,
+) -> anyhow::Result<wgpu::BindGroup> {
+    let sampler = filter
+        .sampler_options()
+        .capped(max_anisotropy)
+        .create_sampler(device, "Plane Sampler")?;
+    Ok(texture.create_bind_group_with_sampler(device, layout, &sampler))
+}
+
+// 輪郭を付ける物体の範囲を示すステンシルの参照値（他の物体は 0 を書き込む）
+pub const OUTLINE_STENCIL_REFERENCE: u32 = 1;
+// 輪郭の色と、元の図形に対する拡大率（H キーで表示を切り替える）
+pub const OUTLINE_COLOR: [f32; 4] = [1.0, 0.6, 0.1, 1.0];
+pub const OUTLINE_SCALE: f32 = 1.06;
+
+// 輪郭のユニフォームデータ
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct OutlineUniform {
+    pub color: [f32; 4],
+    pub scale: f32,
+    // WGSLのユニフォーム構造体は16バイト境界に揃える必要がある
+    pub _padding: [f32; 3],
+}
+
+// 光源の情報、シャドウマップ、光源の配列をまとめたバインドグループを作成する
+// 光源の配列のバッファを作り直したときにも呼び出す
+pub fn create_light_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    info_buffer: &wgpu::Buffer,
+    shadow_map: &Texture,
+    lights: &LightBuffer,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Light Bind Group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: info_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::TextureView(&shadow_map.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::Sampler(&shadow_map.sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: lights.buffer.as_entire_binding(),
+            },
+        ],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn camera_uniform_round_trips_through_bytes() {
+        let matrix = glam::Mat4::perspective_rh(1.0, 1.5, 0.1, 100.0)
+            * glam::Mat4::from_translation(glam::Vec3::new(1.0, 2.0, 3.0));
+        let model = glam::Mat4::from_rotation_y(0.5);
+        let uniform = CameraUniform {
+            view_proj: matrix.to_cols_array_2d(),
+            model: model.to_cols_array_2d(),
+            view_position: [1.0, 2.0, 3.0, 1.0],
+            normal_matrix: model.to_cols_array_2d(),
+        };
+
+        let bytes: &[u8] = bytemuck::cast_slice(std::slice::from_ref(&uniform));
+        assert_eq!(bytes.len(), 208);
+
+        let restored: &[CameraUniform] = bytemuck::cast_slice(bytes);
+        assert_eq!(
+            glam::Mat4::from_cols_array_2d(&restored[0].view_proj),
+            matrix
+        );
+        assert_eq!(glam::Mat4::from_cols_array_2d(&restored[0].model), model);
+        assert_eq!(restored[0].view_position, [1.0, 2.0, 3.0, 1.0]);
+    }
+
+    #[test]
+    fn presets_cycle_back_to_the_first_value() {
+        assert_eq!(next_preset(&ROUGHNESS_PRESETS, 0.0), 0.3);
+        assert_eq!(next_preset(&ROUGHNESS_PRESETS, 1.0), 0.0);
+        // プリセットにない値からは次に大きいプリセットに進む
+        assert_eq!(next_preset(&REFLECTIVITY_PRESETS, 0.7), 1.0);
+        // 16バイト境界に揃えた大きさになっている
+        assert_eq!(std::mem::size_of::<ReflectionUniform>(), 16);
+    }
+}
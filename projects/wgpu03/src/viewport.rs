@@ -0,0 +1,165 @@
+// ウィンドウの論理ピクセル単位の矩形（左上が原点）
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LogicalRect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+// サーフェイスの物理ピクセル単位の矩形
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PhysicalRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+// 論理ピクセルの矩形を物理ピクセルに変換し、サーフェイスからはみ出さないように切り詰める
+// 大きさが 0 のビューポートは検証エラーになるので、少なくとも1ピクセルは残す
+fn to_physical(
+    rect: LogicalRect,
+    scale_factor: f64,
+    surface_width: u32,
+    surface_height: u32,
+) -> PhysicalRect {
+    let scale = |v: f32| (v as f64 * scale_factor).round().max(0.0) as u32;
+    let (surface_width, surface_height) = (surface_width.max(1), surface_height.max(1));
+    let x = scale(rect.x).min(surface_width - 1);
+    let y = scale(rect.y).min(surface_height - 1);
+    // 右端・下端を先に求めてから切り詰め、丸め誤差で隣の矩形と隙間ができないようにする
+    let right = scale(rect.x + rect.width).clamp(x + 1, surface_width);
+    let bottom = scale(rect.y + rect.height).clamp(y + 1, surface_height);
+    PhysicalRect {
+        x,
+        y,
+        width: right - x,
+        height: bottom - y,
+    }
+}
+
+// シーンを描画するウィンドウの中の範囲（ビューポートとシザー矩形を同じ範囲に設定する）
+pub struct Viewport {
+    logical: LogicalRect,
+    scale_factor: f64,
+    surface_size: (u32, u32),
+    physical: PhysicalRect,
+}
+
+impl Viewport {
+    // 物理ピクセルのサーフェイス全体を覆うビューポート
+    pub fn full(scale_factor: f64, surface_width: u32, surface_height: u32) -> Self {
+        let mut viewport = Self {
+            logical: LogicalRect {
+                x: 0.0,
+                y: 0.0,
+                width: 0.0,
+                height: 0.0,
+            },
+            scale_factor,
+            surface_size: (surface_width, surface_height),
+            physical: PhysicalRect {
+                x: 0,
+                y: 0,
+                width: 1,
+                height: 1,
+            },
+        };
+        viewport.set_logical(viewport.surface_logical_rect());
+        viewport
+    }
+
+    // サーフェイス全体の論理ピクセルの矩形
+    pub fn surface_logical_rect(&self) -> LogicalRect {
+        let (width, height) = self.surface_size;
+        LogicalRect {
+            x: 0.0,
+            y: 0.0,
+            width: (width as f64 / self.scale_factor) as f32,
+            height: (height as f64 / self.scale_factor) as f32,
+        }
+    }
+
+    pub fn physical(&self) -> PhysicalRect {
+        self.physical
+    }
+
+    pub fn set_logical(&mut self, logical: LogicalRect) {
+        self.logical = logical;
+        self.update();
+    }
+
+    pub fn set_scale_factor(&mut self, scale_factor: f64) {
+        self.scale_factor = scale_factor;
+        self.update();
+    }
+
+    // Resized のたびに呼び、前の大きさのままの矩形が新しいサーフェイスからはみ出さないようにする
+    pub fn resize(&mut self, surface_width: u32, surface_height: u32) {
+        self.surface_size = (surface_width, surface_height);
+        self.update();
+    }
+
+    fn update(&mut self) {
+        let (width, height) = self.surface_size;
+        self.physical = to_physical(self.logical, self.scale_factor, width, height);
+    }
+
+    // 以降の描画をこの範囲に限る（範囲の外は描画されず、クリアした色のまま残る）
+    pub fn apply(&self, rpass: &mut wgpu::RenderPass) {
+        let PhysicalRect {
+            x,
+            y,
+            width,
+            height,
+        } = self.physical;
+        rpass.set_viewport(x as f32, y as f32, width as f32, height as f32, 0.0, 1.0);
+        rpass.set_scissor_rect(x, y, width, height);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn logical_rect_is_scaled_and_clamped_to_the_surface() {
+        let rect = LogicalRect {
+            x: 240.0,
+            y: 0.0,
+            width: 560.0,
+            height: 600.0,
+        };
+        // 倍率 2 のディスプレイでは物理ピクセルで2倍の大きさになる
+        assert_eq!(
+            to_physical(rect, 2.0, 1600, 1200),
+            PhysicalRect {
+                x: 480,
+                y: 0,
+                width: 1120,
+                height: 1200,
+            }
+        );
+        // ウィンドウを縮めた後の古い矩形は、新しいサーフェイスの大きさに切り詰める
+        assert_eq!(
+            to_physical(rect, 2.0, 800, 600),
+            PhysicalRect {
+                x: 480,
+                y: 0,
+                width: 320,
+                height: 600,
+            }
+        );
+        // 矩形がサーフェイスの外に出ても、1ピクセルは残す
+        assert_eq!(
+            to_physical(rect, 1.0, 100, 100),
+            PhysicalRect {
+                x: 99,
+                y: 0,
+                width: 1,
+                height: 100,
+            }
+        );
+    }
+}
@@ -0,0 +1,163 @@
+use wgpu::util::DeviceExt;
+
+use crate::crash;
+use crate::pipeline_cache::PipelineCache;
+
+// wave.wgsl の @workgroup_size と一致させる
+const WORKGROUP_SIZE: u32 = 64;
+
+// wave.wgsl の Params 構造体に対応するユニフォームデータ
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct WaveParams {
+    time: f32,
+    triangle_count: u32,
+    columns: u32,
+    _padding: u32,
+}
+
+// 三角形を1つずつのスレッドに割り当てるのに必要なワークグループの数
+fn workgroup_count(triangle_count: u32) -> u32 {
+    triangle_count.div_ceil(WORKGROUP_SIZE)
+}
+
+// コンピュートシェーダーで毎フレーム頂点を生成し、そのバッファを頂点バッファとして描画する
+pub struct WaveCompute {
+    pipeline: wgpu::ComputePipeline,
+    bind_group: wgpu::BindGroup,
+    params_buffer: wgpu::Buffer,
+    params: WaveParams,
+    // コンピュートシェーダーが書き込み、レンダーパイプラインが読み込む（STORAGE | VERTEX）
+    pub vertex_buffer: wgpu::Buffer,
+}
+
+impl WaveCompute {
+    // vertex_size は描画に使う頂点構造体の大きさ（wave.wgsl の FLOATS_PER_VERTEX 個の f32 と一致すること）
+    pub fn new(
+        device: &wgpu::Device,
+        pipeline_cache: &PipelineCache,
+        shader: &wgpu::ShaderModule,
+        triangle_count: u32,
+        columns: u32,
+        vertex_size: usize,
+    ) -> Self {
+        let params = WaveParams {
+            time: 0.0,
+            triangle_count,
+            columns,
+            _padding: 0,
+        };
+        let params_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Wave Params Buffer"),
+            contents: bytemuck::cast_slice(&[params]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let vertex_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Wave Vertex Buffer"),
+            size: (triangle_count as usize * 3 * vertex_size) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::VERTEX,
+            mapped_at_creation: false,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Wave Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Wave Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: params_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: vertex_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Wave Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = pipeline_cache.create(|cache| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("Wave Pipeline"),
+                layout: Some(&layout),
+                module: shader,
+                entry_point: Some("cs_main"),
+                compilation_options: Default::default(),
+                cache,
+            })
+        });
+
+        Self {
+            pipeline,
+            bind_group,
+            params_buffer,
+            params,
+            vertex_buffer,
+        }
+    }
+
+    pub fn vertex_count(&self) -> u32 {
+        self.params.triangle_count * 3
+    }
+
+    pub fn update(&mut self, queue: &wgpu::Queue, time: f32) {
+        self.params.time = time;
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::cast_slice(&[self.params]));
+    }
+
+    // 同じエンコーダーで後に続くレンダーパスより前に記録すること
+    pub fn dispatch(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        timestamp_writes: Option<wgpu::ComputePassTimestampWrites<'_>>,
+    ) {
+        let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: crash::pass("Wave Compute Pass"),
+            timestamp_writes,
+        });
+        cpass.set_pipeline(&self.pipeline);
+        cpass.set_bind_group(0, &self.bind_group, &[]);
+        cpass.dispatch_workgroups(workgroup_count(self.params.triangle_count), 1, 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn workgroups_cover_non_multiple_counts() {
+        assert_eq!(workgroup_count(64), 1);
+        assert_eq!(workgroup_count(65), 2);
+        assert_eq!(workgroup_count(1000), 16);
+        assert!(workgroup_count(1000) * WORKGROUP_SIZE >= 1000);
+    }
+}
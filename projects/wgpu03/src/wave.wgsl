@@ -0,0 +1,55 @@
+struct Params {
+    time: f32,
+    triangle_count: u32,
+    columns: u32,
+    _padding: u32,
+};
+
+@group(0) @binding(0) var<uniform> params: Params;
+// Rust 側の Vertex（位置・色・テクスチャ座標・法線）をそのまま詰めた配列
+// vec3 で構造体を定義すると 16 バイト境界に揃えられてしまうので f32 の配列として書き込む
+@group(0) @binding(1) var<storage, read_write> vertices: array<f32>;
+
+const FLOATS_PER_VERTEX: u32 = 11u;
+const TAU: f32 = 6.28318530718;
+const TRIANGLE_SIZE: f32 = 0.035;
+const WAVE_AMPLITUDE: f32 = 0.12;
+
+fn write_vertex(index: u32, position: vec3<f32>, color: vec3<f32>, uv: vec2<f32>) {
+    let base = index * FLOATS_PER_VERTEX;
+    vertices[base + 0u] = position.x;
+    vertices[base + 1u] = position.y;
+    vertices[base + 2u] = position.z;
+    vertices[base + 3u] = color.r;
+    vertices[base + 4u] = color.g;
+    vertices[base + 5u] = color.b;
+    vertices[base + 6u] = uv.x;
+    vertices[base + 7u] = uv.y;
+    vertices[base + 8u] = 0.0;
+    vertices[base + 9u] = 0.0;
+    vertices[base + 10u] = 1.0;
+}
+
+// 1スレッドで1つの三角形の3頂点を書き込む
+@compute @workgroup_size(64)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let i = id.x;
+    // 三角形の数がワークグループの大きさの倍数でない場合、最後のワークグループの余ったスレッドは何もしない
+    if i >= params.triangle_count {
+        return;
+    }
+    let rows = (params.triangle_count + params.columns - 1u) / params.columns;
+    let u = f32(i % params.columns) / f32(max(params.columns, 2u) - 1u);
+    let v = f32(i / params.columns) / f32(max(rows, 2u) - 1u);
+
+    let phase = u * TAU * 1.5 + v * 2.0 + params.time * 2.0;
+    let wave = sin(phase);
+    let center = vec3<f32>((u * 2.0 - 1.0) * 1.5, (v * 2.0 - 1.0) * 0.9 + wave * WAVE_AMPLITUDE, 0.0);
+    let size = TRIANGLE_SIZE * (1.0 + 0.4 * wave);
+    let color = mix(vec3<f32>(0.1, 0.4, 1.0), vec3<f32>(1.0, 0.6, 0.2), 0.5 + 0.5 * wave);
+
+    // 反時計回り（表面）の順に並べる
+    write_vertex(i * 3u + 0u, center + vec3<f32>(0.0, size, 0.0), color, vec2<f32>(0.5, 0.0));
+    write_vertex(i * 3u + 1u, center + vec3<f32>(-size, -size, 0.0), color, vec2<f32>(0.0, 1.0));
+    write_vertex(i * 3u + 2u, center + vec3<f32>(size, -size, 0.0), color, vec2<f32>(1.0, 1.0));
+}
@@ -0,0 +1,103 @@
+use std::sync::Arc;
+
+use anyhow::{Result, bail};
+use tracing::warn;
+use winit::dpi::PhysicalSize;
+use winit::window::Window;
+
+use crate::gpu::surface_view_format;
+use crate::post::PostPass;
+
+// Ctrl+N で開いた、メインのウィンドウ（State が持つ）と同じシーンを表示するウィンドウ
+// デバイスとキューはメインのウィンドウと共有し、サーフェイスとその設定だけをウィンドウごとに持つ
+pub struct WindowState {
+    pub window: Arc<Window>,
+    surface: wgpu::Surface<'static>,
+    config: wgpu::SurfaceConfiguration,
+}
+
+impl WindowState {
+    // ポストプロセスのパイプラインはメインのサーフェイスのフォーマットで作っているので、同じフォーマットにそろえる
+    // （マルチ GPU の環境などで、このウィンドウのサーフェイスがそのフォーマットに対応していなければエラー）
+    pub fn new(
+        instance: &wgpu::Instance,
+        adapter: &wgpu::Adapter,
+        device: &wgpu::Device,
+        window: Arc<Window>,
+        main: &wgpu::SurfaceConfiguration,
+    ) -> Result<Self> {
+        let size = window.inner_size();
+        let surface = instance.create_surface(window.clone())?;
+        let caps = surface.get_capabilities(adapter);
+        if !caps.formats.contains(&main.format) {
+            bail!(
+                "このウィンドウのサーフェイスはメインのウィンドウと同じフォーマット（{:?}）に対応していません",
+                main.format
+            );
+        }
+        let config = wgpu::SurfaceConfiguration {
+            width: size.width.max(1),
+            height: size.height.max(1),
+            // 表示を待ってメインのウィンドウの描画が遅れないよう、垂直同期を待たない表示モードがあれば使う
+            present_mode: wgpu::PresentMode::AutoNoVsync,
+            alpha_mode: caps.alpha_modes[0],
+            ..main.clone()
+        };
+        surface.configure(device, &config);
+        Ok(Self {
+            window,
+            surface,
+            config,
+        })
+    }
+
+    // メインのウィンドウを閉じたときに、State が引き継ぐ
+    pub fn into_parts(
+        self,
+    ) -> (
+        Arc<Window>,
+        wgpu::Surface<'static>,
+        wgpu::SurfaceConfiguration,
+    ) {
+        (self.window, self.surface, self.config)
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, size: PhysicalSize<u32>) {
+        // 最小化すると 0x0 になるので、元の大きさのままにしておく
+        if size.width == 0 || size.height == 0 {
+            return;
+        }
+        self.config.width = size.width;
+        self.config.height = size.height;
+        self.surface.configure(device, &self.config);
+    }
+
+    // メインのウィンドウで描画したシーン（ポストプロセスの入力）を、このウィンドウの大きさに合わせて表示する
+    // 文字や設定パネルはメインのウィンドウにだけ表示する
+    pub fn present(&self, device: &wgpu::Device, queue: &wgpu::Queue, post: &PostPass) {
+        let frame = match self.surface.get_current_texture() {
+            Ok(frame) => frame,
+            Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                self.surface.configure(device, &self.config);
+                return;
+            }
+            // このウィンドウのフレームを飛ばすだけで、メインのウィンドウの描画は続ける
+            Err(wgpu::SurfaceError::Timeout) => return,
+            Err(e) => {
+                warn!("ウィンドウのフレームを取得できませんでした: {}", e);
+                return;
+            }
+        };
+        let view = frame.texture.create_view(&wgpu::TextureViewDescriptor {
+            format: Some(surface_view_format(&self.config)),
+            ..Default::default()
+        });
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Window Encoder"),
+        });
+        post.draw_fitted(&mut encoder, &view, self.config.width, self.config.height);
+        queue.submit(Some(encoder.finish()));
+        self.window.pre_present_notify();
+        frame.present();
+    }
+}